@@ -0,0 +1,113 @@
+//! Running many independent ROMs without a display, e.g. to check the compatibility
+//! of a ROM collection, generate thumbnails, or triage a fuzzing corpus.
+//!
+//! [`run_batch`] runs the ROMs in parallel and requires the `batch` feature.
+
+use crate::{rom::RomError, Chip8, Deterministic, FrameInput, Quirks, Variant};
+
+/// A ROM to run headless, with the settings to run it with.
+#[derive(Debug, Clone)]
+pub struct BatchRom {
+    pub data: Vec<u8>,
+    pub variant: Variant,
+    pub quirks: Quirks,
+
+    /// The amount of steps executed per frame.
+    pub steps_per_frame: u32,
+
+    /// Run the ROM reproducibly with these settings, or with the time of the host
+    /// and random numbers from the operating system if `None`.
+    pub deterministic: Option<Deterministic>,
+}
+
+impl BatchRom {
+    /// A ROM with the default variant and quirks, running 10 steps per frame in
+    /// deterministic mode, seeded with the given `seed`.
+    pub fn new(data: Vec<u8>, seed: u64) -> Self {
+        let steps_per_frame = 10;
+        Self {
+            data,
+            variant: Variant::default(),
+            quirks: Quirks::default(),
+            steps_per_frame,
+            deterministic: Some(Deterministic {
+                seed,
+                steps_per_tick: steps_per_frame,
+            }),
+        }
+    }
+
+    /// Run the ROM for an amount of `frames`, stopping early if it halts.
+    /// Returns the `Chip8` in its final state, or an error if the ROM doesn't fit in memory.
+    pub fn run(&self, frames: u32) -> Result<Chip8, RomError> {
+        let mut chip8 = self.prepare()?;
+        self.run_frames(&mut chip8, frames);
+        Ok(chip8)
+    }
+
+    /// Create a `Chip8` with these settings and the ROM loaded, to be changed further
+    /// before it is run with [`BatchRom::run_frames`].
+    pub fn prepare(&self) -> Result<Chip8, RomError> {
+        let mut builder = Chip8::builder().variant(self.variant).quirks(self.quirks);
+        if let Some(deterministic) = self.deterministic {
+            builder = builder.deterministic(deterministic);
+        }
+        let mut chip8 = builder.build();
+        chip8.load_rom_data(self.data.clone())?;
+        Ok(chip8)
+    }
+
+    /// Run the `chip8` prepared with [`BatchRom::prepare`] for an amount of `frames`,
+    /// stopping early if it halts.
+    pub fn run_frames(&self, chip8: &mut Chip8, frames: u32) {
+        let input = FrameInput {
+            steps: self.steps_per_frame,
+            ..Default::default()
+        };
+        for _ in 0..frames {
+            if chip8.run_frame(&input).event.is_some() {
+                break;
+            }
+        }
+    }
+}
+
+/// Run every ROM for an amount of `frames` on a separate `Chip8`, in parallel on the
+/// threads of the global rayon pool. Returns the final states, in the order of `roms`.
+#[cfg(feature = "batch")]
+pub fn run_batch(roms: &[BatchRom], frames: u32) -> Vec<Result<Chip8, RomError>> {
+    use rayon::prelude::*;
+
+    roms.par_iter().map(|rom| rom.run(frames)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BatchRom;
+
+    /// Draws random bytes in an endless loop: `V0 = rand`, `V1 = rand`, draw at (V0, V1).
+    const RANDOM_ROM: [u8; 10] = [0xA0, 0x00, 0xC0, 0xFF, 0xC1, 0xFF, 0xD0, 0x15, 0x12, 0x02];
+
+    #[test]
+    fn test_deterministic_runs_are_identical() {
+        let rom = BatchRom::new(RANDOM_ROM.to_vec(), 42);
+        let (first, second) = (rom.run(30).unwrap(), rom.run(30).unwrap());
+        assert_eq!(first.registers(), second.registers());
+        assert_eq!(first.display().as_rgb8(), second.display().as_rgb8());
+
+        let other = BatchRom::new(RANDOM_ROM.to_vec(), 7).run(30).unwrap();
+        assert_ne!(first.display().as_rgb8(), other.display().as_rgb8());
+    }
+
+    #[cfg(feature = "batch")]
+    #[test]
+    fn test_run_batch_keeps_order() {
+        let roms: Vec<BatchRom> = (0..8)
+            .map(|seed| BatchRom::new(RANDOM_ROM.to_vec(), seed))
+            .collect();
+        let results = super::run_batch(&roms, 10);
+        for (rom, chip8) in roms.iter().zip(results) {
+            assert_eq!(rom.run(10).unwrap().registers(), chip8.unwrap().registers());
+        }
+    }
+}
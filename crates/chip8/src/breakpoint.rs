@@ -0,0 +1,235 @@
+use std::fmt;
+
+use crate::Chip8;
+
+/// A value of the `Chip8` that a [`Condition`] tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    /// The register `Vx`.
+    V(usize),
+
+    /// The index register `I`.
+    I,
+
+    /// The delay timer.
+    DelayTimer,
+
+    /// The sound timer.
+    SoundTimer,
+}
+
+impl Operand {
+    /// All operands, in the order they should be listed.
+    pub const ALL: [Operand; 19] = [
+        Operand::V(0x0),
+        Operand::V(0x1),
+        Operand::V(0x2),
+        Operand::V(0x3),
+        Operand::V(0x4),
+        Operand::V(0x5),
+        Operand::V(0x6),
+        Operand::V(0x7),
+        Operand::V(0x8),
+        Operand::V(0x9),
+        Operand::V(0xA),
+        Operand::V(0xB),
+        Operand::V(0xC),
+        Operand::V(0xD),
+        Operand::V(0xE),
+        Operand::V(0xF),
+        Operand::I,
+        Operand::DelayTimer,
+        Operand::SoundTimer,
+    ];
+
+    /// The current value of this operand in the given `Chip8`.
+    pub fn value(self, chip8: &Chip8) -> usize {
+        match self {
+            Operand::V(x) => usize::from(chip8.registers()[x]),
+            Operand::I => chip8.index(),
+            Operand::DelayTimer => usize::from(chip8.delay_timer()),
+            Operand::SoundTimer => usize::from(chip8.sound_timer()),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::V(x) => write!(f, "V{x:X}"),
+            Operand::I => write!(f, "I"),
+            Operand::DelayTimer => write!(f, "DT"),
+            Operand::SoundTimer => write!(f, "ST"),
+        }
+    }
+}
+
+/// How a [`Condition`] compares its operand with its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    /// All comparisons, in the order they should be listed.
+    pub const ALL: [Comparison; 6] = [
+        Comparison::Equal,
+        Comparison::NotEqual,
+        Comparison::Less,
+        Comparison::LessOrEqual,
+        Comparison::Greater,
+        Comparison::GreaterOrEqual,
+    ];
+
+    /// Compare `left` with `right`.
+    pub fn compare(self, left: usize, right: usize) -> bool {
+        match self {
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+        };
+        write!(f, "{symbol}")
+    }
+}
+
+/// A test of a register or timer against a value, e.g. `V3 == 0x1F`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub operand: Operand,
+    pub comparison: Comparison,
+    pub value: usize,
+}
+
+impl Condition {
+    /// Returns whether the condition holds in the given `Chip8`.
+    pub fn holds(&self, chip8: &Chip8) -> bool {
+        self.comparison
+            .compare(self.operand.value(chip8), self.value)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {:#X}", self.operand, self.comparison, self.value)
+    }
+}
+
+/// A place to stop the execution, before the instruction at `address` is executed
+/// and only if the `condition` holds then. Without an address, the `condition` is
+/// tested before every instruction, e.g. to break when `I > 0xE00`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub address: Option<usize>,
+    pub condition: Option<Condition>,
+}
+
+impl Breakpoint {
+    /// A breakpoint at the instruction at `address`.
+    pub fn at(address: usize) -> Self {
+        Self {
+            address: Some(address),
+            condition: None,
+        }
+    }
+
+    /// A breakpoint at any instruction once the `condition` holds.
+    pub fn when(condition: Condition) -> Self {
+        Self {
+            address: None,
+            condition: Some(condition),
+        }
+    }
+
+    /// Returns whether the execution should stop before the next instruction of the `Chip8`.
+    pub fn is_hit(&self, chip8: &Chip8) -> bool {
+        let at_address = match self.address {
+            Some(address) => address == chip8.pc(),
+            None => true,
+        };
+        at_address
+            && match self.condition {
+                Some(condition) => condition.holds(chip8),
+                None => true,
+            }
+    }
+}
+
+impl From<usize> for Breakpoint {
+    fn from(address: usize) -> Self {
+        Self::at(address)
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.address, self.condition) {
+            (Some(address), Some(condition)) => write!(f, "{address:#06X} when {condition}"),
+            (Some(address), None) => write!(f, "{address:#06X}"),
+            (None, Some(condition)) => write!(f, "When {condition}"),
+            (None, None) => write!(f, "Every instruction"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breakpoint() {
+        let mut chip8 = Chip8::new();
+        // V3 = 0x1F, I = 0xE02
+        chip8
+            .load_rom_data(vec![0x63, 0x1F, 0xAE, 0x02, 0x12, 0x04])
+            .unwrap();
+        let v3 = Condition {
+            operand: Operand::V(3),
+            comparison: Comparison::Equal,
+            value: 0x1F,
+        };
+        let index = Condition {
+            operand: Operand::I,
+            comparison: Comparison::Greater,
+            value: 0xE00,
+        };
+        let at_jump = Breakpoint {
+            address: Some(0x204),
+            condition: Some(v3),
+        };
+
+        assert!(!at_jump.is_hit(&chip8));
+        assert!(!Breakpoint::when(v3).is_hit(&chip8));
+        chip8.step();
+        assert!(Breakpoint::when(v3).is_hit(&chip8));
+        assert!(!at_jump.is_hit(&chip8));
+        assert!(!Breakpoint::when(index).is_hit(&chip8));
+        chip8.step();
+        assert!(at_jump.is_hit(&chip8));
+        assert!(Breakpoint::when(index).is_hit(&chip8));
+        assert!(Breakpoint::from(0x204).is_hit(&chip8));
+
+        assert_eq!(at_jump.to_string(), "0x0204 when V3 == 0x1F");
+        assert_eq!(Breakpoint::when(index).to_string(), "When I > 0xE00");
+    }
+}
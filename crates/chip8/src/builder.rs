@@ -0,0 +1,195 @@
+use crate::{
+    Chip8, Deterministic, InvalidOpcodePolicy, MachineCodePolicy, MemoryAccessPolicy, MemorySize,
+    Quirks, ReadOnlyRegion, TimeSource, Timing, Variant,
+};
+
+/// Configures a [`Chip8`] at construction time, see [`Chip8::builder`].
+///
+/// Every option that isn't given keeps the default of [`Chip8::new`]. The options are the
+/// settings that are kept when the `Chip8` is reset, and can still be changed afterwards
+/// with the setter of the same name.
+#[derive(Debug, Clone, Default)]
+pub struct Chip8Builder {
+    variant: Option<Variant>,
+    quirks: Option<Quirks>,
+    memory_size: Option<MemorySize>,
+    start_address: Option<usize>,
+    seed: Option<u64>,
+    deterministic: Option<Deterministic>,
+    time_source: Option<TimeSource>,
+    timing: Option<Timing>,
+    machine_code_policy: Option<MachineCodePolicy>,
+    invalid_opcode_policy: Option<InvalidOpcodePolicy>,
+    memory_access_policy: Option<MemoryAccessPolicy>,
+    read_only_regions: Option<Vec<ReadOnlyRegion>>,
+}
+
+impl Chip8Builder {
+    /// The variant of the platform to emulate, see [`Chip8::set_variant`].
+    pub fn variant(mut self, variant: Variant) -> Self {
+        self.variant = Some(variant);
+        self
+    }
+
+    /// The quirk settings, see [`Chip8::set_quirks`].
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = Some(quirks);
+        self
+    }
+
+    /// The size of the memory, instead of the size of the variant. See [`Chip8::set_memory_size`].
+    pub fn memory_size(mut self, size: MemorySize) -> Self {
+        self.memory_size = Some(size);
+        self
+    }
+
+    /// The address programs are loaded at and start at, see [`Chip8::set_start_address`].
+    pub fn start_address(mut self, start: usize) -> Self {
+        self.start_address = Some(start);
+        self
+    }
+
+    /// The seed of the random numbers generated by `Cxnn`, see [`Chip8::set_seed`].
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Run in the deterministic mode with the given settings, see [`Chip8::set_deterministic`].
+    /// Its seed is replaced by the one given to [`Chip8Builder::seed`], if any, and its time
+    /// source by the one given to [`Chip8Builder::time_source`].
+    pub fn deterministic(mut self, deterministic: Deterministic) -> Self {
+        self.deterministic = Some(deterministic);
+        self
+    }
+
+    /// What ticks the delay and sound timers, see [`Chip8::set_time_source`].
+    pub fn time_source(mut self, source: TimeSource) -> Self {
+        self.time_source = Some(source);
+        self
+    }
+
+    /// How the amount of steps of a frame is decided, see [`Chip8::set_timing`].
+    pub fn timing(mut self, timing: Timing) -> Self {
+        self.timing = Some(timing);
+        self
+    }
+
+    /// How calls to machine code routines are handled, see [`Chip8::set_machine_code_policy`].
+    pub fn machine_code_policy(mut self, policy: MachineCodePolicy) -> Self {
+        self.machine_code_policy = Some(policy);
+        self
+    }
+
+    /// How invalid opcodes are handled, see [`Chip8::set_invalid_opcode_policy`].
+    pub fn invalid_opcode_policy(mut self, policy: InvalidOpcodePolicy) -> Self {
+        self.invalid_opcode_policy = Some(policy);
+        self
+    }
+
+    /// How memory accesses past the end are handled, see [`Chip8::set_memory_access_policy`].
+    pub fn memory_access_policy(mut self, policy: MemoryAccessPolicy) -> Self {
+        self.memory_access_policy = Some(policy);
+        self
+    }
+
+    /// The regions of the memory instructions can't write to, see [`Chip8::set_read_only_regions`].
+    pub fn read_only_regions(mut self, regions: Vec<ReadOnlyRegion>) -> Self {
+        self.read_only_regions = Some(regions);
+        self
+    }
+
+    /// Create the `Chip8` with the given options, ready to load a ROM.
+    pub fn build(self) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        // the variant sets the memory size, so it goes first
+        if let Some(variant) = self.variant {
+            chip8.set_variant(variant);
+        }
+        if let Some(size) = self.memory_size {
+            chip8.set_memory_size(size);
+        }
+        if let Some(start) = self.start_address {
+            chip8.set_start_address(start);
+            chip8.processor.pc = start;
+        }
+        if let Some(quirks) = self.quirks {
+            chip8.set_quirks(quirks);
+        }
+        if let Some(deterministic) = self.deterministic {
+            chip8.set_deterministic(Some(deterministic));
+        }
+        if let Some(seed) = self.seed {
+            chip8.set_seed(Some(seed));
+        }
+        if let Some(source) = self.time_source {
+            chip8.set_time_source(source);
+        }
+        if let Some(timing) = self.timing {
+            chip8.set_timing(timing);
+        }
+        if let Some(policy) = self.machine_code_policy {
+            chip8.set_machine_code_policy(policy);
+        }
+        if let Some(policy) = self.invalid_opcode_policy {
+            chip8.set_invalid_opcode_policy(policy);
+        }
+        if let Some(policy) = self.memory_access_policy {
+            chip8.set_memory_access_policy(policy);
+        }
+        if let Some(regions) = self.read_only_regions {
+            chip8.set_read_only_regions(regions);
+        }
+        chip8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        let quirks = Quirks {
+            shift: true,
+            ..Quirks::default()
+        };
+        let chip8 = Chip8::builder()
+            .variant(Variant::SuperChip)
+            .memory_size(MemorySize::Extended)
+            .quirks(quirks)
+            .start_address(0x600)
+            .seed(7)
+            .time_source(TimeSource::Manual)
+            .build();
+        assert_eq!(chip8.variant(), Variant::SuperChip);
+        assert_eq!(chip8.memory_size(), MemorySize::Extended);
+        assert_eq!(chip8.quirks(), quirks);
+        assert_eq!(chip8.start_address(), 0x600);
+        assert_eq!(chip8.pc(), 0x600);
+        assert_eq!(chip8.seed(), Some(7));
+        assert_eq!(chip8.time_source(), TimeSource::Manual);
+    }
+
+    #[test]
+    fn test_build_defaults() {
+        let chip8 = Chip8::builder().build();
+        assert_eq!(chip8.variant(), Variant::default());
+        assert_eq!(chip8.start_address(), 0x200);
+        assert_eq!(chip8.seed(), None);
+        assert_eq!(chip8.time_source(), TimeSource::Host);
+    }
+
+    #[test]
+    fn test_build_deterministic() {
+        let chip8 = Chip8::builder()
+            .seed(3)
+            .deterministic(Deterministic {
+                seed: 1,
+                steps_per_tick: 10,
+            })
+            .build();
+        assert_eq!(chip8.seed(), Some(3));
+        assert_eq!(chip8.time_source(), TimeSource::Steps(10));
+    }
+}
@@ -0,0 +1,11 @@
+use crate::{clock, graphics, input, memory};
+
+/// Contains all the different components of the `Chip8` system, excluding the `Processor`.
+#[derive(Default, Clone)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bus {
+    pub clock: clock::Clock,
+    pub graphics: graphics::GraphicsBuffer,
+    pub input: input::Input,
+    pub memory: memory::Memory,
+}
@@ -0,0 +1,360 @@
+use crate::memory::{Memory, MEMORY_SIZE};
+
+/// The address the CHIP-8 interpreter of the COSMAC VIP stores the `Vx` registers at,
+/// where machine code routines expect them.
+pub const V_REGISTERS_ADDRESS: usize = 0xEF0;
+
+/// The initial stack pointer (`R2`) of machine code routines, which grows downwards
+/// from just below the `Vx` registers like on the COSMAC VIP.
+const STACK_ADDRESS: u16 = 0xECF;
+
+/// The page of the display buffer on the COSMAC VIP, stored in the high byte of `RB`.
+const DISPLAY_PAGE: u8 = 0x0F;
+
+/// The register the interpreter loop runs on. Routines return to the
+/// interpreter by switching the program counter to it (`SEP R4`).
+const INTERPRETER_REGISTER: u8 = 0x4;
+
+/// The register machine code routines are called with.
+const CALL_REGISTER: u8 = 0x3;
+
+/// The register used as the stack pointer.
+const STACK_REGISTER: u8 = 0x2;
+
+/// The register the CHIP-8 interpreter keeps the index register `I` in.
+pub const INDEX_REGISTER: u8 = 0xA;
+
+/// The register the CHIP-8 interpreter keeps the delay (high byte)
+/// and sound (low byte) timers in.
+pub const TIMER_REGISTER: u8 = 0x8;
+
+/// The RCA CDP1802 processor of the COSMAC VIP, used to execute the machine code
+/// routines called by "hybrid" ROMs (`0nnn`). It shares the memory of the `Chip8`.
+///
+/// The processor only runs for the duration of a routine, so interrupts, DMA and
+/// the external flags are not emulated. I/O instructions read `0` and write nothing.
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Default, Clone)]
+pub struct Cdp1802 {
+    /// The 16-bit scratchpad registers `R0`-`RF`.
+    pub r: [u16; 16],
+
+    /// The accumulator.
+    pub d: u8,
+
+    /// The data flag, the carry/borrow of arithmetic and shift instructions.
+    pub df: bool,
+
+    /// The register designated as the program counter.
+    pub p: u8,
+
+    /// The register designated as the data pointer.
+    pub x: u8,
+
+    /// The temporary register, holding `X` and `P` after `MARK` or an interrupt.
+    pub t: u8,
+
+    /// The interrupt enable flag.
+    pub ie: bool,
+
+    /// The `Q` output flip-flop, which drives the speaker on the COSMAC VIP.
+    pub q: bool,
+}
+
+impl Cdp1802 {
+    /// Prepare the processor to call the routine at `address`, the way the CHIP-8
+    /// interpreter of the COSMAC VIP does: `R3` is the program counter and `R2` the stack pointer.
+    pub fn call(&mut self, address: usize) {
+        self.r[usize::from(CALL_REGISTER)] = address as u16;
+        self.r[usize::from(STACK_REGISTER)] = STACK_ADDRESS;
+        self.r[0xB] = u16::from(DISPLAY_PAGE) << 8;
+        self.p = CALL_REGISTER;
+        self.x = STACK_REGISTER;
+    }
+
+    /// Returns whether the routine has returned to the CHIP-8 interpreter.
+    pub fn returned(&self) -> bool {
+        self.p == INTERPRETER_REGISTER
+    }
+
+    /// Read the byte at `address`, which wraps around the end of memory.
+    fn read(memory: &Memory, address: u16) -> u8 {
+        memory[usize::from(address) % MEMORY_SIZE]
+    }
+
+    /// Write the byte at `address`, which wraps around the end of memory.
+    fn write(memory: &mut Memory, address: u16, value: u8) {
+        memory[usize::from(address) % MEMORY_SIZE] = value;
+    }
+
+    /// Fetch the byte at the program counter and advance it.
+    fn fetch(&mut self, memory: &Memory) -> u8 {
+        let pc = &mut self.r[usize::from(self.p)];
+        let byte = Self::read(memory, *pc);
+        *pc = pc.wrapping_add(1);
+        byte
+    }
+
+    /// Get the byte `R(X)` points to.
+    fn data(&self, memory: &Memory) -> u8 {
+        Self::read(memory, self.r[usize::from(self.x)])
+    }
+
+    /// Add `a`, `b` and the `carry`, setting `DF` to the carry out.
+    fn add(&mut self, a: u8, b: u8, carry: bool) -> u8 {
+        let sum = u16::from(a) + u16::from(b) + u16::from(carry);
+        self.df = sum > 0xFF;
+        sum as u8
+    }
+
+    /// Subtract `b` from `a` with the `borrow`, setting `DF` to `1` if there was no borrow.
+    fn subtract(&mut self, a: u8, b: u8, borrow: bool) -> u8 {
+        self.add(a, !b, !borrow)
+    }
+
+    /// Evaluate the condition of a branch or skip instruction, given its low nibble.
+    /// The external flags `EF1`-`EF4` are never set.
+    fn condition(&self, n: u8) -> bool {
+        let condition = match n & 0x7 {
+            0x0 => true,
+            0x1 => self.q,
+            0x2 => self.d == 0,
+            0x3 => self.df,
+            _ => false,
+        };
+        // the upper half of the instructions negates the condition
+        condition != (n & 0x8 != 0)
+    }
+
+    /// Execute a single instruction.
+    pub fn step(&mut self, memory: &mut Memory) {
+        let opcode = self.fetch(memory);
+        let n = opcode & 0xF;
+        let rn = usize::from(n);
+        let rx = usize::from(self.x);
+
+        match opcode >> 4 {
+            // IDL waits for an interrupt or DMA request, which are not emulated
+            0x0 if n == 0 => {}
+            // LDN
+            0x0 => self.d = Self::read(memory, self.r[rn]),
+            // INC
+            0x1 => self.r[rn] = self.r[rn].wrapping_add(1),
+            // DEC
+            0x2 => self.r[rn] = self.r[rn].wrapping_sub(1),
+            // short branches
+            0x3 => {
+                let target = self.fetch(memory);
+                // SKP (0x38) never branches, so it just skips the operand
+                if self.condition(n) {
+                    let pc = &mut self.r[usize::from(self.p)];
+                    // the target is in the page of the branch's operand
+                    *pc = (pc.wrapping_sub(1) & 0xFF00) | u16::from(target);
+                }
+            }
+            // LDA
+            0x4 => {
+                self.d = Self::read(memory, self.r[rn]);
+                self.r[rn] = self.r[rn].wrapping_add(1);
+            }
+            // STR
+            0x5 => Self::write(memory, self.r[rn], self.d),
+            0x6 => match n {
+                // IRX
+                0x0 => self.r[rx] = self.r[rx].wrapping_add(1),
+                // OUT
+                0x1..=0x7 => self.r[rx] = self.r[rx].wrapping_add(1),
+                // INP
+                0x9..=0xF => {
+                    self.d = 0;
+                    Self::write(memory, self.r[rx], 0);
+                }
+                _ => log::warn!("Unknown CDP1802 instruction {opcode:#04X}"),
+            },
+            0x7 => match n {
+                // RET, DIS
+                0x0 | 0x1 => {
+                    let value = self.data(memory);
+                    self.r[rx] = self.r[rx].wrapping_add(1);
+                    self.x = value >> 4;
+                    self.p = value & 0xF;
+                    self.ie = n == 0x0;
+                }
+                // LDXA
+                0x2 => {
+                    self.d = self.data(memory);
+                    self.r[rx] = self.r[rx].wrapping_add(1);
+                }
+                // STXD
+                0x3 => {
+                    Self::write(memory, self.r[rx], self.d);
+                    self.r[rx] = self.r[rx].wrapping_sub(1);
+                }
+                // ADC
+                0x4 => self.d = self.add(self.data(memory), self.d, self.df),
+                // SDB
+                0x5 => self.d = self.subtract(self.data(memory), self.d, !self.df),
+                // SHRC
+                0x6 => {
+                    let carry = self.df;
+                    self.df = self.d & 0x1 != 0;
+                    self.d = (self.d >> 1) | (u8::from(carry) << 7);
+                }
+                // SMB
+                0x7 => self.d = self.subtract(self.d, self.data(memory), !self.df),
+                // SAV
+                0x8 => Self::write(memory, self.r[rx], self.t),
+                // MARK
+                0x9 => {
+                    self.t = (self.x << 4) | self.p;
+                    let sp = &mut self.r[usize::from(STACK_REGISTER)];
+                    Self::write(memory, *sp, self.t);
+                    *sp = sp.wrapping_sub(1);
+                    self.x = self.p;
+                }
+                // REQ, SEQ
+                0xA | 0xB => self.q = n == 0xB,
+                // ADCI
+                0xC => {
+                    let value = self.fetch(memory);
+                    self.d = self.add(value, self.d, self.df);
+                }
+                // SDBI
+                0xD => {
+                    let value = self.fetch(memory);
+                    self.d = self.subtract(value, self.d, !self.df);
+                }
+                // SHLC
+                0xE => {
+                    let carry = self.df;
+                    self.df = self.d & 0x80 != 0;
+                    self.d = (self.d << 1) | u8::from(carry);
+                }
+                // SMBI
+                _ => {
+                    let value = self.fetch(memory);
+                    self.d = self.subtract(self.d, value, !self.df);
+                }
+            },
+            // GLO
+            0x8 => self.d = self.r[rn] as u8,
+            // GHI
+            0x9 => self.d = (self.r[rn] >> 8) as u8,
+            // PLO
+            0xA => self.r[rn] = (self.r[rn] & 0xFF00) | u16::from(self.d),
+            // PHI
+            0xB => self.r[rn] = (self.r[rn] & 0x00FF) | (u16::from(self.d) << 8),
+            // long branches and skips
+            0xC => {
+                let pc = usize::from(self.p);
+                let (skip, condition) = match n {
+                    // NOP
+                    0x4 => (true, false),
+                    // LSIE
+                    0xC => (true, self.ie),
+                    // LSNQ, LSNZ, LSNF
+                    0x5..=0x7 => (true, !self.condition(n & 0x3)),
+                    // LSQ, LSZ, LSDF
+                    0xD..=0xF => (true, self.condition(n & 0x3)),
+                    // the branches, where LSKP (0xC8) is a branch that is never taken
+                    _ => (false, self.condition(n)),
+                };
+                if skip == condition {
+                    // skip the operand of the branch, or the next two bytes
+                    self.r[pc] = self.r[pc].wrapping_add(2);
+                } else if condition {
+                    let high = self.fetch(memory);
+                    let low = self.fetch(memory);
+                    self.r[pc] = u16::from_be_bytes([high, low]);
+                }
+            }
+            // SEP
+            0xD => self.p = n,
+            // SEX
+            0xE => self.x = n,
+            _ => {
+                let value = match n {
+                    // LDI, ORI, ANI, XRI, ADI, SDI, SMI read an immediate operand
+                    0x8..=0xD | 0xF => self.fetch(memory),
+                    _ => self.data(memory),
+                };
+                match n & 0x7 {
+                    // LDX, LDI
+                    0x0 => self.d = value,
+                    // OR, ORI
+                    0x1 => self.d |= value,
+                    // AND, ANI
+                    0x2 => self.d &= value,
+                    // XOR, XRI
+                    0x3 => self.d ^= value,
+                    // ADD, ADI
+                    0x4 => self.d = self.add(value, self.d, false),
+                    // SD, SDI
+                    0x5 => self.d = self.subtract(value, self.d, false),
+                    // SHR, SHL
+                    0x6 if n == 0x6 => {
+                        self.df = self.d & 0x1 != 0;
+                        self.d >>= 1;
+                    }
+                    0x6 => {
+                        self.df = self.d & 0x80 != 0;
+                        self.d <<= 1;
+                    }
+                    // SM, SMI
+                    _ => self.d = self.subtract(self.d, value, false),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run `program` from address `0x300` until it returns to the interpreter.
+    fn run(program: &[u8]) -> (Cdp1802, Memory) {
+        let mut memory = Memory::new();
+        for (offset, &byte) in program.iter().enumerate() {
+            memory[0x300 + offset] = byte;
+        }
+        let mut cpu = Cdp1802::default();
+        cpu.call(0x300);
+        while !cpu.returned() {
+            cpu.step(&mut memory);
+        }
+        (cpu, memory)
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        // LDI 0x10, SMI 0x20, SEP R4
+        let (cpu, _) = run(&[0xF8, 0x10, 0xFF, 0x20, 0xD4]);
+        assert_eq!(cpu.d, 0xF0);
+        // a borrow occurred
+        assert!(!cpu.df);
+
+        // LDI 0xFF, ADI 0x02, SEP R4
+        let (cpu, _) = run(&[0xF8, 0xFF, 0xFC, 0x02, 0xD4]);
+        assert_eq!(cpu.d, 0x01);
+        assert!(cpu.df);
+    }
+
+    #[test]
+    fn test_branches() {
+        // LDI 0x00, BZ 0x06, LDI 0x01, SEP R4, (0x306) LBNZ 0x0304, LSZ, LDI 0x02, SEP R4
+        let (cpu, _) = run(&[
+            0xF8, 0x00, 0x32, 0x06, 0xF8, 0x01, 0xCA, 0x03, 0x04, 0xCE, 0xF8, 0x02, 0xD4,
+        ]);
+        assert_eq!(cpu.d, 0x00);
+    }
+
+    #[test]
+    fn test_stack() {
+        // LDI 0xAB, STXD, IRX, LDX, SEP R4
+        let (cpu, memory) = run(&[0xF8, 0xAB, 0x73, 0x60, 0xF0, 0xD4]);
+        assert_eq!(cpu.d, 0xAB);
+        assert_eq!(memory[usize::from(STACK_ADDRESS)], 0xAB);
+        assert_eq!(cpu.r[usize::from(STACK_REGISTER)], STACK_ADDRESS);
+    }
+}
@@ -1,28 +1,120 @@
-use crate::processor::Processor;
+use std::{collections::VecDeque, io::Write, sync::Arc};
 
+pub mod batch;
+mod breakpoint;
+mod builder;
+mod bus;
+mod cdp1802;
 mod clock;
+mod deterministic;
+pub mod diff;
+pub mod disasm;
+mod frame;
 pub mod graphics;
 mod input;
+mod instr;
+pub mod lint;
+pub mod lockstep;
 mod memory;
+pub mod movie;
+mod observer;
+pub mod octo;
 mod processor;
+mod quirks;
+pub mod reference;
+pub mod report;
+mod rewind;
+pub mod rom;
+pub mod search;
+mod sound;
+pub mod sprites;
+#[cfg(feature = "persistence")]
+pub mod state;
+pub mod symbols;
+mod timing;
+mod trace;
+mod variant;
+pub mod watch;
 
-/// Contains all the different components of the `Chip8` system, excluding the `Processor`.
-#[derive(Default)]
-#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
-pub struct Bus {
-    pub clock: clock::Clock,
-    pub graphics: graphics::GraphicsBuffer,
-    pub input: input::Input,
-    pub memory: memory::Memory,
+use bus::Bus;
+use graphics::{GraphicsBuffer, RGB8};
+use observer::Observers;
+use processor::Processor;
+use rewind::{Rewind, Snapshot};
+use rom::RomError;
+use trace::{Registers, TraceWriter};
+
+pub use breakpoint::{Breakpoint, Comparison, Condition, Operand};
+pub use builder::Chip8Builder;
+pub use clock::TimeSource;
+pub use deterministic::Deterministic;
+pub use frame::{FrameEvent, FrameInput, FrameOutput, FrameReport, RunExit, StepHook};
+pub use instr::{decode, Instr};
+pub use memory::{Freeze, MemorySize, ReadOnlyAction, ReadOnlyRegion};
+pub use observer::{Observer, ObserverId};
+pub use processor::{
+    CallFrame, CodeWrite, Fault, Instruction, InvalidOpcodePolicy, MachineCodePolicy,
+    MemoryAccessPolicy, ProcessorState,
+};
+pub use quirks::{IndexIncrement, QuirkPreset, Quirks};
+pub use report::rom_hash;
+pub use sound::SoundSink;
+pub use symbols::Symbols;
+pub use timing::{Timing, VIP_CYCLES_PER_FRAME};
+pub use variant::Variant;
+
+/// The internal components of the `Chip8`, for debuggers and machine code handlers
+/// that need full access. Their layout may change between any two versions.
+#[cfg(feature = "raw")]
+pub mod raw {
+    pub use crate::bus::Bus;
+    pub use crate::cdp1802::Cdp1802;
+    pub use crate::processor::{MachineCodeHandler, Processor};
 }
 
 /// The main CHIP-8 interpreter state, contains all the components of the
 /// CHIP-8 and procedures to interact with them at a high level.
+///
+/// Frontends should drive the interpreter with [`Chip8::run_frame`] once per frame.
+/// The internal components are only accessible with the `raw` feature.
 #[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Default)]
 pub struct Chip8 {
-    pub processor: Processor,
-    pub bus: Bus,
+    pub(crate) processor: Processor,
+    pub(crate) bus: Bus,
+
+    /// The display as it was at the last vertical blank, see [`Chip8::latched_display`].
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    front: GraphicsBuffer,
+
+    /// The cycles the last frame ran past the [`VIP_CYCLES_PER_FRAME`], which are taken
+    /// from the next frame with the [`Timing::CosmacVip`] timing.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    excess_cycles: u64,
+
+    /// Receives the changes of the sound timer, see [`Chip8::set_sound_sink`].
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    sound_sink: Option<Box<dyn SoundSink>>,
+
+    /// The sound timer as it was last reported to the `sound_sink`.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    reported_sound_timer: u8,
+
+    /// The snapshots to step back to, see [`Chip8::set_rewind_depth`].
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    rewind: Rewind,
+
+    /// Receives every executed instruction, see [`Chip8::set_trace_writer`].
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    trace_writer: Option<TraceWriter>,
+
+    /// The bytes written back after every step, see [`Chip8::set_freezes`].
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    freezes: Vec<Freeze>,
+
+    /// Receive the events of every step, see [`Chip8::add_observer`].
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    observers: Observers,
 }
 
 impl Chip8 {
@@ -34,18 +126,325 @@ impl Chip8 {
         }
     }
 
+    /// Configure a new Chip8 instance at construction time, e.g.
+    /// `Chip8::builder().variant(Variant::SuperChip).seed(42).build()`.
+    pub fn builder() -> Chip8Builder {
+        Chip8Builder::default()
+    }
+
     /// Performs one execution step in the interpreter, cycling
     /// the processor and updating all state accordingly.
     pub fn step(&mut self) {
-        self.bus.clock.update();
+        self.rewind.push(&self.processor, &self.bus);
+        if !(self.processor.quirks.pause_timers_on_key_wait && self.waiting_for_key()) {
+            self.bus.clock.update();
+        } else {
+            // the timers are paused, so this step isn't a vertical blank
+            self.bus.clock.vblank_interrupt = false;
+        }
+        // nothing is drawn while waiting for a key, so the display is complete
+        if self.bus.clock.vblank_interrupt || self.waiting_for_key() {
+            self.latch_display();
+        }
+        let traced = self.traced_instruction();
+        let observed = (!self.observers.is_empty()).then(|| Observers::before_step(self));
         self.processor.cycle(&mut self.bus);
+        if let Some((address, opcode, registers)) = traced {
+            self.write_trace(address, opcode, &registers);
+        }
+        self.apply_freezes();
+        self.report_sound();
+        if let Some(pending) = observed {
+            let mut observers = std::mem::take(&mut self.observers);
+            observers.after_step(self, pending);
+            self.observers = observers;
+        }
+    }
+
+    /// Add an `observer` that receives the events of every step from now on, like the
+    /// executed instructions and sprite draws. It is kept when the `Chip8` is reset.
+    pub fn add_observer(&mut self, observer: Box<dyn Observer>) -> ObserverId {
+        self.observers.add(observer)
+    }
+
+    /// Remove the observer with the given `id`, returning it if it was added.
+    pub fn remove_observer(&mut self, id: ObserverId) -> Option<Box<dyn Observer>> {
+        self.observers.remove(id)
+    }
+
+    /// Write the values of the freezes back into memory.
+    fn apply_freezes(&mut self) {
+        let memory = self.bus.memory.as_mut_slice();
+        for freeze in &self.freezes {
+            if let Some(byte) = memory.get_mut(freeze.address) {
+                *byte = freeze.value;
+            }
+        }
+    }
+
+    /// The address, opcode and registers of the instruction the next cycle executes,
+    /// if it executes one and instructions are written to a trace writer.
+    fn traced_instruction(&self) -> Option<(usize, usize, Registers)> {
+        self.trace_writer.as_ref()?;
+        if self.processor.fault.is_some() || self.bus.input.waiting() {
+            return None;
+        }
+        let opcode = self.processor.peek_opcode(&self.bus)?;
+        Some((self.processor.pc, opcode, Registers::of(&self.processor)))
+    }
+
+    /// Write the instruction at `address` to the trace writer, which is
+    /// dropped if it fails.
+    fn write_trace(&mut self, address: usize, opcode: usize, before: &Registers) {
+        let writer = match &mut self.trace_writer {
+            Some(writer) => writer,
+            None => return,
+        };
+        if let Err(e) = writer.write(address, opcode, before, &self.processor) {
+            log::error!("Failed to write the execution trace, it is stopped: {e}");
+            self.trace_writer = None;
+        }
+    }
+
+    /// Tell the `sound_sink` about a change of the sound timer since the last report.
+    fn report_sound(&mut self) {
+        let ticks = self.bus.clock.sound_timer;
+        let sink = match &mut self.sound_sink {
+            Some(sink) if ticks != self.reported_sound_timer => sink,
+            _ => return,
+        };
+        if self.reported_sound_timer == 0 {
+            sink.beep_started();
+        }
+        sink.remaining_ticks(ticks);
+        if ticks == 0 {
+            sink.beep_stopped();
+        }
+        self.reported_sound_timer = ticks;
+    }
+
+    /// Run a single frame with the given `input`, executing up to `input.steps` steps.
+    /// With the [`Timing::CosmacVip`] timing, the frame also ends once the
+    /// [`VIP_CYCLES_PER_FRAME`] are consumed.
+    ///
+    /// The frame ends early when the `Processor` is halted, when a breakpoint is
+    /// reached, or when the program finished with a jump to itself. The returned
+    /// [`FrameOutput`] contains everything needed to present the frame.
+    pub fn run_frame(&mut self, input: &FrameInput) -> FrameOutput<'_> {
+        self.run_frame_with(input, &mut ())
+    }
+
+    /// Run a single frame like [`Chip8::run_frame`], calling the `hook` around every step.
+    pub fn run_frame_with(
+        &mut self,
+        input: &FrameInput,
+        hook: &mut impl StepHook,
+    ) -> FrameOutput<'_> {
+        if let Some(keys) = input.keys {
+            for (key_code, pressed) in (0..).zip(keys) {
+                self.update_key_state(key_code, pressed);
+            }
+        }
+
+        let start_cycles = self.processor.cycles;
+        let budget = VIP_CYCLES_PER_FRAME.saturating_sub(self.excess_cycles);
+        let vip_timing = self.processor.timing == Timing::CosmacVip;
+        let mut steps = 0;
+        let mut event = None;
+        while steps < input.steps && !(vip_timing && self.processor.cycles - start_cycles >= budget)
+        {
+            if let Some(fault) = self.processor.fault {
+                event = Some(FrameEvent::Halted(fault));
+                break;
+            }
+            if hook.before_step(self) {
+                event = Some(FrameEvent::Stopped);
+                break;
+            }
+            self.step();
+            steps += 1;
+            let stopped = hook.after_step(self);
+            if let Some(address) = self.processor.skipped_invalid_opcode {
+                event = Some(FrameEvent::InvalidOpcode(address));
+                break;
+            }
+            if stopped {
+                event = Some(FrameEvent::Stopped);
+                break;
+            }
+            if input
+                .breakpoints
+                .iter()
+                .any(|breakpoint| breakpoint.is_hit(self))
+            {
+                event = Some(FrameEvent::Breakpoint(self.processor.pc));
+                break;
+            }
+            if let Some(address) = self.finished() {
+                event = Some(FrameEvent::Finished(address));
+                break;
+            }
+        }
+        let cycles = self.processor.cycles - start_cycles;
+        // a frame that ended early starts the next one afresh
+        self.excess_cycles = cycles.saturating_sub(budget);
+
+        // report a fault that occurred on the last step of the frame
+        if event.is_none() {
+            event = self.processor.fault.map(FrameEvent::Halted);
+        }
+        // there won't be another vertical blank to show what was drawn before halting,
+        // or one that callers wait for after the program finished
+        if self.processor.fault.is_some() || matches!(event, Some(FrameEvent::Finished(_))) {
+            self.latch_display();
+        }
+
+        FrameOutput {
+            framebuffer: &self.front,
+            sound_active: self.bus.clock.sound_timer > 0,
+            steps,
+            cycles,
+            event,
+        }
+    }
+
+    /// Run a frame of up to `steps` steps for headless use, independent of the time of
+    /// the host: the timers tick exactly once, at the end of the frame, whatever the
+    /// [`TimeSource`], and the display is latched. With the vertical blank wait quirk,
+    /// a draw after the first one of the frame waits for the next frame.
+    ///
+    /// The frame ends early when the `Processor` is halted. Unlike [`Chip8::run_frame`],
+    /// keys, breakpoints and the [`Timing`] are left to the caller.
+    pub fn step_frame(&mut self, steps: u32) -> FrameReport {
+        let generation = self.bus.graphics.generation();
+        let source = std::mem::replace(&mut self.bus.clock.source, TimeSource::Manual);
+        let mut executed = 0;
+        while executed < steps && self.processor.fault.is_none() {
+            self.step();
+            executed += 1;
+        }
+        self.bus.clock.source = source;
+        self.tick_timers();
+        self.latch_display();
+
+        FrameReport {
+            steps: executed,
+            drew: self.bus.graphics.generation() != generation,
+            sound_active: self.sound_timer() > 0,
+            fault: self.processor.fault,
+        }
     }
 
-    /// Load the given ROM data into memory.
-    /// This will resize the ROM in place to the correct length
-    /// if it is too large/small.
-    pub fn load_rom_data(&mut self, data: Vec<u8>) {
-        self.bus.memory.load_rom(data);
+    /// Execute up to `steps` steps as fast as possible, returning early once the program
+    /// halts, waits for a key press, finishes, or reaches one of the `breakpoints`.
+    /// Like in [`Chip8::run_frame`], a breakpoint stops before its instruction, which
+    /// is executed by the next call.
+    pub fn run_cycles(&mut self, steps: u32, breakpoints: &[Breakpoint]) -> RunExit {
+        for _ in 0..steps {
+            if let Some(exit) = self.run_exit() {
+                return exit;
+            }
+            self.step();
+            if breakpoints.iter().any(|breakpoint| breakpoint.is_hit(self)) {
+                return RunExit::Breakpoint(self.processor.pc);
+            }
+            if let Some(address) = self.finished() {
+                return RunExit::Finished(address);
+            }
+        }
+        self.run_exit().unwrap_or(RunExit::Completed)
+    }
+
+    /// The reason [`Chip8::run_cycles`] can't execute another step, if any.
+    fn run_exit(&self) -> Option<RunExit> {
+        match self.processor.fault {
+            Some(Fault::Exited) => Some(RunExit::Halted),
+            Some(fault) => Some(RunExit::Fault(fault)),
+            None => self.waiting_for_key().then_some(RunExit::WaitingForKey),
+        }
+    }
+
+    /// Step the interpreter until `stop` returns `true`, or until `max_steps`
+    /// steps have been executed. `stop` is checked after every step.
+    ///
+    /// Returns the amount of steps that were executed.
+    pub fn run_until(&mut self, max_steps: usize, mut stop: impl FnMut(&Self) -> bool) -> usize {
+        for steps in 1..=max_steps {
+            self.step();
+            if stop(self) {
+                return steps;
+            }
+        }
+        max_steps
+    }
+
+    /// Execute the next instruction and, if it calls a subroutine (`2nnn`), run the
+    /// subroutine until it returns, or until `max_steps` steps have been executed.
+    /// Stepping also stops when the program halts or waits for a key press.
+    ///
+    /// Returns the amount of steps that were executed.
+    pub fn step_over(&mut self, max_steps: usize) -> usize {
+        let depth = self.processor.sp;
+        self.run_until(max_steps, |chip8| {
+            chip8.processor.sp <= depth || chip8.run_exit().is_some()
+        })
+    }
+
+    /// Run until the current subroutine returns (`00EE`), or until `max_steps` steps have
+    /// been executed. Stepping also stops when the program halts or waits for a key press,
+    /// and nothing is executed outside of a subroutine.
+    ///
+    /// Returns the amount of steps that were executed.
+    pub fn step_out(&mut self, max_steps: usize) -> usize {
+        let depth = self.processor.sp;
+        if depth == 0 {
+            return 0;
+        }
+        self.run_until(max_steps, |chip8| {
+            chip8.processor.sp < depth || chip8.run_exit().is_some()
+        })
+    }
+
+    /// Load the given ROM data into memory, at the [`Chip8::start_address`].
+    /// The memory after the ROM is zeroed. A ROM that doesn't fit in the memory
+    /// is not loaded, see [`Chip8::set_memory_size`].
+    pub fn load_rom_data(&mut self, data: Vec<u8>) -> Result<(), RomError> {
+        self.bus.memory.load_rom(data, self.processor.start_address)
+    }
+
+    /// Whether instructions are executed, or why not: the program waits for a key press,
+    /// or the `Chip8` is halted.
+    pub fn state(&self) -> ProcessorState {
+        if let Some(reason) = self.processor.fault {
+            ProcessorState::Halted { reason }
+        } else if let Some(register) = self.bus.input.request_register() {
+            ProcessorState::WaitingForKey { register }
+        } else if let Some(address) = self.finished() {
+            ProcessorState::Finished { address }
+        } else {
+            ProcessorState::Running
+        }
+    }
+
+    /// The address of the jump to itself (`1nnn` to its own address) that the program counter
+    /// is at, if it is. Programs commonly end this way, looping forever, e.g. test ROMs
+    /// once they have shown their results.
+    pub fn finished(&self) -> Option<usize> {
+        let pc = self.processor.pc;
+        let opcode = self.memory().get(pc..pc + 2)?;
+        let jump_to_self = decode(u16::from_be_bytes([opcode[0], opcode[1]])) == Instr::Jump(pc);
+        jump_to_self.then_some(pc)
+    }
+
+    /// Returns whether the program is blocked until a key is pressed.
+    /// See [`Chip8::state`].
+    pub fn waiting_for_key(&self) -> bool {
+        self.bus.input.waiting()
+    }
+
+    /// The key pressed while the program waits for one, which it gets once the key is released.
+    pub fn pending_key(&self) -> Option<u8> {
+        self.bus.input.pending_key()
     }
 
     /// Update the input state for the given key code.
@@ -56,22 +455,1153 @@ impl Chip8 {
     /// Reset the state of the `Chip8` instance.
     /// This does not reset the foreground/background colors of the `GraphicsBuffer`.
     pub fn reset(&mut self) {
-        self.bus.graphics.clear();
+        let memory_size = self.bus.memory.size();
+        let read_only = self.bus.memory.read_only_regions().to_vec();
+        let time_source = self.time_source();
+        // leave hi-res mode and select the first plane, keeping the colors
+        let (width, height) = self.processor.variant.display_size();
+        self.bus.graphics.resize(width, height);
+        self.bus.graphics.select_planes(graphics::PLANE_1);
         self.bus = Bus {
-            graphics: self.bus.graphics,
+            graphics: std::mem::take(&mut self.bus.graphics),
             ..Default::default()
         };
-        // create new processor with shift quirk and vblank wait settings retained
-        let shift_quirk_enabled = self.processor.shift_quirk_enabled;
-        let vblank_wait = self.processor.vblank_wait;
-        self.processor = Processor::new();
-        self.processor.shift_quirk_enabled = shift_quirk_enabled;
-        self.processor.vblank_wait = vblank_wait;
+        // create new processor with start address, variant, quirk,
+        // machine code and timer settings retained
+        let start = self.processor.start_address;
+        let old = std::mem::replace(&mut self.processor, Processor::new_with_start(start));
+        self.processor.variant = old.variant;
+        self.processor.quirks = old.quirks;
+        self.processor.machine_code_policy = old.machine_code_policy;
+        self.processor.invalid_opcode_policy = old.invalid_opcode_policy;
+        self.processor.memory_access_policy = old.memory_access_policy;
+        self.processor.timing = old.timing;
+        self.processor.tracing_disabled = old.tracing_disabled;
+        self.processor.symbols = old.symbols;
+        self.excess_cycles = 0;
+        self.processor.machine_code_handlers = old.machine_code_handlers;
+        // the flags are stored outside of the program, on the calculator
+        self.processor.rpl_flags = old.rpl_flags;
+        self.set_deterministic(old.deterministic);
+        self.set_seed(old.seed);
+        self.set_time_source(time_source);
+        self.bus.memory.resize(memory_size.bytes());
+        self.bus.memory.set_read_only_regions(read_only);
+        self.rewind.clear();
+        self.latch_display();
     }
 
     /// Convenience method for resetting the `Chip8` and loading the given ROM.
-    pub fn reset_and_load(&mut self, data: Vec<u8>) {
+    pub fn reset_and_load(&mut self, data: Vec<u8>) -> Result<(), RomError> {
         self.reset();
-        self.load_rom_data(data);
+        self.load_rom_data(data)
+    }
+
+    /// The variant of the platform that is emulated.
+    pub fn variant(&self) -> Variant {
+        self.processor.variant
+    }
+
+    /// Set the variant of the platform that is emulated.
+    /// The memory is resized to the size of the variant, see [`Variant::memory_size`],
+    /// and the display is cleared if the variant starts with a different display size.
+    pub fn set_variant(&mut self, variant: Variant) {
+        if variant.display_size() != self.processor.variant.display_size() {
+            let (width, height) = variant.display_size();
+            self.bus.graphics.resize(width, height);
+        }
+        self.processor.variant = variant;
+        self.set_memory_size(variant.memory_size());
+    }
+
+    /// The size of the memory, which is the size of the variant unless changed.
+    pub fn memory_size(&self) -> MemorySize {
+        self.bus.memory.size()
+    }
+
+    /// Change the size of the memory, e.g. to run large programs written for CHIP-8 with
+    /// the memory of XO-CHIP. The bytes past a smaller size are dropped. The size is kept
+    /// on reset, and changed back to the size of the variant when the variant is set.
+    pub fn set_memory_size(&mut self, size: MemorySize) {
+        self.bus.memory.resize(size.bytes());
+    }
+
+    /// The regions of the memory that instructions can't write to, none unless changed.
+    pub fn read_only_regions(&self) -> &[ReadOnlyRegion] {
+        self.bus.memory.read_only_regions()
+    }
+
+    /// Set the regions of the memory that instructions can't write to, e.g.
+    /// [`ReadOnlyRegion::fonts`] to catch programs clobbering the fonts.
+    /// The regions are kept when the `Chip8` is reset.
+    pub fn set_read_only_regions(&mut self, regions: Vec<ReadOnlyRegion>) {
+        self.bus.memory.set_read_only_regions(regions);
+    }
+
+    /// The bytes that are pinned to a value, none unless changed.
+    pub fn freezes(&self) -> &[Freeze] {
+        &self.freezes
+    }
+
+    /// Pin bytes of memory to a value, like the "infinite lives" cheats of games. The values
+    /// are written right away and again after every step, so the program never sees
+    /// another value for long. Freezes past the end of memory are ignored, and they are
+    /// kept when the `Chip8` is reset.
+    pub fn set_freezes(&mut self, freezes: Vec<Freeze>) {
+        self.freezes = freezes;
+        self.apply_freezes();
+    }
+
+    /// Write a single byte of memory once, a cheat the program may overwrite again,
+    /// unlike a [`Freeze`]. See [`Chip8::write_memory`] to write more bytes.
+    pub fn poke(&mut self, address: usize, value: u8) {
+        self.write_memory(address, &[value]);
+    }
+
+    /// Whether each byte of memory was executed as part of an instruction since the ROM
+    /// was loaded, indexed by address. This is the code coverage of the program, e.g. to
+    /// find the branches that playtesting never reached.
+    pub fn coverage(&self) -> &[bool] {
+        self.bus.memory.executed()
+    }
+
+    /// The write of the last executed instruction to memory that was executed before
+    /// since the ROM was loaded, e.g. by self-modifying code.
+    pub fn code_write(&self) -> Option<CodeWrite> {
+        self.processor.code_write
+    }
+
+    /// The address programs are loaded at and start at, 0x200 unless changed.
+    pub fn start_address(&self) -> usize {
+        self.processor.start_address
+    }
+
+    /// Set the address programs are loaded at and start at, e.g. [`rom::ETI_660_START`].
+    /// This takes effect when the `Chip8` is reset, and is kept from then on.
+    pub fn set_start_address(&mut self, start: usize) {
+        self.processor.start_address = start;
+    }
+
+    /// The quirk settings the `Chip8` runs with.
+    pub fn quirks(&self) -> Quirks {
+        self.processor.quirks
+    }
+
+    /// Change the quirk settings. The settings are kept when the `Chip8` is reset.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.processor.quirks = quirks;
+    }
+
+    /// The settings of the deterministic mode, `None` if it is disabled.
+    pub fn deterministic(&self) -> Option<Deterministic> {
+        self.processor.deterministic
+    }
+
+    /// Enable the deterministic mode with the given settings, or disable it with `None`.
+    /// This also sets the seed of the random numbers, see [`Chip8::set_seed`]. The settings
+    /// are kept when the `Chip8` is reset, which also restarts the random numbers.
+    pub fn set_deterministic(&mut self, deterministic: Option<Deterministic>) {
+        self.processor.deterministic = deterministic;
+        self.set_seed(deterministic.map(|settings| settings.seed));
+        self.bus.clock.source = match deterministic {
+            Some(settings) => TimeSource::Steps(settings.steps_per_tick.max(1)),
+            None => TimeSource::Host,
+        };
+    }
+
+    /// What ticks the delay and sound timers.
+    pub fn time_source(&self) -> TimeSource {
+        self.bus.clock.source
+    }
+
+    /// Change what ticks the delay and sound timers. The source is kept when the `Chip8`
+    /// is reset, and replaced by [`Chip8::set_deterministic`].
+    pub fn set_time_source(&mut self, source: TimeSource) {
+        self.bus.clock.source = source;
+    }
+
+    /// Tick the delay and sound timers once, as if 1/60 s passed, which also makes the next
+    /// step a vertical blank. This is how the timers are driven with [`TimeSource::Manual`],
+    /// but it works with every source.
+    pub fn tick_timers(&mut self) {
+        self.bus.clock.tick_60hz();
+    }
+
+    /// The seed of the random numbers generated by `Cxnn`, `None` if they come from the host.
+    pub fn seed(&self) -> Option<u64> {
+        self.processor.seed
+    }
+
+    /// Seed the random numbers generated by `Cxnn`, so replays and tests get the same
+    /// numbers on every run, or take them from the host again with `None`. Unlike
+    /// [`Chip8::set_deterministic`], the timers still tick with the time of the host.
+    /// The seed is kept when the `Chip8` is reset, which restarts the random numbers.
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.processor.seed = seed;
+        // the xorshift state must never be zero
+        self.processor.random_state = seed.map(|seed| seed.max(1));
+    }
+
+    /// How calls to machine code routines (`0nnn`) are handled.
+    pub fn machine_code_policy(&self) -> MachineCodePolicy {
+        self.processor.machine_code_policy
+    }
+
+    /// Set how calls to machine code routines (`0nnn`) are handled.
+    pub fn set_machine_code_policy(&mut self, policy: MachineCodePolicy) {
+        self.processor.machine_code_policy = policy;
+    }
+
+    /// How opcodes that aren't an instruction of the variant are handled.
+    pub fn invalid_opcode_policy(&self) -> InvalidOpcodePolicy {
+        self.processor.invalid_opcode_policy
+    }
+
+    /// Set how opcodes that aren't an instruction of the variant are handled,
+    /// e.g. [`InvalidOpcodePolicy::Break`] for a debugger. This is kept on reset.
+    pub fn set_invalid_opcode_policy(&mut self, policy: InvalidOpcodePolicy) {
+        self.processor.invalid_opcode_policy = policy;
+    }
+
+    /// How instructions accessing memory at `I` handle addresses past the end of memory.
+    pub fn memory_access_policy(&self) -> MemoryAccessPolicy {
+        self.processor.memory_access_policy
+    }
+
+    /// Set how instructions accessing memory at `I` handle addresses past the end of memory,
+    /// e.g. [`MemoryAccessPolicy::Fault`] to catch malformed programs. This is kept on reset.
+    pub fn set_memory_access_policy(&mut self, policy: MemoryAccessPolicy) {
+        self.processor.memory_access_policy = policy;
+    }
+
+    /// How the amount of steps executed in a frame is decided.
+    pub fn timing(&self) -> Timing {
+        self.processor.timing
+    }
+
+    /// Set how the amount of steps executed in a frame is decided. This is kept on reset.
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.processor.timing = timing;
+        self.excess_cycles = 0;
+    }
+
+    /// The COSMAC VIP machine cycles consumed since the last reset, counted with any timing.
+    /// See [`Timing::CosmacVip`].
+    pub fn cycles(&self) -> u64 {
+        self.processor.cycles
+    }
+
+    /// The RPL user flags, which SUPER-CHIP programs store registers in to keep them
+    /// after exiting. They are kept on reset, and frontends may persist them per program.
+    pub fn rpl_flags(&self) -> [u8; 16] {
+        self.processor.rpl_flags
+    }
+
+    /// Set the RPL user flags, e.g. to the ones a program stored in an earlier session.
+    pub fn set_rpl_flags(&mut self, flags: [u8; 16]) {
+        self.processor.rpl_flags = flags;
+    }
+
+    /// The program counter, the address of the next instruction.
+    pub fn pc(&self) -> usize {
+        self.processor.pc
+    }
+
+    /// The value of the index register `I`.
+    pub fn index(&self) -> usize {
+        self.processor.i
+    }
+
+    /// The values of the `V0` to `VF` registers.
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.processor.v
+    }
+
+    /// The return addresses on the stack, the most recent call last.
+    pub fn stack(&self) -> &[usize] {
+        &self.processor.stack[..self.processor.sp]
+    }
+
+    /// The subroutine calls on the stack, with where they were made from and where they
+    /// return to, the most recent call last. See [`Chip8::stack`] for the raw stack.
+    pub fn call_stack(&self) -> Vec<CallFrame> {
+        self.processor.call_stack(&self.bus)
+    }
+
+    /// The opcode at the program counter, which is executed by the next step.
+    /// Returns `None` if the program counter is past the end of memory.
+    pub fn next_opcode(&self) -> Option<usize> {
+        self.processor.peek_opcode(&self.bus)
+    }
+
+    /// The most recently executed instructions, the latest first.
+    /// Only the last one is kept, without a description, while tracing is disabled.
+    pub fn instructions(&self) -> &VecDeque<Instruction> {
+        &self.processor.instructions
+    }
+
+    /// Whether the executed instructions are traced, see [`Chip8::set_tracing`].
+    pub fn tracing(&self) -> bool {
+        self.processor.tracing()
+    }
+
+    /// Enable or disable the trace of the executed instructions, see [`Chip8::instructions`].
+    /// It is enabled by default, and can be disabled to run faster while it isn't shown.
+    /// The setting is kept when the `Chip8` is reset.
+    pub fn set_tracing(&mut self, tracing: bool) {
+        self.processor.set_tracing(tracing);
+    }
+
+    /// The names of the addresses of the program, see [`Chip8::set_symbols`].
+    pub fn symbols(&self) -> &Symbols {
+        &self.processor.symbols
+    }
+
+    /// Name the addresses of the program with the given symbols, e.g. the labels of its
+    /// source, so the descriptions of the traced instructions show the names of the
+    /// addresses they jump to, call or point `I` at. The symbols are kept when the `Chip8`
+    /// is reset, so they should be replaced when another program is loaded.
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.processor.symbols = Arc::new(symbols);
+    }
+
+    /// The amount of steps that are remembered to step back through, see [`Chip8::step_back`].
+    pub fn rewind_depth(&self) -> usize {
+        self.rewind.depth()
+    }
+
+    /// Remember the state before each of the last `depth` steps, so they can be undone with
+    /// [`Chip8::step_back`] and [`Chip8::rewind`]. Every step then takes a copy of the whole
+    /// state, so this is disabled (`0`) by default. The depth is kept when the `Chip8` is
+    /// reset, but the remembered steps are not.
+    pub fn set_rewind_depth(&mut self, depth: usize) {
+        self.rewind.set_depth(depth);
+    }
+
+    /// The amount of steps that can currently be undone.
+    pub fn rewindable_steps(&self) -> usize {
+        self.rewind.len()
+    }
+
+    /// Undo the last step, returning `false` if there is no remembered step to undo.
+    pub fn step_back(&mut self) -> bool {
+        self.rewind(1) == 1
+    }
+
+    /// Undo up to `steps` of the last steps, returning the amount of steps undone.
+    /// The settings of the host, like the [`TimeSource`] and the policies, are not undone.
+    pub fn rewind(&mut self, steps: usize) -> usize {
+        let mut snapshot = None;
+        let mut undone = 0;
+        while undone < steps {
+            match self.rewind.pop() {
+                Some(earlier) => snapshot = Some(earlier),
+                None => break,
+            }
+            undone += 1;
+        }
+        if let Some(snapshot) = snapshot {
+            self.restore(snapshot);
+        }
+        undone
+    }
+
+    /// Replace the state with the given `snapshot`, keeping the settings of the host.
+    fn restore(&mut self, snapshot: Snapshot) {
+        let Snapshot {
+            mut processor,
+            mut bus,
+        } = snapshot;
+        let old = &mut self.processor;
+        processor.machine_code_handlers = std::mem::take(&mut old.machine_code_handlers);
+        processor.invalid_opcode_policy = old.invalid_opcode_policy;
+        processor.memory_access_policy = old.memory_access_policy;
+        processor.timing = old.timing;
+        processor.tracing_disabled = old.tracing_disabled;
+        processor.symbols = Arc::clone(&old.symbols);
+        processor.deterministic = old.deterministic;
+        processor.seed = old.seed;
+        bus.clock.source = self.bus.clock.source;
+        bus.memory
+            .set_read_only_regions(self.bus.memory.read_only_regions().to_vec());
+        self.processor = processor;
+        self.bus = bus;
+        self.excess_cycles = 0;
+        self.latch_display();
+    }
+
+    /// Write every instruction executed from now on to `writer`, one line each: the address,
+    /// the opcode, then the registers the instruction changed with their new values, e.g.
+    /// `0204 7001 V0=02`, so complete traces can be compared with other emulators. Unlike
+    /// [`Chip8::instructions`], nothing is dropped, and it doesn't depend on
+    /// [`Chip8::set_tracing`]. The writer is buffered, and kept when the `Chip8` is reset.
+    ///
+    /// Replacing the writer, e.g. with `None` once done, flushes the previous one.
+    pub fn set_trace_writer(&mut self, writer: Option<Box<dyn Write + Send>>) {
+        let old = std::mem::replace(&mut self.trace_writer, writer.map(TraceWriter::new));
+        if let Some(mut old) = old {
+            if let Err(e) = old.flush() {
+                log::error!("Failed to write the execution trace: {e}");
+            }
+        }
+    }
+
+    /// The fault that halted the `Chip8`, if any. No instructions are executed while halted.
+    /// See [`Chip8::state`].
+    pub fn fault(&self) -> Option<Fault> {
+        self.processor.fault
+    }
+
+    /// A hash of the registers, stack, timers, memory and display, to cheaply check whether
+    /// two `Chip8`s are in the same state, e.g. in regression tests or to keep netplay peers
+    /// in sync. Settings like the quirks, colors and breakpoints aren't included.
+    ///
+    /// The hash is the same on every platform, but may change between releases.
+    pub fn state_hash(&self) -> u64 {
+        report::state_hash(self)
+    }
+
+    /// The whole memory of the `Chip8`, including the interpreter area with the fonts.
+    pub fn memory(&self) -> &[u8] {
+        self.bus.memory.as_slice()
+    }
+
+    /// Write the `data` to memory, starting at `address`. Bytes past the end of memory are dropped.
+    pub fn write_memory(&mut self, address: usize, data: &[u8]) {
+        let memory = self.bus.memory.as_mut_slice();
+        if let Some(memory) = memory.get_mut(address..) {
+            for (byte, &value) in memory.iter_mut().zip(data) {
+                *byte = value;
+            }
+        }
+    }
+
+    /// The display of the `Chip8` as it is being drawn, which may show a partially drawn frame.
+    pub fn display(&self) -> &GraphicsBuffer {
+        &self.bus.graphics
+    }
+
+    /// The display as it was at the last vertical blank, when the program had finished
+    /// drawing the previous frame. This is what [`Chip8::run_frame`] returns to be presented.
+    pub fn latched_display(&self) -> &GraphicsBuffer {
+        &self.front
+    }
+
+    /// Copy the display to the [`Chip8::latched_display`], as if a vertical blank happened.
+    pub fn latch_display(&mut self) {
+        self.front.clone_from(&self.bus.graphics);
+    }
+
+    /// Set the color of the pixels that are on.
+    pub fn set_foreground_color(&mut self, color: RGB8) {
+        self.bus.graphics.set_foreground_color(color);
+        self.front.set_foreground_color(color);
+    }
+
+    /// Set the color of the pixels that are off.
+    pub fn set_background_color(&mut self, color: RGB8) {
+        self.bus.graphics.set_background_color(color);
+        self.front.set_background_color(color);
+    }
+
+    /// The colors of the pixels on each combination of planes, see [`GraphicsBuffer::palette`].
+    pub fn palette(&self) -> [RGB8; 4] {
+        self.bus.graphics.palette()
+    }
+
+    /// Set the colors of the pixels on each combination of planes, e.g. to one of the
+    /// [`OCTO_PALETTES`](crate::graphics::OCTO_PALETTES).
+    pub fn set_palette(&mut self, palette: [RGB8; 4]) {
+        self.bus.graphics.set_palette(palette);
+        self.front.set_palette(palette);
+    }
+
+    /// The value of the delay timer.
+    pub fn delay_timer(&self) -> u8 {
+        self.bus.clock.delay_timer
+    }
+
+    /// The value of the sound timer. The tone plays while it is above zero.
+    pub fn sound_timer(&self) -> u8 {
+        self.bus.clock.sound_timer
+    }
+
+    /// Set the `sink` receiving the changes of the sound timer, dropping the previous one.
+    /// A sound timer that is already active is reported to the new sink on the next step.
+    pub fn set_sound_sink(&mut self, sink: Option<Box<dyn SoundSink>>) {
+        self.sound_sink = sink;
+        self.reported_sound_timer = 0;
+    }
+
+    /// Returns whether the key with the given key code is pressed.
+    pub fn is_key_pressed(&self, key_code: u8) -> bool {
+        self.bus.input.is_key_pressed(key_code)
+    }
+
+    /// The `Processor`, for debuggers that need full access.
+    #[cfg(feature = "raw")]
+    pub fn processor(&self) -> &Processor {
+        &self.processor
+    }
+
+    /// The `Processor`, for debuggers that need to change its state.
+    #[cfg(feature = "raw")]
+    pub fn processor_mut(&mut self) -> &mut Processor {
+        &mut self.processor
+    }
+
+    /// The `Bus` with the memory, display, input and timers, for debuggers that need full access.
+    #[cfg(feature = "raw")]
+    pub fn bus(&self) -> &Bus {
+        &self.bus
+    }
+
+    /// The `Bus`, for debuggers that need to change its state.
+    #[cfg(feature = "raw")]
+    pub fn bus_mut(&mut self) -> &mut Bus {
+        &mut self.bus
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Write,
+        sync::{Arc, Mutex},
+    };
+
+    use super::{
+        Breakpoint, Chip8, Comparison, Condition, Deterministic, Fault, FrameEvent, FrameInput,
+        Freeze, InvalidOpcodePolicy, MemorySize, Operand, ProcessorState, Quirks, RomError,
+        RunExit, SoundSink, Symbols, TimeSource, Timing, Variant, RGB8,
+    };
+
+    /// Create a `Chip8` with the given opcodes loaded as its ROM.
+    fn chip8_with_rom(opcodes: &[u16]) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8
+            .load_rom_data(opcodes.iter().flat_map(|op| op.to_be_bytes()).collect())
+            .unwrap();
+        chip8
+    }
+
+    #[test]
+    fn test_run_until_stops_when_condition_is_met() {
+        // three register loads followed by a draw
+        let mut chip8 = chip8_with_rom(&[0x6001, 0x6102, 0x6203, 0xD011]);
+        let steps = chip8.run_until(100, |chip8| {
+            matches!(
+                chip8.processor.instructions.front(),
+                Some(instruction) if instruction.opcode & 0xF000 == 0xD000
+            )
+        });
+        assert_eq!(steps, 4);
+        assert_eq!(chip8.processor.pc, 0x208);
+    }
+
+    #[test]
+    fn test_quirks_are_kept_on_reset() {
+        let mut chip8 = Chip8::new();
+        let quirks = Quirks {
+            shift: true,
+            vertical_wrap: true,
+            ..Quirks::default()
+        };
+        chip8.set_quirks(quirks);
+        chip8.reset();
+        assert_eq!(chip8.quirks(), quirks);
+    }
+
+    #[test]
+    fn test_start_address() {
+        let mut chip8 = Chip8::new();
+        chip8.set_start_address(crate::rom::ETI_660_START);
+        chip8.reset_and_load(vec![0x60, 0x01]).unwrap();
+        assert_eq!(chip8.pc(), 0x600);
+        assert_eq!(&chip8.memory()[0x600..0x602], &[0x60, 0x01]);
+        assert_eq!(chip8.memory()[0x200], 0);
+
+        chip8.step();
+        chip8.reset();
+        assert_eq!(chip8.start_address(), 0x600);
+        assert_eq!(chip8.pc(), 0x600);
+    }
+
+    #[test]
+    fn test_memory_size() {
+        let mut chip8 = Chip8::new();
+        let rom = vec![0; 0x1000];
+        assert_eq!(
+            chip8.load_rom_data(rom.clone()),
+            Err(RomError::TooLarge {
+                size: 0x1000,
+                capacity: 0xE00
+            })
+        );
+
+        chip8.set_memory_size(MemorySize::Extended);
+        chip8.reset_and_load(rom).unwrap();
+        assert_eq!(chip8.memory_size(), MemorySize::Extended);
+        assert_eq!(chip8.memory().len(), 0x10000);
+
+        // setting the variant changes the memory to its size
+        chip8.set_variant(Variant::SuperChip);
+        assert_eq!(chip8.memory_size(), MemorySize::Standard);
+    }
+
+    #[test]
+    fn test_write_memory_drops_bytes_past_the_end() {
+        let mut chip8 = Chip8::new();
+        let end = chip8.memory().len();
+        chip8.write_memory(end - 2, &[1, 2, 3]);
+        assert_eq!(&chip8.memory()[end - 2..], &[1, 2]);
+        chip8.write_memory(end, &[4]);
+    }
+
+    #[test]
+    fn test_run_until_max_steps() {
+        // jump to self
+        let mut chip8 = chip8_with_rom(&[0x1200]);
+        let steps = chip8.run_until(50, |_| false);
+        assert_eq!(steps, 50);
+        assert_eq!(chip8.processor.instructions.len(), 50);
+    }
+
+    #[test]
+    fn test_run_frame_finished() {
+        // load V0, then jump to self
+        let mut chip8 = chip8_with_rom(&[0x6001, 0x1202]);
+        let input = FrameInput {
+            steps: 10,
+            ..Default::default()
+        };
+        let output = chip8.run_frame(&input);
+        assert_eq!(output.steps, 1);
+        assert_eq!(output.event, Some(FrameEvent::Finished(0x202)));
+        assert_eq!(chip8.state(), ProcessorState::Finished { address: 0x202 });
+
+        // the jump is still executed, once per frame
+        let output = chip8.run_frame(&input);
+        assert_eq!(output.steps, 1);
+        assert_eq!(chip8.pc(), 0x202);
+    }
+
+    #[test]
+    fn test_sound_sink() {
+        struct Recorder(Arc<Mutex<Vec<String>>>);
+
+        impl SoundSink for Recorder {
+            fn beep_started(&mut self) {
+                self.0.lock().unwrap().push("started".to_string());
+            }
+
+            fn beep_stopped(&mut self) {
+                self.0.lock().unwrap().push("stopped".to_string());
+            }
+
+            fn remaining_ticks(&mut self, ticks: u8) {
+                self.0.lock().unwrap().push(ticks.to_string());
+            }
+        }
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut chip8 = chip8_with_rom(&[0x6002, 0xF018, 0x1204]);
+        chip8.set_sound_sink(Some(Box::new(Recorder(events.clone()))));
+        // tick the timers on every step
+        chip8.set_time_source(TimeSource::Steps(1));
+        for _ in 0..5 {
+            chip8.step();
+        }
+        assert_eq!(
+            *events.lock().unwrap(),
+            ["started", "2", "1", "0", "stopped"]
+        );
+    }
+
+    #[test]
+    fn test_manual_time_source() {
+        let mut chip8 = chip8_with_rom(&[0x6005, 0xF015, 0x1204]);
+        chip8.set_time_source(TimeSource::Manual);
+        chip8.run_until(10, |_| false);
+        // no time passes without ticks
+        assert_eq!(chip8.delay_timer(), 5);
+
+        chip8.tick_timers();
+        chip8.tick_timers();
+        assert_eq!(chip8.delay_timer(), 3);
+
+        // the step after a tick is a vertical blank, which latches the display
+        chip8.bus.graphics.draw_byte(0, 0, 0x80);
+        chip8.step();
+        assert!(chip8.latched_display().is_pixel_on(0, 0));
+
+        chip8.reset();
+        assert_eq!(chip8.time_source(), TimeSource::Manual);
+    }
+
+    #[test]
+    fn test_step_frame() {
+        // set the delay timer, then draw the 0 glyph twice while waiting for the vertical blank
+        let mut chip8 = chip8_with_rom(&[0x6105, 0xF115, 0xF029, 0xD005, 0xD005, 0x120A]);
+        chip8.set_quirks(Quirks {
+            vblank_wait: true,
+            ..Quirks::default()
+        });
+        let report = chip8.step_frame(10);
+        assert_eq!(report.steps, 10);
+        assert!(!report.sound_active);
+        assert_eq!(report.fault, None);
+        // the first draw waits for the vertical blank at the end of the frame
+        assert!(!report.drew);
+        assert_eq!(chip8.pc(), 0x206);
+        // the timers tick once per frame, however many steps it has
+        assert_eq!(chip8.delay_timer(), 4);
+
+        let report = chip8.step_frame(10);
+        assert!(report.drew);
+        assert!(chip8.latched_display().is_pixel_on(0, 0));
+        // the second draw waits for the next frame
+        assert_eq!(chip8.pc(), 0x208);
+        assert_eq!(chip8.delay_timer(), 3);
+
+        let report = chip8.step_frame(10);
+        assert!(report.drew);
+        assert!(!chip8.latched_display().is_pixel_on(0, 0));
+        assert_eq!(chip8.pc(), 0x20A);
+
+        let report = chip8.step_frame(10);
+        assert!(!report.drew);
+
+        chip8.processor.pc = 0xFFF;
+        let report = chip8.step_frame(10);
+        assert_eq!(report.steps, 1);
+        assert_eq!(report.fault, Some(Fault::PcOutOfBounds(0xFFF)));
+    }
+
+    #[test]
+    fn test_run_cycles() {
+        // count in V0, wait for a key in V1, exit
+        let mut chip8 = chip8_with_rom(&[0x7001, 0x7001, 0xF10A, 0x00FD]);
+        chip8.set_variant(Variant::SuperChip);
+        let breakpoint = Breakpoint::at(0x202);
+        assert_eq!(
+            chip8.run_cycles(10, &[breakpoint]),
+            RunExit::Breakpoint(0x202)
+        );
+        assert_eq!(chip8.registers()[0], 1);
+        // the instruction at the breakpoint is executed by the next call
+        assert_eq!(chip8.run_cycles(1, &[breakpoint]), RunExit::Completed);
+        assert_eq!(chip8.registers()[0], 2);
+        assert_eq!(chip8.run_cycles(10, &[]), RunExit::WaitingForKey);
+
+        chip8.update_key_state(0x5, true);
+        chip8.update_key_state(0x5, false);
+        assert_eq!(chip8.run_cycles(10, &[]), RunExit::Halted);
+        assert_eq!(chip8.registers()[1], 0x5);
+
+        let mut chip8 = chip8_with_rom(&[0x1202, 0x1202]);
+        assert_eq!(chip8.run_cycles(10, &[]), RunExit::Finished(0x202));
+        chip8.processor.pc = 0xFFF;
+        assert_eq!(
+            chip8.run_cycles(10, &[]),
+            RunExit::Fault(Fault::PcOutOfBounds(0xFFF))
+        );
+    }
+
+    #[test]
+    fn test_step_over_and_out() {
+        // call a subroutine that calls another one, which sets V1
+        let rom = [0x2206, 0x6001, 0x1204, 0x220A, 0x00EE, 0x6105, 0x00EE];
+        let mut chip8 = chip8_with_rom(&rom);
+        assert_eq!(chip8.step_over(100), 5);
+        assert_eq!((chip8.pc(), chip8.registers()[1]), (0x202, 5));
+        // anything but a call is a single step
+        assert_eq!(chip8.step_over(100), 1);
+        assert_eq!(chip8.pc(), 0x204);
+        assert_eq!(chip8.step_out(100), 0);
+        assert_eq!(chip8.pc(), 0x204);
+
+        let mut chip8 = chip8_with_rom(&rom);
+        chip8.step();
+        chip8.step();
+        assert_eq!(chip8.stack(), [0x202, 0x208]);
+        assert_eq!(chip8.step_out(100), 2);
+        assert_eq!(chip8.pc(), 0x208);
+        assert_eq!(chip8.step_out(100), 1);
+        assert_eq!(chip8.pc(), 0x202);
+
+        // the steps are limited
+        let mut chip8 = chip8_with_rom(&[0x2202, 0x1202]);
+        assert_eq!(chip8.step_over(100), 100);
+    }
+
+    #[test]
+    fn test_rewind() {
+        // count up in V0
+        let mut chip8 = chip8_with_rom(&[0x7001, 0x1200]);
+        chip8.run_until(10, |_| false);
+        assert!(!chip8.step_back());
+
+        chip8.set_rewind_depth(4);
+        chip8.set_timing(Timing::CosmacVip);
+        chip8.run_until(6, |_| false);
+        assert_eq!(chip8.registers()[0], 8);
+        assert_eq!(chip8.rewindable_steps(), 4);
+        assert!(chip8.step_back());
+        assert_eq!((chip8.pc(), chip8.registers()[0]), (0x202, 8));
+        assert!(chip8.step_back());
+        assert_eq!((chip8.pc(), chip8.registers()[0]), (0x200, 7));
+        // only the remembered steps are undone, and the settings stay
+        assert_eq!(chip8.rewind(10), 2);
+        assert_eq!((chip8.pc(), chip8.registers()[0]), (0x200, 6));
+        assert_eq!(chip8.timing(), Timing::CosmacVip);
+        assert_eq!(chip8.rewind(1), 0);
+
+        chip8.step();
+        chip8.reset();
+        assert_eq!(chip8.rewindable_steps(), 0);
+        assert_eq!(chip8.rewind_depth(), 4);
+    }
+
+    #[test]
+    fn test_trace_writer() {
+        /// A writer whose output can still be read after it was given to the `Chip8`.
+        #[derive(Clone, Default)]
+        struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for SharedBuffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // set V3 and I, wait for a key in V0, then add it to V3
+        let mut chip8 = chip8_with_rom(&[0x631F, 0xA300, 0xF00A, 0x8304]);
+        let buffer = SharedBuffer::default();
+        chip8.set_trace_writer(Some(Box::new(buffer.clone())));
+        chip8.run_until(4, |_| false);
+        chip8.update_key_state(0x1, true);
+        chip8.update_key_state(0x1, false);
+        chip8.step();
+        chip8.set_trace_writer(None);
+
+        let trace = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            trace,
+            "0200 631F V3=1F\n0202 A300 I=0300\n0204 F00A\n0206 8304 V0=01 V3=20\n"
+        );
+    }
+
+    #[test]
+    fn test_coverage() {
+        // skip the jump away, then loop
+        let mut chip8 = chip8_with_rom(&[0x3000, 0x1234, 0x1206, 0x1206]);
+        chip8.run_until(10, |_| false);
+        let coverage = &chip8.coverage()[0x200..0x208];
+        assert_eq!(coverage, [true, true, false, false, true, true, true, true]);
+        assert!(!chip8.coverage()[0x1FF]);
+
+        chip8.reset_and_load(vec![0x00, 0xE0]).unwrap();
+        assert!(!chip8.coverage().contains(&true));
+    }
+
+    #[test]
+    fn test_symbols() {
+        // call the subroutine, which points I at the sprite and returns
+        let mut chip8 = Chip8::new();
+        let mut symbols = Symbols::new();
+        symbols.insert(0x204, "draw");
+        symbols.insert(0x208, "sprite");
+        chip8.set_symbols(symbols);
+        chip8.reset();
+        chip8
+            .load_rom_data(vec![
+                0x22, 0x04, 0x12, 0x02, 0xA2, 0x08, 0x00, 0xEE, 0xFF, 0x00,
+            ])
+            .unwrap();
+        assert_eq!(chip8.symbols().name(0x204), Some("draw"));
+
+        chip8.run_until(3, |_| false);
+        let displays: Vec<_> = chip8
+            .instructions()
+            .iter()
+            .map(|instruction| instruction.display.as_str())
+            .collect();
+        assert_eq!(
+            displays[..3],
+            [
+                "Return to addr 0x0202",
+                "Set I register to sprite (0x0208)",
+                "Call subroutine at draw (0x0204)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_vip_timing() {
+        // jump back and forth, every jump takes 52 cycles
+        let mut chip8 = chip8_with_rom(&[0x1202, 0x1200]);
+        let input = FrameInput {
+            steps: 10,
+            ..Default::default()
+        };
+        let output = chip8.run_frame(&input);
+        assert_eq!((output.steps, output.cycles), (10, 520));
+
+        chip8.set_timing(Timing::CosmacVip);
+        let input = FrameInput {
+            steps: u32::MAX,
+            ..Default::default()
+        };
+        let output = chip8.run_frame(&input);
+        assert_eq!((output.steps, output.cycles), (34, 34 * 52));
+        // the cycles past the end of the frame are taken from the next one
+        let output = chip8.run_frame(&input);
+        assert_eq!(output.steps, 34);
+        assert_eq!(chip8.cycles(), 520 + 2 * 34 * 52);
+    }
+
+    #[test]
+    fn test_run_frame() {
+        // load V0 and V1, then jump back and forth
+        let mut chip8 = chip8_with_rom(&[0x6001, 0x6102, 0x1206, 0x1204]);
+        let mut input = FrameInput {
+            steps: 10,
+            keys: Some([true; 16]),
+            breakpoints: vec![Breakpoint::at(0x204)],
+        };
+        let output = chip8.run_frame(&input);
+        assert_eq!(output.steps, 2);
+        assert_eq!(output.event, Some(FrameEvent::Breakpoint(0x204)));
+        assert!(!output.sound_active);
+        assert!(chip8.bus.input.is_key_pressed(0xF));
+
+        input.breakpoints.clear();
+        let output = chip8.run_frame(&input);
+        assert_eq!(output.steps, 10);
+        assert_eq!(output.event, None);
+
+        // break at any address once the condition holds
+        let mut chip8 = chip8_with_rom(&[0x6001, 0x6102, 0x1206, 0x1204]);
+        input.breakpoints = vec![Breakpoint::when(Condition {
+            operand: Operand::V(1),
+            comparison: Comparison::Equal,
+            value: 2,
+        })];
+        let output = chip8.run_frame(&input);
+        assert_eq!(output.steps, 2);
+        assert_eq!(output.event, Some(FrameEvent::Breakpoint(0x204)));
+    }
+
+    #[test]
+    fn test_run_frame_halted() {
+        // jump to the end of memory
+        let mut chip8 = chip8_with_rom(&[0x1FFF]);
+        let input = FrameInput {
+            steps: 10,
+            ..Default::default()
+        };
+        let output = chip8.run_frame(&input);
+        assert_eq!(output.steps, 2);
+        assert_eq!(
+            output.event,
+            Some(FrameEvent::Halted(Fault::PcOutOfBounds(0xFFF)))
+        );
+    }
+
+    #[test]
+    fn test_seed() {
+        // random numbers in V0
+        let rom = [0xC0, 0xFF].repeat(8);
+        let random_numbers = |chip8: &mut Chip8| {
+            let mut numbers = Vec::new();
+            chip8.run_until(8, |chip8| {
+                numbers.push(chip8.processor.v[0]);
+                false
+            });
+            numbers
+        };
+        let mut chip8 = Chip8::new();
+        chip8.set_seed(Some(42));
+        chip8.load_rom_data(rom.clone()).unwrap();
+        let numbers = random_numbers(&mut chip8);
+
+        // the seed is kept on reset, which restarts the random numbers
+        chip8.reset_and_load(rom).unwrap();
+        assert_eq!(chip8.seed(), Some(42));
+        assert_eq!(random_numbers(&mut chip8), numbers);
+        assert_eq!(chip8.deterministic(), None);
+    }
+
+    #[test]
+    fn test_run_frame_latches_display() {
+        // draw the 0 glyph, then jump back and forth
+        let mut chip8 = chip8_with_rom(&[0x6000, 0xF029, 0xD015, 0x1208, 0x1206]);
+        chip8.set_deterministic(Some(Deterministic {
+            seed: 0,
+            steps_per_tick: 4,
+        }));
+        let input = FrameInput {
+            steps: 4,
+            ..Default::default()
+        };
+        // the vertical blank happens before the last step of the frame, after the draw
+        let output = chip8.run_frame(&input);
+        assert!(output.framebuffer.is_pixel_on(0, 0));
+
+        // a draw after the vertical blank only shows up in the next frame
+        chip8.processor.pc = 0x204;
+        chip8.processor.v[0] = 8;
+        let output = chip8.run_frame(&FrameInput {
+            steps: 1,
+            ..input.clone()
+        });
+        assert!(!output.framebuffer.is_pixel_on(8, 0));
+        assert!(chip8.display().is_pixel_on(8, 0));
+        chip8.run_frame(&input);
+        assert!(chip8.latched_display().is_pixel_on(8, 0));
+    }
+
+    #[test]
+    fn test_invalid_opcode_policy() {
+        // an invalid opcode, then jump to self
+        let input = FrameInput {
+            steps: 10,
+            ..Default::default()
+        };
+        let mut chip8 = chip8_with_rom(&[0xE000, 0x1202]);
+        assert_eq!(
+            chip8.run_frame(&input).event,
+            Some(FrameEvent::Finished(0x202))
+        );
+
+        chip8.set_invalid_opcode_policy(InvalidOpcodePolicy::Break);
+        chip8.reset_and_load(vec![0xE0, 0x00, 0x12, 0x02]).unwrap();
+        let output = chip8.run_frame(&input);
+        assert_eq!(output.event, Some(FrameEvent::InvalidOpcode(0x200)));
+        assert_eq!(output.steps, 1);
+        assert_eq!(chip8.pc(), 0x202);
+
+        chip8.set_invalid_opcode_policy(InvalidOpcodePolicy::Halt);
+        chip8.reset_and_load(vec![0xE0, 0x00, 0x12, 0x02]).unwrap();
+        let fault = Fault::InvalidOpcode {
+            address: 0x200,
+            opcode: 0xE000,
+        };
+        assert_eq!(
+            chip8.run_frame(&input).event,
+            Some(FrameEvent::Halted(fault))
+        );
+        assert_eq!(chip8.pc(), 0x200);
+    }
+
+    #[test]
+    fn test_state() {
+        // wait for a key press in V3, then jump to the end of memory
+        let mut chip8 = chip8_with_rom(&[0xF30A, 0x1FFF]);
+        assert_eq!(chip8.state(), ProcessorState::Running);
+        chip8.step();
+        assert_eq!(chip8.state(), ProcessorState::WaitingForKey { register: 3 });
+        chip8.update_key_state(0x1, true);
+        chip8.update_key_state(0x1, false);
+        chip8.run_until(3, |_| false);
+        assert_eq!(
+            chip8.state(),
+            ProcessorState::Halted {
+                reason: Fault::PcOutOfBounds(0xFFF)
+            }
+        );
+    }
+
+    #[test]
+    fn test_timers_paused_on_key_wait() {
+        // wait for a key press in V0
+        let mut chip8 = chip8_with_rom(&[0xF00A]);
+        // tick the timers on every step that updates them
+        chip8.set_time_source(TimeSource::Steps(1));
+        chip8.processor.quirks.pause_timers_on_key_wait = true;
+        chip8.step();
+        assert!(chip8.waiting_for_key());
+
+        chip8.bus.clock.delay_timer = 5;
+        chip8.step();
+        assert_eq!(chip8.bus.clock.delay_timer, 5);
+
+        chip8.processor.quirks.pause_timers_on_key_wait = false;
+        chip8.step();
+        assert_eq!(chip8.bus.clock.delay_timer, 4);
+    }
+
+    #[test]
+    fn test_hires_chip8_entry() {
+        let mut rom = vec![0; 0xC2];
+        // the start jump, and a draw of the 0 glyph at the bottom of the 64x64 display
+        rom[..2].copy_from_slice(&[0x12, 0x60]);
+        rom[0xC0..].copy_from_slice(&[0xD0, 0x15]);
+        let mut chip8 = Chip8::new();
+        chip8.set_variant(Variant::HiresChip8);
+        chip8.reset_and_load(rom).unwrap();
+        assert_eq!(
+            (chip8.display().width(), chip8.display().height()),
+            (64, 64)
+        );
+
+        chip8.processor.v[1] = 59;
+        chip8.step();
+        assert_eq!(chip8.pc(), 0x2C0);
+        chip8.step();
+        assert!(chip8.display().is_pixel_on(0, 63));
+    }
+
+    #[test]
+    fn test_state_hash() {
+        // draw the 0 glyph and loop
+        let mut a = chip8_with_rom(&[0xD005, 0x1202]);
+        let mut b = chip8_with_rom(&[0xD005, 0x1202]);
+        assert_eq!(a.state_hash(), b.state_hash());
+
+        a.step();
+        assert_ne!(a.state_hash(), b.state_hash());
+        b.step();
+        assert_eq!(a.state_hash(), b.state_hash());
+
+        // only the state is hashed, not the settings
+        b.set_foreground_color(RGB8([0xFF, 0, 0]));
+        assert_eq!(a.state_hash(), b.state_hash());
+        b.write_memory(0x300, &[1]);
+        assert_ne!(a.state_hash(), b.state_hash());
+    }
+
+    #[test]
+    fn test_freezes() {
+        // decrement V0 and store it at 0x300, forever
+        let mut chip8 = chip8_with_rom(&[0x70FF, 0xA300, 0xF055, 0x1200]);
+        chip8.set_freezes(vec![
+            Freeze {
+                address: 0x300,
+                value: 3,
+            },
+            Freeze {
+                address: 0x10000,
+                value: 1,
+            },
+        ]);
+        assert_eq!(chip8.memory()[0x300], 3);
+        chip8.run_until(3, |_| false);
+        assert_eq!(chip8.registers()[0], 0xFF);
+        assert_eq!(chip8.memory()[0x300], 3);
+
+        // the freezes are kept on reset, and pokes are overwritten by the program
+        chip8.reset_and_load(vec![0xA3, 0x00, 0xF0, 0x55]).unwrap();
+        assert_eq!(chip8.freezes().len(), 2);
+        chip8.set_freezes(Vec::new());
+        chip8.poke(0x300, 7);
+        assert_eq!(chip8.memory()[0x300], 7);
+        chip8.run_until(2, |_| false);
+        assert_eq!(chip8.memory()[0x300], 0);
     }
 }
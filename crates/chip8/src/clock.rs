@@ -1,21 +1,50 @@
-use std::{
-    sync::{
-        atomic::{AtomicU8, Ordering},
-        Arc,
-    },
-    time::Instant,
-};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+/// What ticks the 60 Hz delay and sound timers of a [`Chip8`](crate::Chip8),
+/// see [`Chip8::set_time_source`](crate::Chip8::set_time_source).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum TimeSource {
+    /// The timers tick with the time of the host, whenever 1/60 s passed since the last tick.
+    #[default]
+    Host,
+
+    /// The timers tick once every this many steps.
+    Steps(u32),
+
+    /// The timers only tick when [`Chip8::tick_timers`](crate::Chip8::tick_timers) is called,
+    /// so the embedder drives the time.
+    Manual,
+}
 
 /// Handles the updating of the `Chip8` sound and delay timers. The `delay_timer`  and
 /// the `sound_timer` are decremented by `1` at a rate of `60Hz`.
 #[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone)]
 pub struct Clock {
     pub delay_timer: u8,
-    #[cfg_attr(feature = "persistence", serde(skip))]
-    pub sound_timer: Arc<AtomicU8>,
+    #[cfg_attr(
+        feature = "persistence",
+        serde(with = "crate::state::since_sound_timer_version")
+    )]
+    pub sound_timer: u8,
     pub vblank_interrupt: bool,
     #[cfg_attr(feature = "persistence", serde(skip, default = "Instant::now"))]
     last_delay: Instant,
+
+    /// What ticks the timers. This is a setting of the host, which isn't part of save states.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub source: TimeSource,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    updates: u32,
+
+    /// Whether the timers were ticked with [`Clock::tick_60hz`] since the last update.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    ticked: bool,
 }
 
 impl Default for Clock {
@@ -25,29 +54,86 @@ impl Default for Clock {
             sound_timer: Default::default(),
             last_delay: Instant::now(),
             vblank_interrupt: Default::default(),
+            source: TimeSource::Host,
+            updates: 0,
+            ticked: false,
         }
     }
 }
 
 impl Clock {
     /// Create a new [`Clock`].
+    #[cfg_attr(not(feature = "raw"), allow(dead_code))]
     pub fn new() -> Self {
         Self::default()
     }
 
-    /// Update the delay and sound timers.
+    /// Update the delay and sound timers, ticking them if the [`TimeSource`] says it's time.
+    /// The `vblank_interrupt` tells whether they ticked since the last update.
     pub fn update(&mut self) {
-        if self.last_delay.elapsed().as_secs_f32() >= (1.0 / 60.0) {
-            self.delay_timer -= if self.delay_timer > 0 { 1 } else { 0 };
+        let tick = match self.source {
+            TimeSource::Host => self.last_delay.elapsed().as_secs_f32() >= (1.0 / 60.0),
+            TimeSource::Steps(updates_per_tick) => {
+                self.updates += 1;
+                self.updates >= updates_per_tick
+            }
+            TimeSource::Manual => false,
+        };
+        if tick {
+            self.tick_60hz();
+        }
+        self.vblank_interrupt = std::mem::take(&mut self.ticked);
+    }
 
-            if self.sound_timer.load(Ordering::SeqCst) > 0 {
-                self.sound_timer.fetch_sub(1, Ordering::SeqCst);
+    /// Tick the timers once, as if 1/60 s passed. The next update reports the vertical blank.
+    pub fn tick_60hz(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+
+        self.ticked = true;
+        self.last_delay = Instant::now();
+        self.updates = 0;
+    }
+
+    /// Update the clock until the next vertical blank, returning whether it was reached.
+    /// Nothing ticks the [`TimeSource::Manual`] source during a step, so it only reports
+    /// the vertical blank that the step started with.
+    pub fn wait_for_vblank(&mut self) -> bool {
+        if self.source == TimeSource::Manual {
+            return self.vblank_interrupt;
+        }
+        loop {
+            self.update();
+            if self.vblank_interrupt {
+                return true;
             }
+        }
+    }
+}
+
+/// `std::time::Instant` panics on `wasm32-unknown-unknown`, so web builds
+/// read the time from the host instead.
+#[cfg(target_arch = "wasm32")]
+mod web_time {
+    use std::time::Duration;
+
+    extern "C" {
+        /// Provided by the host page: the current time in milliseconds, e.g. `performance.now()`.
+        fn chip8_now() -> f64;
+    }
+
+    /// A point in time, in milliseconds as reported by the host.
+    #[derive(Clone, Copy)]
+    pub struct Instant(f64);
+
+    impl Instant {
+        pub fn now() -> Self {
+            // SAFETY: the host function takes no arguments and only returns a number
+            Self(unsafe { chip8_now() })
+        }
 
-            self.vblank_interrupt = true;
-            self.last_delay = Instant::now();
-        } else {
-            self.vblank_interrupt = false;
+        pub fn elapsed(&self) -> Duration {
+            Duration::from_secs_f64((Self::now().0 - self.0).max(0.0) / 1000.0)
         }
     }
 }
@@ -0,0 +1,20 @@
+/// Settings that make a [`Chip8`](crate::Chip8) reproducible: the same ROM and
+/// input always give the same result, independent of the speed of the host.
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deterministic {
+    /// The seed of the random numbers generated by `Cxnn`.
+    pub seed: u64,
+
+    /// The amount of steps between ticks of the 60 Hz timers, which otherwise
+    /// tick with the time of the host. Usually the amount of steps per frame.
+    pub steps_per_tick: u32,
+}
+
+/// Advance the xorshift generator with the given nonzero `state`, returning the next random byte.
+pub(crate) fn next_random(state: &mut u64) -> u8 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    (*state >> 32) as u8
+}
@@ -0,0 +1,261 @@
+//! Differences between two states of a [`Chip8`], e.g. before and after a suspect
+//! sequence of instructions, or between two save states.
+
+use std::fmt;
+
+use crate::{Chip8, Operand};
+
+/// A value that differs between two states.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change<T> {
+    pub before: T,
+    pub after: T,
+}
+
+impl<T: PartialEq> Change<T> {
+    /// The change between the given values, `None` if they are equal.
+    fn of(before: T, after: T) -> Option<Self> {
+        (before != after).then_some(Self { before, after })
+    }
+}
+
+/// A run of consecutive bytes of memory that differ between two states.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryChange {
+    /// The address of the first byte.
+    pub address: usize,
+
+    pub before: Vec<u8>,
+    pub after: Vec<u8>,
+}
+
+/// A pixel of the display that is on in different planes in two states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelChange {
+    pub x: usize,
+    pub y: usize,
+
+    /// The planes the pixel is on in before and after, e.g. [`PLANE_1`](crate::graphics::PLANE_1).
+    pub before: u8,
+    pub after: u8,
+}
+
+/// The differences between two states of a [`Chip8`], see [`diff`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    /// The registers and timers that changed, in the order of [`Operand::ALL`].
+    pub registers: Vec<(Operand, Change<usize>)>,
+
+    pub pc: Option<Change<usize>>,
+
+    /// The return addresses on the stack, the most recent call last.
+    pub stack: Option<Change<Vec<usize>>>,
+
+    /// The runs of changed bytes, in ascending order. Only the memory both states
+    /// have is compared, see `memory_size`.
+    pub memory: Vec<MemoryChange>,
+
+    pub memory_size: Option<Change<usize>>,
+
+    /// The resolution of the display, as `(width, height)`.
+    pub resolution: Option<Change<(usize, usize)>>,
+
+    /// The pixels that changed, row by row. Displays of different resolutions aren't
+    /// compared, so this is empty if the `resolution` changed.
+    pub pixels: Vec<PixelChange>,
+}
+
+impl StateDiff {
+    /// Returns whether the states are the same.
+    pub fn is_empty(&self) -> bool {
+        *self == StateDiff::default()
+    }
+}
+
+impl fmt::Display for StateDiff {
+    /// Write one line per difference, e.g. `V3: 0x05 -> 0x07`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences");
+        }
+        for (operand, change) in &self.registers {
+            writeln!(
+                f,
+                "{operand}: {:#04X} -> {:#04X}",
+                change.before, change.after
+            )?;
+        }
+        if let Some(pc) = &self.pc {
+            writeln!(f, "PC: {:#06X} -> {:#06X}", pc.before, pc.after)?;
+        }
+        if let Some(stack) = &self.stack {
+            writeln!(f, "Stack: {:X?} -> {:X?}", stack.before, stack.after)?;
+        }
+        if let Some(size) = &self.memory_size {
+            writeln!(f, "Memory size: {} -> {} bytes", size.before, size.after)?;
+        }
+        let hex = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|byte| format!("{byte:02X}"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        for change in &self.memory {
+            writeln!(
+                f,
+                "Memory {:#06X}..{:#06X}: {} -> {}",
+                change.address,
+                change.address + change.before.len(),
+                hex(&change.before),
+                hex(&change.after)
+            )?;
+        }
+        if let Some(resolution) = &self.resolution {
+            let ((w1, h1), (w2, h2)) = (resolution.before, resolution.after);
+            writeln!(f, "Display: {w1}x{h1} -> {w2}x{h2}")?;
+        }
+        if !self.pixels.is_empty() {
+            writeln!(f, "Display: {} pixels changed", self.pixels.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Find the differences between the state of the `before` and the `after` [`Chip8`].
+/// Only the state of the program is compared, not the settings of the host.
+pub fn diff(before: &Chip8, after: &Chip8) -> StateDiff {
+    let registers = Operand::ALL
+        .iter()
+        .filter_map(|&operand| {
+            Change::of(operand.value(before), operand.value(after)).map(|change| (operand, change))
+        })
+        .collect();
+
+    let mut memory: Vec<MemoryChange> = Vec::new();
+    for (address, (&old, &new)) in before.memory().iter().zip(after.memory()).enumerate() {
+        if old == new {
+            continue;
+        }
+        match memory.last_mut() {
+            Some(change) if change.address + change.before.len() == address => {
+                change.before.push(old);
+                change.after.push(new);
+            }
+            _ => memory.push(MemoryChange {
+                address,
+                before: vec![old],
+                after: vec![new],
+            }),
+        }
+    }
+
+    let (old, new) = (before.display(), after.display());
+    let resolution = Change::of((old.width(), old.height()), (new.width(), new.height()));
+    let mut pixels = Vec::new();
+    if resolution.is_none() {
+        for y in 0..old.height() {
+            for x in 0..old.width() {
+                let change = PixelChange {
+                    x,
+                    y,
+                    before: old.pixel_planes(x, y),
+                    after: new.pixel_planes(x, y),
+                };
+                if change.before != change.after {
+                    pixels.push(change);
+                }
+            }
+        }
+    }
+
+    StateDiff {
+        registers,
+        pc: Change::of(before.pc(), after.pc()),
+        stack: Change::of(before.stack().to_vec(), after.stack().to_vec()),
+        memory,
+        memory_size: Change::of(before.memory().len(), after.memory().len()),
+        resolution,
+        pixels,
+    }
+}
+
+/// Find the differences between two save states, see [`diff`].
+#[cfg(feature = "persistence")]
+pub fn diff_states(before: &[u8], after: &[u8]) -> Result<StateDiff, crate::state::StateError> {
+    Ok(diff(
+        &crate::state::load(before)?,
+        &crate::state::load(after)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chip8() -> Chip8 {
+        let mut chip8 = Chip8::new();
+        // V3 = 7, I = 0x300, store V0-V3 there, draw the font digit of V0 at (V0, V0)
+        chip8
+            .load_rom_data(vec![
+                0x63, 0x07, 0xA3, 0x00, 0xF3, 0x55, 0xF0, 0x29, 0xD0, 0x05,
+            ])
+            .unwrap();
+        chip8
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = chip8();
+        let mut after = chip8();
+        assert!(diff(&before, &after).is_empty());
+        assert_eq!(diff(&before, &after).to_string(), "No differences\n");
+
+        after.run_until(5, |_| false);
+        let diff = diff(&before, &after);
+        assert_eq!(
+            diff.registers,
+            [(
+                Operand::V(3),
+                Change {
+                    before: 0,
+                    after: 7
+                }
+            )]
+        );
+        assert_eq!(
+            diff.pc,
+            Some(Change {
+                before: 0x200,
+                after: 0x20A
+            })
+        );
+        assert_eq!(
+            diff.memory,
+            [MemoryChange {
+                address: 0x303,
+                before: vec![0],
+                after: vec![7],
+            }]
+        );
+        assert_eq!(diff.resolution, None);
+        // the top row of the 0 is 4 pixels wide
+        assert_eq!(diff.pixels.len(), 14);
+        assert_eq!(
+            diff.pixels[0],
+            PixelChange {
+                x: 0,
+                y: 0,
+                before: 0,
+                after: crate::graphics::PLANE_1
+            }
+        );
+        assert_eq!(
+            diff.to_string(),
+            "V3: 0x00 -> 0x07\n\
+             PC: 0x0200 -> 0x020A\n\
+             Memory 0x0303..0x0304: 00 -> 07\n\
+             Display: 14 pixels changed\n"
+        );
+    }
+}
@@ -0,0 +1,442 @@
+//! A static disassembler, which turns a ROM image into addressed mnemonics without
+//! executing it, using the same [`decode`](crate::decode) as the processor.
+//!
+//! The mnemonics are the common ones of Cowgod's reference, e.g. `LD V0, 0x12`.
+//!
+//! A ROM can be disassembled linearly with [`disassemble_rom`], or by following its control
+//! flow from the entry point with [`disassemble_program`], which tells code apart from the
+//! sprites and other data in between.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Write},
+};
+
+use crate::{instr::Instr, symbols::Symbols};
+
+/// Disassemble a single opcode into its mnemonic, e.g. `LD V0, 0x12`. The addresses
+/// named in the `symbols` are given by their name, e.g. `CALL draw_player`.
+///
+/// Returns `None` if the opcode is not a known instruction.
+pub fn disassemble(opcode: u16, symbols: &Symbols) -> Option<String> {
+    let address = |nnn: usize| match symbols.name(nnn) {
+        Some(name) => name.to_owned(),
+        None => format!("{nnn:#05X}"),
+    };
+    let mnemonic = match crate::decode(opcode) {
+        Instr::Clear => "CLS".into(),
+        Instr::Return => "RET".into(),
+        Instr::ScrollDown(n) => format!("SCD {n}"),
+        Instr::ScrollRight => "SCR".into(),
+        Instr::ScrollLeft => "SCL".into(),
+        Instr::Exit => "EXIT".into(),
+        Instr::LowRes => "LOW".into(),
+        Instr::HighRes => "HIGH".into(),
+        // these are machine code routines on CHIP-8, which is how they are assembled
+        Instr::ClearHires | Instr::ScrollUp(_) | Instr::MachineCode(_) => {
+            format!("SYS {:#05X}", opcode & 0x0FFF)
+        }
+        Instr::Jump(nnn) => format!("JP {}", address(nnn)),
+        Instr::Call(nnn) => format!("CALL {}", address(nnn)),
+        Instr::SkipIfEqual { x, nn } => format!("SE V{x:X}, {nn:#04X}"),
+        Instr::SkipIfNotEqual { x, nn } => format!("SNE V{x:X}, {nn:#04X}"),
+        Instr::SkipIfEqualReg { x, y } => format!("SE V{x:X}, V{y:X}"),
+        Instr::Set { x, nn } => format!("LD V{x:X}, {nn:#04X}"),
+        Instr::Add { x, nn } => format!("ADD V{x:X}, {nn:#04X}"),
+        Instr::SetReg { x, y } => format!("LD V{x:X}, V{y:X}"),
+        Instr::Or { x, y } => format!("OR V{x:X}, V{y:X}"),
+        Instr::And { x, y } => format!("AND V{x:X}, V{y:X}"),
+        Instr::Xor { x, y } => format!("XOR V{x:X}, V{y:X}"),
+        Instr::AddReg { x, y } => format!("ADD V{x:X}, V{y:X}"),
+        Instr::SubReg { x, y } => format!("SUB V{x:X}, V{y:X}"),
+        Instr::ShiftRight { x, y } => format!("SHR V{x:X}, V{y:X}"),
+        Instr::SubRegReversed { x, y } => format!("SUBN V{x:X}, V{y:X}"),
+        Instr::ShiftLeft { x, y } => format!("SHL V{x:X}, V{y:X}"),
+        Instr::SkipIfNotEqualReg { x, y } => format!("SNE V{x:X}, V{y:X}"),
+        Instr::SetIndex(nnn) => format!("LD I, {}", address(nnn)),
+        // the register is part of the address, so this assembles back to the same opcode
+        Instr::JumpOffset { nnn, .. } => format!("JP V0, {}", address(nnn)),
+        Instr::Random { x, nn } => format!("RND V{x:X}, {nn:#04X}"),
+        Instr::Draw { x, y, n } => format!("DRW V{x:X}, V{y:X}, {n}"),
+        Instr::SkipIfKey(x) => format!("SKP V{x:X}"),
+        Instr::SkipIfNotKey(x) => format!("SKNP V{x:X}"),
+        Instr::GetDelay(x) => format!("LD V{x:X}, DT"),
+        Instr::WaitKey(x) => format!("LD V{x:X}, K"),
+        Instr::SetDelay(x) => format!("LD DT, V{x:X}"),
+        Instr::SetSound(x) => format!("LD ST, V{x:X}"),
+        Instr::AddIndex(x) => format!("ADD I, V{x:X}"),
+        Instr::Digit(x) => format!("LD F, V{x:X}"),
+        Instr::BigDigit(x) => format!("LD HF, V{x:X}"),
+        Instr::Bcd(x) => format!("LD B, V{x:X}"),
+        Instr::Store(x) => format!("LD [I], V{x:X}"),
+        Instr::Load(x) => format!("LD V{x:X}, [I]"),
+        Instr::SaveFlags(x) => format!("LD R, V{x:X}"),
+        Instr::LoadFlags(x) => format!("LD V{x:X}, R"),
+        // the XO-CHIP instructions don't have mnemonics yet
+        Instr::StoreRange { .. }
+        | Instr::LoadRange { .. }
+        | Instr::SetLongIndex
+        | Instr::SelectPlanes(_)
+        | Instr::Invalid => return None,
+    };
+    Some(mnemonic)
+}
+
+/// A line of a disassembled ROM: a word, or a single byte of data, and its mnemonic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    /// The address of the first byte.
+    pub address: usize,
+
+    /// The two bytes of the word, or a single byte of data, e.g. the odd trailing byte of the ROM.
+    pub bytes: Vec<u8>,
+
+    /// The mnemonic of the instruction, `None` for data.
+    pub mnemonic: Option<String>,
+}
+
+impl Line {
+    /// The opcode of the word, `None` for a single byte.
+    pub fn opcode(&self) -> Option<u16> {
+        match *self.bytes {
+            [high, low] => Some(u16::from_be_bytes([high, low])),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    /// Format the line as `0x0200  6012  LD V0, 0x12`, where data is shown with `DB`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#06X}  ", self.address)?;
+        match (&*self.bytes, &self.mnemonic) {
+            ([high, low], Some(mnemonic)) => write!(f, "{high:02X}{low:02X}  {mnemonic}"),
+            ([high, low], None) => write!(f, "{high:02X}{low:02X}  DB {high:#04X}, {low:#04X}"),
+            (bytes, _) => {
+                let data: Vec<_> = bytes.iter().map(|byte| format!("{byte:#04X}")).collect();
+                let hex: String = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+                write!(f, "{hex:<4}  DB {}", data.join(", "))
+            }
+        }
+    }
+}
+
+/// Disassemble a ROM loaded at `start` linearly, two bytes at a time. Words that
+/// aren't instructions, like sprites, are shown as data.
+pub fn disassemble_rom(rom: &[u8], start: usize, symbols: &Symbols) -> Vec<Line> {
+    (start..)
+        .step_by(2)
+        .zip(rom.chunks(2))
+        .map(|(address, bytes)| {
+            let mnemonic = match *bytes {
+                [high, low] => disassemble(u16::from_be_bytes([high, low]), symbols),
+                _ => None,
+            };
+            Line {
+                address,
+                bytes: bytes.to_vec(),
+                mnemonic,
+            }
+        })
+        .collect()
+}
+
+/// How the control flow gets from an instruction to one of its successors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// On to the next instruction, including after a skip that didn't skip, or a call returns.
+    Next,
+
+    /// A jump (`1nnn`).
+    Jump,
+
+    /// A subroutine call (`2nnn`).
+    Call,
+
+    /// A skip over the next instruction.
+    Skip,
+}
+
+/// An edge of the control flow to the instruction at `target`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub target: usize,
+    pub kind: EdgeKind,
+}
+
+impl Edge {
+    fn new(target: usize, kind: EdgeKind) -> Self {
+        Self { target, kind }
+    }
+}
+
+/// Follow the control flow of a ROM loaded at `start` from `start`, returning the
+/// instructions that were found by their address, with the edges to their successors.
+pub(crate) fn explore(rom: &[u8], start: usize) -> BTreeMap<usize, Vec<Edge>> {
+    let word = |address: usize| {
+        let offset = address.checked_sub(start)?;
+        match rom.get(offset..offset + 2)? {
+            &[high, low] => Some(u16::from_be_bytes([high, low])),
+            _ => None,
+        }
+    };
+    let mut instructions = BTreeMap::new();
+    let mut pending = vec![start];
+    while let Some(address) = pending.pop() {
+        if instructions.contains_key(&address) {
+            continue;
+        }
+        let opcode = match word(address) {
+            Some(opcode) => opcode,
+            None => continue,
+        };
+        let next = address + 2;
+        let edges = match crate::decode(opcode) {
+            // data that happens to be reached, e.g. past the end of the program
+            Instr::Invalid => continue,
+            Instr::Jump(nnn) => vec![Edge::new(nnn, EdgeKind::Jump)],
+            Instr::Call(nnn) => vec![
+                Edge::new(nnn, EdgeKind::Call),
+                Edge::new(next, EdgeKind::Next),
+            ],
+            // the target of Bnnn depends on a register
+            Instr::Return | Instr::Exit | Instr::JumpOffset { .. } => Vec::new(),
+            // the address after F000 is data
+            Instr::SetLongIndex => vec![Edge::new(next + 2, EdgeKind::Next)],
+            Instr::SkipIfEqual { .. }
+            | Instr::SkipIfNotEqual { .. }
+            | Instr::SkipIfEqualReg { .. }
+            | Instr::SkipIfNotEqualReg { .. }
+            | Instr::SkipIfKey(_)
+            | Instr::SkipIfNotKey(_) => {
+                // XO-CHIP skips the whole of F000 nnnn
+                let skipped = if word(next) == Some(0xF000) { 4 } else { 2 };
+                vec![
+                    Edge::new(next, EdgeKind::Next),
+                    Edge::new(next + skipped, EdgeKind::Skip),
+                ]
+            }
+            _ => vec![Edge::new(next, EdgeKind::Next)],
+        };
+        pending.extend(edges.iter().map(|edge| edge.target));
+        instructions.insert(address, edges);
+    }
+    instructions
+}
+
+/// Find the bytes of a ROM loaded at `start` that are part of instructions, by following
+/// the control flow from `start`: jumps, calls and the returns after them, and both ways of
+/// skips. Code that is only reached through `Bnnn` or self-modifying code isn't found.
+pub fn find_code(rom: &[u8], start: usize) -> Vec<bool> {
+    let mut code = vec![false; rom.len()];
+    for address in explore(rom, start).into_keys() {
+        let offset = address - start;
+        code[offset..offset + 2].fill(true);
+    }
+    code
+}
+
+/// A basic block: a run of instructions that is only entered at its first instruction
+/// and only left after its last one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    /// The addresses of the instructions, in the order they are executed.
+    pub instructions: Vec<usize>,
+
+    /// The edges from the last instruction to the blocks it continues at.
+    pub edges: Vec<Edge>,
+}
+
+impl Block {
+    /// The address of the first instruction, which identifies the block.
+    pub fn start(&self) -> usize {
+        self.instructions[0]
+    }
+}
+
+/// Split the code of a ROM loaded at `start` into the [`Block`]s of its control flow graph,
+/// ordered by their address. The code is found like [`find_code`].
+pub fn control_flow_graph(rom: &[u8], start: usize) -> Vec<Block> {
+    let instructions = explore(rom, start);
+    let falls_through = |edges: &[Edge]| matches!(edges, [edge] if edge.kind == EdgeKind::Next);
+    // blocks start at the entry point and at every target of a branch
+    let mut leaders = BTreeSet::from([start]);
+    for edges in instructions.values() {
+        if !falls_through(edges) {
+            leaders.extend(edges.iter().map(|edge| edge.target));
+        }
+    }
+    leaders
+        .iter()
+        .filter(|leader| instructions.contains_key(leader))
+        .map(|&leader| {
+            let mut block = vec![leader];
+            let mut edges = &instructions[&leader];
+            while falls_through(edges) {
+                let next = edges[0].target;
+                if leaders.contains(&next) || !instructions.contains_key(&next) {
+                    break;
+                }
+                block.push(next);
+                edges = &instructions[&next];
+            }
+            Block {
+                instructions: block,
+                edges: edges.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Export the [control flow graph](control_flow_graph) of a ROM loaded at `start` in the
+/// DOT format of Graphviz, with the disassembly of each block. The addresses named in the
+/// `symbols` are given by their name.
+pub fn control_flow_dot(rom: &[u8], start: usize, symbols: &Symbols) -> String {
+    let mut dot = String::new();
+    // writing to a string can't fail
+    let _ = writeln!(dot, "digraph cfg {{");
+    let _ = writeln!(dot, "    node [shape=box, fontname=monospace];");
+    for block in control_flow_graph(rom, start) {
+        let mut label = String::new();
+        if let Some(name) = symbols.name(block.start()) {
+            let _ = write!(label, "{name}:\\l");
+        }
+        for &address in &block.instructions {
+            let offset = address - start;
+            let bytes = rom[offset..offset + 2].to_vec();
+            let mnemonic = disassemble(u16::from_be_bytes([bytes[0], bytes[1]]), symbols);
+            let line = Line {
+                address,
+                bytes,
+                mnemonic,
+            };
+            let _ = write!(label, "{line}\\l");
+        }
+        let label = label.replace('"', "\\\"");
+        let _ = writeln!(dot, "    b{:04X} [label=\"{label}\"];", block.start());
+        for edge in &block.edges {
+            let style = match edge.kind {
+                EdgeKind::Next => "",
+                EdgeKind::Jump => " [label=\"jump\"]",
+                EdgeKind::Call => " [label=\"call\", style=dashed]",
+                EdgeKind::Skip => " [label=\"skip\"]",
+            };
+            let _ = writeln!(
+                dot,
+                "    b{:04X} -> b{:04X}{style};",
+                block.start(),
+                edge.target
+            );
+        }
+    }
+    let _ = writeln!(dot, "}}");
+    dot
+}
+
+/// Disassemble a ROM loaded at `start` by following its control flow, see [`find_code`].
+/// The bytes in between the instructions that were found are shown as data, two at a time.
+pub fn disassemble_program(rom: &[u8], start: usize, symbols: &Symbols) -> Vec<Line> {
+    let code = find_code(rom, start);
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        let (len, mnemonic) = if code[offset] {
+            let opcode = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+            (2, disassemble(opcode, symbols))
+        } else {
+            // stop at the next instruction, even if it isn't aligned with the data
+            let len = if code.get(offset + 1) == Some(&false) {
+                2
+            } else {
+                1
+            };
+            (len, None)
+        };
+        lines.push(Line {
+            address: start + offset,
+            bytes: rom[offset..offset + len].to_vec(),
+            mnemonic,
+        });
+        offset += len;
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disassemble_rom() {
+        let mut symbols = Symbols::new();
+        symbols.insert(0x204, "draw");
+        let lines = disassemble_rom(&[0x22, 0x04, 0x60, 0x12, 0xFF, 0xFF, 0x3C], 0x200, &symbols);
+        let lines: Vec<_> = lines.iter().map(Line::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "0x0200  2204  CALL draw",
+                "0x0202  6012  LD V0, 0x12",
+                "0x0204  FFFF  DB 0xFF, 0xFF",
+                "0x0206  3C    DB 0x3C",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_disassemble_program() {
+        let rom = [
+            0x22, 0x08, // call the subroutine
+            0x12, 0x02, // loop forever
+            0x3C, 0x42, 0x7E, 0x00, // a sprite
+            0xA2, 0x04, // point I at the sprite
+            0x30, 0x00, // skip the return if V0 isn't 0
+            0x00, 0xEE, // return
+            0x00, 0xEE, // return
+            0x81, // padding
+        ];
+        let lines = disassemble_program(&rom, 0x200, &Symbols::new());
+        let lines: Vec<_> = lines.iter().map(Line::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "0x0200  2208  CALL 0x208",
+                "0x0202  1202  JP 0x202",
+                "0x0204  3C42  DB 0x3C, 0x42",
+                "0x0206  7E00  DB 0x7E, 0x00",
+                "0x0208  A204  LD I, 0x204",
+                "0x020A  3000  SE V0, 0x00",
+                "0x020C  00EE  RET",
+                "0x020E  00EE  RET",
+                "0x0210  81    DB 0x81",
+            ]
+        );
+
+        let blocks = control_flow_graph(&rom, 0x200);
+        let starts: Vec<_> = blocks.iter().map(Block::start).collect();
+        assert_eq!(starts, [0x200, 0x202, 0x208, 0x20C, 0x20E]);
+        assert_eq!(blocks[2].instructions, [0x208, 0x20A]);
+        assert_eq!(
+            blocks[2].edges,
+            [
+                Edge::new(0x20C, EdgeKind::Next),
+                Edge::new(0x20E, EdgeKind::Skip)
+            ]
+        );
+        assert!(blocks[3].edges.is_empty());
+
+        let mut symbols = Symbols::new();
+        symbols.insert(0x208, "draw");
+        let dot = control_flow_dot(&rom, 0x200, &symbols);
+        assert!(dot.starts_with("digraph cfg {"));
+        assert!(dot.contains(
+            "    b0208 [label=\"draw:\\l0x0208  A204  LD I, 0x204\\l0x020A  3000  SE V0, 0x00\\l\"];"
+        ));
+        assert!(dot.contains("    b0200 -> b0208 [label=\"call\", style=dashed];"));
+        assert!(dot.contains("    b0200 -> b0202;"));
+
+        // the address of F000 nnnn is data
+        let code = find_code(&[0xF0, 0x00, 0x12, 0x00, 0x12, 0x04], 0x200);
+        assert_eq!(code, [true, true, false, false, true, true]);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::{graphics::GraphicsBuffer, Breakpoint, Chip8, Fault};
+
+/// The input of a single frame, passed to [`Chip8::run_frame`].
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct FrameInput {
+    /// The maximum amount of steps executed in the frame.
+    pub steps: u32,
+
+    /// The pressed state of every key, indexed by key code.
+    /// `None` leaves the keys as they are.
+    pub keys: Option<[bool; 16]>,
+
+    /// The breakpoints to break at. The frame stops as soon as one of them is hit after
+    /// a step, before the next instruction is executed.
+    pub breakpoints: Vec<Breakpoint>,
+}
+
+impl FrameInput {
+    /// The input of a frame of up to `steps` steps, which leaves the keys as they are
+    /// and has no breakpoints.
+    pub fn new(steps: u32) -> Self {
+        Self {
+            steps,
+            ..Default::default()
+        }
+    }
+}
+
+/// An event that ended a frame early.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FrameEvent {
+    /// The `Processor` is halted on the given fault.
+    Halted(Fault),
+
+    /// A breakpoint was hit before the instruction at the given address.
+    Breakpoint(usize),
+
+    /// The [`StepHook`] stopped the frame.
+    Stopped,
+
+    /// The program reached the jump to itself at the given address, and won't do anything
+    /// else. Running more frames only executes the jump once per frame, see [`Chip8::finished`].
+    Finished(usize),
+
+    /// The opcode at the given address isn't an instruction of the variant, and was skipped
+    /// with the [`InvalidOpcodePolicy::Break`](crate::InvalidOpcodePolicy) policy.
+    InvalidOpcode(usize),
+}
+
+/// Why [`Chip8::run_cycles`] returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RunExit {
+    /// All the steps were executed.
+    Completed,
+
+    /// A breakpoint was hit before the instruction at the given address.
+    Breakpoint(usize),
+
+    /// The program waits for a key press, so further steps wouldn't execute anything.
+    WaitingForKey,
+
+    /// The program exited with the SUPER-CHIP exit instruction (`00FD`).
+    Halted,
+
+    /// The `Processor` is halted on the given fault.
+    Fault(Fault),
+
+    /// The program reached the jump to itself at the given address, see [`Chip8::finished`].
+    Finished(usize),
+}
+
+/// The state of the `Chip8` after a frame, returned by [`Chip8::run_frame`].
+pub struct FrameOutput<'a> {
+    /// The display to present, as it was at the last vertical blank.
+    /// See [`Chip8::latched_display`].
+    pub framebuffer: &'a GraphicsBuffer,
+
+    /// Indicates whether the sound timer is active, meaning the tone should be played.
+    pub sound_active: bool,
+
+    /// The amount of steps that were executed.
+    pub steps: u32,
+
+    /// The COSMAC VIP machine cycles consumed by the steps, see [`Chip8::cycles`].
+    pub cycles: u64,
+
+    /// The event that ended the frame early, if any.
+    pub event: Option<FrameEvent>,
+}
+
+/// The result of a frame run with [`Chip8::step_frame`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameReport {
+    /// The amount of steps that were executed.
+    pub steps: u32,
+
+    /// Whether the display changed during the frame.
+    pub drew: bool,
+
+    /// Indicates whether the sound timer is active at the end of the frame.
+    pub sound_active: bool,
+
+    /// The fault that halted the `Processor`, if any, which ends the frame early.
+    pub fault: Option<Fault>,
+}
+
+/// Observes every step of a frame run with [`Chip8::run_frame_with`], e.g. to
+/// implement a debugger. Either method can stop the frame by returning `true`.
+pub trait StepHook {
+    /// Called before each step. Returning `true` stops the frame before the step is executed.
+    fn before_step(&mut self, _chip8: &Chip8) -> bool {
+        false
+    }
+
+    /// Called after each step. Returning `true` stops the frame.
+    fn after_step(&mut self, _chip8: &Chip8) -> bool {
+        false
+    }
+}
+
+/// A hook that observes nothing, used by [`Chip8::run_frame`].
+impl StepHook for () {}
@@ -1,8 +1,93 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The width of the original CHIP-8 display, used by default.
 pub const WIDTH: usize = 64;
+/// The height of the original CHIP-8 display, used by default.
 pub const HEIGHT: usize = 32;
 pub const PIXEL_COUNT: usize = WIDTH * HEIGHT;
+/// The width of the SUPER-CHIP hi-res mode.
+pub const HIRES_WIDTH: usize = 128;
+/// The height of the SUPER-CHIP hi-res mode.
+pub const HIRES_HEIGHT: usize = 64;
+/// The width of the two-page hi-res CHIP-8 display.
+pub const TWO_PAGE_WIDTH: usize = 64;
+/// The height of the two-page hi-res CHIP-8 display, which spans two pages of video memory.
+pub const TWO_PAGE_HEIGHT: usize = 64;
 pub const DEFAULT_FOREGROUND: RGB8 = RGB8([255, 255, 255]);
 pub const DEFAULT_BACKGROUND: RGB8 = RGB8([0, 0, 0]);
+/// The default color of pixels only on the second XO-CHIP plane, like in Octo.
+pub const DEFAULT_PLANE_2: RGB8 = RGB8([255, 102, 0]);
+/// The default color of pixels on both XO-CHIP planes, like in Octo.
+pub const DEFAULT_BOTH_PLANES: RGB8 = RGB8([102, 34, 0]);
+
+/// The palettes of Octo by name, with the colors in the order of [`GraphicsBuffer::palette`].
+pub const OCTO_PALETTES: [(&str, [RGB8; 4]); 6] = [
+    (
+        "Octo",
+        [
+            RGB8([0x99, 0x66, 0x00]),
+            RGB8([0xFF, 0xCC, 0x00]),
+            RGB8([0xFF, 0x66, 0x00]),
+            RGB8([0x66, 0x22, 0x00]),
+        ],
+    ),
+    (
+        "LCD",
+        [
+            RGB8([0xF9, 0xFF, 0xB3]),
+            RGB8([0x3D, 0x80, 0x26]),
+            RGB8([0xAB, 0xCC, 0x47]),
+            RGB8([0x00, 0x13, 0x1A]),
+        ],
+    ),
+    (
+        "Hotdog",
+        [
+            RGB8([0x00, 0x00, 0x00]),
+            RGB8([0xFF, 0x00, 0x00]),
+            RGB8([0xFF, 0xFF, 0x00]),
+            RGB8([0xFF, 0xFF, 0xFF]),
+        ],
+    ),
+    (
+        "Gray",
+        [
+            RGB8([0xAA, 0xAA, 0xAA]),
+            RGB8([0x00, 0x00, 0x00]),
+            RGB8([0xFF, 0xFF, 0xFF]),
+            RGB8([0x66, 0x66, 0x66]),
+        ],
+    ),
+    (
+        "CGA 0",
+        [
+            RGB8([0x00, 0x00, 0x00]),
+            RGB8([0x00, 0xFF, 0x00]),
+            RGB8([0xFF, 0x00, 0x00]),
+            RGB8([0xFF, 0xFF, 0x00]),
+        ],
+    ),
+    (
+        "CGA 1",
+        [
+            RGB8([0x00, 0x00, 0x00]),
+            RGB8([0xFF, 0x00, 0xFF]),
+            RGB8([0x00, 0xFF, 0xFF]),
+            RGB8([0xFF, 0xFF, 0xFF]),
+        ],
+    ),
+];
+
+/// The generation given to the next change of any display, see [`GraphicsBuffer::generation`].
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+/// The bit of the first plane in a set of planes. This is the only plane
+/// of the variants other than XO-CHIP.
+pub const PLANE_1: u8 = 0b01;
+/// The bit of the second XO-CHIP plane in a set of planes.
+pub const PLANE_2: u8 = 0b10;
+/// Both XO-CHIP planes.
+pub const ALL_PLANES: u8 = PLANE_1 | PLANE_2;
 
 /// An RGB value, using 8 bits for each color channel.
 #[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
@@ -10,22 +95,48 @@ pub const DEFAULT_BACKGROUND: RGB8 = RGB8([0, 0, 0]);
 pub struct RGB8(pub [u8; 3]);
 
 /// Handles the graphics state of the `Chip8`.
-#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
-#[derive(Clone, Copy)]
+///
+/// The display is [`WIDTH`] x [`HEIGHT`] pixels by default, but any resolution
+/// can be used, e.g. for hi-res variants.
+///
+/// Every pixel can be on in two planes, which XO-CHIP programs select to draw in four colors.
+/// Drawing, clearing and scrolling only affect the selected planes, by default [`PLANE_1`].
+#[cfg_attr(
+    feature = "persistence",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(remote = "Self")
+)]
+#[derive(Clone)]
 pub struct GraphicsBuffer {
-    #[cfg_attr(feature = "persistence", serde(with = "serde_big_array::BigArray"))]
-    vram: [RGB8; PIXEL_COUNT],
+    width: usize,
+    height: usize,
+
+    /// The planes each pixel is on in, row by row.
+    pixels: BitPlanes,
+
+    /// The selected planes.
+    planes: u8,
     pub foreground_rgb: RGB8,
     pub background_rgb: RGB8,
+
+    /// The color of pixels only on the second plane.
+    pub plane_2_rgb: RGB8,
+
+    /// The color of pixels on both planes.
+    pub both_planes_rgb: RGB8,
+
+    /// The generation of the contents, see [`GraphicsBuffer::generation`].
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    generation: u64,
+
+    /// Whether each row changed since [`GraphicsBuffer::clear_dirty`] was last called.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    dirty_rows: Vec<bool>,
 }
 
 impl Default for GraphicsBuffer {
     fn default() -> Self {
-        Self {
-            vram: [DEFAULT_BACKGROUND; PIXEL_COUNT],
-            foreground_rgb: DEFAULT_FOREGROUND,
-            background_rgb: DEFAULT_BACKGROUND,
-        }
+        Self::with_size(WIDTH, HEIGHT)
     }
 }
 
@@ -35,76 +146,744 @@ impl GraphicsBuffer {
         Self::default()
     }
 
-    /// Draws a byte as a sprite at the given coordinates.
+    /// Create a new empty `GraphicsBuffer` with the given resolution.
+    pub fn with_size(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: BitPlanes::new(width * height),
+            planes: PLANE_1,
+            foreground_rgb: DEFAULT_FOREGROUND,
+            background_rgb: DEFAULT_BACKGROUND,
+            plane_2_rgb: DEFAULT_PLANE_2,
+            both_planes_rgb: DEFAULT_BOTH_PLANES,
+            generation: NEXT_GENERATION.fetch_add(1, Ordering::Relaxed),
+            dirty_rows: vec![true; height],
+        }
+    }
+
+    /// The width of the display in pixels.
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The height of the display in pixels.
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns whether the display is in the SUPER-CHIP hi-res mode.
+    #[inline]
+    pub fn is_hires(&self) -> bool {
+        self.width == HIRES_WIDTH && self.height == HIRES_HEIGHT
+    }
+
+    /// The amount of pixels in the display.
+    #[inline]
+    pub fn pixel_count(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// The planes that are drawn to, cleared and scrolled.
+    #[inline]
+    pub fn planes(&self) -> u8 {
+        self.planes
+    }
+
+    /// Select the planes that are drawn to, cleared and scrolled, e.g. [`ALL_PLANES`].
+    pub fn select_planes(&mut self, planes: u8) {
+        self.planes = planes & ALL_PLANES;
+    }
+
+    /// Change the resolution of the display, clearing all planes.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.pixels = BitPlanes::new(width * height);
+        self.mark_all_dirty();
+    }
+
+    /// Switch the display to the given resolution.
+    ///
+    /// If `clear` is `false`, the current contents are scaled to the new resolution,
+    /// as if both resolutions were drawn into the same buffer at different pixel sizes.
+    pub fn set_resolution(&mut self, width: usize, height: usize, clear: bool) {
+        if clear {
+            self.resize(width, height);
+            return;
+        }
+        let pixels = (0..width * height)
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                self.pixels
+                    .get((y * self.height / height) * self.width + x * self.width / width)
+            })
+            .collect();
+        self.width = width;
+        self.height = height;
+        self.pixels = pixels;
+        self.mark_all_dirty();
+    }
+
+    /// Draws a sprite at the given coordinates, one byte per row.
+    /// With several planes selected, the rows of each plane follow each other,
+    /// starting with the rows of the first plane.
+    ///
+    /// Pixels past the right edge wrap around to the left if `wrap_horizontally` is `true`,
+    /// and rows past the bottom edge wrap around to the top if `wrap_vertically` is `true`.
+    /// Otherwise they are clipped.
+    /// Returns the amount of rows that had a collision in any plane.
+    pub fn draw_sprite(
+        &mut self,
+        x: usize,
+        y: usize,
+        sprite: &[u8],
+        wrap_horizontally: bool,
+        wrap_vertically: bool,
+    ) -> usize {
+        self.draw_rows(x, y, sprite, 1, wrap_horizontally, wrap_vertically)
+    }
+
+    /// Draws a 16 pixel wide sprite at the given coordinates, two bytes per row,
+    /// like [`GraphicsBuffer::draw_sprite`] draws sprites of one byte per row.
+    pub fn draw_wide_sprite(
+        &mut self,
+        x: usize,
+        y: usize,
+        sprite: &[u8],
+        wrap_horizontally: bool,
+        wrap_vertically: bool,
+    ) -> usize {
+        self.draw_rows(x, y, sprite, 2, wrap_horizontally, wrap_vertically)
+    }
+
+    /// Draws a sprite of `row_bytes` bytes per row, see [`GraphicsBuffer::draw_sprite`].
+    fn draw_rows(
+        &mut self,
+        x: usize,
+        y: usize,
+        sprite: &[u8],
+        row_bytes: usize,
+        wrap_horizontally: bool,
+        wrap_vertically: bool,
+    ) -> usize {
+        let plane_count = self.planes.count_ones() as usize;
+        if plane_count == 0 {
+            return 0;
+        }
+        let plane_len = sprite.len() / plane_count;
+        let mut collided = vec![false; plane_len / row_bytes];
+        let selected = self.planes;
+        let planes = [PLANE_1, PLANE_2]
+            .into_iter()
+            .filter(|&plane| selected & plane != 0);
+        for (plane, plane_sprite) in planes.zip(sprite.chunks(plane_len.max(1))) {
+            for (i, row_data) in plane_sprite.chunks_exact(row_bytes).enumerate() {
+                let row = if wrap_vertically {
+                    (y + i) % self.height
+                } else {
+                    y + i
+                };
+                for (byte, &data) in row_data.iter().enumerate() {
+                    let x = x + 8 * byte;
+                    collided[i] |= self.draw_byte_in(plane, x, row, data, wrap_horizontally);
+                }
+            }
+        }
+        collided.into_iter().filter(|&collided| collided).count()
+    }
+
+    /// Draws a byte as a sprite at the given coordinates, in every selected plane.
+    /// Pixels outside of the display are clipped.
     /// Returns whether or not there was a collision
     pub fn draw_byte(&mut self, x: usize, y: usize, data: u8) -> bool {
+        let mut collision = false;
+        for plane in [PLANE_1, PLANE_2] {
+            if self.planes & plane != 0 {
+                collision |= self.draw_byte_in(plane, x, y, data, false);
+            }
+        }
+        collision
+    }
+
+    /// Draws a byte as a sprite at the given coordinates in the given plane.
+    /// Pixels past the right edge wrap around to the left if `wrap` is `true`.
+    fn draw_byte_in(&mut self, plane: u8, x: usize, y: usize, data: u8, wrap: bool) -> bool {
         // clipping check
-        if y > HEIGHT {
+        if y >= self.height {
             return false;
         }
 
-        let max_x = (WIDTH as isize - x as isize).clamp(0, 8) as usize;
+        let max_x = if wrap {
+            8
+        } else {
+            (self.width as isize - x as isize).clamp(0, 8) as usize
+        };
+
+        // the bits of the pixels that aren't clipped
+        let visible = (0xFF00u16 >> max_x) as u8;
+        if data & visible != 0 {
+            self.mark_dirty(y);
+        }
 
         let mut collision = false;
         // iterate bits
         for b in 0..max_x {
-            let pos = ((WIDTH * y) + x + b) % PIXEL_COUNT;
-            let new_pixel_active = (data & (0x80 >> b)) != 0;
-            let old_pixel_active = self.vram[pos] == self.foreground_rgb;
-            if new_pixel_active && old_pixel_active {
-                collision = true;
-            }
-            let new_pixel_state = new_pixel_active ^ old_pixel_active;
-            if new_pixel_state {
-                self.vram[pos] = self.foreground_rgb;
-            } else {
-                self.vram[pos] = self.background_rgb;
+            let pos = (self.width * y) + (x + b) % self.width;
+            if (data & (0x80 >> b)) != 0 {
+                if self.pixels.get(pos) & plane != 0 {
+                    collision = true;
+                }
+                self.pixels.toggle(pos, plane);
             }
         }
         collision
     }
 
-    /// Get the RGB8 pixel buffer representation of this graphics buffer.
-    /// The length of the buffer will be `PIXEL_COUNT * COLOR_CHANNEL_COUNT`.
-    pub fn as_rgb8(&self) -> [u8; PIXEL_COUNT * 3] {
-        let mut data = [0; PIXEL_COUNT * 3];
-        // safety: the length of the following iterator should be len(self.vram) * 3, which
-        // is equal to the length of `data`.
-        self.vram
+    /// Scroll the selected planes up by `rows` pixels. The rows at the bottom are cleared.
+    pub fn scroll_up(&mut self, rows: usize) {
+        self.scroll(0, -(rows.min(self.height) as isize));
+    }
+
+    /// Scroll the selected planes down by `rows` pixels. The rows at the top are cleared.
+    pub fn scroll_down(&mut self, rows: usize) {
+        self.scroll(0, rows.min(self.height) as isize);
+    }
+
+    /// Scroll the selected planes left by `columns` pixels. The columns at the right are cleared.
+    pub fn scroll_left(&mut self, columns: usize) {
+        self.scroll(-(columns.min(self.width) as isize), 0);
+    }
+
+    /// Scroll the selected planes right by `columns` pixels. The columns at the left are cleared.
+    pub fn scroll_right(&mut self, columns: usize) {
+        self.scroll(columns.min(self.width) as isize, 0);
+    }
+
+    /// Move the selected planes by `dx` columns and `dy` rows, clearing the pixels
+    /// that are scrolled in from outside of the display.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        self.mark_all_dirty();
+        let pixels = self.pixels.clone();
+        let (width, height) = (self.width as isize, self.height as isize);
+        for y in 0..height {
+            for x in 0..width {
+                let (from_x, from_y) = (x - dx, y - dy);
+                let from = if (0..width).contains(&from_x) && (0..height).contains(&from_y) {
+                    pixels.get((from_y * width + from_x) as usize)
+                } else {
+                    0
+                };
+                let index = (y * width + x) as usize;
+                let pixel = self.pixels.get(index);
+                self.pixels
+                    .set(index, (pixel & !self.planes) | (from & self.planes));
+            }
+        }
+    }
+
+    /// Returns whether the pixel at the given coordinates is on in any plane.
+    pub fn is_pixel_on(&self, x: usize, y: usize) -> bool {
+        self.pixels.get(self.width * y + x) != 0
+    }
+
+    /// The planes the pixel at the given coordinates is on in, e.g. [`PLANE_1`].
+    pub fn pixel_planes(&self, x: usize, y: usize) -> u8 {
+        self.pixels.get(self.width * y + x)
+    }
+
+    /// The color of a pixel on the given planes.
+    fn color(&self, planes: u8) -> RGB8 {
+        match planes & ALL_PLANES {
+            0 => self.background_rgb,
+            PLANE_1 => self.foreground_rgb,
+            PLANE_2 => self.plane_2_rgb,
+            _ => self.both_planes_rgb,
+        }
+    }
+
+    /// Get the RGB8 pixel buffer representation of this graphics buffer, row by row.
+    /// The length of the buffer will be `pixel_count() * COLOR_CHANNEL_COUNT`.
+    pub fn as_rgb8(&self) -> Vec<u8> {
+        self.pixels
             .iter()
-            .flat_map(|RGB8(color)| color)
-            .enumerate()
-            .for_each(|(i, x)| {
-                data[i] = *x;
-            });
-        data
+            .flat_map(|planes| self.color(planes).0)
+            .collect()
     }
 
     /// Set the foreground color used by the RGB representation of the graphics buffer.
     #[inline]
     pub fn set_foreground_color(&mut self, foreground: RGB8) {
-        self.vram.iter_mut().for_each(|color| {
-            if *color == self.foreground_rgb {
-                *color = foreground;
-            }
-        });
         self.foreground_rgb = foreground;
+        self.mark_all_dirty();
     }
 
     /// Set the background color used by the RGB representation of the graphics buffer.
     #[inline]
     pub fn set_background_color(&mut self, background: RGB8) {
-        self.vram.iter_mut().for_each(|color| {
-            if *color == self.background_rgb {
-                *color = background;
-            }
-        });
         self.background_rgb = background;
+        self.mark_all_dirty();
+    }
+
+    /// The colors of the pixels on each combination of planes, indexed by the planes:
+    /// off, only on [`PLANE_1`], only on [`PLANE_2`], and on both.
+    pub fn palette(&self) -> [RGB8; 4] {
+        [
+            self.background_rgb,
+            self.foreground_rgb,
+            self.plane_2_rgb,
+            self.both_planes_rgb,
+        ]
+    }
+
+    /// Set the colors used by the RGB representation of the graphics buffer,
+    /// in the order of [`GraphicsBuffer::palette`], e.g. one of the [`OCTO_PALETTES`].
+    pub fn set_palette(&mut self, palette: [RGB8; 4]) {
+        [
+            self.background_rgb,
+            self.foreground_rgb,
+            self.plane_2_rgb,
+            self.both_planes_rgb,
+        ] = palette;
+        self.mark_all_dirty();
     }
 
-    /// Clear the graphics buffer with the background color.
+    /// Turn off the pixels of the selected planes.
     #[inline]
     pub fn clear(&mut self) {
-        self.vram = [self.background_rgb; PIXEL_COUNT];
+        self.pixels.clear(self.planes);
+        self.mark_all_dirty();
+    }
+
+    /// A number that changes whenever the RGB representation of the display may change,
+    /// and is unique to the contents among all displays. A frontend can keep the generation
+    /// of the last frame it presented, and skip presenting the display again while it is the same.
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns whether any row changed since [`GraphicsBuffer::clear_dirty`] was last called.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty_rows.contains(&true)
+    }
+
+    /// The rows that changed since [`GraphicsBuffer::clear_dirty`] was last called, top to bottom.
+    pub fn dirty_rows(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.height).filter(|&y| self.dirty_rows[y])
+    }
+
+    /// Mark all rows as unchanged, e.g. after the changed ones have been presented.
+    pub fn clear_dirty(&mut self) {
+        self.dirty_rows.fill(false);
+    }
+
+    /// Record a change of the given row.
+    fn mark_dirty(&mut self, y: usize) {
+        self.generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+        self.dirty_rows[y] = true;
+    }
+
+    /// Record a change of the whole display.
+    fn mark_all_dirty(&mut self) {
+        self.generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+        self.dirty_rows = vec![true; self.height];
+    }
+}
+
+/// The pixels of a display as one bit per pixel and plane, packed into words.
+/// The colors are only derived from the planes when the display is presented.
+#[derive(Clone)]
+struct BitPlanes {
+    /// The amount of pixels.
+    len: usize,
+
+    /// The bits of [`PLANE_1`] and [`PLANE_2`], row by row.
+    planes: [Vec<u64>; 2],
+}
+
+impl BitPlanes {
+    /// Create `BitPlanes` with `len` pixels that are off.
+    fn new(len: usize) -> Self {
+        let words = len.div_ceil(u64::BITS as usize);
+        Self {
+            len,
+            planes: [vec![0; words], vec![0; words]],
+        }
+    }
+
+    /// The word of a pixel, and the bit of the pixel in it.
+    #[inline]
+    fn position(index: usize) -> (usize, u32) {
+        let bits = u64::BITS as usize;
+        (index / bits, (index % bits) as u32)
+    }
+
+    /// The planes the pixel at `index` is on in.
+    #[inline]
+    fn get(&self, index: usize) -> u8 {
+        assert!(index < self.len, "pixel {index} is outside of the display");
+        let (word, bit) = Self::position(index);
+        let plane_1 = (self.planes[0][word] >> bit & 1) as u8;
+        let plane_2 = (self.planes[1][word] >> bit & 1) as u8;
+        (plane_1 * PLANE_1) | (plane_2 * PLANE_2)
+    }
+
+    /// Put the pixel at `index` on in exactly the given planes.
+    fn set(&mut self, index: usize, planes: u8) {
+        self.toggle(index, self.get(index) ^ planes);
+    }
+
+    /// Flip the pixel at `index` in the given planes.
+    #[inline]
+    fn toggle(&mut self, index: usize, planes: u8) {
+        assert!(index < self.len, "pixel {index} is outside of the display");
+        let (word, bit) = Self::position(index);
+        for (plane, bits) in [PLANE_1, PLANE_2].into_iter().zip(&mut self.planes) {
+            if planes & plane != 0 {
+                bits[word] ^= 1 << bit;
+            }
+        }
+    }
+
+    /// Turn off every pixel in the given planes.
+    fn clear(&mut self, planes: u8) {
+        for (plane, bits) in [PLANE_1, PLANE_2].into_iter().zip(&mut self.planes) {
+            if planes & plane != 0 {
+                bits.fill(0);
+            }
+        }
+    }
+
+    /// The planes of every pixel, row by row.
+    fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..self.len).map(|index| self.get(index))
+    }
+}
+
+impl FromIterator<u8> for BitPlanes {
+    fn from_iter<T: IntoIterator<Item = u8>>(iter: T) -> Self {
+        let pixels: Vec<u8> = iter.into_iter().collect();
+        let mut bit_planes = BitPlanes::new(pixels.len());
+        for (index, planes) in pixels.into_iter().enumerate() {
+            bit_planes.toggle(index, planes);
+        }
+        bit_planes
+    }
+}
+
+/// The planes are stored with a byte per pixel, as before they were packed.
+#[cfg(feature = "persistence")]
+impl serde::Serialize for BitPlanes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+#[cfg(feature = "persistence")]
+impl<'de> serde::Deserialize<'de> for BitPlanes {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<u8>::deserialize(deserializer).map(|pixels| pixels.into_iter().collect())
+    }
+}
+
+/// The layout of a `GraphicsBuffer` in save states before format version 2,
+/// which stored the color of every pixel and had a single plane.
+#[cfg(feature = "persistence")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct LegacyGraphicsBuffer {
+    width: usize,
+    height: usize,
+    vram: Vec<RGB8>,
+    foreground_rgb: RGB8,
+    background_rgb: RGB8,
+}
+
+#[cfg(feature = "persistence")]
+impl serde::Serialize for GraphicsBuffer {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if crate::state::format_version() < crate::state::XO_CHIP_VERSION {
+            let legacy = LegacyGraphicsBuffer {
+                width: self.width,
+                height: self.height,
+                vram: self
+                    .pixels
+                    .iter()
+                    .map(|planes| self.color(planes & PLANE_1))
+                    .collect(),
+                foreground_rgb: self.foreground_rgb,
+                background_rgb: self.background_rgb,
+            };
+            legacy.serialize(serializer)
+        } else {
+            GraphicsBuffer::serialize(self, serializer)
+        }
+    }
+}
+
+#[cfg(feature = "persistence")]
+impl<'de> serde::Deserialize<'de> for GraphicsBuffer {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if crate::state::format_version() < crate::state::XO_CHIP_VERSION {
+            let legacy = LegacyGraphicsBuffer::deserialize(deserializer)?;
+            let mut graphics = GraphicsBuffer::with_size(legacy.width, legacy.height);
+            graphics.pixels = legacy
+                .vram
+                .iter()
+                .map(|&color| u8::from(color == legacy.foreground_rgb))
+                .collect();
+            graphics.foreground_rgb = legacy.foreground_rgb;
+            graphics.background_rgb = legacy.background_rgb;
+            Ok(graphics)
+        } else {
+            let mut graphics = GraphicsBuffer::deserialize(deserializer)?;
+            graphics.mark_all_dirty();
+            Ok(graphics)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_byte_custom_size() {
+        let mut graphics = GraphicsBuffer::with_size(128, 64);
+        assert_eq!(graphics.pixel_count(), 128 * 64);
+
+        assert!(!graphics.draw_byte(100, 50, 0b1000_0001));
+        let rgb = graphics.as_rgb8();
+        assert_eq!(rgb.len(), 128 * 64 * 3);
+        let pixel = |x: usize, y: usize| &rgb[(y * 128 + x) * 3..][..3];
+        assert_eq!(pixel(100, 50), DEFAULT_FOREGROUND.0);
+        assert_eq!(pixel(101, 50), DEFAULT_BACKGROUND.0);
+        assert_eq!(pixel(107, 50), DEFAULT_FOREGROUND.0);
+
+        // drawing the same byte again erases it and collides
+        assert!(graphics.draw_byte(100, 50, 0b1000_0001));
+        assert!(graphics.as_rgb8().iter().all(|&channel| channel == 0));
+    }
+
+    #[test]
+    fn test_resize_clears_display() {
+        let mut graphics = GraphicsBuffer::new();
+        graphics.draw_byte(0, 0, 0xFF);
+        graphics.resize(32, 16);
+        assert_eq!((graphics.width(), graphics.height()), (32, 16));
+        assert!(graphics.as_rgb8().iter().all(|&channel| channel == 0));
+
+        // pixels past the right edge are clipped
+        graphics.draw_byte(28, 0, 0xFF);
+        let lit = graphics.as_rgb8().chunks(3).filter(|p| p[0] != 0).count();
+        assert_eq!(lit, 4);
+    }
+
+    #[test]
+    fn test_draw_sprite_clips_vertically() {
+        let mut graphics = GraphicsBuffer::new();
+        graphics.draw_sprite(0, HEIGHT - 1, &[0x80, 0x80], false, false);
+        let lit: Vec<usize> = graphics
+            .as_rgb8()
+            .chunks(3)
+            .enumerate()
+            .filter(|(_, p)| p[0] != 0)
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(lit, [(HEIGHT - 1) * WIDTH]);
+    }
+
+    #[test]
+    fn test_draw_sprite_wraps_vertically() {
+        let mut graphics = GraphicsBuffer::new();
+        graphics.draw_sprite(0, HEIGHT - 1, &[0x80, 0x80], false, true);
+        let lit: Vec<usize> = graphics
+            .as_rgb8()
+            .chunks(3)
+            .enumerate()
+            .filter(|(_, p)| p[0] != 0)
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(lit, [0, (HEIGHT - 1) * WIDTH]);
+    }
+
+    #[test]
+    fn test_draw_sprite_wraps_horizontally() {
+        let lit = |wrap: bool| {
+            let mut graphics = GraphicsBuffer::new();
+            graphics.draw_sprite(WIDTH - 4, 0, &[0xFF], wrap, false);
+            graphics
+                .as_rgb8()
+                .chunks(3)
+                .enumerate()
+                .filter(|(_, p)| p[0] != 0)
+                .map(|(i, _)| i)
+                .collect::<Vec<usize>>()
+        };
+        assert_eq!(lit(false), [60, 61, 62, 63]);
+        assert_eq!(lit(true), [0, 1, 2, 3, 60, 61, 62, 63]);
+    }
+
+    #[test]
+    fn test_draw_wide_sprite() {
+        let mut graphics = GraphicsBuffer::with_size(HIRES_WIDTH, HIRES_HEIGHT);
+        let sprite = [0x80, 0x01, 0x00, 0x01];
+        assert_eq!(graphics.draw_wide_sprite(0, 0, &sprite, false, false), 0);
+        assert!(graphics.is_pixel_on(0, 0));
+        assert!(graphics.is_pixel_on(15, 0));
+        assert!(graphics.is_pixel_on(15, 1));
+
+        // the right half is clipped at the edge, the collision is counted once per row
+        assert_eq!(graphics.draw_wide_sprite(0, 0, &sprite, false, false), 2);
+        assert_eq!(
+            graphics.draw_wide_sprite(HIRES_WIDTH - 8, 0, &[0x80, 0x80], false, false),
+            0
+        );
+        assert!(!graphics.is_pixel_on(0, 0));
+    }
+
+    #[test]
+    fn test_draw_sprite_in_both_planes() {
+        let mut graphics = GraphicsBuffer::new();
+        graphics.select_planes(ALL_PLANES);
+        // one row for each plane
+        assert_eq!(
+            graphics.draw_sprite(0, 0, &[0b1100_0000, 0b1010_0000], false, false),
+            0
+        );
+        let rgb = graphics.as_rgb8();
+        let pixel = |x: usize| &rgb[x * 3..][..3];
+        assert_eq!(pixel(0), DEFAULT_BOTH_PLANES.0);
+        assert_eq!(pixel(1), DEFAULT_FOREGROUND.0);
+        assert_eq!(pixel(2), DEFAULT_PLANE_2.0);
+        assert_eq!(pixel(3), DEFAULT_BACKGROUND.0);
+
+        // a collision in either plane counts the row
+        graphics.select_planes(PLANE_2);
+        assert_eq!(graphics.draw_sprite(0, 0, &[0b0010_0000], false, false), 1);
+        graphics.clear();
+        assert!(graphics.is_pixel_on(0, 0));
+        assert!(!graphics.is_pixel_on(2, 0));
+    }
+
+    #[test]
+    fn test_collisions_ignore_colors() {
+        let mut graphics = GraphicsBuffer::new();
+        graphics.draw_byte(0, 0, 0x80);
+        // the foreground changing to the background color mid-frame doesn't hide the pixel
+        graphics.set_foreground_color(DEFAULT_BACKGROUND);
+        assert!(graphics.draw_byte(0, 0, 0x80));
+        assert!(!graphics.is_pixel_on(0, 0));
+    }
+
+    #[test]
+    fn test_bit_planes() {
+        // not a multiple of the word size
+        let mut pixels = BitPlanes::new(100);
+        pixels.set(99, ALL_PLANES);
+        pixels.toggle(64, PLANE_2);
+        pixels.set(99, PLANE_2);
+        assert_eq!(pixels.get(99), PLANE_2);
+        assert_eq!(pixels.get(64), PLANE_2);
+        assert_eq!(pixels.get(63), 0);
+        pixels.clear(PLANE_2);
+        assert!(pixels.iter().all(|planes| planes == 0));
+    }
+
+    #[test]
+    fn test_dirty_rows() {
+        let mut graphics = GraphicsBuffer::new();
+        assert!(graphics.is_dirty());
+        graphics.clear_dirty();
+        let generation = graphics.generation();
+
+        // nothing is drawn by an empty byte, or a pixel past the right edge
+        graphics.draw_byte(0, 3, 0);
+        graphics.draw_byte(WIDTH - 1, 4, 0x40);
+        assert!(!graphics.is_dirty());
+        assert_eq!(graphics.generation(), generation);
+
+        graphics.draw_sprite(0, 3, &[0x80, 0x80], false, false);
+        assert_eq!(graphics.dirty_rows().collect::<Vec<_>>(), [3, 4]);
+        assert_ne!(graphics.generation(), generation);
+
+        // a copy has the same contents, so it keeps the generation
+        let copy = graphics.clone();
+        assert_eq!(copy.generation(), graphics.generation());
+        graphics.clear_dirty();
+        graphics.clear();
+        assert_eq!(graphics.dirty_rows().count(), HEIGHT);
+        assert_ne!(graphics.generation(), copy.generation());
+    }
+
+    #[test]
+    fn test_palette() {
+        let mut graphics = GraphicsBuffer::new();
+        let (_, palette) = OCTO_PALETTES[0];
+        graphics.set_palette(palette);
+        assert!(graphics.palette() == palette);
+        graphics.select_planes(PLANE_2);
+        graphics.draw_byte(0, 0, 0x80);
+        let rgb = graphics.as_rgb8();
+        assert_eq!(rgb[..3], palette[usize::from(PLANE_2)].0);
+        assert_eq!(rgb[3..6], palette[0].0);
+    }
+
+    #[test]
+    fn test_scroll_up_selected_planes() {
+        let mut graphics = GraphicsBuffer::new();
+        graphics.select_planes(ALL_PLANES);
+        graphics.draw_sprite(0, 2, &[0x80, 0x80], false, false);
+        graphics.select_planes(PLANE_2);
+        graphics.scroll_up(2);
+        let rgb = graphics.as_rgb8();
+        let pixel = |y: usize| &rgb[y * WIDTH * 3..][..3];
+        assert_eq!(pixel(0), DEFAULT_PLANE_2.0);
+        assert_eq!(pixel(2), DEFAULT_FOREGROUND.0);
+    }
+
+    #[test]
+    fn test_scroll_sideways_and_down() {
+        let mut graphics = GraphicsBuffer::new();
+        graphics.draw_byte(4, 0, 0x80);
+        graphics.scroll_down(3);
+        assert!(graphics.is_pixel_on(4, 3));
+        assert!(!graphics.is_pixel_on(4, 0));
+        graphics.scroll_right(4);
+        assert!(graphics.is_pixel_on(8, 3));
+        graphics.scroll_left(4);
+        graphics.scroll_left(4);
+        assert!(graphics.is_pixel_on(0, 3));
+        assert!(!graphics.is_pixel_on(8, 3));
+
+        // pixels scrolled past the edge are gone
+        graphics.scroll_left(1);
+        graphics.scroll_right(1);
+        assert!(!graphics.is_pixel_on(0, 3));
+    }
+
+    #[test]
+    fn test_set_resolution_scales_contents() {
+        let mut graphics = GraphicsBuffer::new();
+        graphics.draw_byte(0, 0, 0x80);
+        graphics.set_resolution(HIRES_WIDTH, HIRES_HEIGHT, false);
+        assert!(graphics.is_hires());
+        let lit: Vec<usize> = graphics
+            .as_rgb8()
+            .chunks(3)
+            .enumerate()
+            .filter(|(_, p)| p[0] != 0)
+            .map(|(i, _)| i)
+            .collect();
+        // the pixel is scaled to a 2x2 block
+        assert_eq!(lit, [0, 1, HIRES_WIDTH, HIRES_WIDTH + 1]);
+
+        graphics.set_resolution(WIDTH, HEIGHT, false);
+        assert_eq!(graphics.as_rgb8()[..6], [255, 255, 255, 0, 0, 0]);
+
+        graphics.set_resolution(HIRES_WIDTH, HIRES_HEIGHT, true);
+        assert!(graphics.as_rgb8().iter().all(|&channel| channel == 0));
     }
 }
@@ -10,17 +10,28 @@ pub struct KeyRequestResponse {
 
 /// Input system for the `Chip8`. This keeps track of the pressed state of all 16 keys,
 /// as well as any key press requests from programs.
+///
+/// Like the COSMAC VIP, a key press request is only answered once the pressed key
+/// is released, so a program doesn't see the same press again right after.
 #[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct Input {
     state: [bool; 16],
     waiting: bool,
     request_reg: usize,
     request_response: Option<KeyRequestResponse>,
+
+    /// The key pressed while waiting, which answers the request once it is released.
+    #[cfg_attr(
+        feature = "persistence",
+        serde(with = "crate::state::since_key_release_version")
+    )]
+    pending_key: Option<u8>,
 }
 
 impl Input {
     /// Create a new [`Input`] instance.
+    #[cfg_attr(not(feature = "raw"), allow(dead_code))]
     pub fn new() -> Self {
         Self::default()
     }
@@ -28,12 +39,20 @@ impl Input {
     /// Update the input state of the given key code.
     pub fn update(&mut self, key_code: u8, pressed: bool) {
         self.state[usize::from(key_code)] = pressed;
-        if pressed && self.waiting {
-            self.waiting = false;
-            self.request_response = Some(KeyRequestResponse {
-                key_code,
-                register: self.request_reg,
-            });
+        if !self.waiting {
+            return;
+        }
+        match self.pending_key {
+            None if pressed => self.pending_key = Some(key_code),
+            Some(pending) if pending == key_code && !pressed => {
+                self.waiting = false;
+                self.pending_key = None;
+                self.request_response = Some(KeyRequestResponse {
+                    key_code,
+                    register: self.request_reg,
+                });
+            }
+            _ => {}
         }
     }
 
@@ -41,6 +60,7 @@ impl Input {
     pub fn request_key_press(&mut self, register: usize) {
         self.waiting = true;
         self.request_reg = register;
+        self.pending_key = None;
     }
 
     /// Get the input request response. This will be `None` if
@@ -56,8 +76,43 @@ impl Input {
         self.waiting
     }
 
+    /// The register the key code of the requested key press is stored in, if waiting for one.
+    pub fn request_register(&self) -> Option<usize> {
+        self.waiting.then_some(self.request_reg)
+    }
+
+    /// The key that was pressed while waiting, and has to be released to answer the request.
+    pub fn pending_key(&self) -> Option<u8> {
+        self.pending_key
+    }
+
     /// Returns whether the given key is currently pressed.
     pub fn is_key_pressed(&self, key_code: u8) -> bool {
         self.state[usize::from(key_code)]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Input;
+
+    #[test]
+    fn test_key_request_answered_on_release() {
+        let mut input = Input::new();
+        input.request_key_press(3);
+        input.update(0x5, true);
+        assert!(input.waiting());
+        assert_eq!(input.pending_key(), Some(0x5));
+
+        // other keys don't answer the request
+        input.update(0x6, true);
+        input.update(0x6, false);
+        assert!(input.waiting());
+
+        input.update(0x5, false);
+        assert!(!input.waiting());
+        let response = input.request_response().unwrap();
+        assert_eq!((response.key_code, response.register), (0x5, 3));
+        assert!(input.request_response().is_none());
+    }
+}
@@ -0,0 +1,264 @@
+/// A decoded CHIP-8 instruction, with its operands taken out of the opcode.
+///
+/// Decoding doesn't depend on the [`Variant`](crate::Variant): instructions that only some
+/// variants support are decoded as such, and it is up to the interpreter to reject them
+/// or, for the `0___` instructions, treat them as machine code routines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Instr {
+    /// `00E0`: clear the screen.
+    Clear,
+
+    /// `0230`: clear the screen of hi-res CHIP-8.
+    ClearHires,
+
+    /// `00EE`: return from a subroutine.
+    Return,
+
+    /// `00Cn`: scroll the display down by `n` pixels (SUPER-CHIP).
+    ScrollDown(usize),
+
+    /// `00Dn`: scroll the selected planes up by `n` pixels (XO-CHIP).
+    ScrollUp(usize),
+
+    /// `00FB`: scroll the display right by 4 pixels (SUPER-CHIP).
+    ScrollRight,
+
+    /// `00FC`: scroll the display left by 4 pixels (SUPER-CHIP).
+    ScrollLeft,
+
+    /// `00FD`: exit the interpreter (SUPER-CHIP).
+    Exit,
+
+    /// `00FE`: disable hi-res mode (SUPER-CHIP).
+    LowRes,
+
+    /// `00FF`: enable hi-res mode (SUPER-CHIP).
+    HighRes,
+
+    /// `0nnn`: execute the machine code routine at `nnn`.
+    MachineCode(usize),
+
+    /// `1nnn`: jump to `nnn`.
+    Jump(usize),
+
+    /// `2nnn`: call the subroutine at `nnn`.
+    Call(usize),
+
+    /// `3xnn`: skip the next instruction if `Vx == nn`.
+    SkipIfEqual { x: usize, nn: u8 },
+
+    /// `4xnn`: skip the next instruction if `Vx != nn`.
+    SkipIfNotEqual { x: usize, nn: u8 },
+
+    /// `5xy0`: skip the next instruction if `Vx == Vy`.
+    SkipIfEqualReg { x: usize, y: usize },
+
+    /// `5xy2`: store `Vx` to `Vy` starting at `I` (XO-CHIP).
+    StoreRange { x: usize, y: usize },
+
+    /// `5xy3`: load `Vx` to `Vy` from memory starting at `I` (XO-CHIP).
+    LoadRange { x: usize, y: usize },
+
+    /// `6xnn`: set `Vx` to `nn`.
+    Set { x: usize, nn: u8 },
+
+    /// `7xnn`: add `nn` to `Vx`, without a carry.
+    Add { x: usize, nn: u8 },
+
+    /// `8xy0`: set `Vx` to `Vy`.
+    SetReg { x: usize, y: usize },
+
+    /// `8xy1`: set `Vx` to `Vx OR Vy`.
+    Or { x: usize, y: usize },
+
+    /// `8xy2`: set `Vx` to `Vx AND Vy`.
+    And { x: usize, y: usize },
+
+    /// `8xy3`: set `Vx` to `Vx XOR Vy`.
+    Xor { x: usize, y: usize },
+
+    /// `8xy4`: add `Vy` to `Vx`, with the carry in `VF`.
+    AddReg { x: usize, y: usize },
+
+    /// `8xy5`: subtract `Vy` from `Vx`, with the borrow in `VF`.
+    SubReg { x: usize, y: usize },
+
+    /// `8xy6`: shift `Vx` (or `Vy`) one right, with the shifted out bit in `VF`.
+    ShiftRight { x: usize, y: usize },
+
+    /// `8xy7`: set `Vx` to `Vy - Vx`, with the borrow in `VF`.
+    SubRegReversed { x: usize, y: usize },
+
+    /// `8xyE`: shift `Vx` (or `Vy`) one left, with the shifted out bit in `VF`.
+    ShiftLeft { x: usize, y: usize },
+
+    /// `9xy0`: skip the next instruction if `Vx != Vy`.
+    SkipIfNotEqualReg { x: usize, y: usize },
+
+    /// `Annn`: set `I` to `nnn`.
+    SetIndex(usize),
+
+    /// `Bnnn`: jump to `nnn` plus `V0`, or plus `Vx` with the jump quirk.
+    JumpOffset { x: usize, nnn: usize },
+
+    /// `Cxnn`: set `Vx` to a random number AND `nn`.
+    Random { x: usize, nn: u8 },
+
+    /// `Dxyn`: draw the `n` byte sprite at `I` at `(Vx, Vy)`.
+    Draw { x: usize, y: usize, n: usize },
+
+    /// `Ex9E`: skip the next instruction if the key in `Vx` is pressed.
+    SkipIfKey(usize),
+
+    /// `ExA1`: skip the next instruction if the key in `Vx` isn't pressed.
+    SkipIfNotKey(usize),
+
+    /// `F000 nnnn`: set `I` to the address in the next word (XO-CHIP).
+    SetLongIndex,
+
+    /// `Fn01`: select the planes `n` (XO-CHIP).
+    SelectPlanes(u8),
+
+    /// `Fx07`: set `Vx` to the delay timer.
+    GetDelay(usize),
+
+    /// `Fx0A`: wait for a key press and release, and store the key in `Vx`.
+    WaitKey(usize),
+
+    /// `Fx15`: set the delay timer to `Vx`.
+    SetDelay(usize),
+
+    /// `Fx18`: set the sound timer to `Vx`.
+    SetSound(usize),
+
+    /// `Fx1E`: add `Vx` to `I`.
+    AddIndex(usize),
+
+    /// `Fx29`: set `I` to the sprite of the digit in `Vx`.
+    Digit(usize),
+
+    /// `Fx30`: set `I` to the large sprite of the digit in `Vx` (SUPER-CHIP).
+    BigDigit(usize),
+
+    /// `Fx33`: store the BCD representation of `Vx` starting at `I`.
+    Bcd(usize),
+
+    /// `Fx55`: store `V0` to `Vx` starting at `I`.
+    Store(usize),
+
+    /// `Fx65`: load `V0` to `Vx` from memory starting at `I`.
+    Load(usize),
+
+    /// `Fx75`: store `V0` to `Vx` in the RPL user flags (SUPER-CHIP).
+    SaveFlags(usize),
+
+    /// `Fx85`: load `V0` to `Vx` from the RPL user flags (SUPER-CHIP).
+    LoadFlags(usize),
+
+    /// An opcode that isn't an instruction of any variant.
+    Invalid,
+}
+
+/// Decode an opcode into the instruction it encodes.
+pub fn decode(opcode: u16) -> Instr {
+    let x = usize::from((opcode & 0x0F00) >> 8);
+    let y = usize::from((opcode & 0x00F0) >> 4);
+    let n = usize::from(opcode & 0x000F);
+    let nn = (opcode & 0x00FF) as u8;
+    let nnn = usize::from(opcode & 0x0FFF);
+
+    match opcode >> 12 {
+        0x0 => match opcode {
+            0x00E0 => Instr::Clear,
+            0x0230 => Instr::ClearHires,
+            0x00EE => Instr::Return,
+            0x00C0..=0x00CF => Instr::ScrollDown(n),
+            0x00D0..=0x00DF => Instr::ScrollUp(n),
+            0x00FB => Instr::ScrollRight,
+            0x00FC => Instr::ScrollLeft,
+            0x00FD => Instr::Exit,
+            0x00FE => Instr::LowRes,
+            0x00FF => Instr::HighRes,
+            _ => Instr::MachineCode(nnn),
+        },
+        0x1 => Instr::Jump(nnn),
+        0x2 => Instr::Call(nnn),
+        0x3 => Instr::SkipIfEqual { x, nn },
+        0x4 => Instr::SkipIfNotEqual { x, nn },
+        0x5 => match n {
+            0x0 => Instr::SkipIfEqualReg { x, y },
+            0x2 => Instr::StoreRange { x, y },
+            0x3 => Instr::LoadRange { x, y },
+            _ => Instr::Invalid,
+        },
+        0x6 => Instr::Set { x, nn },
+        0x7 => Instr::Add { x, nn },
+        0x8 => match n {
+            0x0 => Instr::SetReg { x, y },
+            0x1 => Instr::Or { x, y },
+            0x2 => Instr::And { x, y },
+            0x3 => Instr::Xor { x, y },
+            0x4 => Instr::AddReg { x, y },
+            0x5 => Instr::SubReg { x, y },
+            0x6 => Instr::ShiftRight { x, y },
+            0x7 => Instr::SubRegReversed { x, y },
+            0xE => Instr::ShiftLeft { x, y },
+            _ => Instr::Invalid,
+        },
+        0x9 if n == 0 => Instr::SkipIfNotEqualReg { x, y },
+        0xA => Instr::SetIndex(nnn),
+        0xB => Instr::JumpOffset { x, nnn },
+        0xC => Instr::Random { x, nn },
+        0xD => Instr::Draw { x, y, n },
+        0xE => match nn {
+            0x9E => Instr::SkipIfKey(x),
+            0xA1 => Instr::SkipIfNotKey(x),
+            _ => Instr::Invalid,
+        },
+        0xF => match nn {
+            0x00 if x == 0 => Instr::SetLongIndex,
+            0x01 => Instr::SelectPlanes(x as u8),
+            0x07 => Instr::GetDelay(x),
+            0x0A => Instr::WaitKey(x),
+            0x15 => Instr::SetDelay(x),
+            0x18 => Instr::SetSound(x),
+            0x1E => Instr::AddIndex(x),
+            0x29 => Instr::Digit(x),
+            0x30 => Instr::BigDigit(x),
+            0x33 => Instr::Bcd(x),
+            0x55 => Instr::Store(x),
+            0x65 => Instr::Load(x),
+            0x75 => Instr::SaveFlags(x),
+            0x85 => Instr::LoadFlags(x),
+            _ => Instr::Invalid,
+        },
+        _ => Instr::Invalid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(0x00E0), Instr::Clear);
+        assert_eq!(decode(0x0123), Instr::MachineCode(0x123));
+        assert_eq!(decode(0x00C4), Instr::ScrollDown(4));
+        assert_eq!(decode(0x00FB), Instr::ScrollRight);
+        assert_eq!(decode(0x1234), Instr::Jump(0x234));
+        assert_eq!(decode(0x8AB4), Instr::AddReg { x: 0xA, y: 0xB });
+        assert_eq!(decode(0xB345), Instr::JumpOffset { x: 3, nnn: 0x345 });
+        assert_eq!(decode(0xD12F), Instr::Draw { x: 1, y: 2, n: 0xF });
+        assert_eq!(decode(0xF301), Instr::SelectPlanes(3));
+        assert_eq!(decode(0xF165), Instr::Load(1));
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        for opcode in [0x5121, 0x8128, 0x9121, 0xE100, 0xF100, 0xF1FF] {
+            assert_eq!(decode(opcode), Instr::Invalid, "{opcode:04X}");
+        }
+    }
+}
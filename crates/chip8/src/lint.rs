@@ -0,0 +1,303 @@
+//! A static analyzer that flags suspicious patterns in a ROM before it is run, like jumps
+//! to odd addresses or sprites drawn before `I` is set.
+//!
+//! Only the code found by following the control flow from the entry point is analyzed,
+//! see [`disasm::find_code`](crate::disasm::find_code), and memory is assumed to be the
+//! 4 KB of CHIP-8.
+
+use std::{collections::BTreeMap, fmt};
+
+use crate::{
+    disasm::{self, Edge, EdgeKind},
+    instr::Instr,
+    memory::MEMORY_SIZE,
+};
+
+/// A suspicious pattern found by [`lint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Problem {
+    /// A jump or call to an odd address, which is rarely intended.
+    OddTarget { target: usize },
+
+    /// A jump or call to an address outside of the ROM.
+    TargetOutsideRom { target: usize },
+
+    /// A call to a subroutine that never reaches a return.
+    NoReturn { subroutine: usize },
+
+    /// An access to `len` bytes at `I`, which go past the end of memory.
+    PastEndOfMemory { index: usize, len: usize },
+
+    /// A sprite drawn while `I` may not have been set yet.
+    UninitializedIndex,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::OddTarget { target } => write!(f, "Jump to the odd address {target:#06X}"),
+            Problem::TargetOutsideRom { target } => {
+                write!(f, "Jump to {target:#06X}, outside of the ROM")
+            }
+            Problem::NoReturn { subroutine } => {
+                write!(
+                    f,
+                    "Call to the subroutine at {subroutine:#06X}, which never returns"
+                )
+            }
+            Problem::PastEndOfMemory { index, len } => write!(
+                f,
+                "Access to {len} bytes at I = {index:#06X}, past the end of memory"
+            ),
+            Problem::UninitializedIndex => write!(f, "Sprite drawn before I is set"),
+        }
+    }
+}
+
+/// A [`Problem`] found at the instruction at `address`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub address: usize,
+    pub problem: Problem,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#06X}: {}", self.address, self.problem)
+    }
+}
+
+/// What is known about `I` before an instruction, on every path that leads to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct IndexState {
+    /// Whether `I` was set on every path.
+    initialized: bool,
+
+    /// The value of `I`, if it is the same on every path.
+    value: Option<usize>,
+}
+
+impl IndexState {
+    /// The state on entry, before `I` is set.
+    const ENTRY: IndexState = IndexState {
+        initialized: false,
+        value: None,
+    };
+
+    /// The state after a subroutine returns, which may have changed `I`. The subroutine is
+    /// assumed to have set it, to not flag the common routines that point `I` at a sprite.
+    const AFTER_CALL: IndexState = IndexState {
+        initialized: true,
+        value: None,
+    };
+
+    /// Combine the states of two paths that lead to the same instruction.
+    fn join(self, other: IndexState) -> IndexState {
+        IndexState {
+            initialized: self.initialized && other.initialized,
+            value: if self.value == other.value {
+                self.value
+            } else {
+                None
+            },
+        }
+    }
+
+    /// The state after the given instruction, whose next word is `next_word`.
+    fn after(self, instr: Instr, next_word: Option<u16>) -> IndexState {
+        let set = |value| IndexState {
+            initialized: true,
+            value,
+        };
+        match instr {
+            Instr::SetIndex(nnn) => set(Some(nnn)),
+            Instr::SetLongIndex => set(next_word.map(usize::from)),
+            // the font is always in memory
+            Instr::Digit(_) | Instr::BigDigit(_) => set(None),
+            // whether Fx55 and Fx65 increment I is a quirk
+            Instr::AddIndex(_) | Instr::Store(_) | Instr::Load(_) => IndexState {
+                value: None,
+                ..self
+            },
+            _ => self,
+        }
+    }
+}
+
+/// Analyze a ROM loaded at `start`, returning the suspicious patterns that were found,
+/// ordered by their address.
+pub fn lint(rom: &[u8], start: usize) -> Vec<Diagnostic> {
+    let instructions = disasm::explore(rom, start);
+    let word = |address: usize| {
+        let offset = address.checked_sub(start)?;
+        match rom.get(offset..offset + 2)? {
+            &[high, low] => Some(u16::from_be_bytes([high, low])),
+            _ => None,
+        }
+    };
+    let index_states = index_states(&instructions, start, word);
+    let mut diagnostics = Vec::new();
+    for (&address, state) in &index_states {
+        let opcode = match word(address) {
+            Some(opcode) => opcode,
+            None => continue,
+        };
+        let mut report = |problem| diagnostics.push(Diagnostic { address, problem });
+        let instr = crate::decode(opcode);
+        match instr {
+            Instr::Jump(target) | Instr::Call(target) | Instr::JumpOffset { nnn: target, .. } => {
+                if target % 2 == 1 {
+                    report(Problem::OddTarget { target });
+                }
+                // the offset of Bnnn can still take it into the ROM
+                let is_offset = matches!(instr, Instr::JumpOffset { .. });
+                if target < start || (target >= start + rom.len() && !is_offset) {
+                    report(Problem::TargetOutsideRom { target });
+                }
+                if matches!(instr, Instr::Call(_)) && !returns(&instructions, target, word) {
+                    report(Problem::NoReturn { subroutine: target });
+                }
+            }
+            Instr::Draw { n, .. } => {
+                if !state.initialized {
+                    report(Problem::UninitializedIndex);
+                }
+                // 16x16 sprites of SUPER-CHIP take 32 bytes
+                let len = if n == 0 { 32 } else { n };
+                check_bounds(state, len, &mut report);
+            }
+            Instr::Bcd(_) => check_bounds(state, 3, &mut report),
+            Instr::Store(x) | Instr::Load(x) => check_bounds(state, x + 1, &mut report),
+            _ => {}
+        }
+    }
+    diagnostics
+}
+
+/// Report a [`Problem::PastEndOfMemory`] if the `len` bytes at the known value of `I` go past
+/// the end of memory.
+fn check_bounds(state: &IndexState, len: usize, report: &mut impl FnMut(Problem)) {
+    if let Some(index) = state.value {
+        if index + len > MEMORY_SIZE {
+            report(Problem::PastEndOfMemory { index, len });
+        }
+    }
+}
+
+/// Find what is known about `I` before each of the `instructions`, by propagating it along
+/// the control flow from `start` until nothing changes.
+fn index_states(
+    instructions: &BTreeMap<usize, Vec<Edge>>,
+    start: usize,
+    word: impl Fn(usize) -> Option<u16>,
+) -> BTreeMap<usize, IndexState> {
+    let mut states: BTreeMap<usize, IndexState> = BTreeMap::new();
+    let mut pending = vec![(start, IndexState::ENTRY)];
+    while let Some((address, state)) = pending.pop() {
+        let edges = match instructions.get(&address) {
+            Some(edges) => edges,
+            None => continue,
+        };
+        let state = match states.get(&address) {
+            Some(&old) if old.join(state) == old => continue,
+            Some(&old) => old.join(state),
+            None => state,
+        };
+        states.insert(address, state);
+        let instr = match word(address) {
+            Some(opcode) => crate::decode(opcode),
+            None => continue,
+        };
+        let after = state.after(instr, word(address + 2));
+        for edge in edges {
+            let next = match (instr, edge.kind) {
+                (Instr::Call(_), EdgeKind::Call) => state,
+                (Instr::Call(_), _) => IndexState::AFTER_CALL,
+                _ => after,
+            };
+            pending.push((edge.target, next));
+        }
+    }
+    states
+}
+
+/// Returns whether the subroutine at `subroutine` can reach a return, without following
+/// the calls it makes. A jump through `Bnnn` or an exit count as a return.
+fn returns(
+    instructions: &BTreeMap<usize, Vec<Edge>>,
+    subroutine: usize,
+    word: impl Fn(usize) -> Option<u16>,
+) -> bool {
+    let mut visited = Vec::new();
+    let mut pending = vec![subroutine];
+    while let Some(address) = pending.pop() {
+        if visited.contains(&address) {
+            continue;
+        }
+        visited.push(address);
+        let edges = match instructions.get(&address) {
+            Some(edges) => edges,
+            None => continue,
+        };
+        if let Some(opcode) = word(address) {
+            if matches!(
+                crate::decode(opcode),
+                Instr::Return | Instr::Exit | Instr::JumpOffset { .. }
+            ) {
+                return true;
+            }
+        }
+        pending.extend(
+            edges
+                .iter()
+                .filter(|edge| edge.kind != EdgeKind::Call)
+                .map(|edge| edge.target),
+        );
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint() {
+        let rom = [
+            0xD0, 0x15, // draw before I is set
+            0x22, 0x0C, // call a subroutine that doesn't return
+            0xAF, 0xFE, // point I at the end of memory
+            0xD0, 0x15, // draw 5 bytes past it
+            0x12, 0x0B, // jump to an odd address
+            0x00, 0xEE, // unreachable return
+            0x12, 0x0C, // the subroutine, which loops forever
+        ];
+        let diagnostics: Vec<_> = lint(&rom, 0x200)
+            .iter()
+            .map(Diagnostic::to_string)
+            .collect();
+        assert_eq!(
+            diagnostics,
+            [
+                "0x0200: Sprite drawn before I is set",
+                "0x0202: Call to the subroutine at 0x020C, which never returns",
+                "0x0206: Access to 5 bytes at I = 0x0FFE, past the end of memory",
+                "0x0208: Jump to the odd address 0x020B",
+            ]
+        );
+
+        // I is set on every path to the draw
+        let rom = [0x30, 0x00, 0xA3, 0x00, 0xA3, 0x02, 0xD0, 0x15, 0x12, 0x08];
+        assert!(lint(&rom, 0x200).is_empty());
+        // but not on the path that skips over it
+        let rom = [0x30, 0x00, 0xA3, 0x00, 0xD0, 0x15, 0x12, 0x06];
+        assert_eq!(
+            lint(&rom, 0x200),
+            [Diagnostic {
+                address: 0x204,
+                problem: Problem::UninitializedIndex
+            }]
+        );
+    }
+}
@@ -0,0 +1,379 @@
+//! Running a [`Chip8`] in lockstep with a reference implementation, like a known-good
+//! emulator, to find the first step where their states diverge. This is mostly useful
+//! to validate quirks against the interpreter a program was written for.
+
+use std::fmt;
+
+use crate::{rom::RomError, Chip8, TimeSource};
+
+/// The state of a CHIP-8 implementation that is compared after every step.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoreState {
+    /// The values of the `V0` to `VF` registers.
+    pub v: [u8; 16],
+    pub i: usize,
+    pub pc: usize,
+
+    /// The return addresses on the stack, the most recent call last.
+    pub stack: Vec<usize>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+
+    /// The whole memory, or `None` to not compare it.
+    pub memory: Option<Vec<u8>>,
+
+    /// The width of the display and whether each of its pixels is on, row by row,
+    /// or `None` to not compare it.
+    pub display: Option<(usize, Vec<bool>)>,
+}
+
+impl CoreState {
+    /// The full state of the given `Chip8`.
+    pub fn of(chip8: &Chip8) -> Self {
+        let display = chip8.display();
+        Self {
+            v: *chip8.registers(),
+            i: chip8.index(),
+            pc: chip8.pc(),
+            stack: chip8.stack().to_vec(),
+            delay_timer: chip8.delay_timer(),
+            sound_timer: chip8.sound_timer(),
+            memory: Some(chip8.memory().to_vec()),
+            display: Some((
+                display.width(),
+                (0..display.height())
+                    .flat_map(|y| (0..display.width()).map(move |x| (x, y)))
+                    .map(|(x, y)| display.is_pixel_on(x, y))
+                    .collect(),
+            )),
+        }
+    }
+}
+
+/// A CHIP-8 implementation to compare a [`Chip8`] against, see [`run_lockstep`].
+pub trait ReferenceCore {
+    /// Reset the implementation and load the ROM at its start address.
+    fn load_rom(&mut self, rom: &[u8]);
+
+    /// Execute a single instruction.
+    fn step(&mut self);
+
+    /// Tick the delay and sound timers once, as if 1/60 s passed.
+    fn tick_timers(&mut self);
+
+    /// Press or release a key of the keypad, from 0x0 to 0xF.
+    fn set_key(&mut self, key: u8, pressed: bool);
+
+    /// The current state. The memory and display are only compared if they are given.
+    fn state(&self) -> CoreState;
+}
+
+/// Another `Chip8` can be the reference, e.g. to compare two sets of quirks.
+impl ReferenceCore for Chip8 {
+    fn load_rom(&mut self, rom: &[u8]) {
+        // a ROM that doesn't fit diverges on the first step
+        let _ = self.reset_and_load(rom.to_vec());
+        self.set_time_source(TimeSource::Manual);
+    }
+
+    fn step(&mut self) {
+        Chip8::step(self);
+    }
+
+    fn tick_timers(&mut self) {
+        Chip8::tick_timers(self);
+    }
+
+    fn set_key(&mut self, key: u8, pressed: bool) {
+        self.update_key_state(key, pressed);
+    }
+
+    fn state(&self) -> CoreState {
+        CoreState::of(self)
+    }
+}
+
+/// A key press or release before the given step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// The amount of steps executed before the key changes.
+    pub step: usize,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+/// The settings of a lockstep run, see [`run_lockstep`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Lockstep {
+    /// The amount of steps to execute, unless the states diverge or the `Chip8` halts first.
+    pub max_steps: usize,
+
+    /// The amount of steps between ticks of the timers of both implementations.
+    pub steps_per_tick: u32,
+
+    /// The key presses and releases, ordered by their step.
+    pub keys: Vec<KeyEvent>,
+}
+
+impl Default for Lockstep {
+    fn default() -> Self {
+        Self {
+            max_steps: 100_000,
+            steps_per_tick: 10,
+            keys: Vec::new(),
+        }
+    }
+}
+
+/// A value that differs between the `Chip8` and the reference implementation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// What differs, like `V3`, `stack[1]`, `mem[0x0300]` or `pixel (4, 2)`.
+    pub name: String,
+    pub ours: usize,
+    pub reference: usize,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {:#X}, but {:#X} in the reference",
+            self.name, self.ours, self.reference
+        )
+    }
+}
+
+/// The first step where the states diverged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// The amount of steps executed, 0 if the states differ after loading the ROM.
+    pub step: usize,
+
+    /// The address and opcode of the last instruction the `Chip8` executed,
+    /// `None` if the states differ after loading the ROM.
+    pub instruction: Option<(usize, u16)>,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.instruction {
+            Some((address, opcode)) => writeln!(
+                f,
+                "Diverged at step {}, after {opcode:04X} at {address:#06X}",
+                self.step
+            )?,
+            None => writeln!(f, "Diverged after loading the ROM")?,
+        }
+        for mismatch in &self.mismatches {
+            writeln!(f, "  {mismatch}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Add a [`Mismatch`] named by `name` if the values differ. Only the mismatches are named,
+/// as every byte of memory is compared after every step.
+fn compare(
+    mismatches: &mut Vec<Mismatch>,
+    name: impl FnOnce() -> String,
+    ours: usize,
+    reference: usize,
+) {
+    if ours != reference {
+        mismatches.push(Mismatch {
+            name: name(),
+            ours,
+            reference,
+        });
+    }
+}
+
+/// Compare the state of the `Chip8` to the one of the reference.
+fn mismatches(ours: &CoreState, reference: &CoreState) -> Vec<Mismatch> {
+    let mut mismatches = Vec::new();
+    let m = &mut mismatches;
+    for (x, (&a, &b)) in ours.v.iter().zip(&reference.v).enumerate() {
+        compare(m, || format!("V{x:X}"), usize::from(a), usize::from(b));
+    }
+    compare(m, || "I".to_string(), ours.i, reference.i);
+    compare(m, || "PC".to_string(), ours.pc, reference.pc);
+    compare(
+        m,
+        || "stack length".to_string(),
+        ours.stack.len(),
+        reference.stack.len(),
+    );
+    for (index, (&a, &b)) in ours.stack.iter().zip(&reference.stack).enumerate() {
+        compare(m, || format!("stack[{index}]"), a, b);
+    }
+    compare(
+        m,
+        || "delay timer".to_string(),
+        usize::from(ours.delay_timer),
+        usize::from(reference.delay_timer),
+    );
+    compare(
+        m,
+        || "sound timer".to_string(),
+        usize::from(ours.sound_timer),
+        usize::from(reference.sound_timer),
+    );
+    if let (Some(a), Some(b)) = (&ours.memory, &reference.memory) {
+        compare(m, || "memory size".to_string(), a.len(), b.len());
+        for (address, (&a, &b)) in a.iter().zip(b).enumerate() {
+            compare(
+                m,
+                || format!("mem[{address:#06X}]"),
+                usize::from(a),
+                usize::from(b),
+            );
+        }
+    }
+    if let (Some((width, a)), Some((reference_width, b))) = (&ours.display, &reference.display) {
+        compare(m, || "display width".to_string(), *width, *reference_width);
+        compare(m, || "display pixels".to_string(), a.len(), b.len());
+        // pixels can only be matched up on displays of the same size
+        if width == reference_width && a.len() == b.len() {
+            for (index, (&a, &b)) in a.iter().zip(b).enumerate() {
+                compare(
+                    m,
+                    || format!("pixel ({}, {})", index % width, index / width),
+                    usize::from(a),
+                    usize::from(b),
+                );
+            }
+        }
+    }
+    mismatches
+}
+
+/// Load the ROM into the `Chip8` and the reference, then step both with the same key
+/// presses until their states diverge, returning the first [`Divergence`], or `None`
+/// if the `Chip8` halted or executed `max_steps` without diverging.
+///
+/// The `Chip8` keeps its quirks and variant, but its timers are ticked together with the
+/// ones of the reference every `steps_per_tick` steps. Programs that use random numbers
+/// only stay in sync if the reference generates the same ones.
+pub fn run_lockstep(
+    chip8: &mut Chip8,
+    reference: &mut impl ReferenceCore,
+    rom: &[u8],
+    settings: &Lockstep,
+) -> Result<Option<Divergence>, RomError> {
+    chip8.reset_and_load(rom.to_vec())?;
+    chip8.set_time_source(TimeSource::Manual);
+    reference.load_rom(rom);
+
+    let compare = |chip8: &Chip8, reference: &dyn ReferenceCore| {
+        mismatches(&CoreState::of(chip8), &reference.state())
+    };
+    let initial = compare(chip8, reference);
+    if !initial.is_empty() {
+        return Ok(Some(Divergence {
+            step: 0,
+            instruction: None,
+            mismatches: initial,
+        }));
+    }
+
+    let mut keys = settings.keys.iter().peekable();
+    for step in 1..=settings.max_steps {
+        if chip8.fault().is_some() {
+            break;
+        }
+        while let Some(event) = keys.next_if(|event| event.step < step) {
+            chip8.update_key_state(event.key, event.pressed);
+            reference.set_key(event.key, event.pressed);
+        }
+        let address = chip8.pc();
+        let opcode = match chip8.memory().get(address..address + 2) {
+            Some(&[high, low]) => u16::from_be_bytes([high, low]),
+            _ => 0,
+        };
+        chip8.step();
+        reference.step();
+        if step % settings.steps_per_tick.max(1) as usize == 0 {
+            chip8.tick_timers();
+            reference.tick_timers();
+        }
+        let mismatches = compare(chip8, reference);
+        if !mismatches.is_empty() {
+            return Ok(Some(Divergence {
+                step,
+                instruction: Some((address, opcode)),
+                mismatches,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_lockstep() {
+        // V0 = 1, V1 = 6, shift V0 (or V1 with the shift quirk) right into V0, loop
+        let rom = [0x60, 0x01, 0x61, 0x06, 0x80, 0x16, 0x12, 0x06];
+        let settings = Lockstep {
+            max_steps: 100,
+            ..Lockstep::default()
+        };
+        let mut chip8 = Chip8::new();
+        let mut reference = Chip8::new();
+        assert_eq!(
+            run_lockstep(&mut chip8, &mut reference, &rom, &settings).unwrap(),
+            None
+        );
+
+        let mut quirks = chip8.quirks();
+        quirks.shift = true;
+        chip8.set_quirks(quirks);
+        let divergence = run_lockstep(&mut chip8, &mut reference, &rom, &settings)
+            .unwrap()
+            .unwrap();
+        assert_eq!(divergence.step, 3);
+        assert_eq!(divergence.instruction, Some((0x204, 0x8016)));
+        assert_eq!(
+            divergence.to_string(),
+            "Diverged at step 3, after 8016 at 0x0204\n  \
+             V0: 0x3, but 0x0 in the reference\n  \
+             VF: 0x0, but 0x1 in the reference\n"
+        );
+    }
+
+    #[test]
+    fn test_keys_and_timers() {
+        // wait for a key in V0, set the delay timer to it, loop
+        let rom = [0xF0, 0x0A, 0xF0, 0x15, 0x12, 0x04];
+        let settings = Lockstep {
+            max_steps: 20,
+            steps_per_tick: 4,
+            keys: vec![
+                KeyEvent {
+                    step: 5,
+                    key: 0x9,
+                    pressed: true,
+                },
+                KeyEvent {
+                    step: 6,
+                    key: 0x9,
+                    pressed: false,
+                },
+            ],
+        };
+        let mut chip8 = Chip8::new();
+        assert_eq!(
+            run_lockstep(&mut chip8, &mut Chip8::new(), &rom, &settings).unwrap(),
+            None
+        );
+        assert_eq!(chip8.pc(), 0x204);
+        // the key is read when released after step 6, the timer set to 9 in step 7 and
+        // ticked after steps 8, 12, 16 and 20
+        assert_eq!(chip8.delay_timer(), 5);
+    }
+}
@@ -1,13 +1,21 @@
-use std::ops::{Index, IndexMut};
+use std::{
+    fmt,
+    ops::{Index, IndexMut, Range},
+};
+
+use crate::rom::RomError;
 
 /// Total size of the Chip8 memory.
-const MEMORY_SIZE: usize = 4096;
+pub const MEMORY_SIZE: usize = 4096;
+
+/// Total size of the XO-CHIP memory, the whole 16-bit address space.
+pub const XO_CHIP_MEMORY_SIZE: usize = 0x10000;
 
 /// The size of the interpreter.
 /// 
 /// This is really only used to determine where 
 /// the program memory should start.
-const INTERPRETER_SIZE: usize = 512;
+pub(crate) const INTERPRETER_SIZE: usize = 512;
 
 /// Built in Chip8 font data. This will be stored in the
 /// interpreter's memory.
@@ -30,35 +38,242 @@ const FONT: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
-/// The memory of the `Chip8`.
+/// The address of the large SUPER-CHIP font, stored right after the built in font.
+pub const BIG_FONT_START: usize = FONT.len();
+
+/// The size of a single digit of the large SUPER-CHIP font.
+pub const BIG_FONT_DIGIT_SIZE: usize = 10;
+
+/// Large SUPER-CHIP font data, with 8x10 pixel digits used in hi-res mode.
+/// This will be stored in the interpreter's memory.
+const BIG_FONT: [u8; 160] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+/// The standard sizes of the memory of the `Chip8`.
 #[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MemorySize {
+    /// The 4 KB of the original CHIP-8 and SUPER-CHIP, [`MEMORY_SIZE`] bytes.
+    #[default]
+    Standard,
+
+    /// The 64 KB of XO-CHIP, the whole 16-bit address space, for larger programs.
+    Extended,
+}
+
+impl MemorySize {
+    /// All sizes, in the order they should be listed.
+    pub const ALL: [MemorySize; 2] = [MemorySize::Standard, MemorySize::Extended];
+
+    /// The size in bytes.
+    pub fn bytes(self) -> usize {
+        match self {
+            MemorySize::Standard => MEMORY_SIZE,
+            MemorySize::Extended => XO_CHIP_MEMORY_SIZE,
+        }
+    }
+}
+
+impl fmt::Display for MemorySize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemorySize::Standard => write!(f, "4 KB"),
+            MemorySize::Extended => write!(f, "64 KB"),
+        }
+    }
+}
+
+/// What happens when an instruction writes into a [`ReadOnlyRegion`] of the memory.
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadOnlyAction {
+    /// The write is ignored, and the instruction continues.
+    Ignore,
+
+    /// The `Processor` is halted with a [`Fault::ReadOnlyWrite`](crate::Fault::ReadOnlyWrite)
+    /// before anything is written.
+    Fault,
+}
+
+/// A range of the memory that instructions can't write to, e.g. to catch programs
+/// clobbering the built-in fonts with `Fx55` or `Fx33`. The host can still write to it.
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadOnlyRegion {
+    pub range: Range<usize>,
+    pub action: ReadOnlyAction,
+}
+
+impl ReadOnlyRegion {
+    /// The built-in fonts at the start of the memory, the small and the large one.
+    pub fn fonts(action: ReadOnlyAction) -> Self {
+        Self {
+            range: 0..BIG_FONT_START + BIG_FONT.len(),
+            action,
+        }
+    }
+}
+
+/// A cheat that pins the byte at `address` to `value`, e.g. to keep the lives of a game
+/// from decreasing. See [`Chip8::set_freezes`](crate::Chip8::set_freezes).
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Freeze {
+    pub address: usize,
+    pub value: u8,
+}
+
+/// The memory of the `Chip8`, [`MEMORY_SIZE`] bytes unless resized to another [`MemorySize`].
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone)]
 pub struct Memory {
-    #[cfg_attr(feature = "persistence", serde(with = "serde_big_array::BigArray"))]
-    memory: [u8; MEMORY_SIZE],
+    #[cfg_attr(feature = "persistence", serde(with = "layout"))]
+    memory: Vec<u8>,
+
+    /// The regions instructions can't write to.
+    /// This is a setting of the host, which isn't part of save states.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    read_only: Vec<ReadOnlyRegion>,
+
+    /// Whether each byte was executed as part of an instruction since the ROM was loaded.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    executed: Vec<bool>,
 }
 
 impl Default for Memory {
     fn default() -> Self {
-        let mut memory = [0; MEMORY_SIZE];
+        let mut memory = vec![0; MEMORY_SIZE];
         memory[..80].clone_from_slice(&FONT);
-        Self { memory }
+        memory[BIG_FONT_START..BIG_FONT_START + BIG_FONT.len()].clone_from_slice(&BIG_FONT);
+        Self {
+            memory,
+            read_only: Vec::new(),
+            executed: vec![false; MEMORY_SIZE],
+        }
     }
 }
 
 impl Memory {
     /// Create a new `Memory` object filled with zeroes.
+    #[cfg_attr(not(feature = "raw"), allow(dead_code))]
     pub fn new() -> Self {
         Self::default()
     }
 
-    /// Load the ROM bytes from `data`.
+    /// Change the size of the memory to `size` bytes. Added bytes are zero,
+    /// and the bytes past the new size are dropped.
+    pub fn resize(&mut self, size: usize) {
+        self.memory.resize(size, 0);
+        self.executed.resize(size, false);
+    }
+
+    /// The standard size closest to the size of the memory.
+    pub fn size(&self) -> MemorySize {
+        if self.memory.len() > MEMORY_SIZE {
+            MemorySize::Extended
+        } else {
+            MemorySize::Standard
+        }
+    }
+
+    /// Load the ROM bytes from `data` into the memory starting at `start`,
+    /// usually [`INTERPRETER_SIZE`].
     ///
     /// If this is smaller than the program size
-    /// (`MEMORY_SIZE - INTERPRETER_SIZE`), then the remaining
-    /// memory will be filled with zeroes.
-    pub fn load_rom(&mut self, mut data: Vec<u8>) {
-        data.resize(MEMORY_SIZE - INTERPRETER_SIZE, 0);
-        self.memory[INTERPRETER_SIZE..=0xFFF].clone_from_slice(&data);
+    /// (the size of the memory minus `start`), then the remaining
+    /// memory will be filled with zeroes. A ROM larger than the program size
+    /// is not loaded, and [`RomError::TooLarge`] is returned.
+    pub fn load_rom(&mut self, mut data: Vec<u8>, start: usize) -> Result<(), RomError> {
+        let start = start.min(self.memory.len());
+        let program_size = self.memory.len() - start;
+        if data.len() > program_size {
+            return Err(RomError::TooLarge {
+                size: data.len(),
+                capacity: program_size,
+            });
+        }
+        data.resize(program_size, 0);
+        self.memory[start..].clone_from_slice(&data);
+        self.executed = vec![false; self.memory.len()];
+        Ok(())
+    }
+
+    /// Get the whole memory as a slice.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// Get the whole memory as a mutable slice.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+
+    /// The regions instructions can't write to.
+    pub fn read_only_regions(&self) -> &[ReadOnlyRegion] {
+        &self.read_only
+    }
+
+    /// Set the regions instructions can't write to, replacing the previous ones.
+    pub fn set_read_only_regions(&mut self, regions: Vec<ReadOnlyRegion>) {
+        self.read_only = regions;
+    }
+
+    /// What happens to a write of an instruction to `address`, `None` if it isn't read-only.
+    /// The first region containing the address decides.
+    pub fn read_only_action(&self, address: usize) -> Option<ReadOnlyAction> {
+        self.read_only
+            .iter()
+            .find(|region| region.range.contains(&address))
+            .map(|region| region.action)
+    }
+
+    /// Remember that the `len` bytes starting at `address` were executed as an instruction.
+    pub fn mark_executed(&mut self, address: usize, len: usize) {
+        // save states don't store which bytes were executed
+        self.executed.resize(self.memory.len(), false);
+        let end = (address + len).min(self.executed.len());
+        if let Some(bytes) = self.executed.get_mut(address..end) {
+            bytes.fill(true);
+        }
+    }
+
+    /// Whether the byte at `address` was executed as part of an instruction
+    /// since the ROM was loaded.
+    pub fn was_executed(&self, address: usize) -> bool {
+        self.executed.get(address).copied().unwrap_or(false)
+    }
+
+    /// Whether each byte was executed as part of an instruction since the ROM was loaded,
+    /// indexed by address.
+    pub fn executed(&self) -> &[bool] {
+        &self.executed
+    }
+
+    /// Write `value` to `address` for an instruction, unless the address is read-only.
+    /// Returns whether the value was written.
+    pub fn write(&mut self, address: usize, value: u8) -> bool {
+        if self.read_only_action(address).is_some() {
+            log::debug!("Ignoring write to read-only memory at {address:#06X}");
+            return false;
+        }
+        self.memory[address] = value;
+        true
     }
 }
 
@@ -75,3 +290,34 @@ impl IndexMut<usize> for Memory {
         &mut self.memory[index]
     }
 }
+
+/// The layout of the memory in save states. Before format version 2, it was always
+/// [`MEMORY_SIZE`] bytes, stored without a length.
+#[cfg(feature = "persistence")]
+mod layout {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde_big_array::BigArray;
+
+    use super::MEMORY_SIZE;
+    use crate::state;
+
+    pub fn serialize<S: Serializer>(memory: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        if state::format_version() < state::XO_CHIP_VERSION {
+            let mut legacy = [0; MEMORY_SIZE];
+            let len = memory.len().min(MEMORY_SIZE);
+            legacy[..len].copy_from_slice(&memory[..len]);
+            BigArray::serialize(&legacy, serializer)
+        } else {
+            memory.serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        if state::format_version() < state::XO_CHIP_VERSION {
+            let legacy: [u8; MEMORY_SIZE] = BigArray::deserialize(deserializer)?;
+            Ok(legacy.to_vec())
+        } else {
+            Vec::deserialize(deserializer)
+        }
+    }
+}
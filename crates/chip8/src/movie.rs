@@ -0,0 +1,297 @@
+//! Recording the key presses of a session into a [`Movie`] that replays exactly the same
+//! way on a fresh [`Chip8`], e.g. for tool-assisted runs or to reproduce a bug report.
+//!
+//! Movies are recorded in the deterministic mode, see [`Deterministic`], so the random
+//! numbers and the timers only depend on the executed steps. Key changes are recorded with
+//! the step they happened before, which makes replays independent of how the steps were
+//! split into frames.
+
+use std::fmt;
+
+use crate::{
+    rom::RomError, rom_hash, Chip8, Deterministic, Freeze, InvalidOpcodePolicy, MachineCodePolicy,
+    MemoryAccessPolicy, MemorySize, Quirks, ReadOnlyRegion, StepHook, Variant,
+};
+
+/// A press or release of a key, before the step `step` of a [`Movie`].
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovieEvent {
+    /// The amount of steps executed before the key changed.
+    pub step: u64,
+
+    /// The frame the key changed in, counted from 0. Only informational, as replays
+    /// follow the steps.
+    pub frame: u64,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+/// The settings and key presses of a recorded session, see [`MovieRecorder`].
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    /// The [`rom_hash`] of the recorded ROM.
+    pub rom_hash: u64,
+    pub variant: Variant,
+    pub quirks: Quirks,
+    pub start_address: usize,
+    pub memory_size: MemorySize,
+    pub machine_code_policy: MachineCodePolicy,
+    pub invalid_opcode_policy: InvalidOpcodePolicy,
+    pub memory_access_policy: MemoryAccessPolicy,
+    pub read_only_regions: Vec<ReadOnlyRegion>,
+    pub freezes: Vec<Freeze>,
+    pub rpl_flags: [u8; 16],
+    pub deterministic: Deterministic,
+
+    /// The key changes, in the order they happened.
+    pub events: Vec<MovieEvent>,
+
+    /// The length of the movie, in steps and frames.
+    pub steps: u64,
+    pub frames: u64,
+}
+
+/// An error replaying a [`Movie`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ReplayError {
+    /// The movie was recorded with another ROM.
+    RomMismatch { expected: u64, actual: u64 },
+
+    /// The ROM couldn't be loaded.
+    Rom(RomError),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::RomMismatch { expected, actual } => write!(
+                f,
+                "The movie was recorded with the ROM {expected:016x}, not {actual:016x}"
+            ),
+            ReplayError::Rom(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<RomError> for ReplayError {
+    fn from(e: RomError) -> Self {
+        ReplayError::Rom(e)
+    }
+}
+
+impl Movie {
+    /// Replay the movie on a fresh `Chip8` with the settings of the recording, returning the
+    /// `Chip8` in the state the recording ended in.
+    pub fn replay(&self, rom: &[u8]) -> Result<Chip8, ReplayError> {
+        let actual = rom_hash(rom);
+        if actual != self.rom_hash {
+            return Err(ReplayError::RomMismatch {
+                expected: self.rom_hash,
+                actual,
+            });
+        }
+        let mut chip8 = Chip8::new();
+        chip8.set_variant(self.variant);
+        chip8.set_quirks(self.quirks);
+        chip8.set_start_address(self.start_address);
+        chip8.set_memory_size(self.memory_size);
+        chip8.set_machine_code_policy(self.machine_code_policy);
+        chip8.set_invalid_opcode_policy(self.invalid_opcode_policy);
+        chip8.set_memory_access_policy(self.memory_access_policy);
+        chip8.set_read_only_regions(self.read_only_regions.clone());
+        chip8.set_freezes(self.freezes.clone());
+        chip8.set_rpl_flags(self.rpl_flags);
+        chip8.set_deterministic(Some(self.deterministic));
+        chip8.reset_and_load(rom.to_vec())?;
+
+        let mut events = self.events.iter().peekable();
+        for step in 0..self.steps {
+            while let Some(event) = events.next_if(|event| event.step <= step) {
+                chip8.update_key_state(event.key, event.pressed);
+            }
+            chip8.step();
+        }
+        for event in events {
+            chip8.update_key_state(event.key, event.pressed);
+        }
+        Ok(chip8)
+    }
+}
+
+/// Records the key presses of a `Chip8` into a [`Movie`]. The recorder observes the steps
+/// as a [`StepHook`] of [`Chip8::run_frame_with`], and every frame has to end with
+/// [`MovieRecorder::end_frame`].
+///
+/// The `Chip8` must only change through the steps while recording: loading a state or
+/// stepping back makes the movie replay differently.
+#[derive(Debug, Clone)]
+pub struct MovieRecorder {
+    movie: Movie,
+
+    /// The pressed state of every key before the last step, indexed by key code.
+    keys: [bool; 16],
+}
+
+impl MovieRecorder {
+    /// Enable the `deterministic` mode, reset the `Chip8`, load the ROM, and start recording.
+    /// The other settings of the `Chip8` that change the execution, like its quirks, policies
+    /// and freezes, are kept and recorded, so they must not change while recording.
+    pub fn start(
+        chip8: &mut Chip8,
+        rom: Vec<u8>,
+        deterministic: Deterministic,
+    ) -> Result<Self, RomError> {
+        chip8.set_deterministic(Some(deterministic));
+        let movie = Movie {
+            rom_hash: rom_hash(&rom),
+            variant: chip8.variant(),
+            quirks: chip8.quirks(),
+            start_address: chip8.start_address(),
+            memory_size: chip8.memory_size(),
+            machine_code_policy: chip8.machine_code_policy(),
+            invalid_opcode_policy: chip8.invalid_opcode_policy(),
+            memory_access_policy: chip8.memory_access_policy(),
+            read_only_regions: chip8.read_only_regions().to_vec(),
+            freezes: chip8.freezes().to_vec(),
+            rpl_flags: chip8.rpl_flags(),
+            deterministic,
+            events: Vec::new(),
+            steps: 0,
+            frames: 0,
+        };
+        chip8.reset_and_load(rom)?;
+        Ok(Self {
+            movie,
+            keys: [false; 16],
+        })
+    }
+
+    /// Record the keys that changed since the last step. This should be called before every
+    /// step, which [`StepHook::before_step`] does.
+    pub fn before_step(&mut self, chip8: &Chip8) {
+        for (key, pressed) in (0..).zip(&mut self.keys) {
+            if chip8.is_key_pressed(key) != *pressed {
+                *pressed = !*pressed;
+                self.movie.events.push(MovieEvent {
+                    step: self.movie.steps,
+                    frame: self.movie.frames,
+                    key,
+                    pressed: *pressed,
+                });
+            }
+        }
+    }
+
+    /// Count a step. This should be called after every step, which [`StepHook::after_step`]
+    /// does.
+    pub fn after_step(&mut self) {
+        self.movie.steps += 1;
+    }
+
+    /// Count a frame.
+    pub fn end_frame(&mut self) {
+        self.movie.frames += 1;
+    }
+
+    /// The movie recorded so far.
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    /// Stop recording, returning the movie.
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+impl StepHook for MovieRecorder {
+    fn before_step(&mut self, chip8: &Chip8) -> bool {
+        MovieRecorder::before_step(self, chip8);
+        false
+    }
+
+    fn after_step(&mut self, _chip8: &Chip8) -> bool {
+        MovieRecorder::after_step(self);
+        false
+    }
+}
+
+/// Encode a movie to save it to disk.
+#[cfg(feature = "persistence")]
+pub fn save(movie: &Movie) -> Result<Vec<u8>, bincode::Error> {
+    bincode::serialize(movie)
+}
+
+/// Decode a movie written by [`save`].
+#[cfg(feature = "persistence")]
+pub fn load(bytes: &[u8]) -> Result<Movie, bincode::Error> {
+    bincode::deserialize(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FrameInput;
+
+    /// Wait for a key in V0, draw a random sprite where the key says, then start over.
+    const ROM: [u8; 10] = [0xF0, 0x0A, 0xC1, 0xFF, 0xF1, 0x29, 0xD0, 0x05, 0x12, 0x00];
+
+    #[test]
+    fn test_record_and_replay() {
+        let deterministic = Deterministic {
+            seed: 42,
+            steps_per_tick: 5,
+        };
+        let mut chip8 = Chip8::new();
+        chip8.set_invalid_opcode_policy(InvalidOpcodePolicy::Halt);
+        // pin `D005` to `D003`, which draws the top 3 rows of the sprites
+        chip8.set_freezes(vec![Freeze {
+            address: 0x207,
+            value: 0x03,
+        }]);
+        let mut recorder = MovieRecorder::start(&mut chip8, ROM.to_vec(), deterministic).unwrap();
+        let input = FrameInput {
+            steps: 7,
+            ..Default::default()
+        };
+        for frame in 0..20 {
+            // tap a key every few frames
+            let key = frame % 16;
+            chip8.update_key_state(key, frame % 3 == 0);
+            chip8.run_frame_with(&input, &mut recorder);
+            recorder.end_frame();
+        }
+        let movie = recorder.finish();
+        assert_eq!(movie.frames, 20);
+        assert_eq!(movie.steps, 140);
+        assert_eq!(
+            movie.events[0],
+            MovieEvent {
+                step: 0,
+                frame: 0,
+                key: 0,
+                pressed: true
+            }
+        );
+
+        let replayed = movie.replay(&ROM).unwrap();
+        assert_eq!(replayed.state_hash(), chip8.state_hash());
+        assert_eq!(replayed.invalid_opcode_policy(), InvalidOpcodePolicy::Halt);
+        assert_eq!(replayed.freezes(), chip8.freezes());
+        #[cfg(feature = "persistence")]
+        assert_eq!(load(&save(&movie).unwrap()).unwrap(), movie);
+
+        assert_eq!(
+            movie.replay(&[0x12, 0x00]).err(),
+            Some(ReplayError::RomMismatch {
+                expected: movie.rom_hash,
+                actual: rom_hash(&[0x12, 0x00]),
+            })
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use crate::Chip8;
+
+/// Receives the events of the execution of a [`Chip8`], e.g. to implement a debugger,
+/// a profiler or a script that reacts to the program. Registered with
+/// [`Chip8::add_observer`](crate::Chip8::add_observer).
+///
+/// The methods are called right after the step the event happened in, with the `Chip8`
+/// in the state after the step.
+pub trait Observer: Send {
+    /// An instruction was executed at `address`. Steps that wait for a key
+    /// or are halted don't execute one.
+    fn on_instruction(&mut self, _chip8: &Chip8, _address: usize, _opcode: u16) {}
+
+    /// A sprite `height` rows high was drawn at `(x, y)`, wrapped to the display.
+    /// `collision` tells whether it turned off a pixel.
+    fn on_draw(&mut self, _chip8: &Chip8, _x: usize, _y: usize, _height: usize, _collision: bool) {}
+
+    /// The display was cleared, with `00E0` or the `0230` of hi-res CHIP-8.
+    fn on_clear(&mut self, _chip8: &Chip8) {}
+
+    /// The program started waiting for a key press, which is stored in `V[register]`.
+    fn on_key_wait(&mut self, _chip8: &Chip8, _register: usize) {}
+
+    /// The delay and sound timers ticked, as 1/60 s passed.
+    fn on_timer_tick(&mut self, _chip8: &Chip8) {}
+}
+
+/// Identifies an [`Observer`] added to a `Chip8`, to remove it again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObserverId(u64);
+
+/// The observers of a `Chip8`.
+#[derive(Default)]
+pub(crate) struct Observers {
+    observers: Vec<(ObserverId, Box<dyn Observer>)>,
+    next_id: u64,
+}
+
+/// What is needed to find the events of a step, taken before it is executed.
+pub(crate) struct PendingStep {
+    /// The address and opcode of the instruction the step executes, if it executes one.
+    instruction: Option<(usize, u16)>,
+    registers: [u8; 16],
+    waiting_for_key: bool,
+    timers_ticked: bool,
+}
+
+impl Observers {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+
+    pub(crate) fn add(&mut self, observer: Box<dyn Observer>) -> ObserverId {
+        let id = ObserverId(self.next_id);
+        self.next_id += 1;
+        self.observers.push((id, observer));
+        id
+    }
+
+    pub(crate) fn remove(&mut self, id: ObserverId) -> Option<Box<dyn Observer>> {
+        let index = self.observers.iter().position(|(other, _)| *other == id)?;
+        Some(self.observers.remove(index).1)
+    }
+
+    /// Take what is needed to find the events of the next step, once the timers were
+    /// updated for it.
+    pub(crate) fn before_step(chip8: &Chip8) -> PendingStep {
+        let processor = &chip8.processor;
+        let executes = processor.fault.is_none() && !chip8.bus.input.waiting();
+        PendingStep {
+            instruction: executes
+                .then(|| processor.peek_opcode(&chip8.bus))
+                .flatten()
+                .map(|opcode| (processor.pc, opcode as u16)),
+            registers: processor.v,
+            waiting_for_key: chip8.waiting_for_key(),
+            timers_ticked: chip8.bus.clock.vblank_interrupt,
+        }
+    }
+
+    /// Notify the observers of the events of the step that `pending` was taken before.
+    pub(crate) fn after_step(&mut self, chip8: &Chip8, pending: PendingStep) {
+        let observers = self.observers.iter_mut().map(|(_, observer)| observer);
+        for observer in observers {
+            if pending.timers_ticked {
+                observer.on_timer_tick(chip8);
+            }
+            if let Some((address, opcode)) = pending.instruction {
+                observer.on_instruction(chip8, address, opcode);
+                // a draw waiting for the vertical blank jumps to itself to try again
+                let completed = chip8.pc() != address;
+                match crate::decode(opcode) {
+                    crate::Instr::Clear | crate::Instr::ClearHires if completed => {
+                        observer.on_clear(chip8)
+                    }
+                    crate::Instr::Draw { x, y, n } if completed => {
+                        let display = chip8.display();
+                        let height = if n == 0 && chip8.variant().has_hires() {
+                            16
+                        } else {
+                            n
+                        };
+                        observer.on_draw(
+                            chip8,
+                            usize::from(pending.registers[x]) % display.width(),
+                            usize::from(pending.registers[y]) % display.height(),
+                            height,
+                            chip8.processor.draw_collision,
+                        );
+                    }
+                    _ => {}
+                }
+            }
+            if !pending.waiting_for_key {
+                if let Some(register) = chip8.bus.input.request_register() {
+                    observer.on_key_wait(chip8, register);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{TimeSource, Variant};
+
+    /// Writes every event into a shared log.
+    struct Log(Arc<Mutex<Vec<String>>>);
+
+    impl Observer for Log {
+        fn on_instruction(&mut self, _chip8: &Chip8, address: usize, opcode: u16) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{address:#06X}: {opcode:04X}"));
+        }
+
+        fn on_draw(&mut self, _chip8: &Chip8, x: usize, y: usize, height: usize, collision: bool) {
+            self.0.lock().unwrap().push(format!(
+                "draw {height} rows at ({x}, {y}), collision: {collision}"
+            ));
+        }
+
+        fn on_clear(&mut self, _chip8: &Chip8) {
+            self.0.lock().unwrap().push("clear".to_string());
+        }
+
+        fn on_key_wait(&mut self, _chip8: &Chip8, register: usize) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("wait for key in V{register:X}"));
+        }
+
+        fn on_timer_tick(&mut self, _chip8: &Chip8) {
+            self.0.lock().unwrap().push("tick".to_string());
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let mut chip8 = Chip8::new();
+        chip8.set_time_source(TimeSource::Manual);
+        // clear, V0 = 66, draw the 0 glyph at (66 % 64, 66 % 32) twice, wait for a key in V3
+        chip8
+            .load_rom_data(vec![
+                0x00, 0xE0, 0x60, 0x42, 0xD0, 0x05, 0xD0, 0x05, 0xF3, 0x0A,
+            ])
+            .unwrap();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let id = chip8.add_observer(Box::new(Log(Arc::clone(&log))));
+        chip8.run_until(4, |_| false);
+        chip8.tick_timers();
+        chip8.run_until(2, |_| false);
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "0x0200: 00E0",
+                "clear",
+                "0x0202: 6042",
+                "0x0204: D005",
+                "draw 5 rows at (2, 2), collision: false",
+                "0x0206: D005",
+                "draw 5 rows at (2, 2), collision: true",
+                "tick",
+                "0x0208: F30A",
+                "wait for key in V3",
+            ]
+        );
+
+        assert!(chip8.remove_observer(id).is_some());
+        assert!(chip8.remove_observer(id).is_none());
+        chip8.tick_timers();
+        chip8.step();
+        assert_eq!(log.lock().unwrap().len(), 10);
+    }
+
+    #[test]
+    fn test_observer_timers_paused_on_key_wait() {
+        let mut chip8 = Chip8::new();
+        chip8.set_time_source(TimeSource::Steps(1));
+        let mut quirks = chip8.quirks();
+        quirks.pause_timers_on_key_wait = true;
+        chip8.set_quirks(quirks);
+        // wait for a key in V3
+        chip8.load_rom_data(vec![0xF3, 0x0A]).unwrap();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        chip8.add_observer(Box::new(Log(Arc::clone(&log))));
+        chip8.run_until(50, |_| false);
+        // only the step before the wait ticks the timers
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["tick", "0x0200: F30A", "wait for key in V3"]
+        );
+    }
+
+    #[test]
+    fn test_observer_hires_collision() {
+        let mut chip8 = Chip8::new();
+        chip8.set_variant(Variant::SuperChip);
+        chip8.set_time_source(TimeSource::Manual);
+        // enable hi-res, V0 = 66, draw the 0 glyph at (66, 2) twice
+        chip8
+            .load_rom_data(vec![0x00, 0xFF, 0x60, 0x42, 0xD0, 0x05, 0xD0, 0x05])
+            .unwrap();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        chip8.add_observer(Box::new(Log(Arc::clone(&log))));
+        chip8.run_until(4, |_| false);
+        // SUPER-CHIP sets VF to the amount of rows that collided
+        assert_eq!(chip8.registers()[0xF], 5);
+        assert_eq!(
+            log.lock().unwrap()[3..],
+            [
+                "draw 5 rows at (66, 2), collision: false",
+                "0x0206: D005",
+                "draw 5 rows at (66, 2), collision: true",
+            ]
+        );
+    }
+}
@@ -0,0 +1,1097 @@
+//! A compiler for [Octo](https://github.com/JohnEarnest/Octo), the high level
+//! CHIP-8 assembly language used by the Octo IDE and its cartridges.
+//!
+//! The whole language is supported except for `:stringmode`. Like Octo, the
+//! output starts at [`START`] and ends at the highest address that was written.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+};
+
+use crate::{rom::START, symbols::Symbols};
+
+/// The highest address a program can write to, the end of the 64KB XO-CHIP memory.
+const MAX_ADDRESS: usize = 0xFFFF;
+
+/// An error in an Octo program, with the line it occurred on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OctoError {
+    /// The line of the error, starting at 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for OctoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for OctoError {}
+
+/// Compile the given Octo source into a ROM.
+pub fn compile(source: &str) -> Result<Vec<u8>, OctoError> {
+    compile_with_symbols(source).map(|(rom, _)| rom)
+}
+
+/// Compile the given Octo source into a ROM, along with the symbols of its labels.
+pub fn compile_with_symbols(source: &str) -> Result<(Vec<u8>, Symbols), OctoError> {
+    let mut compiler = Compiler::new(tokenize(source));
+    while !compiler.tokens.is_empty() {
+        compiler.statement()?;
+    }
+    let mut symbols = Symbols::new();
+    for (name, &address) in &compiler.labels {
+        symbols.insert(address, name.clone());
+    }
+    compiler.finish().map(|rom| (rom, symbols))
+}
+
+/// A word of the source, with the line it is on.
+#[derive(Clone)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+/// Split the source into tokens, dropping `#` comments. Strings in double
+/// quotes are kept as a single token, including the quotes.
+fn tokenize(source: &str) -> VecDeque<Token> {
+    let mut tokens = VecDeque::new();
+    for (index, line) in source.lines().enumerate() {
+        let mut chars = line.chars().peekable();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+                continue;
+            }
+            if c == '#' {
+                break;
+            }
+            let mut text = String::new();
+            if c == '"' {
+                text.push(c);
+                chars.next();
+                for c in chars.by_ref() {
+                    text.push(c);
+                    if c == '"' {
+                        break;
+                    }
+                }
+            } else {
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() {
+                        break;
+                    }
+                    text.push(c);
+                    chars.next();
+                }
+            }
+            tokens.push_back(Token {
+                text,
+                line: index + 1,
+            });
+        }
+    }
+    tokens
+}
+
+/// Parse a number literal: decimal, hexadecimal with a `0x` prefix,
+/// or binary with a `0b` prefix, optionally negated with a `-`.
+fn parse_number(text: &str) -> Option<f64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()? as f64
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()? as f64
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// Parse a register name, `v0` to `vF`.
+fn parse_register(text: &str) -> Option<u8> {
+    match text.as_bytes() {
+        [b'v' | b'V', digit] => (*digit as char).to_digit(16).map(|x| x as u8),
+        _ => None,
+    }
+}
+
+/// How a forward reference to a label is filled in once the label is defined.
+#[derive(Clone, Copy)]
+enum Fixup {
+    /// The low 12 bits of the instruction at the address, e.g. `jump` or `i :=`.
+    Address,
+
+    /// The 16-bit address following `i := long`, at the given address.
+    Long,
+
+    /// The pair of `v0 :=` and `v1 :=` instructions of `:unpack`, with the given high nibble.
+    Unpack(u8),
+}
+
+/// A use of a label before it is defined.
+struct Reference {
+    name: String,
+    address: usize,
+    fixup: Fixup,
+    line: usize,
+}
+
+/// An open control flow block.
+enum Block {
+    /// An `if ... begin`, with the address of the jump to the `else` or `end`.
+    If(usize),
+
+    /// An `else`, with the address of the jump to the `end`.
+    Else(usize),
+
+    /// A `loop`, with the address it starts at and the addresses of the jumps of its `while`s.
+    Loop { start: usize, whiles: Vec<usize> },
+}
+
+/// A macro defined with `:macro`.
+struct Macro {
+    parameters: Vec<String>,
+    body: Vec<Token>,
+}
+
+/// A condition of an `if` or `while`.
+enum Condition {
+    /// `vx == n` or `vx != n`.
+    Immediate { x: u8, equal: bool, value: u8 },
+
+    /// `vx == vy` or `vx != vy`.
+    Register { x: u8, equal: bool, y: u8 },
+
+    /// `vx key` or `vx -key`.
+    Key { x: u8, pressed: bool },
+
+    /// An ordering comparison of `vx` with an immediate or register.
+    Compare {
+        x: u8,
+        operator: Ordering,
+        right: Operand,
+    },
+}
+
+/// The operator of a [`Condition::Compare`].
+#[derive(Clone, Copy)]
+enum Ordering {
+    Less,
+    Greater,
+    LessOrEqual,
+    GreaterOrEqual,
+}
+
+/// The right hand side of a comparison.
+enum Operand {
+    Immediate(u8),
+    Register(u8),
+}
+
+impl Condition {
+    /// Get the condition that holds exactly when this one doesn't.
+    fn negate(self) -> Self {
+        match self {
+            Condition::Immediate { x, equal, value } => Condition::Immediate {
+                x,
+                equal: !equal,
+                value,
+            },
+            Condition::Register { x, equal, y } => Condition::Register {
+                x,
+                equal: !equal,
+                y,
+            },
+            Condition::Key { x, pressed } => Condition::Key {
+                x,
+                pressed: !pressed,
+            },
+            Condition::Compare { x, operator, right } => Condition::Compare {
+                x,
+                operator: match operator {
+                    Ordering::Less => Ordering::GreaterOrEqual,
+                    Ordering::Greater => Ordering::LessOrEqual,
+                    Ordering::LessOrEqual => Ordering::Greater,
+                    Ordering::GreaterOrEqual => Ordering::Less,
+                },
+                right,
+            },
+        }
+    }
+}
+
+struct Compiler {
+    tokens: VecDeque<Token>,
+
+    /// The line of the last token that was read, used for errors.
+    line: usize,
+
+    /// The compiled program, starting at [`START`].
+    rom: Vec<u8>,
+
+    /// The address the next byte is written to.
+    here: usize,
+
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    references: Vec<Reference>,
+    blocks: Vec<Block>,
+}
+
+impl Compiler {
+    fn new(tokens: VecDeque<Token>) -> Self {
+        Self {
+            tokens,
+            line: 1,
+            rom: Vec::new(),
+            here: START,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            references: Vec::new(),
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Create an error on the current line.
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, OctoError> {
+        Err(OctoError {
+            line: self.line,
+            message: message.into(),
+        })
+    }
+
+    /// Read the next token.
+    fn next(&mut self) -> Result<String, OctoError> {
+        match self.tokens.pop_front() {
+            Some(token) => {
+                self.line = token.line;
+                Ok(token.text)
+            }
+            None => self.error("Unexpected end of program"),
+        }
+    }
+
+    /// Read the next token, which must be `expected`.
+    fn expect(&mut self, expected: &str) -> Result<(), OctoError> {
+        let token = self.next()?;
+        if token != expected {
+            return self.error(format!("Expected `{expected}`, found `{token}`"));
+        }
+        Ok(())
+    }
+
+    /// Returns whether the next token is `text`, reading it if it is.
+    fn accept(&mut self, text: &str) -> bool {
+        let matches = matches!(self.tokens.front(), Some(token) if token.text == text);
+        if matches {
+            self.tokens.pop_front();
+        }
+        matches
+    }
+
+    /// Resolve a register name or alias.
+    fn register_of(&self, name: &str) -> Option<u8> {
+        parse_register(name).or_else(|| self.aliases.get(name).copied())
+    }
+
+    /// Read a register.
+    fn register(&mut self) -> Result<u8, OctoError> {
+        let token = self.next()?;
+        match self.register_of(&token) {
+            Some(x) => Ok(x),
+            None => self.error(format!("Expected a register, found `{token}`")),
+        }
+    }
+
+    /// Resolve a number, constant or defined label.
+    fn value_of(&self, name: &str) -> Option<f64> {
+        parse_number(name)
+            .or_else(|| self.constants.get(name).copied())
+            .or_else(|| self.labels.get(name).map(|&address| address as f64))
+    }
+
+    /// Read a value, which must fit in the range `min..=max`.
+    fn value(&mut self, min: i64, max: i64) -> Result<i64, OctoError> {
+        let token = self.next()?;
+        let value = if token == "{" {
+            self.expression_block()?
+        } else {
+            match self.value_of(&token) {
+                Some(value) => value,
+                None => return self.error(format!("Undefined name `{token}`")),
+            }
+        };
+        let value = value.floor() as i64;
+        if !(min..=max).contains(&value) {
+            return self.error(format!("Value {value} is out of range {min}..={max}"));
+        }
+        Ok(value)
+    }
+
+    /// Read a byte, which can be negative.
+    fn byte(&mut self) -> Result<u8, OctoError> {
+        Ok(self.value(-128, 255)? as u8)
+    }
+
+    /// Read a nibble.
+    fn nibble(&mut self) -> Result<u8, OctoError> {
+        Ok(self.value(0, 15)? as u8)
+    }
+
+    /// Read an address, which may be a label that is defined later. Returns `None`
+    /// for such labels, after recording a reference to be filled in with `fixup`.
+    fn address(&mut self, fixup: Fixup, max: usize) -> Result<Option<usize>, OctoError> {
+        let is_name = matches!(self.tokens.front(), Some(token) if self.value_of(&token.text).is_none()
+                && token.text != "{"
+                && self.register_of(&token.text).is_none());
+        if is_name {
+            let name = self.next()?;
+            self.references.push(Reference {
+                name,
+                address: self.here,
+                fixup,
+                line: self.line,
+            });
+            return Ok(None);
+        }
+        Ok(Some(self.value(0, max as i64)? as usize))
+    }
+
+    /// Write a byte at the current address.
+    fn emit(&mut self, byte: u8) -> Result<(), OctoError> {
+        if !(START..=MAX_ADDRESS).contains(&self.here) {
+            return self.error(format!(
+                "Address {:#06X} is outside of the program",
+                self.here
+            ));
+        }
+        let index = self.here - START;
+        if index >= self.rom.len() {
+            self.rom.resize(index + 1, 0);
+        }
+        self.rom[index] = byte;
+        self.here += 1;
+        Ok(())
+    }
+
+    /// Write an instruction at the current address.
+    fn emit_opcode(&mut self, opcode: u16) -> Result<(), OctoError> {
+        let [high, low] = opcode.to_be_bytes();
+        self.emit(high)?;
+        self.emit(low)
+    }
+
+    /// Write an instruction whose low 12 bits are an address, e.g. `jump`.
+    fn emit_address_opcode(&mut self, opcode: u16) -> Result<(), OctoError> {
+        let address = self.address(Fixup::Address, 0xFFF)?.unwrap_or(0);
+        self.emit_opcode(opcode | address as u16)
+    }
+
+    /// Overwrite the low 12 bits of the instruction at `address`.
+    fn patch_address(&mut self, address: usize, target: usize) {
+        let index = address - START;
+        self.rom[index] = (self.rom[index] & 0xF0) | ((target >> 8) & 0xF) as u8;
+        self.rom[index + 1] = target as u8;
+    }
+
+    /// Compile the next statement.
+    fn statement(&mut self) -> Result<(), OctoError> {
+        let token = self.next()?;
+        if let Some(x) = self.register_of(&token) {
+            return self.register_statement(x);
+        }
+        match token.as_str() {
+            ":" => {
+                let name = self.next()?;
+                self.define_label(name, self.here)?;
+            }
+            ":next" => {
+                let name = self.next()?;
+                self.define_label(name, self.here + 1)?;
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let x = self.register()?;
+                self.aliases.insert(name, x);
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.next()?;
+                match self.value_of(&value) {
+                    Some(value) => {
+                        self.constants.insert(name, value);
+                    }
+                    None => return self.error(format!("Undefined name `{value}`")),
+                }
+            }
+            ":calc" => {
+                let name = self.next()?;
+                self.expect("{")?;
+                let value = self.expression_block()?;
+                self.constants.insert(name, value);
+            }
+            ":byte" => {
+                let byte = self.byte()?;
+                self.emit(byte)?;
+            }
+            ":org" => self.here = self.value(0, MAX_ADDRESS as i64)? as usize,
+            ":unpack" => {
+                let nibble = self.nibble()?;
+                let address = self.address(Fixup::Unpack(nibble), 0xFFF)?.unwrap_or(0);
+                self.emit_opcode(0x6000 | u16::from(nibble) << 4 | (address >> 8) as u16)?;
+                self.emit_opcode(0x6100 | (address & 0xFF) as u16)?;
+            }
+            ":call" => self.emit_address_opcode(0x2000)?,
+            ":macro" => self.define_macro()?,
+            ":breakpoint" => {
+                self.next()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            ":assert" => {
+                let message = match self.tokens.front() {
+                    Some(token) if token.text.starts_with('"') => {
+                        Some(self.next()?.trim_matches('"').to_string())
+                    }
+                    _ => None,
+                };
+                self.expect("{")?;
+                if self.expression_block()? == 0.0 {
+                    return self.error(message.unwrap_or_else(|| "Assertion failed".into()));
+                }
+            }
+            ":stringmode" => return self.error("`:stringmode` is not supported"),
+            ";" | "return" => self.emit_opcode(0x00EE)?,
+            "clear" => self.emit_opcode(0x00E0)?,
+            "exit" => self.emit_opcode(0x00FD)?,
+            "lores" => self.emit_opcode(0x00FE)?,
+            "hires" => self.emit_opcode(0x00FF)?,
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.emit_opcode(0x00C0 | u16::from(n))?;
+            }
+            "scroll-up" => {
+                let n = self.nibble()?;
+                self.emit_opcode(0x00D0 | u16::from(n))?;
+            }
+            "scroll-right" => self.emit_opcode(0x00FB)?,
+            "scroll-left" => self.emit_opcode(0x00FC)?,
+            "audio" => self.emit_opcode(0xF002)?,
+            "native" => self.emit_address_opcode(0x0000)?,
+            "jump" => self.emit_address_opcode(0x1000)?,
+            "jump0" => self.emit_address_opcode(0xB000)?,
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.nibble()?;
+                self.emit_opcode(0xD000 | u16::from(x) << 8 | u16::from(y) << 4 | u16::from(n))?;
+            }
+            "bcd" => self.emit_x_opcode(0xF033)?,
+            "saveflags" => self.emit_x_opcode(0xF075)?,
+            "loadflags" => self.emit_x_opcode(0xF085)?,
+            "save" | "load" => {
+                let x = self.register()?;
+                if self.accept("-") {
+                    let y = self.register()?;
+                    let opcode = if token == "save" { 0x5002 } else { 0x5003 };
+                    self.emit_opcode(opcode | u16::from(x) << 8 | u16::from(y) << 4)?;
+                } else {
+                    let opcode = if token == "save" { 0xF055 } else { 0xF065 };
+                    self.emit_opcode(opcode | u16::from(x) << 8)?;
+                }
+            }
+            "plane" => {
+                let n = self.nibble()?;
+                self.emit_opcode(0xF001 | u16::from(n) << 8)?;
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let opcode = match token.as_str() {
+                    "delay" => 0xF015,
+                    "buzzer" => 0xF018,
+                    _ => 0xF03A,
+                };
+                self.emit_x_opcode(opcode)?;
+            }
+            "i" => self.index_statement()?,
+            "if" => self.if_statement()?,
+            "else" => match self.blocks.pop() {
+                Some(Block::If(jump)) => {
+                    self.emit_opcode(0x1000)?;
+                    self.patch_address(jump, self.here);
+                    self.blocks.push(Block::Else(self.here - 2));
+                }
+                _ => return self.error("`else` without `if ... begin`"),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If(jump) | Block::Else(jump)) => self.patch_address(jump, self.here),
+                _ => return self.error("`end` without `if ... begin`"),
+            },
+            "loop" => self.blocks.push(Block::Loop {
+                start: self.here,
+                whiles: Vec::new(),
+            }),
+            "while" => {
+                let condition = self.condition()?;
+                // skip the jump out of the loop while the condition holds
+                self.emit_skip_unless(condition.negate())?;
+                let jump = self.here;
+                self.emit_opcode(0x1000)?;
+                match self.blocks.iter_mut().rev().find_map(|block| match block {
+                    Block::Loop { whiles, .. } => Some(whiles),
+                    _ => None,
+                }) {
+                    Some(whiles) => whiles.push(jump),
+                    None => return self.error("`while` outside of a loop"),
+                }
+            }
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop { start, whiles }) => {
+                    self.emit_opcode(0x1000 | start as u16)?;
+                    for jump in whiles {
+                        self.patch_address(jump, self.here);
+                    }
+                }
+                _ => return self.error("`again` without `loop`"),
+            },
+            _ => {
+                if let Some(definition) = self.macros.get(&token) {
+                    let (parameters, body) =
+                        (definition.parameters.clone(), definition.body.clone());
+                    return self.expand_macro(parameters, body);
+                }
+                if let Some(value) =
+                    parse_number(&token).or_else(|| self.constants.get(&token).copied())
+                {
+                    let value = value as i64;
+                    if !(-128..=255).contains(&value) {
+                        return self.error(format!("Value {value} doesn't fit in a byte"));
+                    }
+                    return self.emit(value as u8);
+                }
+                // any other name calls the subroutine with that label
+                self.tokens.push_front(Token {
+                    text: token,
+                    line: self.line,
+                });
+                self.emit_address_opcode(0x2000)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write an instruction with a register in the second nibble, e.g. `bcd vx`.
+    fn emit_x_opcode(&mut self, opcode: u16) -> Result<(), OctoError> {
+        let x = self.register()?;
+        self.emit_opcode(opcode | u16::from(x) << 8)
+    }
+
+    /// Compile a statement starting with the register `vx`.
+    fn register_statement(&mut self, x: u8) -> Result<(), OctoError> {
+        let x16 = u16::from(x) << 8;
+        let operator = self.next()?;
+        let right = self.next()?;
+        if let Some(y) = self.register_of(&right) {
+            let y16 = u16::from(y) << 4;
+            let opcode = match operator.as_str() {
+                ":=" => 0x8000,
+                "|=" => 0x8001,
+                "&=" => 0x8002,
+                "^=" => 0x8003,
+                "+=" => 0x8004,
+                "-=" => 0x8005,
+                ">>=" => 0x8006,
+                "=-" => 0x8007,
+                "<<=" => 0x800E,
+                _ => return self.error(format!("Unknown operator `{operator}`")),
+            };
+            return self.emit_opcode(opcode | x16 | y16);
+        }
+
+        match (operator.as_str(), right.as_str()) {
+            (":=", "random") => {
+                let mask = self.byte()?;
+                self.emit_opcode(0xC000 | x16 | u16::from(mask))
+            }
+            (":=", "delay") => self.emit_opcode(0xF007 | x16),
+            (":=", "key") => self.emit_opcode(0xF00A | x16),
+            (":=" | "+=" | "-=", _) => {
+                self.tokens.push_front(Token {
+                    text: right,
+                    line: self.line,
+                });
+                let value = self.byte()?;
+                match operator.as_str() {
+                    ":=" => self.emit_opcode(0x6000 | x16 | u16::from(value)),
+                    "+=" => self.emit_opcode(0x7000 | x16 | u16::from(value)),
+                    _ => self.emit_opcode(0x7000 | x16 | u16::from(value.wrapping_neg())),
+                }
+            }
+            _ => self.error(format!("Unknown operator `{operator}` for `{right}`")),
+        }
+    }
+
+    /// Compile a statement assigning the `i` register.
+    fn index_statement(&mut self) -> Result<(), OctoError> {
+        let operator = self.next()?;
+        match operator.as_str() {
+            "+=" => self.emit_x_opcode(0xF01E),
+            ":=" => {
+                if self.accept("hex") {
+                    self.emit_x_opcode(0xF029)
+                } else if self.accept("bighex") {
+                    self.emit_x_opcode(0xF030)
+                } else if self.accept("long") {
+                    self.emit_opcode(0xF000)?;
+                    let address = self.address(Fixup::Long, MAX_ADDRESS)?.unwrap_or(0);
+                    self.emit_opcode(address as u16)
+                } else {
+                    self.emit_address_opcode(0xA000)
+                }
+            }
+            _ => self.error(format!("Unknown operator `{operator}` for `i`")),
+        }
+    }
+
+    /// Compile an `if ... then` or `if ... begin`.
+    fn if_statement(&mut self) -> Result<(), OctoError> {
+        let condition = self.condition()?;
+        let token = self.next()?;
+        match token.as_str() {
+            // the next statement is skipped unless the condition holds
+            "then" => self.emit_skip_unless(condition),
+            "begin" => {
+                // skip the jump to the `else` or `end` if the condition holds
+                self.emit_skip_unless(condition.negate())?;
+                self.blocks.push(Block::If(self.here));
+                self.emit_opcode(0x1000)
+            }
+            _ => self.error(format!("Expected `then` or `begin`, found `{token}`")),
+        }
+    }
+
+    /// Read the condition of an `if` or `while`.
+    fn condition(&mut self) -> Result<Condition, OctoError> {
+        let x = self.register()?;
+        let operator = self.next()?;
+        match operator.as_str() {
+            "key" => return Ok(Condition::Key { x, pressed: true }),
+            "-key" => return Ok(Condition::Key { x, pressed: false }),
+            _ => {}
+        }
+
+        let right = match self
+            .tokens
+            .front()
+            .and_then(|token| self.register_of(&token.text))
+        {
+            Some(y) => {
+                self.next()?;
+                Operand::Register(y)
+            }
+            None => Operand::Immediate(self.byte()?),
+        };
+        let operator = match operator.as_str() {
+            "==" | "!=" => {
+                let equal = operator == "==";
+                return Ok(match right {
+                    Operand::Immediate(value) => Condition::Immediate { x, equal, value },
+                    Operand::Register(y) => Condition::Register { x, equal, y },
+                });
+            }
+            "<" => Ordering::Less,
+            ">" => Ordering::Greater,
+            "<=" => Ordering::LessOrEqual,
+            ">=" => Ordering::GreaterOrEqual,
+            _ => return self.error(format!("Unknown comparison `{operator}`")),
+        };
+        Ok(Condition::Compare { x, operator, right })
+    }
+
+    /// Write the instructions that skip the next instruction unless the `condition` holds.
+    fn emit_skip_unless(&mut self, condition: Condition) -> Result<(), OctoError> {
+        let opcode = match condition {
+            Condition::Immediate { x, equal, value } => {
+                let opcode = if equal { 0x4000 } else { 0x3000 };
+                opcode | u16::from(x) << 8 | u16::from(value)
+            }
+            Condition::Register { x, equal, y } => {
+                let opcode = if equal { 0x9000 } else { 0x5000 };
+                opcode | u16::from(x) << 8 | u16::from(y) << 4
+            }
+            Condition::Key { x, pressed } => {
+                let opcode = if pressed { 0xE0A1 } else { 0xE09E };
+                opcode | u16::from(x) << 8
+            }
+            Condition::Compare { x, operator, right } => {
+                let x = u16::from(x);
+                // compute the flag of `vx >= right` or `right >= vx` in vF
+                match (operator, right) {
+                    (Ordering::Less | Ordering::GreaterOrEqual, Operand::Register(y)) => {
+                        self.emit_opcode(0x8F00 | x << 4)?;
+                        self.emit_opcode(0x8F05 | u16::from(y) << 4)?;
+                    }
+                    (Ordering::Less | Ordering::GreaterOrEqual, Operand::Immediate(value)) => {
+                        self.emit_opcode(0x6F00 | u16::from(value))?;
+                        self.emit_opcode(0x8F07 | x << 4)?;
+                    }
+                    (Ordering::Greater | Ordering::LessOrEqual, Operand::Register(y)) => {
+                        self.emit_opcode(0x8F00 | u16::from(y) << 4)?;
+                        self.emit_opcode(0x8F05 | x << 4)?;
+                    }
+                    (Ordering::Greater | Ordering::LessOrEqual, Operand::Immediate(value)) => {
+                        self.emit_opcode(0x6F00 | u16::from(value))?;
+                        self.emit_opcode(0x8F05 | x << 4)?;
+                    }
+                }
+                match operator {
+                    // the condition holds if the flag is clear
+                    Ordering::Less | Ordering::Greater => 0x4F00,
+                    Ordering::LessOrEqual | Ordering::GreaterOrEqual => 0x3F00,
+                }
+            }
+        };
+        self.emit_opcode(opcode)
+    }
+
+    /// Define a label at the given address.
+    fn define_label(&mut self, name: String, address: usize) -> Result<(), OctoError> {
+        if parse_number(&name).is_some() || self.register_of(&name).is_some() {
+            return self.error(format!("`{name}` can't be used as a label"));
+        }
+        if self.labels.contains_key(&name) {
+            return self.error(format!("The label `{name}` is already defined"));
+        }
+        self.labels.insert(name, address);
+        Ok(())
+    }
+
+    /// Read the parameters and body of a `:macro`.
+    fn define_macro(&mut self) -> Result<(), OctoError> {
+        let name = self.next()?;
+        let mut parameters = Vec::new();
+        loop {
+            let token = self.next()?;
+            if token == "{" {
+                break;
+            }
+            parameters.push(token);
+        }
+        let mut body = Vec::new();
+        let mut depth = 1;
+        loop {
+            let token = match self.tokens.pop_front() {
+                Some(token) => token,
+                None => return self.error(format!("Unterminated macro `{name}`")),
+            };
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                break;
+            }
+            body.push(token);
+        }
+        self.macros.insert(name, Macro { parameters, body });
+        Ok(())
+    }
+
+    /// Insert the body of a macro, replacing its parameters with the arguments that follow.
+    fn expand_macro(&mut self, parameters: Vec<String>, body: Vec<Token>) -> Result<(), OctoError> {
+        let mut arguments = HashMap::new();
+        for parameter in parameters {
+            let argument = self.next()?;
+            arguments.insert(parameter, argument);
+        }
+        for mut token in body.into_iter().rev() {
+            if let Some(argument) = arguments.get(&token.text) {
+                token.text = argument.clone();
+            }
+            self.tokens.push_front(token);
+        }
+        Ok(())
+    }
+
+    /// Evaluate the expression up to the closing `}`, after the opening `{` was read.
+    fn expression_block(&mut self) -> Result<f64, OctoError> {
+        let value = self.expression()?;
+        self.expect("}")?;
+        Ok(value)
+    }
+
+    /// Evaluate an expression. Like Octo, binary operators have no precedence
+    /// and are evaluated from right to left.
+    fn expression(&mut self) -> Result<f64, OctoError> {
+        let left = self.term()?;
+        let operator = match self.tokens.front() {
+            Some(token) if is_binary_operator(&token.text) => self.next()?,
+            _ => return Ok(left),
+        };
+        let right = self.expression()?;
+        let (a, b) = (left as i64, right as i64);
+        Ok(match operator.as_str() {
+            "+" => left + right,
+            "-" => left - right,
+            "*" => left * right,
+            "/" => left / right,
+            "%" => left % right,
+            "pow" => left.powf(right),
+            "min" => left.min(right),
+            "max" => left.max(right),
+            "&" => (a & b) as f64,
+            "|" => (a | b) as f64,
+            "^" => (a ^ b) as f64,
+            "<<" => (a << b) as f64,
+            ">>" => (a >> b) as f64,
+            "<" => f64::from(u8::from(left < right)),
+            ">" => f64::from(u8::from(left > right)),
+            "<=" => f64::from(u8::from(left <= right)),
+            ">=" => f64::from(u8::from(left >= right)),
+            "==" => f64::from(u8::from(left == right)),
+            _ => f64::from(u8::from(left != right)),
+        })
+    }
+
+    /// Evaluate a single term of an expression.
+    fn term(&mut self) -> Result<f64, OctoError> {
+        let token = self.next()?;
+        let unary: Option<fn(f64) -> f64> = match token.as_str() {
+            "(" => {
+                let value = self.expression()?;
+                self.expect(")")?;
+                return Ok(value);
+            }
+            "-" => Some(|value| -value),
+            "~" => Some(|value| !(value as i64) as f64),
+            "!" => Some(|value| f64::from(u8::from(value == 0.0))),
+            "sin" => Some(f64::sin),
+            "cos" => Some(f64::cos),
+            "tan" => Some(f64::tan),
+            "exp" => Some(f64::exp),
+            "log" => Some(f64::ln),
+            "abs" => Some(f64::abs),
+            "sqrt" => Some(f64::sqrt),
+            "sign" => Some(f64::signum),
+            "ceil" => Some(f64::ceil),
+            "floor" => Some(f64::floor),
+            _ => None,
+        };
+        if let Some(unary) = unary {
+            return Ok(unary(self.term()?));
+        }
+        if token == "@" {
+            // the byte that has been compiled at an address
+            let address = self.term()? as usize;
+            let byte = address
+                .checked_sub(START)
+                .and_then(|index| self.rom.get(index))
+                .copied()
+                .unwrap_or(0);
+            return Ok(f64::from(byte));
+        }
+        match token.as_str() {
+            "HERE" => Ok(self.here as f64),
+            "PI" => Ok(std::f64::consts::PI),
+            "E" => Ok(std::f64::consts::E),
+            _ => match self.value_of(&token) {
+                Some(value) => Ok(value),
+                None => self.error(format!("Undefined name `{token}` in expression")),
+            },
+        }
+    }
+
+    /// Fill in the references to labels and return the compiled program.
+    fn finish(mut self) -> Result<Vec<u8>, OctoError> {
+        if let Some(block) = self.blocks.last() {
+            let name = match block {
+                Block::If(_) | Block::Else(_) => "if ... begin",
+                Block::Loop { .. } => "loop",
+            };
+            return self.error(format!("Unterminated `{name}`"));
+        }
+        for reference in std::mem::take(&mut self.references) {
+            let address = match self.labels.get(&reference.name) {
+                Some(&address) => address,
+                None => {
+                    return Err(OctoError {
+                        line: reference.line,
+                        message: format!("Undefined name `{}`", reference.name),
+                    })
+                }
+            };
+            match reference.fixup {
+                Fixup::Address if address > 0xFFF => {
+                    return Err(OctoError {
+                        line: reference.line,
+                        message: format!(
+                            "The label `{}` at {address:#06X} is out of reach, use `i := long`",
+                            reference.name
+                        ),
+                    })
+                }
+                Fixup::Address => self.patch_address(reference.address, address),
+                Fixup::Long => {
+                    let index = reference.address - START;
+                    self.rom[index..index + 2].copy_from_slice(&(address as u16).to_be_bytes());
+                }
+                Fixup::Unpack(nibble) => {
+                    let index = reference.address - START;
+                    self.rom[index + 1] = nibble << 4 | ((address >> 8) & 0xF) as u8;
+                    self.rom[index + 3] = address as u8;
+                }
+            }
+        }
+        Ok(self.rom)
+    }
+}
+
+/// Returns whether the token is a binary operator of an expression.
+fn is_binary_operator(token: &str) -> bool {
+    matches!(
+        token,
+        "+" | "-"
+            | "*"
+            | "/"
+            | "%"
+            | "&"
+            | "|"
+            | "^"
+            | "<<"
+            | ">>"
+            | "pow"
+            | "min"
+            | "max"
+            | "<"
+            | ">"
+            | "<="
+            | ">="
+            | "=="
+            | "!="
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_instructions() {
+        let source = "
+            : main            # the entry point
+                clear
+                v0 := 5
+                v1 += -1
+                v2 := v0
+                v2 <<= v0
+                i := face
+                sprite v0 v1 3
+                i := hex v0
+                delay := v0
+                draw
+                jump main
+            : draw
+                bcd v3
+                save v3
+                load v0 - v2
+                ;
+            : face 0x3C 0b01000010 255
+        ";
+        let rom = compile(source).unwrap();
+        assert_eq!(
+            rom,
+            [
+                0x00, 0xE0, 0x60, 0x05, 0x71, 0xFF, 0x82, 0x00, 0x82, 0x0E, 0xA2, 0x1E, 0xD0, 0x13,
+                0xF0, 0x29, 0xF0, 0x15, 0x22, 0x16, 0x12, 0x00, 0xF3, 0x33, 0xF3, 0x55, 0x50, 0x23,
+                0x00, 0xEE, 0x3C, 0x42, 0xFF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_control_flow() {
+        let source = "
+            loop
+                if v0 == 3 then v1 := 1
+                if v0 != v1 begin
+                    v2 := 1
+                else
+                    v2 := 2
+                end
+                v0 += 1
+                while v0 < 10
+            again
+        ";
+        let rom = compile(source).unwrap();
+        assert_eq!(
+            rom,
+            [
+                0x40, 0x03, 0x61, 0x01, // if v0 == 3 then v1 := 1
+                0x90, 0x10, 0x12, 0x0C, // skip the jump to else if v0 != v1
+                0x62, 0x01, 0x12, 0x0E, // the if branch, then jump to end
+                0x62, 0x02, // else
+                0x70, 0x01, // v0 += 1
+                0x6F, 0x0A, 0x8F, 0x07, // vF = v0 >= 10
+                0x3F, 0x00, 0x12, 0x1A, // leave the loop if vF is set
+                0x12, 0x00, // again
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_macros_and_constants() {
+        let source = "
+            :const SPEED 2
+            :alias x v4
+            :calc DOUBLE { SPEED * 2 + 1 }
+            :macro move register amount { register += amount }
+            move x SPEED
+            move v5 DOUBLE
+            :byte { 1 << 4 }
+            :unpack 0xA data
+            : data
+        ";
+        let rom = compile(source).unwrap();
+        assert_eq!(rom, [0x74, 0x02, 0x75, 0x06, 0x10, 0x60, 0xA2, 0x61, 0x09]);
+    }
+
+    #[test]
+    fn test_compile_with_symbols() {
+        let source = "
+            : main
+              loop again
+            : sprite
+              0xFF
+        ";
+        let (rom, symbols) = compile_with_symbols(source).unwrap();
+        assert_eq!(rom, [0x12, 0x00, 0xFF]);
+        assert_eq!(symbols.name(0x200), Some("main"));
+        assert_eq!(symbols.address_of("sprite"), Some(0x202));
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        let error = compile("v0 := 1\nundefined").unwrap_err();
+        assert_eq!(error.line, 2);
+        assert_eq!(error.message, "Undefined name `undefined`");
+        assert_eq!(compile("v0 := 256").unwrap_err().line, 1);
+        assert!(compile("loop v0 += 1").is_err());
+        assert!(compile(": a : a").is_err());
+    }
+}
@@ -1,17 +1,54 @@
-use std::collections::VecDeque;
-
-use crate::graphics;
-
-use super::Bus;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::Arc,
+};
+
+use crate::bus::Bus;
+use crate::cdp1802::{self, Cdp1802};
+use crate::deterministic::{self, Deterministic};
+use crate::instr::{self, Instr};
+use crate::symbols::Symbols;
+use crate::timing::{self, Timing};
+use crate::{graphics, memory, Quirks, ReadOnlyAction, Variant};
 
 /// The default starting address for the `Processor`.
 /// For most Chip8 programs, 0x200 should be
-const STARTING_PC: usize = 0x200;
+const STARTING_PC: usize = memory::INTERPRETER_SIZE;
+
+/// The jump that two-page hi-res CHIP-8 programs start with. The interpreter replaces
+/// it with a jump to [`HIRES_ENTRY`], as its own code occupies the memory in between.
+const HIRES_START_JUMP: usize = 0x260;
+
+/// The address two-page hi-res CHIP-8 programs are entered at.
+const HIRES_ENTRY: usize = 0x2C0;
+
+/// The amount of pixels `00FB` and `00FC` scroll the display sideways.
+const SCROLL_COLUMNS: usize = 4;
 
 /// The maximum amount of instructions that should be stored
 /// in the `Processor`'s buffer of instructions.
 const INSTRUCTION_BUFFER_LENGTH: usize = 100;
 
+/// The seed of the random numbers when the host can't provide any.
+const FALLBACK_SEED: u64 = 0x2545_F491_4F6C_DD1D;
+
+/// The maximum amount of CDP1802 instructions a machine code routine may execute
+/// before returning to the interpreter, so a routine that never returns can't hang the `Chip8`.
+const MAX_MACHINE_CODE_INSTRUCTIONS: usize = 100_000;
+
+/// Describe an executed instruction for the instruction trace like `format!`, or give an
+/// empty description without formatting anything if the `processor` isn't tracing.
+macro_rules! describe {
+    ($processor:expr, $($arg:tt)*) => {
+        if $processor.tracing_disabled {
+            String::new()
+        } else {
+            format!($($arg)*)
+        }
+    };
+}
+
 /// Describes how the program counter should be updated after
 /// executing an instruction.
 enum PCUpdate {
@@ -25,7 +62,243 @@ enum PCUpdate {
     Jump(usize),
 }
 
+/// The reason the `Processor` was halted: the program exited, or did
+/// something the interpreter can't recover from.
 #[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Fault {
+    /// The program counter moved past the end of memory.
+    PcOutOfBounds(usize),
+
+    /// The program called a machine code routine (`0nnn`) at the given address, with
+    /// the [`MachineCodePolicy::Halt`] policy or without a handler for the routine.
+    MachineCodeCall(usize),
+
+    /// The machine code routine at the given address, executed on the CDP1802 with the
+    /// [`MachineCodePolicy::Emulate`] policy, didn't return to the interpreter.
+    MachineCodeTimeout(usize),
+
+    /// The program exited with the SUPER-CHIP exit instruction (`00FD`). This is the only
+    /// reason to halt that isn't caused by an error.
+    Exited,
+
+    /// The opcode at the given address isn't an instruction of the variant,
+    /// with the [`InvalidOpcodePolicy::Halt`] policy.
+    InvalidOpcode { address: usize, opcode: usize },
+
+    /// The subroutine call at the given address didn't fit on the full stack.
+    StackOverflow(usize),
+
+    /// The return at the given address was executed with an empty stack.
+    StackUnderflow(usize),
+
+    /// The instruction at `address` accessed memory past the end, starting at `index`,
+    /// with the [`MemoryAccessPolicy::Fault`] policy.
+    IndexOutOfBounds { address: usize, index: usize },
+
+    /// The instruction at `address` wrote to `target`, in a read-only region of the memory
+    /// with the [`ReadOnlyAction::Fault`](crate::ReadOnlyAction::Fault) action.
+    ReadOnlyWrite { address: usize, target: usize },
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::PcOutOfBounds(pc) => {
+                write!(f, "Program counter {pc:#06X} is out of bounds")
+            }
+            Fault::MachineCodeCall(address) => {
+                write!(
+                    f,
+                    "Unhandled call to machine code routine at {address:#06X}"
+                )
+            }
+            Fault::Exited => write!(f, "The program exited"),
+            Fault::InvalidOpcode { address, opcode } => {
+                write!(f, "Invalid opcode {opcode:04X} at {address:#06X}")
+            }
+            Fault::StackOverflow(address) => {
+                write!(f, "Subroutine call at {address:#06X} overflowed the stack")
+            }
+            Fault::StackUnderflow(address) => {
+                write!(f, "Return at {address:#06X} with an empty stack")
+            }
+            Fault::IndexOutOfBounds { address, index } => write!(
+                f,
+                "Instruction at {address:#06X} accessed memory past the end from I = {index:#06X}"
+            ),
+            Fault::ReadOnlyWrite { address, target } => write!(
+                f,
+                "Instruction at {address:#06X} wrote to read-only memory at {target:#06X}"
+            ),
+            Fault::MachineCodeTimeout(address) => {
+                write!(
+                    f,
+                    "Machine code routine at {address:#06X} didn't return to the interpreter"
+                )
+            }
+        }
+    }
+}
+
+/// Whether the `Processor` is executing instructions, and why not if it isn't.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProcessorState {
+    /// Instructions are executed.
+    Running,
+
+    /// The program waits for a key press (`Fx0A`), whose key code is stored in `V{register}`.
+    WaitingForKey { register: usize },
+
+    /// No instructions are executed until the `Chip8` is reset.
+    Halted { reason: Fault },
+
+    /// The program is at a jump to itself at `address`, the usual way of ending
+    /// a program without the exit of SUPER-CHIP. Only the timers still change.
+    Finished { address: usize },
+}
+
+impl fmt::Display for ProcessorState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessorState::Running => write!(f, "Running"),
+            ProcessorState::WaitingForKey { register } => {
+                write!(f, "Waiting for a key press to store in V{register:X}")
+            }
+            ProcessorState::Halted { reason } => write!(f, "Halted: {reason}"),
+            ProcessorState::Finished { address } => {
+                write!(f, "Finished with a jump to itself at {address:#06X}")
+            }
+        }
+    }
+}
+
+/// A write of an instruction to memory that was executed before, e.g. by self-modifying code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeWrite {
+    /// The address of the instruction that wrote to the code.
+    pub address: usize,
+
+    /// The address of the first byte of executed code that was written to.
+    pub target: usize,
+}
+
+/// Describes how calls to machine code routines (`0nnn`) are handled. These routines
+/// were written for the CDP1802 processor of the COSMAC VIP.
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum MachineCodePolicy {
+    /// Skip the call and continue with the next instruction.
+    #[default]
+    Ignore,
+
+    /// Halt the `Processor` with a [`Fault::MachineCodeCall`].
+    Halt,
+
+    /// Call the handler registered for the routine's address, emulating the routine.
+    /// The `Processor` halts if no handler is registered for the address.
+    Trap,
+
+    /// Execute the routine on an emulated CDP1802, which shares the memory of the `Chip8`.
+    /// This allows "hybrid" ROMs that mix CHIP-8 and machine code to run.
+    Emulate,
+}
+
+impl MachineCodePolicy {
+    /// All policies, in the order they should be listed.
+    pub const ALL: [MachineCodePolicy; 4] = [
+        MachineCodePolicy::Ignore,
+        MachineCodePolicy::Halt,
+        MachineCodePolicy::Trap,
+        MachineCodePolicy::Emulate,
+    ];
+}
+
+impl fmt::Display for MachineCodePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MachineCodePolicy::Ignore => write!(f, "Ignore"),
+            MachineCodePolicy::Halt => write!(f, "Halt"),
+            MachineCodePolicy::Trap => write!(f, "Trap"),
+            MachineCodePolicy::Emulate => write!(f, "Emulate CDP1802"),
+        }
+    }
+}
+
+/// Describes how opcodes that aren't an instruction of the variant are handled,
+/// e.g. when a program runs into its data.
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum InvalidOpcodePolicy {
+    /// Log the opcode and continue with the next instruction.
+    #[default]
+    Ignore,
+
+    /// Halt the `Processor` with a [`Fault::InvalidOpcode`].
+    Halt,
+
+    /// Skip the opcode, but end the frame with a [`FrameEvent::InvalidOpcode`](crate::FrameEvent)
+    /// so a debugger can break on it.
+    Break,
+}
+
+impl InvalidOpcodePolicy {
+    /// All policies, in the order they should be listed.
+    pub const ALL: [InvalidOpcodePolicy; 3] = [
+        InvalidOpcodePolicy::Ignore,
+        InvalidOpcodePolicy::Halt,
+        InvalidOpcodePolicy::Break,
+    ];
+}
+
+impl fmt::Display for InvalidOpcodePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidOpcodePolicy::Ignore => write!(f, "Ignore"),
+            InvalidOpcodePolicy::Halt => write!(f, "Halt"),
+            InvalidOpcodePolicy::Break => write!(f, "Break"),
+        }
+    }
+}
+
+/// Describes how instructions accessing memory at `I` (`Dxyn`, `Fx33`, `Fx55`, `Fx65`
+/// and the XO-CHIP `5xy2` and `5xy3`) handle addresses past the end of memory.
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum MemoryAccessPolicy {
+    /// Wrap the addresses around to the start of memory.
+    #[default]
+    Wrap,
+
+    /// Halt the `Processor` with a [`Fault::IndexOutOfBounds`].
+    Fault,
+}
+
+impl MemoryAccessPolicy {
+    /// All policies, in the order they should be listed.
+    pub const ALL: [MemoryAccessPolicy; 2] = [MemoryAccessPolicy::Wrap, MemoryAccessPolicy::Fault];
+}
+
+impl fmt::Display for MemoryAccessPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemoryAccessPolicy::Wrap => write!(f, "Wrap"),
+            MemoryAccessPolicy::Fault => write!(f, "Fault"),
+        }
+    }
+}
+
+/// A function emulating a machine code routine, called by the [`MachineCodePolicy::Trap`]
+/// policy. Execution continues with the instruction after the call once it returns.
+pub type MachineCodeHandler = fn(&mut Processor, &mut Bus);
+
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone)]
 pub struct Instruction {
     /// The address of the instruction.
     pub address: usize,
@@ -37,8 +310,29 @@ pub struct Instruction {
     pub display: String,
 }
 
-#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
-#[derive(Default)]
+/// A subroutine call on the stack, see [`Processor::call_stack`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallFrame {
+    /// The address of the subroutine that made the call, the start address
+    /// for calls from the main program.
+    pub caller: usize,
+
+    /// The address of the call instruction (`2nnn`).
+    pub call_site: usize,
+
+    /// The address of the called subroutine.
+    pub subroutine: usize,
+
+    /// The address execution continues at once the subroutine returns.
+    pub return_address: usize,
+}
+
+#[cfg_attr(
+    feature = "persistence",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(remote = "Self")
+)]
+#[derive(Default, Clone)]
 pub struct Processor {
     /// Vx registers
     pub v: [u8; 16],
@@ -55,54 +349,156 @@ pub struct Processor {
     /// Stack memory
     pub stack: [usize; 16],
 
-    /// Indicates whether the shift quirk is enabled.
-    /// This affects the 8xy6 and 8xyE instructions.
-    ///
-    /// When `true`, the `Vx` register takes the value of `Vy` before being shifted.
-    pub shift_quirk_enabled: bool,
+    /// The subroutines called by the calls on the `stack`, `None` if they aren't known,
+    /// e.g. after loading a save state, in which case they are read from the call site.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) call_targets: [Option<usize>; 16],
 
-    /// Indicates whether the processor should wait for the vertical
-    /// blank interrupt before drawing a sprite.
-    ///
-    /// This will limit the sprite drawing to 60 sprites per second.
-    pub vblank_wait: bool,
+    /// The variant of the platform that is emulated.
+    pub variant: Variant,
 
-    /// A display string explaining what the current opcode is doing.
-    pub display: String,
+    /// The quirks that change the behavior of some instructions.
+    pub quirks: Quirks,
+
+    /// How calls to machine code routines (`0nnn`) are handled.
+    pub machine_code_policy: MachineCodePolicy,
+
+    /// The handlers emulating machine code routines, by address of the routine.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) machine_code_handlers: HashMap<usize, MachineCodeHandler>,
+
+    /// The CDP1802 executing machine code routines with the [`MachineCodePolicy::Emulate`] policy.
+    pub cdp1802: Cdp1802,
+
+    /// How opcodes that aren't an instruction of the variant are handled.
+    /// This is a setting of the host, which isn't part of save states.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub invalid_opcode_policy: InvalidOpcodePolicy,
+
+    /// How instructions accessing memory at `I` handle addresses past the end of memory.
+    /// This is a setting of the host, which isn't part of save states.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub memory_access_policy: MemoryAccessPolicy,
+
+    /// How the amount of steps executed in a frame is decided.
+    /// This is a setting of the host, which isn't part of save states.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub timing: Timing,
+
+    /// The COSMAC VIP machine cycles consumed since the `Processor` was created,
+    /// see [`Timing::CosmacVip`].
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub cycles: u64,
+
+    /// The address of the invalid opcode skipped in the last cycle with the
+    /// [`InvalidOpcodePolicy::Break`] policy.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) skipped_invalid_opcode: Option<usize>,
+
+    /// The first write of the instruction executed in the last cycle to executed code.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) code_write: Option<CodeWrite>,
+
+    /// Whether the sprite drawn in the last cycle turned off a pixel, which `VF` doesn't
+    /// tell for the hi-res draws of SUPER-CHIP.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) draw_collision: bool,
+
+    /// The address programs are loaded at, and the program counter starts at.
+    pub start_address: usize,
+
+    /// A display string explaining what the current opcode is doing. It is no longer
+    /// written, and only kept to keep the layout of save states.
+    #[cfg(feature = "persistence")]
+    pub(crate) display: String,
 
     /// The last [`INSTRUCTION_BUFFER_LENGTH`] instructions that the
-    /// `Processor` has executed.
+    /// `Processor` has executed, only the last one without a description
+    /// while tracing is disabled.
     pub instructions: VecDeque<Instruction>,
+
+    /// Whether the instruction trace is disabled, see [`Processor::set_tracing`].
+    /// This is a setting of the host, which isn't part of save states.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) tracing_disabled: bool,
+
+    /// The names of the addresses in the instruction trace, see [`Chip8::set_symbols`](crate::Chip8::set_symbols).
+    /// This is a setting of the host, which isn't part of save states.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) symbols: Arc<Symbols>,
+
+    /// The fault that halted the `Processor`, if any.
+    /// No instructions are executed while this is set.
+    pub fault: Option<Fault>,
+
+    /// The RPL user flags of the HP 48, which SUPER-CHIP programs store registers in
+    /// with `Fx75` to keep them after exiting, e.g. high scores.
+    #[cfg_attr(
+        feature = "persistence",
+        serde(with = "crate::state::since_rpl_flags_version")
+    )]
+    pub rpl_flags: [u8; 16],
+
+    /// The settings of the deterministic mode, `None` if it is disabled.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub deterministic: Option<Deterministic>,
+
+    /// The seed of the random numbers generated by `Cxnn`, `None` to take them from the host.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub seed: Option<u64>,
+
+    /// The state of the random number generator, when the random numbers are seeded.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) random_state: Option<u64>,
 }
 
 impl Processor {
     /// Create a new `Processor` instance. This is similar to `Processor::default`,
     /// with the exception that the program counter is set to [`STARTING_PC`].
     pub fn new() -> Self {
+        Self::new_with_start(STARTING_PC)
+    }
+
+    /// Create a new `Processor` for programs loaded at `start`, e.g. 0x600 for the ETI-660.
+    pub fn new_with_start(start: usize) -> Self {
         Self {
-            pc: STARTING_PC,
+            pc: start,
+            start_address: start,
             ..Default::default()
         }
     }
 
     /// Execute one processor cycle. This will fetch, decode, and execute the next
     /// opcode from memory. Note that if the processor is currently waiting on
-    /// input from the user, no instructions will be executed.
+    /// input from the user, or has been halted by a [`Fault`], no instructions
+    /// will be executed.
     pub fn cycle(&mut self, bus: &mut Bus) {
+        self.skipped_invalid_opcode = None;
+        self.code_write = None;
+        self.draw_collision = false;
         // if the input system is waiting for a key, don't process any opcodes
-        if bus.input.waiting() {
+        if self.fault.is_some() {
+            return;
+        } else if bus.input.waiting() {
+            self.cycles += u64::from(timing::KEY_WAIT_CYCLES);
             return;
         } else if let Some(request) = bus.input.request_response() {
             self.v[request.register] = request.key_code;
         }
 
-        if self.pc >= 4096 {
-            return;
-        }
-        // get the next two bytes and combine into one two-byte instruction
-        let opcode = (usize::from(bus.memory[self.pc]) << 8) | usize::from(bus.memory[self.pc + 1]);
+        let opcode = match self.peek_opcode(bus) {
+            Some(opcode) => opcode,
+            None => {
+                self.halt(Fault::PcOutOfBounds(self.pc));
+                return;
+            }
+        };
 
+        let size = self.instruction_size(self.pc, bus);
+        bus.memory.mark_executed(self.pc, size);
         let (pc_update, display) = self.process_opcode(opcode, bus);
+        let skipped = matches!(pc_update, PCUpdate::SkipNext);
+        self.cycles += u64::from(timing::vip_cycles(instr::decode(opcode as u16), skipped));
 
         // push new instruction
         let instruction = Instruction {
@@ -114,15 +510,255 @@ impl Processor {
 
         match pc_update {
             PCUpdate::Next => self.pc += 2,
-            PCUpdate::SkipNext => self.pc += 4,
+            PCUpdate::SkipNext => self.pc += 2 + self.instruction_size(self.pc + 2, bus),
             PCUpdate::Jump(addr) => self.pc = addr,
         }
     }
 
+    /// The subroutine calls on the stack, the most recent call last.
+    pub fn call_stack(&self, bus: &Bus) -> Vec<CallFrame> {
+        let mut caller = self.start_address;
+        let mut frames = Vec::with_capacity(self.sp);
+        for (&return_address, &target) in self.stack[..self.sp].iter().zip(&self.call_targets) {
+            let call_site = return_address.wrapping_sub(2);
+            let subroutine = target.unwrap_or_else(|| {
+                Self::read_word(call_site, bus).map_or(0, |opcode| opcode & 0x0FFF)
+            });
+            frames.push(CallFrame {
+                caller,
+                call_site,
+                subroutine,
+                return_address,
+            });
+            caller = subroutine;
+        }
+        frames
+    }
+
+    /// Get the opcode at the program counter, which will be executed on the next cycle.
+    /// Returns `None` if the program counter is past the end of memory.
+    pub fn peek_opcode(&self, bus: &Bus) -> Option<usize> {
+        Self::read_word(self.pc, bus)
+    }
+
+    /// Read the two bytes at `address` as one word, `None` if they are past the end of memory.
+    fn read_word(address: usize, bus: &Bus) -> Option<usize> {
+        if address + 1 >= bus.memory.as_slice().len() {
+            return None;
+        }
+        // combine the two bytes into one two-byte word
+        Some((usize::from(bus.memory[address]) << 8) | usize::from(bus.memory[address + 1]))
+    }
+
+    /// The size in bytes of the instruction at `address`. This is 2, except for the
+    /// 4 byte XO-CHIP instruction `F000 nnnn`, which skip instructions skip entirely.
+    fn instruction_size(&self, address: usize, bus: &Bus) -> usize {
+        if self.variant == Variant::XoChip && Self::read_word(address, bus) == Some(0xF000) {
+            4
+        } else {
+            2
+        }
+    }
+
+    /// Whether the executed instructions are traced, see [`Processor::set_tracing`].
+    pub fn tracing(&self) -> bool {
+        !self.tracing_disabled
+    }
+
+    /// Enable or disable the trace of the executed instructions, which is enabled by default.
+    /// Without it, only the last instruction is kept, without a description, so programs
+    /// run faster while nobody looks at the trace.
+    pub fn set_tracing(&mut self, tracing: bool) {
+        self.tracing_disabled = !tracing;
+        if !tracing {
+            self.instructions.truncate(1);
+        }
+    }
+
+    /// Register a `handler` emulating the machine code routine at `address`,
+    /// which is called by `0nnn` instructions with the [`MachineCodePolicy::Trap`] policy.
+    #[cfg(any(test, feature = "raw"))]
+    pub fn register_machine_code_handler(&mut self, address: usize, handler: MachineCodeHandler) {
+        self.machine_code_handlers.insert(address, handler);
+    }
+
+    /// Handle a call to the machine code routine at `address`, according to the
+    /// [`MachineCodePolicy`].
+    fn call_machine_code(&mut self, address: usize, bus: &mut Bus) -> (PCUpdate, String) {
+        let handler = self.machine_code_handlers.get(&address).copied();
+        match (self.machine_code_policy, handler) {
+            (MachineCodePolicy::Ignore, _) => {
+                log::debug!("Ignoring call to machine code routine at {address:#06X}");
+                let display = describe!(
+                    self,
+                    "Ignore machine code routine at addr {}",
+                    self.symbols.format_address(address)
+                );
+                (PCUpdate::Next, display)
+            }
+            (MachineCodePolicy::Trap, Some(handler)) => {
+                handler(self, bus);
+                let display = describe!(
+                    self,
+                    "Emulate machine code routine at addr {}",
+                    self.symbols.format_address(address)
+                );
+                (PCUpdate::Next, display)
+            }
+            (MachineCodePolicy::Emulate, _) => self.run_cdp1802(address, bus),
+            (MachineCodePolicy::Halt | MachineCodePolicy::Trap, _) => {
+                self.halt(Fault::MachineCodeCall(address));
+                let display =
+                    describe!(self, "Halt on machine code routine at addr {address:#06X}");
+                // stay on the call, so it is shown as the faulting instruction
+                (PCUpdate::Jump(self.pc), display)
+            }
+        }
+    }
+
+    /// Execute the machine code routine at `address` on the [`Cdp1802`] until it returns
+    /// to the interpreter. The `Vx` registers, `I` and the timers are shared with the
+    /// routine where the interpreter of the COSMAC VIP keeps them.
+    fn run_cdp1802(&mut self, address: usize, bus: &mut Bus) -> (PCUpdate, String) {
+        for (offset, &value) in self.v.iter().enumerate() {
+            bus.memory[cdp1802::V_REGISTERS_ADDRESS + offset] = value;
+        }
+        self.cdp1802.r[usize::from(cdp1802::INDEX_REGISTER)] = self.i as u16;
+        self.cdp1802.r[usize::from(cdp1802::TIMER_REGISTER)] =
+            u16::from_be_bytes([bus.clock.delay_timer, bus.clock.sound_timer]);
+        self.cdp1802.call(address);
+
+        let mut instructions = 0;
+        while !self.cdp1802.returned() {
+            if instructions == MAX_MACHINE_CODE_INSTRUCTIONS {
+                self.halt(Fault::MachineCodeTimeout(address));
+                let display =
+                    describe!(self, "Halt on machine code routine at addr {address:#06X}");
+                return (PCUpdate::Jump(self.pc), display);
+            }
+            self.cdp1802.step(&mut bus.memory);
+            instructions += 1;
+        }
+
+        for (offset, value) in self.v.iter_mut().enumerate() {
+            *value = bus.memory[cdp1802::V_REGISTERS_ADDRESS + offset];
+        }
+        self.i = usize::from(self.cdp1802.r[usize::from(cdp1802::INDEX_REGISTER)]) & 0xFFF;
+        let [delay_timer, sound_timer] =
+            self.cdp1802.r[usize::from(cdp1802::TIMER_REGISTER)].to_be_bytes();
+        bus.clock.delay_timer = delay_timer;
+        bus.clock.sound_timer = sound_timer;
+
+        let display = describe!(
+            self,
+            "Execute machine code routine at addr {}",
+            self.symbols.format_address(address)
+        );
+        (PCUpdate::Next, display)
+    }
+
+    /// The registers from `Vx` to `Vy`, in reverse order if `x` is greater than `y`.
+    fn register_range(x: usize, y: usize) -> Box<dyn Iterator<Item = usize>> {
+        if x <= y {
+            Box::new(x..=y)
+        } else {
+            Box::new((y..=x).rev())
+        }
+    }
+
+    /// Handle an opcode that isn't an instruction of the variant, as decided by the
+    /// [`InvalidOpcodePolicy`].
+    fn invalid_opcode(&mut self, opcode: usize) -> (PCUpdate, String) {
+        match self.invalid_opcode_policy {
+            InvalidOpcodePolicy::Ignore => {
+                log::error!("Invalid opcode {opcode:04X} at {:#06X}", self.pc);
+            }
+            InvalidOpcodePolicy::Halt => {
+                self.halt(Fault::InvalidOpcode {
+                    address: self.pc,
+                    opcode,
+                });
+                // stay on the opcode, so it is shown as the faulting instruction
+                return (
+                    PCUpdate::Jump(self.pc),
+                    describe!(self, "Halt on invalid instruction"),
+                );
+            }
+            InvalidOpcodePolicy::Break => self.skipped_invalid_opcode = Some(self.pc),
+        }
+        (PCUpdate::Next, describe!(self, "Invalid instruction"))
+    }
+
+    /// The address of the byte at `offset` from `I`, wrapped around to the start of memory.
+    fn index_address(&self, offset: usize, bus: &Bus) -> usize {
+        (self.i + offset) % bus.memory.as_slice().len()
+    }
+
+    /// Write `value` to `address` for the instruction being executed, unless the address is
+    /// read-only, noting the first write of the instruction to code that was executed before.
+    fn write_memory(&mut self, address: usize, value: u8, bus: &mut Bus) {
+        if bus.memory.write(address, value)
+            && bus.memory.was_executed(address)
+            && self.code_write.is_none()
+        {
+            self.code_write = Some(CodeWrite {
+                address: self.pc,
+                target: address,
+            });
+        }
+    }
+
+    /// Check that none of the `len` bytes starting at `I`, which the instruction writes to,
+    /// are in a read-only region that faults. If one is, the `Processor` is halted before
+    /// anything is written, and the result of the instruction is returned.
+    fn check_read_only(&mut self, len: usize, bus: &Bus) -> Option<(PCUpdate, String)> {
+        let target = (0..len)
+            .map(|offset| self.index_address(offset, bus))
+            .find(|&address| bus.memory.read_only_action(address) == Some(ReadOnlyAction::Fault))?;
+        self.halt(Fault::ReadOnlyWrite {
+            address: self.pc,
+            target,
+        });
+        // stay on the instruction, so it is shown as the faulting instruction
+        Some((
+            PCUpdate::Jump(self.pc),
+            describe!(self, "Halt on write to read-only memory"),
+        ))
+    }
+
+    /// Check that the `len` bytes starting at `I` are within memory. If they aren't and
+    /// the [`MemoryAccessPolicy`] doesn't wrap, the `Processor` is halted and the
+    /// result of the instruction is returned.
+    fn check_index_range(&mut self, len: usize, bus: &Bus) -> Option<(PCUpdate, String)> {
+        if self.i + len <= bus.memory.as_slice().len()
+            || self.memory_access_policy == MemoryAccessPolicy::Wrap
+        {
+            return None;
+        }
+        self.halt(Fault::IndexOutOfBounds {
+            address: self.pc,
+            index: self.i,
+        });
+        // stay on the instruction, so it is shown as the faulting instruction
+        Some((
+            PCUpdate::Jump(self.pc),
+            describe!(self, "Halt on memory access past the end"),
+        ))
+    }
+
+    /// Halt the `Processor` with the given fault.
+    fn halt(&mut self, fault: Fault) {
+        log::error!("Processor halted: {fault}");
+        self.fault = Some(fault);
+    }
+
     /// Push an instruction to the instruction buffer. This will
     /// remove the last instruction in the list if the length has exceeded
     /// the [`INSTRUCTION_BUFFER_LENGTH`].
     fn push_instruction(&mut self, instruction: Instruction) {
+        if self.tracing_disabled {
+            self.instructions.clear();
+        }
         self.instructions.push_front(instruction);
         if self.instructions.len() > INSTRUCTION_BUFFER_LENGTH {
             self.instructions.pop_back();
@@ -132,54 +768,142 @@ impl Processor {
     /// Process a single opcode. This will apply any state changing effects of the
     /// instructions onto the given [`Bus`].
     fn process_opcode(&mut self, opcode: usize, bus: &mut Bus) -> (PCUpdate, String) {
-        // define some commonly used variables
-        let x = (opcode & 0x0F00) >> 8;
-        let y = (opcode & 0x00F0) >> 4;
-        let nn = u8::try_from(opcode & 0x00FF).unwrap();
-        let nnn = opcode & 0x0FFF;
-
-        match (opcode & 0xF000) >> 12 {
-            // 0___
-            0x0 => match opcode & 0x000F {
-                // 00E0
-                0x0000 => {
-                    bus.graphics.clear();
-                    let display = "Clear the screen".into();
-                    (PCUpdate::Next, display)
-                }
+        match instr::decode(opcode as u16) {
+            // 00E0
+            Instr::Clear => {
+                bus.graphics.clear();
+                let display = describe!(self, "Clear the screen");
+                (PCUpdate::Next, display)
+            }
 
-                // 00EE
-                0x000E => {
-                    self.sp -= 1;
-                    let display = format!("Return to addr {:#06X}", self.stack[self.sp]);
-                    (PCUpdate::Jump(self.stack[self.sp]), display)
-                }
+            // 0230
+            Instr::ClearHires if self.variant == Variant::HiresChip8 => {
+                bus.graphics.clear();
+                let display = describe!(self, "Clear the hi-res screen");
+                (PCUpdate::Next, display)
+            }
 
-                // invalid
-                _ => {
-                    log::error!("Invalid 0x0___ instruction: {opcode:X}");
-                    let display = "Invalid instruction".into();
-                    (PCUpdate::Next, display)
-                }
-            },
+            // 00EE
+            Instr::Return if self.sp == 0 => {
+                self.halt(Fault::StackUnderflow(self.pc));
+                (
+                    PCUpdate::Jump(self.pc),
+                    describe!(self, "Halt on empty stack"),
+                )
+            }
+            Instr::Return => {
+                self.sp -= 1;
+                let display = describe!(
+                    self,
+                    "Return to addr {}",
+                    self.symbols.format_address(self.stack[self.sp])
+                );
+                (PCUpdate::Jump(self.stack[self.sp]), display)
+            }
+
+            // 00Cn
+            Instr::ScrollDown(n) if self.variant.has_hires() => {
+                bus.graphics.scroll_down(n);
+                let display = describe!(self, "Scroll the display down by {n} pixels");
+                (PCUpdate::Next, display)
+            }
+
+            // 00Dn
+            Instr::ScrollUp(n) if self.variant == Variant::XoChip => {
+                bus.graphics.scroll_up(n);
+                let display = describe!(self, "Scroll the selected planes up by {n} pixels");
+                (PCUpdate::Next, display)
+            }
+
+            // 00FB, 00FC
+            instr @ (Instr::ScrollRight | Instr::ScrollLeft) if self.variant.has_hires() => {
+                let display = if instr == Instr::ScrollRight {
+                    bus.graphics.scroll_right(SCROLL_COLUMNS);
+                    describe!(self, "Scroll the display right by {SCROLL_COLUMNS} pixels")
+                } else {
+                    bus.graphics.scroll_left(SCROLL_COLUMNS);
+                    describe!(self, "Scroll the display left by {SCROLL_COLUMNS} pixels")
+                };
+                (PCUpdate::Next, display)
+            }
+
+            // 00FD
+            Instr::Exit if self.variant.has_hires() => {
+                log::info!("The program exited");
+                self.fault = Some(Fault::Exited);
+                // stay on the exit, so it is shown as the last instruction
+                (
+                    PCUpdate::Jump(self.pc),
+                    describe!(self, "Exit the interpreter"),
+                )
+            }
+
+            // 00FE, 00FF
+            instr @ (Instr::LowRes | Instr::HighRes) if self.variant.has_hires() => {
+                let hires = instr == Instr::HighRes;
+                let (width, height) = if hires {
+                    (graphics::HIRES_WIDTH, graphics::HIRES_HEIGHT)
+                } else {
+                    (graphics::WIDTH, graphics::HEIGHT)
+                };
+                let clear = self.variant.clears_on_resolution_switch();
+                bus.graphics.set_resolution(width, height, clear);
+                let display = if hires {
+                    "Enable hi-res mode"
+                } else {
+                    "Disable hi-res mode"
+                };
+                (PCUpdate::Next, describe!(self, "{display}"))
+            }
+
+            // 0nnn, and the instructions above on variants without them
+            Instr::ClearHires
+            | Instr::ScrollDown(_)
+            | Instr::ScrollUp(_)
+            | Instr::ScrollRight
+            | Instr::ScrollLeft
+            | Instr::Exit
+            | Instr::LowRes
+            | Instr::HighRes
+            | Instr::MachineCode(_) => self.call_machine_code(opcode & 0x0FFF, bus),
 
             // 1nnn
-            0x1 => {
-                let display = format!("Jump to addr {nnn:#06X}");
+            Instr::Jump(nnn)
+                if self.variant == Variant::HiresChip8
+                    && self.pc == STARTING_PC
+                    && nnn == HIRES_START_JUMP =>
+            {
+                let display = describe!(self, "Enter the hi-res program at {HIRES_ENTRY:#06X}");
+                (PCUpdate::Jump(HIRES_ENTRY), display)
+            }
+            Instr::Jump(nnn) => {
+                let display = describe!(self, "Jump to addr {}", self.symbols.format_address(nnn));
                 (PCUpdate::Jump(nnn), display)
             }
 
             // 2nnn
-            0x2 => {
+            Instr::Call(_) if self.sp == self.stack.len() => {
+                self.halt(Fault::StackOverflow(self.pc));
+                (
+                    PCUpdate::Jump(self.pc),
+                    describe!(self, "Halt on full stack"),
+                )
+            }
+            Instr::Call(nnn) => {
                 self.stack[self.sp] = self.pc + 2;
+                self.call_targets[self.sp] = Some(nnn);
                 self.sp += 1;
-                let display = format!("Call subroutine at {nnn:#06X}");
+                let display = describe!(
+                    self,
+                    "Call subroutine at {}",
+                    self.symbols.format_address(nnn)
+                );
                 (PCUpdate::Jump(nnn), display)
             }
 
             // 3xnn
-            0x3 => {
-                let display = format!("If V{x:X} ({}) == {nn}, skip next instr", self.v[x]);
+            Instr::SkipIfEqual { x, nn } => {
+                let display = describe!(self, "If V{x:X} ({}) == {nn}, skip next instr", self.v[x]);
                 if self.v[x] == nn {
                     (PCUpdate::SkipNext, display)
                 } else {
@@ -188,8 +912,8 @@ impl Processor {
             }
 
             // 4Xnn
-            0x4 => {
-                let display = format!("If V{x:X} ({}) != {nn}, skip next instr", self.v[x]);
+            Instr::SkipIfNotEqual { x, nn } => {
+                let display = describe!(self, "If V{x:X} ({}) != {nn}, skip next instr", self.v[x]);
                 if self.v[x] != nn {
                     (PCUpdate::SkipNext, display)
                 } else {
@@ -197,11 +921,42 @@ impl Processor {
                 }
             }
 
+            // 5xy2
+            Instr::StoreRange { x, y } if self.variant == Variant::XoChip => {
+                let display = describe!(self, "Store V{x:X} to V{y:X} starting at I");
+                let len = x.abs_diff(y) + 1;
+                if let Some(halt) = self
+                    .check_index_range(len, bus)
+                    .or_else(|| self.check_read_only(len, bus))
+                {
+                    return halt;
+                }
+                for (offset, register) in Self::register_range(x, y).enumerate() {
+                    let address = self.index_address(offset, bus);
+                    self.write_memory(address, self.v[register], bus);
+                }
+                (PCUpdate::Next, display)
+            }
+
+            // 5xy3
+            Instr::LoadRange { x, y } if self.variant == Variant::XoChip => {
+                let display = describe!(self, "Read memory at I into V{x:X} to V{y:X}");
+                if let Some(halt) = self.check_index_range(x.abs_diff(y) + 1, bus) {
+                    return halt;
+                }
+                for (offset, register) in Self::register_range(x, y).enumerate() {
+                    self.v[register] = bus.memory[self.index_address(offset, bus)];
+                }
+                (PCUpdate::Next, display)
+            }
+
             // 5xy0
-            0x5 => {
-                let display = format!(
+            Instr::SkipIfEqualReg { x, y } => {
+                let display = describe!(
+                    self,
                     "If V{x:X} ({}) == V{y:X} ({}), skip next instr",
-                    self.v[x], self.v[y]
+                    self.v[x],
+                    self.v[y]
                 );
                 if self.v[x] == self.v[y] {
                     (PCUpdate::SkipNext, display)
@@ -211,331 +966,572 @@ impl Processor {
             }
 
             // 6xnn
-            0x6 => {
-                let display = format!("Set V{x:X} to {nn}");
+            Instr::Set { x, nn } => {
+                let display = describe!(self, "Set V{x:X} to {nn}");
                 self.v[x] = nn;
                 (PCUpdate::Next, display)
             }
 
             // 7xnn
-            0x7 => {
-                let display = format!("Add {nn} to V{x:X}");
+            Instr::Add { x, nn } => {
+                let display = describe!(self, "Add {nn} to V{x:X}");
                 self.v[x] = self.v[x].wrapping_add(nn);
                 (PCUpdate::Next, display)
             }
 
-            // 8___
-            0x8 => match opcode & 0x000F {
-                // 8xy0
-                0x0 => {
-                    let display = format!("Set V{x:X} to V{y:X} ({})", self.v[y]);
-                    self.v[x] = self.v[y];
-                    (PCUpdate::Next, display)
-                }
+            // 8xy0
+            Instr::SetReg { x, y } => {
+                let display = describe!(self, "Set V{x:X} to V{y:X} ({})", self.v[y]);
+                self.v[x] = self.v[y];
+                (PCUpdate::Next, display)
+            }
 
-                // 8xy1
-                0x1 => {
-                    let display = format!(
-                        "Set V{x:X} to V{x:X} OR V{y:X} ({:2X} OR {:2X})",
-                        self.v[x], self.v[y]
-                    );
-                    self.v[x] |= self.v[y];
+            // 8xy1
+            Instr::Or { x, y } => {
+                let display = describe!(
+                    self,
+                    "Set V{x:X} to V{x:X} OR V{y:X} ({:2X} OR {:2X})",
+                    self.v[x],
+                    self.v[y]
+                );
+                self.v[x] |= self.v[y];
+                if self.quirks.logic {
                     self.v[0xF] = 0;
-                    (PCUpdate::Next, display)
                 }
+                (PCUpdate::Next, display)
+            }
 
-                // 8xy2
-                0x2 => {
-                    let display = format!(
-                        "Set V{x:X} to V{x:X} AND V{y:X} ({:2X} AND {:2X})",
-                        self.v[x], self.v[y]
-                    );
-                    self.v[x] &= self.v[y];
+            // 8xy2
+            Instr::And { x, y } => {
+                let display = describe!(
+                    self,
+                    "Set V{x:X} to V{x:X} AND V{y:X} ({:2X} AND {:2X})",
+                    self.v[x],
+                    self.v[y]
+                );
+                self.v[x] &= self.v[y];
+                if self.quirks.logic {
                     self.v[0xF] = 0;
-                    (PCUpdate::Next, display)
                 }
+                (PCUpdate::Next, display)
+            }
 
-                // 8xy3
-                0x3 => {
-                    let display = format!(
-                        "Set V{x:X} to V{x:X} XOR V{y:X} ({:2X} XOR {:2X})",
-                        self.v[x], self.v[y]
-                    );
-                    self.v[x] ^= self.v[y];
+            // 8xy3
+            Instr::Xor { x, y } => {
+                let display = describe!(
+                    self,
+                    "Set V{x:X} to V{x:X} XOR V{y:X} ({:2X} XOR {:2X})",
+                    self.v[x],
+                    self.v[y]
+                );
+                self.v[x] ^= self.v[y];
+                if self.quirks.logic {
                     self.v[0xF] = 0;
-                    (PCUpdate::Next, display)
                 }
+                (PCUpdate::Next, display)
+            }
 
-                // 8xy4
-                0x4 => {
-                    let (result, overflow) = self.v[x].overflowing_add(self.v[y]);
-                    let display = format!(
-                        "Set V{x:X} to ({} + {}), VF = {}",
-                        self.v[x],
-                        self.v[y],
-                        u8::from(overflow)
-                    );
-                    self.v[x] = result;
-                    self.v[0xF] = u8::from(overflow);
-                    (PCUpdate::Next, display)
+            // 8xy4
+            Instr::AddReg { x, y } => {
+                let (result, overflow) = self.v[x].overflowing_add(self.v[y]);
+                let display = describe!(
+                    self,
+                    "Set V{x:X} to ({} + {}), VF = {}",
+                    self.v[x],
+                    self.v[y],
+                    u8::from(overflow)
+                );
+                self.v[x] = result;
+                self.v[0xF] = u8::from(overflow);
+                (PCUpdate::Next, display)
+            }
+
+            // 8xy5
+            Instr::SubReg { x, y } => {
+                let (result, overflow) = self.v[x].overflowing_sub(self.v[y]);
+                let display = describe!(
+                    self,
+                    "Set V{x:X} to ({} - {}), VF = {}",
+                    self.v[x],
+                    self.v[y],
+                    u8::from(!overflow)
+                );
+                self.v[x] = result;
+                self.v[0xF] = u8::from(!overflow);
+                (PCUpdate::Next, display)
+            }
+
+            // 8xy6
+            Instr::ShiftRight { x, y } => {
+                if self.quirks.shift {
+                    self.v[x] = self.v[y];
                 }
+                let overflow = self.v[x] & 1;
+                let display = describe!(self, "V{x:X} shifted one right, VF = {}", overflow);
+                self.v[x] >>= 1;
+                self.v[0xF] = overflow;
+                (PCUpdate::Next, display)
+            }
 
-                // 8xy5
-                0x5 => {
-                    let (result, overflow) = self.v[x].overflowing_sub(self.v[y]);
-                    let display = format!(
-                        "Set V{x:X} to ({} - {}), VF = {}",
-                        self.v[x],
-                        self.v[y],
-                        u8::from(!overflow)
-                    );
-                    self.v[x] = result;
-                    self.v[0xF] = u8::from(!overflow);
-                    (PCUpdate::Next, display)
+            // 8xy7
+            Instr::SubRegReversed { x, y } => {
+                let (result, overflow) = self.v[y].overflowing_sub(self.v[x]);
+                let display = describe!(
+                    self,
+                    "Set V{x:X} to ({} - {}), VF = {}",
+                    self.v[y],
+                    self.v[x],
+                    u8::from(!overflow)
+                );
+                self.v[x] = result;
+                self.v[0xF] = u8::from(!overflow);
+                (PCUpdate::Next, display)
+            }
+
+            // 8xyE
+            Instr::ShiftLeft { x, y } => {
+                if self.quirks.shift {
+                    self.v[x] = self.v[y];
                 }
+                let overflow = (self.v[x] & 0x80) >> 7;
+                let display = describe!(self, "V{x:X} shifted one left, VF = {}", overflow);
+                self.v[x] <<= 1;
+                self.v[0xF] = overflow;
+                (PCUpdate::Next, display)
+            }
 
-                // 8xy6
-                0x6 => {
-                    if self.shift_quirk_enabled {
-                        self.v[x] = self.v[y];
-                    }
-                    let overflow = self.v[x] & 1;
-                    let display = format!("V{x:X} shifted one right, VF = {}", overflow);
-                    self.v[x] >>= 1;
-                    self.v[0xF] = overflow;
+            // 9xy0
+            Instr::SkipIfNotEqualReg { x, y } => {
+                let display = describe!(
+                    self,
+                    "If V{x:X} ({}) != V{y:X} ({}), skip next instr",
+                    self.v[x],
+                    self.v[y]
+                );
+                if self.v[x] != self.v[y] {
+                    (PCUpdate::SkipNext, display)
+                } else {
                     (PCUpdate::Next, display)
                 }
+            }
 
-                // 8xy7
-                0x7 => {
-                    let (result, overflow) = self.v[y].overflowing_sub(self.v[x]);
-                    let display = format!(
-                        "Set V{x:X} to ({} - {}), VF = {}",
-                        self.v[y],
-                        self.v[x],
-                        u8::from(!overflow)
-                    );
-                    self.v[x] = result;
-                    self.v[0xF] = u8::from(!overflow);
-                    (PCUpdate::Next, display)
+            // Annn
+            Instr::SetIndex(nnn) => {
+                let display = describe!(
+                    self,
+                    "Set I register to {}",
+                    self.symbols.format_address(nnn)
+                );
+                self.i = nnn;
+                (PCUpdate::Next, display)
+            }
+
+            // Bnnn
+            Instr::JumpOffset { x, nnn } => {
+                let register = if self.quirks.jump { x } else { 0 };
+                let offset = usize::from(self.v[register]);
+                let display = describe!(
+                    self,
+                    "Jump to {} + V{register:X} ({offset:#06X})",
+                    self.symbols.format_address(nnn)
+                );
+                (PCUpdate::Jump(nnn + offset), display)
+            }
+
+            // Cxnn
+            Instr::Random { x, nn } => {
+                let mut buf = [0u8; 1];
+                if self.random_state.is_none() && getrandom::getrandom(&mut buf).is_err() {
+                    // don't crash on hosts without an entropy source, the numbers are
+                    // then the same on every run
+                    log::warn!("No random numbers from the host, using a fixed seed");
+                    self.random_state = Some(FALLBACK_SEED);
                 }
+                if let Some(state) = &mut self.random_state {
+                    buf[0] = deterministic::next_random(state);
+                }
+                let display = describe!(self, "Set V{x:X} to {} [rand] AND {nn:#X}", buf[0]);
+                self.v[x] = buf[0] & nn;
+                (PCUpdate::Next, display)
+            }
 
-                // 8xyE
-                0xE => {
-                    if self.shift_quirk_enabled {
-                        self.v[x] = self.v[y];
-                    }
-                    let overflow = (self.v[x] & 0x80) >> 7;
-                    let display = format!("V{x:X} shifted one left, VF = {}", overflow);
-                    self.v[x] <<= 1;
-                    self.v[0xF] = overflow;
-                    (PCUpdate::Next, display)
+            // Dxyn
+            Instr::Draw { x, y, n } => {
+                if self.quirks.vblank_wait && !bus.clock.wait_for_vblank() {
+                    // the timers are ticked by the host, so try again on the next step
+                    let display = describe!(self, "Wait for vertical blank to draw sprite");
+                    return (PCUpdate::Jump(self.pc), display);
                 }
 
-                // invalid
-                _ => {
-                    let display = "Invalid instruction".into();
-                    log::error!("Invalid 8XY_ instruction: {opcode:X}");
+                let x = usize::from(self.v[x]) % bus.graphics.width();
+                let y = usize::from(self.v[y]) % bus.graphics.height();
+                // Dxy0 draws a 16x16 sprite on the variants with hi-res graphics
+                let wide = n == 0 && self.variant.has_hires();
+                let (rows, row_bytes) = if wide { (16, 2) } else { (n, 1) };
+                let display = if wide {
+                    describe!(
+                        self,
+                        "Draw 16x16 sprite from addr {:#06X} at point ({x}, {y})",
+                        self.i
+                    )
+                } else {
+                    describe!(
+                        self,
+                        "Draw {n} byte sprite from addr {:#06X} at point ({x}, {y})",
+                        self.i
+                    )
+                };
+                // the rows of every selected plane follow each other
+                let len = rows * row_bytes * bus.graphics.planes().count_ones() as usize;
+                if let Some(halt) = self.check_index_range(len, bus) {
+                    return halt;
+                }
+                let sprite: Vec<u8> = (0..len)
+                    .map(|i| bus.memory[self.index_address(i, bus)])
+                    .collect();
+                let draw = if wide {
+                    graphics::GraphicsBuffer::draw_wide_sprite
+                } else {
+                    graphics::GraphicsBuffer::draw_sprite
+                };
+                let collided_rows = draw(
+                    &mut bus.graphics,
+                    x,
+                    y,
+                    &sprite,
+                    self.quirks.horizontal_wrap,
+                    self.quirks.vertical_wrap,
+                );
+                self.draw_collision = collided_rows > 0;
+                self.v[0xF] = if self.variant == Variant::SuperChip && bus.graphics.is_hires() {
+                    // SUPER-CHIP counts the rows that collide or are clipped at the bottom edge
+                    let clipped_rows = if self.quirks.vertical_wrap {
+                        0
+                    } else {
+                        (y + rows).saturating_sub(bus.graphics.height())
+                    };
+                    (collided_rows + clipped_rows) as u8
+                } else {
+                    u8::from(collided_rows > 0)
+                };
+                (PCUpdate::Next, display)
+            }
+
+            // Ex9E
+            Instr::SkipIfKey(x) => {
+                let pressed = bus.input.is_key_pressed(self.v[x]);
+                let display = describe!(
+                    self,
+                    "Skip instr if key {:#X} pressed ({pressed})",
+                    self.v[x]
+                );
+                if pressed {
+                    (PCUpdate::SkipNext, display)
+                } else {
                     (PCUpdate::Next, display)
                 }
-            },
+            }
 
-            // 9xy0
-            9 => {
-                let display = format!(
-                    "If V{x:X} ({}) != V{y:X} ({}), skip next instr",
-                    self.v[x], self.v[y]
+            // ExA1
+            Instr::SkipIfNotKey(x) => {
+                let not_pressed = !bus.input.is_key_pressed(self.v[x]);
+                let display = describe!(
+                    self,
+                    "Skip next instr if key code {:#X} not pressed ({not_pressed})",
+                    self.v[x]
                 );
-                if self.v[x] != self.v[y] {
+                if not_pressed {
                     (PCUpdate::SkipNext, display)
                 } else {
                     (PCUpdate::Next, display)
                 }
             }
 
-            // Annn
-            0xA => {
-                let display = format!("Set I register to {nnn:#06X}");
-                self.i = nnn;
+            // F000 nnnn
+            Instr::SetLongIndex if self.variant == Variant::XoChip => {
+                match Self::read_word(self.pc + 2, bus) {
+                    Some(address) => {
+                        let display = describe!(
+                            self,
+                            "Set I register to {}",
+                            self.symbols.format_address(address)
+                        );
+                        self.i = address;
+                        (PCUpdate::SkipNext, display)
+                    }
+                    None => {
+                        self.halt(Fault::PcOutOfBounds(self.pc + 2));
+                        let display = describe!(self, "Halt on missing address");
+                        (PCUpdate::Jump(self.pc), display)
+                    }
+                }
+            }
+
+            // Fn01
+            Instr::SelectPlanes(planes) if self.variant == Variant::XoChip => {
+                let display = describe!(self, "Select planes {planes}");
+                bus.graphics.select_planes(planes);
+                (PCUpdate::Next, display)
+            }
+
+            // Fx07
+            Instr::GetDelay(x) => {
+                let display = describe!(
+                    self,
+                    "Set V{x:X} to delay timer ({})",
+                    bus.clock.delay_timer
+                );
+                self.v[x] = bus.clock.delay_timer;
+                (PCUpdate::Next, display)
+            }
+
+            // Fx0A
+            Instr::WaitKey(x) => {
+                let display = describe!(self, "Store next key press and release in V{x:X}");
+                bus.input.request_key_press(x);
+                (PCUpdate::Next, display)
+            }
+
+            // Fx15
+            Instr::SetDelay(x) => {
+                let display = describe!(self, "Set delay timer to V{x:X} ({})", self.v[x]);
+                bus.clock.delay_timer = self.v[x];
+                (PCUpdate::Next, display)
+            }
+
+            // Fx18
+            Instr::SetSound(x) => {
+                let display = describe!(self, "Set sound timer to V{x:X} ({})", self.v[x]);
+                bus.clock.sound_timer = self.v[x];
+                (PCUpdate::Next, display)
+            }
+
+            // Fx1E
+            Instr::AddIndex(x) => {
+                let display = describe!(self, "Set I to I + V{x:X}");
+                self.i += usize::from(self.v[x]);
+                if self.quirks.index_overflow {
+                    self.v[0xF] = u8::from(self.i > 0xFFF);
+                }
                 (PCUpdate::Next, display)
             }
 
-            // Bnnn
-            0xB => {
-                let display = format!("Jump to {nnn:#06X} + {:#06X}", self.v[0]);
-                (PCUpdate::Jump(nnn + usize::from(self.v[0])), display)
+            // Fx29
+            Instr::Digit(x) => {
+                let display = describe!(self, "Set I to addr of sprite digit {}", self.v[x]);
+                // set I to the sprite address of the digit in Vx
+                self.i = 5 * usize::from(self.v[x]);
+                (PCUpdate::Next, display)
             }
 
-            // Cxnn
-            0xC => {
-                let mut buf = [0u8; 1];
-                getrandom::getrandom(&mut buf).unwrap();
-                let display = format!("Set V{x:X} to {} [rand] AND {nn:#X}", buf[0]);
-                self.v[x] = buf[0] & nn;
+            // Fx30
+            Instr::BigDigit(x) if self.variant.has_hires() => {
+                let display = describe!(self, "Set I to addr of large sprite digit {}", self.v[x]);
+                // set I to the large sprite address of the digit in Vx
+                self.i = memory::BIG_FONT_START
+                    + memory::BIG_FONT_DIGIT_SIZE * usize::from(self.v[x] & 0xF);
                 (PCUpdate::Next, display)
             }
 
-            // Dxyn
-            0xD => {
-                if self.vblank_wait {
-                    // spin wait for vblank
-                    loop {
-                        bus.clock.update();
-                        if bus.clock.vblank_interrupt {
-                            break;
-                        }
-                    }
+            // Fx33
+            Instr::Bcd(x) => {
+                let display = describe!(self, "Store BCD of {} starting at I", self.v[x]);
+                if let Some(halt) = self
+                    .check_index_range(3, bus)
+                    .or_else(|| self.check_read_only(3, bus))
+                {
+                    return halt;
                 }
-
-                let n = opcode & 0xF;
-                let x = usize::from(self.v[x]) % graphics::WIDTH;
-                let y = usize::from(self.v[y]) % graphics::HEIGHT;
-                let display = format!(
-                    "Draw {n} byte sprite from addr {:#06X} at point ({x}, {y})",
-                    self.i
-                );
-                let mut collision = false;
-                for i in 0..n {
-                    let data = bus.memory[self.i + i];
-                    collision |= bus.graphics.draw_byte(x, y + i, data);
+                // store BCD representation of decimal in Vx
+                let digits = [self.v[x] / 100, (self.v[x] / 10) % 10, self.v[x] % 10];
+                for (offset, digit) in digits.into_iter().enumerate() {
+                    let address = self.index_address(offset, bus);
+                    self.write_memory(address, digit, bus);
                 }
-                self.v[0xF] = collision.into();
                 (PCUpdate::Next, display)
             }
 
-            // E___
-            0xE => match opcode & 0x000F {
-                // Ex9E
-                0x000E => {
-                    let pressed = bus.input.is_key_pressed(self.v[x]);
-                    let display = format!("Skip instr if key {:#X} pressed ({pressed})", self.v[x]);
-                    if pressed {
-                        (PCUpdate::SkipNext, display)
-                    } else {
-                        (PCUpdate::Next, display)
-                    }
+            // Fx55
+            Instr::Store(x) => {
+                let display = describe!(self, "Store V0 to V{x:X} starting at I");
+                if let Some(halt) = self
+                    .check_index_range(x + 1, bus)
+                    .or_else(|| self.check_read_only(x + 1, bus))
+                {
+                    return halt;
                 }
-
-                // ExA1
-                0x0001 => {
-                    let not_pressed = !bus.input.is_key_pressed(self.v[x]);
-                    let display = format!(
-                        "Skip next instr if key code {:#X} not pressed ({not_pressed})",
-                        self.v[x]
-                    );
-                    if not_pressed {
-                        (PCUpdate::SkipNext, display)
-                    } else {
-                        (PCUpdate::Next, display)
-                    }
+                for i in 0..=x {
+                    let address = self.index_address(i, bus);
+                    self.write_memory(address, self.v[i], bus);
                 }
+                self.i += self.quirks.index_increment.amount(x);
+                (PCUpdate::Next, display)
+            }
 
-                // invalid
-                _ => {
-                    let display = "Invalid instruction".into();
-                    log::error!("Invalid EX__ instruction: {opcode:X}");
-                    (PCUpdate::Next, display)
+            // Fx65
+            Instr::Load(x) => {
+                let display = describe!(self, "Read memory at I into V0 to V{x:X}");
+                if let Some(halt) = self.check_index_range(x + 1, bus) {
+                    return halt;
                 }
-            },
-
-            // F___
-            0xF => match opcode & 0x00FF {
-                // Fx07
-                0x0007 => {
-                    let display = format!("Set V{x:X} to delay timer ({})", bus.clock.delay_timer);
-                    self.v[x] = bus.clock.delay_timer;
-                    (PCUpdate::Next, display)
+                for i in 0..=x {
+                    self.v[i] = bus.memory[self.index_address(i, bus)];
                 }
+                self.i += self.quirks.index_increment.amount(x);
+                (PCUpdate::Next, display)
+            }
 
-                // Fx0A
-                0x000A => {
-                    let display = format!("Store next key press in V{x:X}");
-                    bus.input.request_key_press(x);
-                    (PCUpdate::Next, display)
-                }
+            // Fx75
+            Instr::SaveFlags(x) if self.variant.has_hires() => {
+                let display = describe!(self, "Store V0 to V{x:X} in the RPL flags");
+                self.rpl_flags[..=x].copy_from_slice(&self.v[..=x]);
+                (PCUpdate::Next, display)
+            }
 
-                // Fx15
-                0x0015 => {
-                    let display = format!("Set delay timer to V{x:X} ({})", self.v[x]);
-                    bus.clock.delay_timer = self.v[x];
-                    (PCUpdate::Next, display)
-                }
+            // Fx85
+            Instr::LoadFlags(x) if self.variant.has_hires() => {
+                let display = describe!(self, "Load V0 to V{x:X} from the RPL flags");
+                self.v[..=x].copy_from_slice(&self.rpl_flags[..=x]);
+                (PCUpdate::Next, display)
+            }
 
-                // Fx18
-                0x0018 => {
-                    let display = format!("Set sound timer to V{x:X} ({})", self.v[x]);
-                    (*bus.clock.sound_timer).store(self.v[x], std::sync::atomic::Ordering::SeqCst);
-                    (PCUpdate::Next, display)
-                }
+            // invalid, or not an instruction of the variant
+            Instr::StoreRange { .. }
+            | Instr::LoadRange { .. }
+            | Instr::SetLongIndex
+            | Instr::SelectPlanes(_)
+            | Instr::BigDigit(_)
+            | Instr::SaveFlags(_)
+            | Instr::LoadFlags(_)
+            | Instr::Invalid => self.invalid_opcode(opcode),
+        }
+    }
+}
 
-                // Fx1E
-                0x001E => {
-                    let display = format!("Set I to I + V{x:X}");
-                    self.i += usize::from(self.v[x]);
-                    (PCUpdate::Next, display)
-                }
+/// The layout of the `Processor` in save states before format version 5,
+/// in which the quirks were separate fields.
+#[cfg(feature = "persistence")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct LegacyProcessor {
+    v: [u8; 16],
+    i: usize,
+    pc: usize,
+    sp: usize,
+    stack: [usize; 16],
+    variant: Variant,
+    shift_quirk_enabled: bool,
+    vblank_wait: bool,
+    machine_code_policy: MachineCodePolicy,
+    cdp1802: Cdp1802,
+    vertical_wrap: bool,
+    pause_timers_on_key_wait: bool,
+    #[serde(with = "crate::state::since_chip_48_version")]
+    jump_quirk_enabled: bool,
+    #[serde(with = "crate::state::since_chip_48_version")]
+    index_increment: crate::IndexIncrement,
+    #[serde(with = "start_address_layout")]
+    start_address: usize,
+    display: String,
+    instructions: VecDeque<Instruction>,
+    fault: Option<Fault>,
+}
 
-                // Fx29
-                0x0029 => {
-                    let display = format!("Set I to addr of sprite digit {}", self.v[x]);
-                    // set I to the sprite address of the digit in Vx
-                    self.i = 5 * usize::from(self.v[x]);
-                    (PCUpdate::Next, display)
-                }
+#[cfg(feature = "persistence")]
+impl serde::Serialize for Processor {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if crate::state::format_version() < crate::state::QUIRKS_VERSION {
+            let legacy = LegacyProcessor {
+                v: self.v,
+                i: self.i,
+                pc: self.pc,
+                sp: self.sp,
+                stack: self.stack,
+                variant: self.variant,
+                shift_quirk_enabled: self.quirks.shift,
+                vblank_wait: self.quirks.vblank_wait,
+                machine_code_policy: self.machine_code_policy,
+                cdp1802: self.cdp1802.clone(),
+                vertical_wrap: self.quirks.vertical_wrap,
+                pause_timers_on_key_wait: self.quirks.pause_timers_on_key_wait,
+                jump_quirk_enabled: self.quirks.jump,
+                index_increment: self.quirks.index_increment,
+                start_address: self.start_address,
+                display: self.display.clone(),
+                instructions: self.instructions.clone(),
+                fault: self.fault,
+            };
+            legacy.serialize(serializer)
+        } else {
+            Processor::serialize(self, serializer)
+        }
+    }
+}
 
-                // Fx33
-                0x0033 => {
-                    let display = format!("Store BCD of {} starting at I", self.v[x]);
-                    // store BCD representation of decimal in Vx
-                    bus.memory[self.i] = (self.v[x] / 100) % 10;
-                    bus.memory[self.i + 1] = (self.v[x] / 10) % 10;
-                    bus.memory[self.i + 2] = self.v[x] % 10;
-                    (PCUpdate::Next, display)
-                }
+#[cfg(feature = "persistence")]
+impl<'de> serde::Deserialize<'de> for Processor {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if crate::state::format_version() < crate::state::QUIRKS_VERSION {
+            let legacy = LegacyProcessor::deserialize(deserializer)?;
+            Ok(Processor {
+                v: legacy.v,
+                i: legacy.i,
+                pc: legacy.pc,
+                sp: legacy.sp,
+                stack: legacy.stack,
+                variant: legacy.variant,
+                quirks: Quirks {
+                    shift: legacy.shift_quirk_enabled,
+                    vblank_wait: legacy.vblank_wait,
+                    vertical_wrap: legacy.vertical_wrap,
+                    pause_timers_on_key_wait: legacy.pause_timers_on_key_wait,
+                    jump: legacy.jump_quirk_enabled,
+                    index_increment: legacy.index_increment,
+                    logic: true,
+                    index_overflow: false,
+                    horizontal_wrap: false,
+                },
+                machine_code_policy: legacy.machine_code_policy,
+                cdp1802: legacy.cdp1802,
+                start_address: legacy.start_address,
+                display: legacy.display,
+                instructions: legacy.instructions,
+                fault: legacy.fault,
+                ..Default::default()
+            })
+        } else {
+            Processor::deserialize(deserializer)
+        }
+    }
+}
 
-                // Fx55
-                0x0055 => {
-                    let display = format!("Store V0 to V{x:X} starting at I");
-                    for i in 0..=x {
-                        bus.memory[self.i] = self.v[i];
-                        self.i += 1;
-                    }
-                    (PCUpdate::Next, display)
-                }
+/// The layout of the start address in save states. Before format version 4, programs
+/// always started at [`STARTING_PC`].
+#[cfg(feature = "persistence")]
+mod start_address_layout {
+    use serde::{Deserializer, Serializer};
 
-                // Fx65
-                0x0065 => {
-                    let display = format!("Read memory at I into V0 to V{x:X}");
-                    for i in 0..=x {
-                        self.v[i] = bus.memory[self.i];
-                        self.i += 1;
-                    }
-                    (PCUpdate::Next, display)
-                }
+    use super::STARTING_PC;
+    use crate::state;
 
-                // invalid
-                _ => {
-                    let display = "Invalid instruction".into();
-                    log::error!("Invalid FX__ instruction: {opcode:X}");
-                    (PCUpdate::Next, display)
-                }
-            },
+    pub fn serialize<S: Serializer>(start: &usize, serializer: S) -> Result<S::Ok, S::Error> {
+        state::serialize_since(state::START_ADDRESS_VERSION, start, serializer)
+    }
 
-            // invalid
-            _ => {
-                let display = "Invalid instruction".into();
-                log::error!("Unknown opcode: {opcode:X}");
-                (PCUpdate::Next, display)
-            }
-        }
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+        state::deserialize_since(state::START_ADDRESS_VERSION, deserializer, || STARTING_PC)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::Bus;
+    use crate::{
+        bus::Bus, graphics, IndexIncrement, Quirks, ReadOnlyAction, ReadOnlyRegion, Variant,
+    };
 
-    use super::{Processor, STARTING_PC};
+    use super::{
+        CallFrame, CodeWrite, Fault, InvalidOpcodePolicy, MachineCodePolicy, MemoryAccessPolicy,
+        Processor, STARTING_PC,
+    };
 
     /// Helper function that executes a single opcode on the given
     /// 'Processor` and a new `Bus`.
@@ -556,6 +1552,275 @@ mod tests {
         processor
     }
 
+    #[test]
+    fn test_pc_out_of_bounds_halts() {
+        let mut p = test_op(0x1FFF);
+        p.cycle(&mut Bus::default());
+        assert_eq!(p.fault, Some(Fault::PcOutOfBounds(0xFFF)));
+
+        // no further instructions are executed once halted
+        p.pc = STARTING_PC;
+        test_op_with(0x6001, &mut p);
+        assert_eq!(p.v[0], 0);
+        assert_eq!(p.pc, STARTING_PC);
+    }
+
+    /// Draw a two row sprite at the bottom edge of the display, returning the bus.
+    fn draw_at_bottom_edge(vertical_wrap: bool) -> Bus {
+        let mut p = Processor::new();
+        p.quirks.vertical_wrap = vertical_wrap;
+        p.v[1] = 31;
+        p.i = 0x300;
+        let mut bus = Bus::default();
+        bus.memory[0x300] = 0x80;
+        bus.memory[0x301] = 0x80;
+        bus.memory[p.pc] = 0xD0;
+        bus.memory[p.pc + 1] = 0x12;
+        p.cycle(&mut bus);
+        bus
+    }
+
+    #[test]
+    fn test_draw_vertical_wrap() {
+        let top_left_lit = |bus: &Bus| bus.graphics.as_rgb8()[0] != 0;
+        assert!(top_left_lit(&draw_at_bottom_edge(true)));
+        assert!(!top_left_lit(&draw_at_bottom_edge(false)));
+    }
+
+    /// Draw an 8x2 sprite at the bottom right corner of the display, like the clipping test
+    /// of the Timendus quirks ROM, returning the lit pixels.
+    fn draw_at_bottom_right_corner(quirks: Quirks) -> Vec<usize> {
+        let mut p = Processor::new();
+        p.quirks = quirks;
+        p.v[0] = 60;
+        p.v[1] = 31;
+        p.i = 0x300;
+        let mut bus = Bus::default();
+        bus.memory[0x300] = 0xFF;
+        bus.memory[0x301] = 0xFF;
+        bus.memory[p.pc] = 0xD0;
+        bus.memory[p.pc + 1] = 0x12;
+        p.cycle(&mut bus);
+        bus.graphics
+            .as_rgb8()
+            .chunks(3)
+            .enumerate()
+            .filter(|(_, pixel)| pixel[0] != 0)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    #[test]
+    fn test_draw_clip_or_wrap() {
+        let bottom_row = 31 * 64;
+        assert_eq!(
+            draw_at_bottom_right_corner(Quirks::default()),
+            (bottom_row + 60..bottom_row + 64).collect::<Vec<_>>()
+        );
+        // XO-CHIP wraps the sprite around both edges, into all four corners
+        let expected: Vec<usize> = [0, 60, bottom_row, bottom_row + 60]
+            .into_iter()
+            .flat_map(|start| start..start + 4)
+            .collect();
+        assert_eq!(draw_at_bottom_right_corner(Quirks::XO_CHIP), expected);
+    }
+
+    #[test]
+    fn test_draw_schip_wide_sprite_collision_rows() {
+        let mut p = Processor::new();
+        p.variant = Variant::SuperChip;
+        p.v[1] = 60;
+        p.i = 0x300;
+        let mut bus = Bus::default();
+        bus.graphics.resize(128, 64);
+        for address in 0x300..0x320 {
+            bus.memory[address] = 0xFF;
+        }
+
+        // draw a 16x16 sprite twice, with 12 of its rows clipped at the bottom
+        for _ in 0..2 {
+            bus.memory[p.pc] = 0xD0;
+            bus.memory[p.pc + 1] = 0x10;
+            p.cycle(&mut bus);
+        }
+        assert_eq!(p.v[0xF], 16);
+        assert!(!bus.graphics.is_pixel_on(15, 63));
+        p.v[1] = 0;
+        bus.memory[p.pc] = 0xD0;
+        bus.memory[p.pc + 1] = 0x10;
+        p.cycle(&mut bus);
+        assert!(bus.graphics.is_pixel_on(15, 15));
+
+        // Dxy0 draws nothing on CHIP-8
+        let mut p = Processor::new();
+        let mut bus = Bus::default();
+        p.i = 0x300;
+        bus.memory[0x300] = 0xFF;
+        bus.memory[p.pc] = 0xD0;
+        bus.memory[p.pc + 1] = 0x00;
+        p.cycle(&mut bus);
+        assert!(!bus.graphics.is_pixel_on(0, 0));
+    }
+
+    #[test]
+    fn test_draw_schip_hires_collision_rows() {
+        let mut p = Processor::new();
+        p.variant = Variant::SuperChip;
+        p.v[1] = 62;
+        p.i = 0x300;
+        let mut bus = Bus::default();
+        bus.graphics.resize(128, 64);
+        for address in 0x300..0x303 {
+            bus.memory[address] = 0xFF;
+        }
+
+        // draw a three row sprite twice, with the last row clipped at the bottom
+        for _ in 0..2 {
+            bus.memory[p.pc] = 0xD0;
+            bus.memory[p.pc + 1] = 0x13;
+            p.cycle(&mut bus);
+        }
+        assert_eq!(p.v[0xF], 3);
+
+        // in lo-res mode the collision flag is a boolean
+        bus.graphics.resize(64, 32);
+        p.v[1] = 0;
+        for _ in 0..2 {
+            bus.memory[p.pc] = 0xD0;
+            bus.memory[p.pc + 1] = 0x13;
+            p.cycle(&mut bus);
+        }
+        assert_eq!(p.v[0xF], 1);
+    }
+
+    #[test]
+    fn test_resolution_switch() {
+        let mut p = Processor::new();
+        p.variant = Variant::SuperChip;
+        let mut bus = Bus::default();
+        for (i, opcode) in [0x00FFu16, 0x00FE].into_iter().enumerate() {
+            bus.memory[STARTING_PC + i * 2] = (opcode >> 8) as u8;
+            bus.memory[STARTING_PC + i * 2 + 1] = opcode as u8;
+        }
+        p.cycle(&mut bus);
+        assert!(bus.graphics.is_hires());
+        p.cycle(&mut bus);
+        assert!(!bus.graphics.is_hires());
+
+        // the original CHIP-8 doesn't support hi-res mode
+        let mut p = Processor::new();
+        bus.memory[STARTING_PC] = 0x00;
+        bus.memory[STARTING_PC + 1] = 0xFF;
+        p.cycle(&mut bus);
+        assert!(!bus.graphics.is_hires());
+    }
+
+    #[test]
+    fn test_exit() {
+        // 00FD is a machine code call on CHIP-8
+        let mut p = Processor::new();
+        p.machine_code_policy = MachineCodePolicy::Ignore;
+        test_op_with(0x00FD, &mut p);
+        assert_eq!(p.fault, None);
+
+        let mut p = Processor::new();
+        p.variant = Variant::SuperChip;
+        test_op_with(0x00FD, &mut p);
+        assert_eq!(p.fault, Some(Fault::Exited));
+        assert_eq!(p.pc, STARTING_PC);
+    }
+
+    #[test]
+    fn test_rpl_flags() {
+        // Fx75 isn't an instruction of CHIP-8
+        let mut p = Processor::new();
+        p.invalid_opcode_policy = InvalidOpcodePolicy::Halt;
+        test_op_with(0xF275, &mut p);
+        assert!(p.fault.is_some());
+
+        let mut p = Processor::new();
+        p.variant = Variant::SuperChip;
+        p.v[..4].copy_from_slice(&[1, 2, 3, 4]);
+        test_op_with(0xF275, &mut p);
+        assert_eq!(p.rpl_flags[..4], [1, 2, 3, 0]);
+
+        p.v = [0; 16];
+        test_op_with(0xF185, &mut p);
+        assert_eq!(p.v[..3], [1, 2, 0]);
+    }
+
+    #[test]
+    fn test_big_font_digit() {
+        let mut p = Processor::new();
+        p.variant = Variant::SuperChip;
+        p.v[2] = 3;
+        let mut bus = Bus::default();
+        bus.memory[STARTING_PC] = 0xF2;
+        bus.memory[STARTING_PC + 1] = 0x30;
+        p.cycle(&mut bus);
+        assert_eq!(p.i, 110);
+        assert_eq!(bus.memory[p.i], 0x3C);
+        assert_eq!(bus.memory[p.i + 9], 0x3C);
+    }
+
+    /// A machine code routine that sets `V0` to `0xAB`.
+    fn set_v0(p: &mut Processor, _: &mut Bus) {
+        p.v[0] = 0xAB;
+    }
+
+    #[test]
+    fn test_machine_code_policy() {
+        let p = test_op(0x0300);
+        assert_eq!(p.pc, STARTING_PC + 2);
+        assert_eq!(p.fault, None);
+
+        let mut p = Processor::new();
+        p.machine_code_policy = MachineCodePolicy::Halt;
+        test_op_with(0x0300, &mut p);
+        assert_eq!(p.fault, Some(Fault::MachineCodeCall(0x300)));
+        assert_eq!(p.pc, STARTING_PC);
+
+        let mut p = Processor::new();
+        p.machine_code_policy = MachineCodePolicy::Trap;
+        p.register_machine_code_handler(0x300, set_v0);
+        test_op_with(0x0300, &mut p);
+        assert_eq!(p.v[0], 0xAB);
+        assert_eq!(p.pc, STARTING_PC + 2);
+
+        // trapping a routine without a handler halts
+        test_op_with(0x0400, &mut p);
+        assert_eq!(p.fault, Some(Fault::MachineCodeCall(0x400)));
+    }
+
+    #[test]
+    fn test_machine_code_emulation() {
+        let mut p = Processor::new();
+        p.machine_code_policy = MachineCodePolicy::Emulate;
+        p.v[0] = 0x41;
+        let mut bus = Bus::default();
+        bus.memory[STARTING_PC] = 0x03;
+        bus.memory[STARTING_PC + 1] = 0x00;
+        // point R6 at V0, then increment it: LDI 0x0E, PHI R6, LDI 0xF0, PLO R6,
+        // LDN R6, ADI 0x01, STR R6, SEP R4
+        let routine = [
+            0xF8, 0x0E, 0xB6, 0xF8, 0xF0, 0xA6, 0x06, 0xFC, 0x01, 0x56, 0xD4,
+        ];
+        for (offset, &byte) in routine.iter().enumerate() {
+            bus.memory[0x300 + offset] = byte;
+        }
+        p.cycle(&mut bus);
+        assert_eq!(p.v[0], 0x42);
+        assert_eq!(p.pc, STARTING_PC + 2);
+
+        // a routine that loops forever halts the processor
+        let mut p = Processor::new();
+        p.machine_code_policy = MachineCodePolicy::Emulate;
+        bus.memory[0x300] = 0x30;
+        bus.memory[0x301] = 0x00;
+        p.cycle(&mut bus);
+        assert_eq!(p.fault, Some(Fault::MachineCodeTimeout(0x300)));
+    }
+
     #[test]
     fn test_jump() {
         let p = test_op(0x1300);
@@ -580,6 +1845,54 @@ mod tests {
         assert_eq!(p.pc, STARTING_PC + 2);
     }
 
+    #[test]
+    fn test_stack_faults() {
+        let mut p = test_op(0x00EE);
+        assert_eq!(p.fault, Some(Fault::StackUnderflow(STARTING_PC)));
+
+        // call self until the stack is full
+        p = Processor::new();
+        let mut bus = Bus::default();
+        bus.memory[STARTING_PC] = 0x22;
+        bus.memory[STARTING_PC + 1] = 0x00;
+        for _ in 0..=p.stack.len() {
+            p.cycle(&mut bus);
+        }
+        assert_eq!(p.sp, p.stack.len());
+        assert_eq!(p.fault, Some(Fault::StackOverflow(STARTING_PC)));
+        assert_eq!(p.pc, STARTING_PC);
+    }
+
+    #[test]
+    fn test_call_stack() {
+        let mut p = Processor::new();
+        let mut bus = Bus::default();
+        // call 0x300, which calls 0x400
+        bus.memory[0x200] = 0x23;
+        bus.memory[0x300] = 0x24;
+        p.cycle(&mut bus);
+        p.cycle(&mut bus);
+        let frames = [
+            CallFrame {
+                caller: 0x200,
+                call_site: 0x200,
+                subroutine: 0x300,
+                return_address: 0x202,
+            },
+            CallFrame {
+                caller: 0x300,
+                call_site: 0x300,
+                subroutine: 0x400,
+                return_address: 0x302,
+            },
+        ];
+        assert_eq!(p.call_stack(&bus), frames);
+
+        // the subroutines of a restored stack are read from the call sites
+        p.call_targets = Default::default();
+        assert_eq!(p.call_stack(&bus), frames);
+    }
+
     /// test the 0x3___ instruction when register and compared value are equal
     #[test]
     fn test_compare_skip_equal() {
@@ -658,6 +1971,18 @@ mod tests {
         assert_eq!(p.v[0xA], 0xFF);
     }
 
+    #[test]
+    fn test_logic_quirk() {
+        let mut p = test_op(0x6F01);
+        test_op_with(0x8AB1, &mut p);
+        assert_eq!(p.v[0xF], 0);
+
+        p.quirks.logic = false;
+        test_op_with(0x6F01, &mut p);
+        test_op_with(0x8AB1, &mut p);
+        assert_eq!(p.v[0xF], 1);
+    }
+
     #[test]
     fn test_and() {
         let mut p = test_op(0x6AFF);
@@ -797,6 +2122,15 @@ mod tests {
         assert_eq!(p.pc, 0x312);
     }
 
+    #[test]
+    fn test_jump_quirk() {
+        let mut p = test_op(0x6012);
+        test_op_with(0x6320, &mut p);
+        p.quirks.jump = true;
+        test_op_with(0xB300, &mut p);
+        assert_eq!(p.pc, 0x320);
+    }
+
     #[test]
     fn test_get_random() {
         // we just test that the get random instruction doesn't panic
@@ -822,18 +2156,38 @@ mod tests {
         assert_eq!(bus.clock.delay_timer, 0x12);
     }
 
+    #[test]
+    fn test_tracing() {
+        let mut p = test_op(0x6A12);
+        test_op_with(0x6B34, &mut p);
+        assert_eq!(p.instructions.len(), 2);
+        assert_eq!(p.instructions[0].display, "Set VB to 52");
+
+        // only the last instruction is kept, without a description
+        p.set_tracing(false);
+        assert_eq!(p.instructions.len(), 1);
+        test_op_with(0x6C56, &mut p);
+        test_op_with(0x6D78, &mut p);
+        assert!(!p.tracing());
+        assert_eq!(p.instructions.len(), 1);
+        assert_eq!(p.instructions[0].address, 0x206);
+        assert_eq!(p.instructions[0].opcode, 0x6D78);
+        assert_eq!(p.instructions[0].display, "");
+        assert_eq!(p.v[0xD], 0x78);
+
+        p.set_tracing(true);
+        test_op_with(0x6E9A, &mut p);
+        assert_eq!(p.instructions.len(), 2);
+        assert_eq!(p.instructions[0].display, "Set VE to 154");
+    }
+
     #[test]
     fn test_set_sound_timer() {
         let mut p = Processor::new();
         let mut bus = Bus::default();
         p.process_opcode(0x6A12, &mut bus);
         p.process_opcode(0xFA18, &mut bus);
-        assert_eq!(
-            bus.clock
-                .sound_timer
-                .load(std::sync::atomic::Ordering::SeqCst),
-            0x12
-        );
+        assert_eq!(bus.clock.sound_timer, 0x12);
     }
 
     #[test]
@@ -844,6 +2198,20 @@ mod tests {
         assert_eq!(p.i, 0x312);
     }
 
+    #[test]
+    fn test_index_overflow_quirk() {
+        let mut p = test_op(0x6A12);
+        p.quirks.index_overflow = true;
+        test_op_with(0xAFF0, &mut p);
+        test_op_with(0xFA1E, &mut p);
+        assert_eq!(p.i, 0x1002);
+        assert_eq!(p.v[0xF], 1);
+
+        test_op_with(0xA300, &mut p);
+        test_op_with(0xFA1E, &mut p);
+        assert_eq!(p.v[0xF], 0);
+    }
+
     #[test]
     fn test_load_font_address() {
         let mut p = test_op(0x6004);
@@ -900,4 +2268,206 @@ mod tests {
             assert_eq!(processor.v[usize::from(i)], i);
         }
     }
+
+    #[test]
+    fn test_memory_access_policy() {
+        let mut p = Processor::new();
+        let mut bus = Bus::default();
+        p.i = 0xFFE;
+        p.v[..3].copy_from_slice(&[1, 2, 3]);
+        p.process_opcode(0xF255, &mut bus);
+        assert_eq!(&bus.memory.as_slice()[0xFFE..], &[1, 2]);
+        assert_eq!(bus.memory[0], 3);
+
+        p.memory_access_policy = MemoryAccessPolicy::Fault;
+        p.i = 0xFFE;
+        test_op_with(0xF233, &mut p);
+        assert_eq!(
+            p.fault,
+            Some(Fault::IndexOutOfBounds {
+                address: STARTING_PC,
+                index: 0xFFE
+            })
+        );
+    }
+
+    #[test]
+    fn test_code_write() {
+        let mut p = Processor::new();
+        let mut bus = Bus::default();
+        // store V0 over the first instruction, then over the unexecuted next one
+        for (i, opcode) in [0xA200u16, 0xF055, 0xA206, 0xF055].into_iter().enumerate() {
+            bus.memory[STARTING_PC + i * 2] = (opcode >> 8) as u8;
+            bus.memory[STARTING_PC + i * 2 + 1] = opcode as u8;
+        }
+        p.cycle(&mut bus);
+        assert_eq!(p.code_write, None);
+        p.cycle(&mut bus);
+        assert_eq!(
+            p.code_write,
+            Some(CodeWrite {
+                address: 0x202,
+                target: 0x200
+            })
+        );
+        p.cycle(&mut bus);
+        assert_eq!(p.code_write, None);
+        // 0x206 is being executed, but 0x207 isn't written
+        p.cycle(&mut bus);
+        assert_eq!(
+            p.code_write,
+            Some(CodeWrite {
+                address: 0x206,
+                target: 0x206
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_only_regions() {
+        let mut p = Processor::new();
+        let mut bus = Bus::default();
+        let font = bus.memory.as_slice()[..3].to_vec();
+        bus.memory
+            .set_read_only_regions(vec![ReadOnlyRegion::fonts(ReadOnlyAction::Ignore)]);
+        // the writes to the font are ignored, the ones past it still happen
+        p.i = 0x0EF;
+        p.v[..3].copy_from_slice(&[1, 2, 3]);
+        p.process_opcode(0xF255, &mut bus);
+        assert_eq!(&bus.memory.as_slice()[0x0EF..0x0F2], &[0xC0, 2, 3]);
+        assert_eq!(p.fault, None);
+
+        bus.memory
+            .set_read_only_regions(vec![ReadOnlyRegion::fonts(ReadOnlyAction::Fault)]);
+        p.i = 0;
+        p.v[0] = 123;
+        p.process_opcode(0xF033, &mut bus);
+        assert_eq!(
+            p.fault,
+            Some(Fault::ReadOnlyWrite {
+                address: STARTING_PC,
+                target: 0
+            })
+        );
+        assert_eq!(&bus.memory.as_slice()[..3], font.as_slice());
+    }
+
+    #[test]
+    fn test_store_registers_index_increment() {
+        for (increment, index) in [
+            (IndexIncrement::PastLast, 0x303),
+            (IndexIncrement::ToLast, 0x302),
+            (IndexIncrement::Unchanged, 0x300),
+        ] {
+            let mut processor = Processor::new();
+            let mut bus = Bus::default();
+            processor.quirks.index_increment = increment;
+            processor.process_opcode(0xA300, &mut bus);
+            processor.process_opcode(0xF255, &mut bus);
+            assert_eq!(processor.i, index);
+            processor.process_opcode(0xA300, &mut bus);
+            processor.process_opcode(0xF265, &mut bus);
+            assert_eq!(processor.i, index);
+        }
+    }
+
+    /// Create a `Processor` and a `Bus` with the XO-CHIP memory, with the given
+    /// program at the starting address.
+    fn xo_chip(program: &[u8]) -> (Processor, Bus) {
+        let mut p = Processor::new();
+        p.variant = Variant::XoChip;
+        let mut bus = Bus::default();
+        bus.memory.resize(Variant::XoChip.memory_size().bytes());
+        for (offset, &byte) in program.iter().enumerate() {
+            bus.memory[STARTING_PC + offset] = byte;
+        }
+        (p, bus)
+    }
+
+    #[test]
+    fn test_xo_chip_long_index() {
+        let (mut p, mut bus) = xo_chip(&[0xF0, 0x00, 0xE0, 0x10]);
+        p.cycle(&mut bus);
+        assert_eq!(p.i, 0xE010);
+        assert_eq!(p.pc, STARTING_PC + 4);
+    }
+
+    #[test]
+    fn test_xo_chip_skip_long_index() {
+        // skips the whole 4 byte instruction
+        let (mut p, mut bus) = xo_chip(&[0x30, 0x00, 0xF0, 0x00, 0xE0, 0x10]);
+        p.cycle(&mut bus);
+        assert_eq!(p.pc, STARTING_PC + 6);
+
+        // other variants only skip 2 bytes
+        let (mut p, mut bus) = xo_chip(&[0x30, 0x00, 0xF0, 0x00, 0xE0, 0x10]);
+        p.variant = Variant::SuperChip;
+        p.cycle(&mut bus);
+        assert_eq!(p.pc, STARTING_PC + 4);
+    }
+
+    #[test]
+    fn test_xo_chip_save_and_load_register_range() {
+        let (mut p, mut bus) = xo_chip(&[0x53, 0x12, 0x51, 0x33]);
+        p.v[1] = 10;
+        p.v[2] = 20;
+        p.v[3] = 30;
+        p.i = 0x8000;
+        // save V3 down to V1
+        p.cycle(&mut bus);
+        assert_eq!(bus.memory.as_slice()[0x8000..0x8003], [30, 20, 10]);
+        assert_eq!(p.i, 0x8000);
+
+        // load V1 up to V3
+        p.cycle(&mut bus);
+        assert_eq!(p.v[1..4], [30, 20, 10]);
+    }
+
+    #[test]
+    fn test_xo_chip_draw_in_selected_planes() {
+        // select both planes, then draw a 1 row sprite from 0x300
+        let (mut p, mut bus) = xo_chip(&[0xF3, 0x01, 0xD0, 0x01]);
+        p.i = 0x300;
+        bus.memory[0x300] = 0x80;
+        bus.memory[0x301] = 0x40;
+        p.cycle(&mut bus);
+        assert_eq!(bus.graphics.planes(), graphics::ALL_PLANES);
+        p.cycle(&mut bus);
+        let rgb = bus.graphics.as_rgb8();
+        assert_eq!(rgb[..3], graphics::DEFAULT_FOREGROUND.0);
+        assert_eq!(rgb[3..6], graphics::DEFAULT_PLANE_2.0);
+    }
+
+    #[test]
+    fn test_super_chip_scroll() {
+        let mut p = Processor::new();
+        p.variant = Variant::SuperChip;
+        let mut bus = Bus::default();
+        bus.memory.as_mut_slice()[STARTING_PC..STARTING_PC + 4]
+            .copy_from_slice(&[0x00, 0xC2, 0x00, 0xFB]);
+        bus.graphics.draw_byte(0, 0, 0x80);
+        p.cycle(&mut bus);
+        assert!(bus.graphics.is_pixel_on(0, 2));
+        p.cycle(&mut bus);
+        assert!(bus.graphics.is_pixel_on(4, 2));
+        assert!(!bus.graphics.is_pixel_on(0, 2));
+
+        // 00FC is a machine code call on CHIP-8
+        let mut p = Processor::new();
+        p.machine_code_policy = MachineCodePolicy::Ignore;
+        let mut bus = Bus::default();
+        bus.memory.as_mut_slice()[STARTING_PC..STARTING_PC + 2].copy_from_slice(&[0x00, 0xFC]);
+        bus.graphics.draw_byte(4, 0, 0x80);
+        p.cycle(&mut bus);
+        assert!(bus.graphics.is_pixel_on(4, 0));
+    }
+
+    #[test]
+    fn test_xo_chip_scroll_up() {
+        let (mut p, mut bus) = xo_chip(&[0x00, 0xD1]);
+        bus.graphics.draw_byte(0, 1, 0x80);
+        p.cycle(&mut bus);
+        assert!(bus.graphics.is_pixel_on(0, 0));
+        assert!(!bus.graphics.is_pixel_on(0, 1));
+    }
 }
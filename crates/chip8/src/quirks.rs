@@ -0,0 +1,257 @@
+use std::fmt;
+
+/// The quirk settings of a [`Chip8`](crate::Chip8). Quirks change the behavior of some
+/// instructions, to match the interpreter a program was written for.
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Quirks {
+    /// `8xy6` and `8xyE` set `Vx` to `Vy` before shifting it.
+    pub shift: bool,
+
+    /// `Dxyn` waits for the vertical blank interrupt before drawing,
+    /// which limits drawing to 60 sprites per second.
+    pub vblank_wait: bool,
+
+    /// Sprites drawn past the bottom edge of the display wrap around to the top
+    /// instead of being clipped.
+    pub vertical_wrap: bool,
+
+    /// The delay and sound timers are paused while waiting for a key press (`Fx0A`).
+    pub pause_timers_on_key_wait: bool,
+
+    /// `Bnnn` jumps to `nnn` plus `Vx`, where `x` is the highest digit of `nnn`, instead of `V0`.
+    pub jump: bool,
+
+    /// How `Fx55` and `Fx65` change `I`.
+    pub index_increment: IndexIncrement,
+
+    /// `8xy1`, `8xy2` and `8xy3` reset `VF` to 0. This is the only quirk enabled by default.
+    #[cfg_attr(feature = "persistence", serde(with = "logic_layout"))]
+    pub logic: bool,
+
+    /// `Fx1E` sets `VF` to 1 if `I` overflows past 0xFFF, and to 0 otherwise,
+    /// like the CHIP-8 interpreter for the Amiga.
+    #[cfg_attr(
+        feature = "persistence",
+        serde(with = "crate::state::since_index_overflow_version")
+    )]
+    pub index_overflow: bool,
+
+    /// Sprites drawn past the right edge of the display wrap around to the left
+    /// instead of being clipped.
+    #[cfg_attr(
+        feature = "persistence",
+        serde(with = "crate::state::since_horizontal_wrap_version")
+    )]
+    pub horizontal_wrap: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            shift: false,
+            vblank_wait: false,
+            vertical_wrap: false,
+            pause_timers_on_key_wait: false,
+            jump: false,
+            index_increment: IndexIncrement::default(),
+            // VF was always reset before the quirk could be disabled
+            logic: true,
+            index_overflow: false,
+            horizontal_wrap: false,
+        }
+    }
+}
+
+impl Quirks {
+    /// The quirks of the original CHIP-8 interpreter for the COSMAC VIP.
+    pub const COSMAC_VIP: Quirks = Quirks {
+        shift: true,
+        vblank_wait: true,
+        vertical_wrap: false,
+        pause_timers_on_key_wait: false,
+        jump: false,
+        index_increment: IndexIncrement::PastLast,
+        logic: true,
+        index_overflow: false,
+        horizontal_wrap: false,
+    };
+
+    /// The quirks of CHIP-48 for the HP 48 calculators, which many programs of its era rely on:
+    /// shifts work on `Vx` alone, `Bxnn` jumps relative to `Vx`, and `Fx55` and `Fx65`
+    /// increase `I` by one less than the amount of registers.
+    pub const CHIP_48: Quirks = Quirks {
+        shift: false,
+        vblank_wait: false,
+        vertical_wrap: false,
+        pause_timers_on_key_wait: false,
+        jump: true,
+        index_increment: IndexIncrement::ToLast,
+        logic: false,
+        index_overflow: false,
+        horizontal_wrap: false,
+    };
+
+    /// The quirks of SUPER-CHIP 1.1, which keeps `I` unchanged when storing and loading registers.
+    pub const SUPER_CHIP: Quirks = Quirks {
+        shift: false,
+        vblank_wait: false,
+        vertical_wrap: false,
+        pause_timers_on_key_wait: false,
+        jump: true,
+        index_increment: IndexIncrement::Unchanged,
+        logic: false,
+        index_overflow: false,
+        horizontal_wrap: false,
+    };
+
+    /// The quirks of XO-CHIP, which goes back to the COSMAC VIP behavior of most
+    /// instructions, but draws without waiting and wraps sprites around all edges.
+    pub const XO_CHIP: Quirks = Quirks {
+        shift: true,
+        vblank_wait: false,
+        vertical_wrap: true,
+        pause_timers_on_key_wait: false,
+        jump: false,
+        index_increment: IndexIncrement::PastLast,
+        logic: false,
+        index_overflow: false,
+        horizontal_wrap: true,
+    };
+
+    /// The quirks of Octo with all of its quirk options disabled, which are the ones of XO-CHIP.
+    pub const OCTO: Quirks = Quirks::XO_CHIP;
+}
+
+/// A platform whose quirks can be applied all at once, see [`QuirkPreset::quirks`].
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum QuirkPreset {
+    CosmacVip,
+    Chip48,
+    SuperChip,
+    XoChip,
+    Octo,
+}
+
+impl QuirkPreset {
+    /// All presets, in the order they should be listed.
+    pub const ALL: [QuirkPreset; 5] = [
+        QuirkPreset::CosmacVip,
+        QuirkPreset::Chip48,
+        QuirkPreset::SuperChip,
+        QuirkPreset::XoChip,
+        QuirkPreset::Octo,
+    ];
+
+    /// The quirk settings of the platform.
+    pub fn quirks(self) -> Quirks {
+        match self {
+            QuirkPreset::CosmacVip => Quirks::COSMAC_VIP,
+            QuirkPreset::Chip48 => Quirks::CHIP_48,
+            QuirkPreset::SuperChip => Quirks::SUPER_CHIP,
+            QuirkPreset::XoChip => Quirks::XO_CHIP,
+            QuirkPreset::Octo => Quirks::OCTO,
+        }
+    }
+
+    /// The preset with the given quirk settings, if there is one.
+    /// The first one listed is returned if several presets have the same settings.
+    pub fn of(quirks: Quirks) -> Option<QuirkPreset> {
+        QuirkPreset::ALL
+            .into_iter()
+            .find(|preset| preset.quirks() == quirks)
+    }
+}
+
+impl fmt::Display for QuirkPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuirkPreset::CosmacVip => write!(f, "COSMAC VIP"),
+            QuirkPreset::Chip48 => write!(f, "CHIP-48"),
+            QuirkPreset::SuperChip => write!(f, "SUPER-CHIP"),
+            QuirkPreset::XoChip => write!(f, "XO-CHIP"),
+            QuirkPreset::Octo => write!(f, "Octo"),
+        }
+    }
+}
+
+/// How the register store and load instructions (`Fx55` and `Fx65`) change `I`.
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IndexIncrement {
+    /// `I` is increased by `x + 1`, past the last register, like the COSMAC VIP.
+    #[default]
+    PastLast,
+
+    /// `I` is increased by `x`, to the last register, like CHIP-48.
+    ToLast,
+
+    /// `I` is left unchanged, like SUPER-CHIP 1.1.
+    Unchanged,
+}
+
+impl IndexIncrement {
+    /// All index increments, in the order they should be listed.
+    pub const ALL: [IndexIncrement; 3] = [
+        IndexIncrement::PastLast,
+        IndexIncrement::ToLast,
+        IndexIncrement::Unchanged,
+    ];
+
+    /// The amount `I` is increased by after storing or loading the registers up to `Vx`.
+    pub fn amount(self, x: usize) -> usize {
+        match self {
+            IndexIncrement::PastLast => x + 1,
+            IndexIncrement::ToLast => x,
+            IndexIncrement::Unchanged => 0,
+        }
+    }
+}
+
+impl fmt::Display for IndexIncrement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexIncrement::PastLast => write!(f, "Past last register (COSMAC VIP)"),
+            IndexIncrement::ToLast => write!(f, "To last register (CHIP-48)"),
+            IndexIncrement::Unchanged => write!(f, "Unchanged (SUPER-CHIP)"),
+        }
+    }
+}
+
+/// The layout of the logic quirk in save states. Before format version 6, `VF` was always reset.
+#[cfg(feature = "persistence")]
+mod logic_layout {
+    use serde::{Deserializer, Serializer};
+
+    use crate::state;
+
+    pub fn serialize<S: Serializer>(logic: &bool, serializer: S) -> Result<S::Ok, S::Error> {
+        state::serialize_since(state::LOGIC_VERSION, logic, serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+        state::deserialize_since(state::LOGIC_VERSION, deserializer, || true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preset_of_quirks() {
+        for preset in [
+            QuirkPreset::CosmacVip,
+            QuirkPreset::Chip48,
+            QuirkPreset::SuperChip,
+        ] {
+            assert_eq!(QuirkPreset::of(preset.quirks()), Some(preset));
+        }
+        // Octo has the same settings as XO-CHIP, which is listed first
+        assert_eq!(QuirkPreset::of(Quirks::OCTO), Some(QuirkPreset::XoChip));
+        assert_eq!(QuirkPreset::of(Quirks::default()), None);
+    }
+}
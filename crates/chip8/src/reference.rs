@@ -0,0 +1,608 @@
+//! A reference of the instructions supported by the interpreter, explaining
+//! what each opcode does, which quirks change it, and which variants have it.
+
+use std::fmt;
+
+use crate::{IndexIncrement, Quirks, Variant};
+
+/// A setting of the [`Quirks`] that changes the behavior of an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quirk {
+    /// `shift`: `Vx` takes the value of `Vy` before being shifted.
+    Shift,
+
+    /// `vblank_wait`: drawing waits for the vertical blank interrupt.
+    VblankWait,
+
+    /// `vertical_wrap`: sprites wrap around the bottom edge instead of being clipped.
+    VerticalWrap,
+
+    /// `horizontal_wrap`: sprites wrap around the right edge instead of being clipped.
+    HorizontalWrap,
+
+    /// `pause_timers_on_key_wait`: the timers stop while waiting for a key press.
+    PauseTimersOnKeyWait,
+
+    /// `jump`: `Bxnn` jumps relative to `Vx` instead of `V0`.
+    Jump,
+
+    /// `index_increment`: `I` isn't increased past the stored or loaded registers.
+    IndexIncrement,
+
+    /// `logic`: `VF` is reset by the bitwise logic instructions.
+    Logic,
+
+    /// `index_overflow`: `VF` is set when `I` overflows past 0xFFF.
+    IndexOverflow,
+}
+
+impl Quirk {
+    /// Returns whether the quirk is enabled in the given `Quirks`.
+    pub fn is_enabled(self, quirks: &Quirks) -> bool {
+        match self {
+            Quirk::Shift => quirks.shift,
+            Quirk::VblankWait => quirks.vblank_wait,
+            Quirk::VerticalWrap => quirks.vertical_wrap,
+            Quirk::HorizontalWrap => quirks.horizontal_wrap,
+            Quirk::PauseTimersOnKeyWait => quirks.pause_timers_on_key_wait,
+            Quirk::Jump => quirks.jump,
+            Quirk::IndexIncrement => quirks.index_increment != IndexIncrement::PastLast,
+            Quirk::Logic => quirks.logic,
+            Quirk::IndexOverflow => quirks.index_overflow,
+        }
+    }
+
+    /// Explain how the instruction behaves when the quirk is enabled.
+    pub fn description(self) -> &'static str {
+        match self {
+            Quirk::Shift => "Vx is set to Vy before shifting, like the COSMAC VIP interpreter",
+            Quirk::VblankWait => "Waits for the next 60 Hz vertical blank before drawing",
+            Quirk::VerticalWrap => {
+                "Rows past the bottom edge wrap around to the top instead of being clipped"
+            }
+            Quirk::HorizontalWrap => {
+                "Pixels past the right edge wrap around to the left instead of being clipped"
+            }
+            Quirk::PauseTimersOnKeyWait => {
+                "The delay and sound timers stop counting down until a key is pressed"
+            }
+            Quirk::Jump => "Jumps to xnn plus Vx, like CHIP-48",
+            Quirk::IndexIncrement => {
+                "I is increased by x like CHIP-48, or left unchanged like SUPER-CHIP 1.1"
+            }
+            Quirk::Logic => "VF is reset to 0, like the COSMAC VIP interpreter",
+            Quirk::IndexOverflow => "VF is set to 1 if I goes past 0xFFF, and to 0 otherwise",
+        }
+    }
+}
+
+impl fmt::Display for Quirk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Quirk::Shift => "Shift quirk",
+            Quirk::VblankWait => "Wait for vertical blank",
+            Quirk::VerticalWrap => "Vertical wrap",
+            Quirk::HorizontalWrap => "Horizontal wrap",
+            Quirk::PauseTimersOnKeyWait => "Pause timers on key wait",
+            Quirk::Jump => "Jump quirk",
+            Quirk::IndexIncrement => "Index increment",
+            Quirk::Logic => "Logic quirk",
+            Quirk::IndexOverflow => "Index overflow",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// The reference entry of an instruction.
+#[derive(Debug)]
+pub struct Entry {
+    /// The opcode pattern, e.g. `8xy4`. Lowercase letters are operands.
+    pub pattern: &'static str,
+
+    /// A one line summary of the instruction.
+    pub summary: &'static str,
+
+    /// A longer explanation of the instruction's behavior and side effects.
+    pub details: &'static str,
+
+    /// The quirks that change the behavior of the instruction.
+    pub quirks: &'static [Quirk],
+
+    /// The variants that support the instruction.
+    pub variants: &'static [Variant],
+}
+
+/// An operand of an instruction, as decoded from an opcode by [`Entry::operands`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operand {
+    /// The name of the operand in the pattern, e.g. `nnn`.
+    pub name: &'static str,
+
+    /// The value of the operand in the opcode.
+    pub value: u16,
+
+    /// What the value stands for, e.g. `V3` or `0x0234`.
+    pub meaning: String,
+}
+
+impl Entry {
+    /// Returns whether the given opcode is an instance of this entry.
+    pub fn matches(&self, opcode: u16) -> bool {
+        self.pattern.chars().enumerate().all(|(i, c)| {
+            let nibble = u32::from((opcode >> (12 - 4 * i)) & 0xF);
+            match c.to_digit(16) {
+                Some(digit) => nibble == digit,
+                // an operand
+                None => true,
+            }
+        })
+    }
+
+    /// Decode the operands of the given opcode, in the order they appear in the pattern.
+    pub fn operands(&self, opcode: u16) -> Vec<Operand> {
+        let pattern = self.pattern.as_bytes();
+        let mut operands = Vec::new();
+        let mut position = 0;
+        while position < pattern.len() {
+            let c = pattern[position];
+            if !matches!(c, b'x' | b'y' | b'n') {
+                position += 1;
+                continue;
+            }
+            let len = pattern[position..]
+                .iter()
+                .take_while(|&&other| other == c)
+                .count();
+            let shift = 4 * (pattern.len() - position - len);
+            let value = (opcode >> shift) & ((1 << (4 * len)) - 1);
+            let meaning = match (c, len) {
+                (b'x' | b'y', _) => format!("V{value:X}"),
+                (_, 3) => format!("address {value:#06X}"),
+                (_, 2) => format!("{value} ({value:#04X})"),
+                _ => value.to_string(),
+            };
+            operands.push(Operand {
+                name: &self.pattern[position..position + len],
+                value,
+                meaning,
+            });
+            position += len;
+        }
+        operands
+    }
+
+    /// Returns whether the given variant supports the instruction.
+    pub fn is_supported(&self, variant: Variant) -> bool {
+        self.variants.contains(&variant)
+    }
+}
+
+const ALL: &[Variant] = &Variant::ALL;
+// XO-CHIP extends SUPER-CHIP
+const SUPER_CHIP: &[Variant] = &[Variant::SuperChip, Variant::XoChip];
+const XO_CHIP: &[Variant] = &[Variant::XoChip];
+const HIRES_CHIP8: &[Variant] = &[Variant::HiresChip8];
+
+/// The reference entries of all instructions. More specific patterns come first.
+pub const ENTRIES: &[Entry] = &[
+    Entry {
+        pattern: "00E0",
+        summary: "Clear the screen",
+        details: "Turns off all pixels of the display.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "00EE",
+        summary: "Return from a subroutine",
+        details: "Pops the return address from the stack and jumps to it.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "00Cn",
+        summary: "Scroll down n pixels",
+        details: "Moves the display down by n pixels. The rows at the top are cleared.",
+        quirks: &[],
+        variants: SUPER_CHIP,
+    },
+    Entry {
+        pattern: "00Dn",
+        summary: "Scroll up n pixels",
+        details: "Moves the selected planes up by n pixels. The rows at the bottom are cleared.",
+        quirks: &[],
+        variants: XO_CHIP,
+    },
+    Entry {
+        pattern: "00FB",
+        summary: "Scroll right 4 pixels",
+        details: "Moves the display right by 4 pixels. The columns at the left are cleared.",
+        quirks: &[],
+        variants: SUPER_CHIP,
+    },
+    Entry {
+        pattern: "00FC",
+        summary: "Scroll left 4 pixels",
+        details: "Moves the display left by 4 pixels. The columns at the right are cleared.",
+        quirks: &[],
+        variants: SUPER_CHIP,
+    },
+    Entry {
+        pattern: "00FD",
+        summary: "Exit the interpreter",
+        details: "Halts the program, which has finished.",
+        quirks: &[],
+        variants: SUPER_CHIP,
+    },
+    Entry {
+        pattern: "00FE",
+        summary: "Disable hi-res mode",
+        details: "Switches the display to 64x32 pixels.",
+        quirks: &[],
+        variants: SUPER_CHIP,
+    },
+    Entry {
+        pattern: "00FF",
+        summary: "Enable hi-res mode",
+        details: "Switches the display to 128x64 pixels.",
+        quirks: &[],
+        variants: SUPER_CHIP,
+    },
+    Entry {
+        pattern: "0230",
+        summary: "Clear the hi-res screen",
+        details: "Turns off all pixels of the 64x64 display.",
+        quirks: &[],
+        variants: HIRES_CHIP8,
+    },
+    Entry {
+        pattern: "0nnn",
+        summary: "Call a machine code routine",
+        details: "Runs the machine code at nnn. How it is handled depends on the machine code \
+                  policy: the call can be skipped, halt the program, be emulated by a registered \
+                  handler, or run on the emulated CDP1802.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "1nnn",
+        summary: "Jump to nnn",
+        details: "Sets the program counter to nnn. In two-page hi-res CHIP-8, the jump to 0x260 \
+                  at the start of a program enters it at 0x2C0 instead.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "2nnn",
+        summary: "Call the subroutine at nnn",
+        details: "Pushes the address of the next instruction to the stack, then jumps to nnn.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "3xnn",
+        summary: "Skip if Vx == nn",
+        details: "Skips the next instruction if Vx equals nn.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "4xnn",
+        summary: "Skip if Vx != nn",
+        details: "Skips the next instruction if Vx doesn't equal nn.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "5xy0",
+        summary: "Skip if Vx == Vy",
+        details: "Skips the next instruction if Vx equals Vy.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "5xy2",
+        summary: "Store Vx to Vy at I",
+        details: "Writes the registers from Vx to Vy to the memory starting at I, in reverse \
+                  order if x is greater than y. I is not changed.",
+        quirks: &[],
+        variants: XO_CHIP,
+    },
+    Entry {
+        pattern: "5xy3",
+        summary: "Load Vx to Vy from I",
+        details: "Reads the memory starting at I into the registers from Vx to Vy, in reverse \
+                  order if x is greater than y. I is not changed.",
+        quirks: &[],
+        variants: XO_CHIP,
+    },
+    Entry {
+        pattern: "6xnn",
+        summary: "Set Vx to nn",
+        details: "Loads the value nn into Vx.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "7xnn",
+        summary: "Add nn to Vx",
+        details: "Adds nn to Vx, wrapping around on overflow. VF is not changed.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "8xy0",
+        summary: "Set Vx to Vy",
+        details: "Copies the value of Vy into Vx.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "8xy1",
+        summary: "Set Vx to Vx OR Vy",
+        details: "Stores the bitwise OR of Vx and Vy in Vx.",
+        quirks: &[Quirk::Logic],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "8xy2",
+        summary: "Set Vx to Vx AND Vy",
+        details: "Stores the bitwise AND of Vx and Vy in Vx.",
+        quirks: &[Quirk::Logic],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "8xy3",
+        summary: "Set Vx to Vx XOR Vy",
+        details: "Stores the bitwise XOR of Vx and Vy in Vx.",
+        quirks: &[Quirk::Logic],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "8xy4",
+        summary: "Add Vy to Vx",
+        details: "Adds Vy to Vx, then sets VF to 1 if the addition overflowed, 0 otherwise.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "8xy5",
+        summary: "Subtract Vy from Vx",
+        details: "Sets Vx to Vx - Vy, then sets VF to 0 if the subtraction borrowed, 1 otherwise.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "8xy6",
+        summary: "Shift Vx right",
+        details: "Shifts Vx right by one bit, then sets VF to the bit that was shifted out.",
+        quirks: &[Quirk::Shift],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "8xy7",
+        summary: "Set Vx to Vy - Vx",
+        details: "Sets Vx to Vy - Vx, then sets VF to 0 if the subtraction borrowed, 1 otherwise.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "8xyE",
+        summary: "Shift Vx left",
+        details: "Shifts Vx left by one bit, then sets VF to the bit that was shifted out.",
+        quirks: &[Quirk::Shift],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "9xy0",
+        summary: "Skip if Vx != Vy",
+        details: "Skips the next instruction if Vx doesn't equal Vy.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "Annn",
+        summary: "Set I to nnn",
+        details: "Loads the address nnn into the index register I.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "Bnnn",
+        summary: "Jump to nnn + V0",
+        details: "Sets the program counter to nnn plus the value of V0.",
+        quirks: &[Quirk::Jump],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "Cxnn",
+        summary: "Set Vx to a random number AND nn",
+        details: "Generates a random byte, masks it with nn, and stores the result in Vx.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "Dxy0",
+        summary: "Draw a 16x16 sprite at (Vx, Vy)",
+        details: "XORs the 16 rows of two bytes each of the sprite at I onto the display, \
+                  starting at the position (Vx, Vy) wrapped to the display. Sets VF like Dxyn. \
+                  On CHIP-8 nothing is drawn.",
+        quirks: &[
+            Quirk::VblankWait,
+            Quirk::HorizontalWrap,
+            Quirk::VerticalWrap,
+        ],
+        variants: SUPER_CHIP,
+    },
+    Entry {
+        pattern: "Dxyn",
+        summary: "Draw an n byte sprite at (Vx, Vy)",
+        details: "XORs the n rows of the sprite at I onto the display, starting at the position \
+                  (Vx, Vy) wrapped to the display. Sets VF to 1 if a pixel was turned off. \
+                  In SUPER-CHIP hi-res mode, VF is the amount of rows that collided or were clipped. \
+                  In XO-CHIP, the sprite has n rows for each selected plane.",
+        quirks: &[
+            Quirk::VblankWait,
+            Quirk::HorizontalWrap,
+            Quirk::VerticalWrap,
+        ],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "Ex9E",
+        summary: "Skip if key Vx is pressed",
+        details: "Skips the next instruction if the key with the key code in Vx is pressed.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "ExA1",
+        summary: "Skip if key Vx is not pressed",
+        details: "Skips the next instruction if the key with the key code in Vx is not pressed.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "F000",
+        summary: "Set I to the following address",
+        details: "Loads the 16-bit address in the next two bytes into the index register I. \
+                  The instruction is 4 bytes long, and skipped entirely by skip instructions.",
+        quirks: &[],
+        variants: XO_CHIP,
+    },
+    Entry {
+        pattern: "Fn01",
+        summary: "Select the planes n",
+        details: "Selects the planes drawn to, cleared and scrolled: 1 for the first, \
+                  2 for the second and 3 for both. Pixels get a color for each plane they are on.",
+        quirks: &[],
+        variants: XO_CHIP,
+    },
+    Entry {
+        pattern: "Fx07",
+        summary: "Set Vx to the delay timer",
+        details: "Loads the current value of the delay timer into Vx.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "Fx0A",
+        summary: "Wait for a key press",
+        details: "Stops execution until a key is pressed and released, \
+                  then stores its key code in Vx.",
+        quirks: &[Quirk::PauseTimersOnKeyWait],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "Fx15",
+        summary: "Set the delay timer to Vx",
+        details: "The delay timer counts down to 0 at 60 Hz.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "Fx18",
+        summary: "Set the sound timer to Vx",
+        details: "The sound timer counts down to 0 at 60 Hz, and a tone plays while it is active.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "Fx1E",
+        summary: "Add Vx to I",
+        details: "Adds the value of Vx to the index register I. VF is not changed.",
+        quirks: &[Quirk::IndexOverflow],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "Fx29",
+        summary: "Set I to the font digit Vx",
+        details: "Points I to the 4x5 font sprite of the hexadecimal digit in Vx.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "Fx30",
+        summary: "Set I to the large font digit Vx",
+        details: "Points I to the 8x10 font sprite of the digit in the low nibble of Vx.",
+        quirks: &[],
+        variants: SUPER_CHIP,
+    },
+    Entry {
+        pattern: "Fx33",
+        summary: "Store the BCD of Vx at I",
+        details: "Writes the hundreds, tens and ones digits of Vx to the memory at I, I + 1 and I + 2.",
+        quirks: &[],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "Fx55",
+        summary: "Store V0 to Vx at I",
+        details: "Writes the registers V0 up to and including Vx to the memory starting at I. \
+                  I is incremented past the stored registers.",
+        quirks: &[Quirk::IndexIncrement],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "Fx65",
+        summary: "Load V0 to Vx from I",
+        details: "Reads the memory starting at I into the registers V0 up to and including Vx. \
+                  I is incremented past the loaded registers.",
+        quirks: &[Quirk::IndexIncrement],
+        variants: ALL,
+    },
+    Entry {
+        pattern: "Fx75",
+        summary: "Store V0 to Vx in the RPL flags",
+        details: "Copies the registers V0 up to and including Vx to the RPL user flags of the \
+                  HP 48, which are kept after the program exits.",
+        quirks: &[],
+        variants: SUPER_CHIP,
+    },
+    Entry {
+        pattern: "Fx85",
+        summary: "Load V0 to Vx from the RPL flags",
+        details: "Copies the RPL user flags into the registers V0 up to and including Vx.",
+        quirks: &[],
+        variants: SUPER_CHIP,
+    },
+];
+
+/// Look up the reference entry of the given opcode, or `None` if it is not an instruction.
+pub fn lookup(opcode: u16) -> Option<&'static Entry> {
+    ENTRIES.iter().find(|entry| entry.matches(opcode))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(lookup(0x00E0).unwrap().pattern, "00E0");
+        assert_eq!(lookup(0x0123).unwrap().pattern, "0nnn");
+        assert_eq!(lookup(0x8AB6).unwrap().pattern, "8xy6");
+        assert_eq!(lookup(0xF265).unwrap().pattern, "Fx65");
+        assert!(lookup(0x5AB1).is_none());
+        assert_eq!(lookup(0x5AB2).unwrap().pattern, "5xy2");
+        assert_eq!(lookup(0xF000).unwrap().pattern, "F000");
+        assert_eq!(lookup(0xF301).unwrap().pattern, "Fn01");
+        assert!(lookup(0xE0FF).is_none());
+    }
+
+    #[test]
+    fn test_operands() {
+        let names = |opcode| -> Vec<(&str, u16)> {
+            lookup(opcode)
+                .unwrap()
+                .operands(opcode)
+                .iter()
+                .map(|operand| (operand.name, operand.value))
+                .collect()
+        };
+        assert_eq!(names(0xD125), [("x", 1), ("y", 2), ("n", 5)]);
+        assert_eq!(names(0x3A2B), [("x", 0xA), ("nn", 0x2B)]);
+        assert_eq!(names(0x2345), [("nnn", 0x345)]);
+        assert!(names(0x00EE).is_empty());
+    }
+}
@@ -0,0 +1,130 @@
+use std::fmt;
+
+use crate::Chip8;
+
+/// Write a human readable report of the full state of the given `Chip8` to `w`,
+/// containing the registers, stack, timers, the last executed instructions and the whole
+/// memory, whatever its size.
+pub fn write_state(w: &mut impl fmt::Write, chip8: &Chip8) -> fmt::Result {
+    let processor = &chip8.processor;
+    let clock = &chip8.bus.clock;
+
+    writeln!(w, "[Registers]")?;
+    for (i, v) in processor.v.iter().enumerate() {
+        writeln!(w, "V{i:X}: {v:#04X}")?;
+    }
+    writeln!(w, "I: {:#06X}", processor.i)?;
+    writeln!(w, "PC: {:#06X}", processor.pc)?;
+    writeln!(w, "SP: {}", processor.sp)?;
+    writeln!(w)?;
+
+    writeln!(w, "[Stack]")?;
+    for (i, address) in processor.stack.iter().enumerate() {
+        writeln!(w, "{i:2}: {address:#06X}")?;
+    }
+    writeln!(w)?;
+
+    writeln!(w, "[Timers]")?;
+    writeln!(w, "Delay: {}", clock.delay_timer)?;
+    writeln!(w, "Sound: {}", clock.sound_timer)?;
+    writeln!(w)?;
+
+    writeln!(w, "[Instructions] (most recent first)")?;
+    for instruction in &processor.instructions {
+        writeln!(
+            w,
+            "{:#06X}: {:04X}  {}",
+            instruction.address, instruction.opcode, instruction.display
+        )?;
+    }
+    writeln!(w)?;
+
+    writeln!(w, "[Memory]")?;
+    for (row, bytes) in chip8.memory().chunks(16).enumerate() {
+        write!(w, "{:#06X}:", row * 16)?;
+        for byte in bytes {
+            write!(w, " {byte:02X}")?;
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+/// The 64-bit FNV-1a hash.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    /// Hash an address or size as 8 bytes, so the hash is the same on 32-bit platforms.
+    fn write_usize(&mut self, value: usize) {
+        self.write(&(value as u64).to_le_bytes());
+    }
+}
+
+/// Hash ROM data with the 64-bit FNV-1a hash, which is stable across
+/// platforms and releases, so it can be used to identify a ROM.
+pub fn rom_hash(data: &[u8]) -> u64 {
+    let mut hasher = Fnv1a::new();
+    hasher.write(data);
+    hasher.0
+}
+
+/// Hash the registers, stack, timers, memory and display of the given `Chip8`,
+/// see [`Chip8::state_hash`].
+pub(crate) fn state_hash(chip8: &Chip8) -> u64 {
+    let processor = &chip8.processor;
+    let clock = &chip8.bus.clock;
+    let mut hasher = Fnv1a::new();
+    hasher.write(&processor.v);
+    hasher.write_usize(processor.i);
+    hasher.write_usize(processor.pc);
+    hasher.write_usize(processor.sp);
+    for &address in chip8.stack() {
+        hasher.write_usize(address);
+    }
+    hasher.write(&[clock.delay_timer, clock.sound_timer]);
+    hasher.write_usize(chip8.memory().len());
+    hasher.write(chip8.memory());
+    let display = chip8.display();
+    hasher.write_usize(display.width());
+    hasher.write_usize(display.height());
+    for y in 0..display.height() {
+        for x in 0..display.width() {
+            hasher.write(&[display.pixel_planes(x, y)]);
+        }
+    }
+    hasher.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rom_hash() {
+        // FNV-1a test vectors
+        assert_eq!(rom_hash(b""), 0xcbf29ce484222325);
+        assert_eq!(rom_hash(b"a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn test_write_state_memory() {
+        let mut chip8 = Chip8::new();
+        chip8.set_memory_size(crate::MemorySize::Extended);
+        chip8.poke(0xFFFF, 0xAB);
+        let mut report = String::new();
+        write_state(&mut report, &chip8).unwrap();
+        let memory = &report[report.find("[Memory]").unwrap()..];
+        assert_eq!(memory.lines().count(), 1 + 0x10000 / 16);
+        assert!(memory.ends_with("0xFFF0: 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 AB\n"));
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::VecDeque;
+
+use crate::{bus::Bus, processor::Processor};
+
+/// The state of the `Chip8` before a step, to step back to.
+pub(crate) struct Snapshot {
+    pub processor: Processor,
+    pub bus: Bus,
+}
+
+/// A ring buffer of the snapshots taken before the most recent steps, see
+/// [`Chip8::set_rewind_depth`](crate::Chip8::set_rewind_depth).
+#[derive(Default)]
+pub(crate) struct Rewind {
+    /// The maximum amount of snapshots kept, `0` to take none.
+    depth: usize,
+
+    /// The snapshots, oldest first.
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl Rewind {
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Set the maximum amount of snapshots kept, dropping the oldest ones past it.
+    pub fn set_depth(&mut self, depth: usize) {
+        self.depth = depth;
+        let excess = self.snapshots.len().saturating_sub(depth);
+        self.snapshots.drain(..excess);
+    }
+
+    /// The amount of snapshots kept.
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Take a snapshot of the given state, dropping the oldest one if the buffer is full.
+    pub fn push(&mut self, processor: &Processor, bus: &Bus) {
+        if self.depth == 0 {
+            return;
+        }
+        if self.snapshots.len() == self.depth {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(Snapshot {
+            processor: processor.clone(),
+            bus: bus.clone(),
+        });
+    }
+
+    /// Take the most recent snapshot out of the buffer.
+    pub fn pop(&mut self) -> Option<Snapshot> {
+        self.snapshots.pop_back()
+    }
+
+    /// Drop all snapshots, keeping the depth.
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
@@ -0,0 +1,147 @@
+//! Tools for editing ROM images, e.g. to clean up dumps. Addresses are memory
+//! addresses, so the first byte of a ROM is at [`START`].
+
+use std::fmt;
+
+use crate::MemorySize;
+
+/// The address ROMs are loaded at.
+pub const START: usize = 0x200;
+
+/// The address ROMs for the ETI-660 are loaded at.
+pub const ETI_660_START: usize = 0x600;
+
+/// The maximum size of a ROM in memory of the given size, which is the memory after
+/// the interpreter.
+pub fn max_size(memory_size: MemorySize) -> usize {
+    memory_size.bytes() - START
+}
+
+/// An error caused by an invalid ROM edit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RomError {
+    /// The segment from `start` up to `end` is not part of the ROM.
+    SegmentOutOfBounds { start: usize, end: usize },
+
+    /// A segment of the given length doesn't fit in memory at the destination.
+    DestinationOutOfBounds { destination: usize, length: usize },
+
+    /// A ROM of `size` bytes doesn't fit in the `capacity` bytes of memory it is loaded into.
+    TooLarge { size: usize, capacity: usize },
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RomError::SegmentOutOfBounds { start, end } => {
+                write!(f, "Segment {start:#06X}..{end:#06X} is not part of the ROM")
+            }
+            RomError::DestinationOutOfBounds {
+                destination,
+                length,
+            } => write!(
+                f,
+                "{length} bytes don't fit in memory at {destination:#06X}"
+            ),
+            RomError::TooLarge { size, capacity } => write!(
+                f,
+                "The ROM is {size} bytes, but only {capacity} bytes fit in memory"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RomError {}
+
+/// Strip the trailing zero padding that ROM dumps often contain.
+pub fn trim(rom: &[u8]) -> &[u8] {
+    let length = rom.iter().rposition(|&byte| byte != 0).map_or(0, |i| i + 1);
+    &rom[..length]
+}
+
+/// Concatenate the given blocks of data into a single ROM.
+pub fn concat<'a>(blocks: impl IntoIterator<Item = &'a [u8]>) -> Vec<u8> {
+    blocks.into_iter().flatten().copied().collect()
+}
+
+/// Move the segment from `start` up to (excluding) `end` to `destination`. The bytes the
+/// segment is moved away from are zeroed, and the ROM grows if the segment is moved past its end,
+/// up to the end of memory of the given size.
+///
+/// Note that instructions referring to the segment are not updated.
+pub fn relocate(
+    rom: &[u8],
+    start: usize,
+    end: usize,
+    destination: usize,
+    memory_size: MemorySize,
+) -> Result<Vec<u8>, RomError> {
+    if start < START || start > end || end > START + rom.len() {
+        return Err(RomError::SegmentOutOfBounds { start, end });
+    }
+    let length = end - start;
+    if destination < START || destination + length > START + max_size(memory_size) {
+        return Err(RomError::DestinationOutOfBounds {
+            destination,
+            length,
+        });
+    }
+
+    let (start, end, destination) = (start - START, end - START, destination - START);
+    let segment = rom[start..end].to_vec();
+    let mut result = rom.to_vec();
+    result[start..end].fill(0);
+    if result.len() < destination + length {
+        result.resize(destination + length, 0);
+    }
+    result[destination..destination + length].copy_from_slice(&segment);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trim() {
+        assert_eq!(trim(&[0x12, 0x00, 0x34, 0x00, 0x00]), [0x12, 0x00, 0x34]);
+        assert!(trim(&[0x00, 0x00]).is_empty());
+    }
+
+    #[test]
+    fn test_concat() {
+        let blocks: [&[u8]; 2] = [&[0x12, 0x34], &[0x56]];
+        assert_eq!(concat(blocks), [0x12, 0x34, 0x56]);
+    }
+
+    #[test]
+    fn test_relocate() {
+        let rom = [0x01, 0x02, 0x03, 0x04];
+        // move the middle two bytes past the end
+        let relocated = relocate(&rom, 0x201, 0x203, 0x205, MemorySize::Standard).unwrap();
+        assert_eq!(relocated, [0x01, 0x00, 0x00, 0x04, 0x00, 0x02, 0x03]);
+        // overlapping segments keep the moved bytes
+        let relocated = relocate(&rom, 0x200, 0x202, 0x201, MemorySize::Standard).unwrap();
+        assert_eq!(relocated, [0x00, 0x01, 0x02, 0x04]);
+
+        assert_eq!(
+            relocate(&rom, 0x202, 0x205, 0x300, MemorySize::Standard),
+            Err(RomError::SegmentOutOfBounds {
+                start: 0x202,
+                end: 0x205
+            })
+        );
+        assert_eq!(
+            relocate(&rom, 0x200, 0x202, 0xFFF, MemorySize::Standard),
+            Err(RomError::DestinationOutOfBounds {
+                destination: 0xFFF,
+                length: 2
+            })
+        );
+        // the 64 KB of XO-CHIP fit segments past the first 4 KB
+        let relocated = relocate(&rom, 0x200, 0x202, 0xFFF, MemorySize::Extended).unwrap();
+        assert_eq!(relocated.len(), 0x1001 - START);
+        assert_eq!(relocated[0xDFF..], [0x01, 0x02]);
+    }
+}
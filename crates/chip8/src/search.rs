@@ -0,0 +1,157 @@
+//! Searching the memory for the variables of a program, like the lives of a game, by
+//! narrowing down the addresses whose values change the way the variable does.
+//! This is the backend of cheat searches, see [`Chip8::set_freezes`].
+
+use crate::Chip8;
+
+/// A filter that narrows down the candidates of a [`RamSearch`].
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SearchFilter {
+    /// The value is equal to the given value.
+    Equal(u8),
+
+    /// The value is greater than the given value.
+    GreaterThan(u8),
+
+    /// The value is less than the given value.
+    LessThan(u8),
+
+    /// The value changed since the last snapshot.
+    Changed,
+
+    /// The value didn't change since the last snapshot.
+    Unchanged,
+
+    /// The value increased since the last snapshot.
+    Increased,
+
+    /// The value decreased since the last snapshot.
+    Decreased,
+}
+
+impl SearchFilter {
+    /// Returns whether a value passes this filter, given its value in the last snapshot.
+    fn matches(self, previous: u8, current: u8) -> bool {
+        match self {
+            SearchFilter::Equal(value) => current == value,
+            SearchFilter::GreaterThan(value) => current > value,
+            SearchFilter::LessThan(value) => current < value,
+            SearchFilter::Changed => current != previous,
+            SearchFilter::Unchanged => current == previous,
+            SearchFilter::Increased => current > previous,
+            SearchFilter::Decreased => current < previous,
+        }
+    }
+
+    /// Get a display friendly name for this filter.
+    pub fn name(self) -> &'static str {
+        match self {
+            SearchFilter::Equal(_) => "Equal to",
+            SearchFilter::GreaterThan(_) => "Greater than",
+            SearchFilter::LessThan(_) => "Less than",
+            SearchFilter::Changed => "Changed",
+            SearchFilter::Unchanged => "Unchanged",
+            SearchFilter::Increased => "Increased",
+            SearchFilter::Decreased => "Decreased",
+        }
+    }
+
+    /// Returns whether this filter compares against a user-provided value.
+    pub fn has_value(self) -> bool {
+        matches!(
+            self,
+            SearchFilter::Equal(_) | SearchFilter::GreaterThan(_) | SearchFilter::LessThan(_)
+        )
+    }
+
+    /// Get this filter with its compared value replaced by `value`.
+    pub fn with_value(self, value: u8) -> Self {
+        match self {
+            SearchFilter::Equal(_) => SearchFilter::Equal(value),
+            SearchFilter::GreaterThan(_) => SearchFilter::GreaterThan(value),
+            SearchFilter::LessThan(_) => SearchFilter::LessThan(value),
+            filter => filter,
+        }
+    }
+}
+
+/// An iterative search over the `Chip8` memory, used to locate game variables.
+///
+/// A search starts with every address as a candidate. Each filter
+/// compares the candidates against the snapshot taken by the previous
+/// filter, removing any addresses that don't match.
+pub struct RamSearch {
+    candidates: Vec<usize>,
+    snapshot: Vec<u8>,
+}
+
+impl RamSearch {
+    /// Start a new search with every address as a candidate.
+    pub fn new(chip8: &Chip8) -> Self {
+        Self {
+            candidates: (0..chip8.memory().len()).collect(),
+            snapshot: Self::take_snapshot(chip8),
+        }
+    }
+
+    /// Remove all candidates that don't match `filter`, and take a new snapshot.
+    pub fn filter(&mut self, chip8: &Chip8, filter: SearchFilter) {
+        let snapshot = Self::take_snapshot(chip8);
+        // the memory shrinks when switching to a variant with less memory
+        self.candidates.retain(|&address| {
+            matches!(
+                (self.snapshot.get(address), snapshot.get(address)),
+                (Some(&previous), Some(&current)) if filter.matches(previous, current)
+            )
+        });
+        self.snapshot = snapshot;
+    }
+
+    /// Start a new search with the addresses currently holding `value` as candidates.
+    pub fn find(chip8: &Chip8, value: u8) -> Self {
+        let mut search = Self::new(chip8);
+        search.filter(chip8, SearchFilter::Equal(value));
+        search
+    }
+
+    /// Get the remaining candidate addresses.
+    pub fn candidates(&self) -> &[usize] {
+        &self.candidates
+    }
+
+    /// Get the value of `address` when the last snapshot was taken.
+    pub fn snapshot_value(&self, address: usize) -> u8 {
+        self.snapshot[address]
+    }
+
+    /// Copy the current state of the `Chip8` memory.
+    fn take_snapshot(chip8: &Chip8) -> Vec<u8> {
+        chip8.memory().to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ram_search() {
+        let mut chip8 = Chip8::new();
+        chip8.write_memory(0x300, &[3, 3, 5]);
+        let mut search = RamSearch::find(&chip8, 3);
+        assert!(search.candidates().contains(&0x300));
+        assert!(search.candidates().contains(&0x301));
+        assert!(!search.candidates().contains(&0x302));
+
+        // the lives at 0x301 decrease
+        chip8.write_memory(0x301, &[2]);
+        search.filter(&chip8, SearchFilter::Decreased);
+        assert_eq!(search.candidates(), [0x301]);
+        assert_eq!(search.snapshot_value(0x301), 2);
+
+        search.filter(&chip8, SearchFilter::Unchanged);
+        assert_eq!(search.candidates(), [0x301]);
+        search.filter(&chip8, SearchFilter::Changed);
+        assert!(search.candidates().is_empty());
+    }
+}
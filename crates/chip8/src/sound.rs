@@ -0,0 +1,14 @@
+/// Receives the changes of the sound timer of a [`Chip8`](crate::Chip8), e.g. to play the
+/// tone on an audio thread. Set with [`Chip8::set_sound_sink`](crate::Chip8::set_sound_sink).
+///
+/// The methods are called right after the step that changed the sound timer.
+pub trait SoundSink: Send {
+    /// The sound timer became active, the tone should start playing.
+    fn beep_started(&mut self);
+
+    /// The sound timer reached zero, the tone should stop playing.
+    fn beep_stopped(&mut self);
+
+    /// The sound timer changed to `ticks`, the amount of 60 Hz ticks the tone keeps playing for.
+    fn remaining_ticks(&mut self, _ticks: u8) {}
+}
@@ -0,0 +1,132 @@
+//! Finds the sprite data in a ROM without running it, so the
+//! assets of a program can be recovered.
+
+use std::fmt::Write;
+
+use crate::rom;
+
+/// A sprite found in a ROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sprite {
+    /// The memory address of the sprite's first row.
+    pub address: usize,
+
+    /// The amount of rows of the sprite.
+    pub height: usize,
+
+    /// Whether this is a 16x16 sprite drawn with `Dxy0`, which has two bytes per row.
+    pub wide: bool,
+}
+
+impl Sprite {
+    /// The width of the sprite in pixels.
+    pub fn width(&self) -> usize {
+        if self.wide {
+            16
+        } else {
+            8
+        }
+    }
+
+    /// The amount of bytes the sprite takes up.
+    pub fn size(&self) -> usize {
+        self.height * self.width() / 8
+    }
+
+    /// Get the bytes of this sprite in the given `rom`, or `None` if it
+    /// doesn't fit in the ROM, e.g. when it is stored in the font.
+    pub fn data<'a>(&self, rom: &'a [u8]) -> Option<&'a [u8]> {
+        let start = self.address.checked_sub(rom::START)?;
+        rom.get(start..start + self.size())
+    }
+
+    /// Returns whether the pixel at the given coordinates is set in the sprite `data`.
+    pub fn pixel(&self, data: &[u8], x: usize, y: usize) -> bool {
+        let byte = data[y * self.width() / 8 + x / 8];
+        byte & (0x80 >> (x % 8)) != 0
+    }
+}
+
+/// Find the likely sprites of a ROM by looking for the addresses loaded into `I` (`Annn`)
+/// before a sprite is drawn (`Dxyn`). Sprites drawn from the same address are merged,
+/// keeping the largest height. The sprites are sorted by address.
+///
+/// Sprites whose address is computed at runtime, e.g. with `Fx1E`, are not found.
+pub fn find_sprites(rom: &[u8]) -> Vec<Sprite> {
+    let mut sprites: Vec<Sprite> = Vec::new();
+    let mut i = None;
+    for bytes in rom.chunks_exact(2) {
+        let opcode = usize::from(u16::from_be_bytes([bytes[0], bytes[1]]));
+        match (opcode & 0xF000, i) {
+            (0xA000, _) => i = Some(opcode & 0x0FFF),
+            (0xD000, Some(address)) => {
+                let n = opcode & 0x000F;
+                let sprite = Sprite {
+                    address,
+                    height: if n == 0 { 16 } else { n },
+                    wide: n == 0,
+                };
+                match sprites.iter_mut().find(|s| s.address == address) {
+                    Some(existing) if existing.size() < sprite.size() => *existing = sprite,
+                    Some(_) => {}
+                    None => sprites.push(sprite),
+                }
+            }
+            // `I` is changed in a way that can't be followed without running the program
+            (0xF000, _) if matches!(opcode & 0x00FF, 0x1E | 0x29 | 0x30 | 0x55 | 0x65) => i = None,
+            _ => {}
+        }
+    }
+    sprites.sort_by_key(|sprite| sprite.address);
+    sprites
+}
+
+/// Write the given sprites of `rom` as Octo sprite declarations, one label per sprite.
+/// Sprites that are not part of the ROM are skipped.
+pub fn to_octo(rom: &[u8], sprites: &[Sprite]) -> String {
+    let mut octo = String::new();
+    for (sprite, data) in sprites
+        .iter()
+        .filter_map(|sprite| Some((sprite, sprite.data(rom)?)))
+    {
+        let _ = writeln!(octo, ": sprite_{:03x}", sprite.address);
+        for row in data.chunks(sprite.width() / 8) {
+            let row: Vec<String> = row.iter().map(|byte| format!("0b{byte:08b}")).collect();
+            let _ = writeln!(octo, "  {}", row.join(" "));
+        }
+        octo.push('\n');
+    }
+    octo
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_sprites() {
+        let rom = [
+            0xA2, 0x0A, // LD I, 0x20A
+            0xD0, 0x12, // DRW V0, V1, 2
+            0xD0, 0x13, // DRW V0, V1, 3
+            0xF0, 0x29, // LD F, V0
+            0xD0, 0x15, // DRW V0, V1, 5 (font, unknown address)
+            0x3C, 0x42, 0x81, // sprite data
+        ];
+        let sprites = find_sprites(&rom);
+        assert_eq!(
+            sprites,
+            [Sprite {
+                address: 0x20A,
+                height: 3,
+                wide: false
+            }]
+        );
+        assert_eq!(sprites[0].data(&rom), Some(&[0x3C, 0x42, 0x81][..]));
+        assert!(sprites[0].pixel(&rom[10..], 2, 0));
+        assert_eq!(
+            to_octo(&rom, &sprites),
+            ": sprite_20a\n  0b00111100\n  0b01000010\n  0b10000001\n\n"
+        );
+    }
+}
@@ -0,0 +1,401 @@
+//! The file format of save states: a header with a magic number and a format
+//! version, followed by the `bincode` encoded [`Chip8`].
+//!
+//! Save states written before the format was versioned are plain `bincode` dumps
+//! without a header. They are still loaded, and can be upgraded with [`migrate`].
+//!
+//! Components whose layout changed between versions read and write the layout of the
+//! [`format_version`] of the save state being decoded or encoded.
+
+use std::{cell::Cell, fmt};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::Chip8;
+
+/// The bytes every versioned save state starts with.
+pub const MAGIC: &[u8; 8] = b"CHIP8SAV";
+
+/// The version of the save state format, increased whenever the format changes.
+pub const VERSION: u32 = 11;
+
+/// The version reported for save states without a header. Their layout is the one of version 1.
+pub const LEGACY_VERSION: u32 = 0;
+
+/// The first version in which the memory is sized by the variant, and the display
+/// stores the planes of every pixel instead of its color, for XO-CHIP.
+pub(crate) const XO_CHIP_VERSION: u32 = 2;
+
+/// The first version with the CHIP-48 jump and index increment quirks.
+pub(crate) const CHIP_48_VERSION: u32 = 3;
+
+/// The first version with a configurable start address.
+pub(crate) const START_ADDRESS_VERSION: u32 = 4;
+
+/// The first version in which the quirks of the processor are stored together.
+pub(crate) const QUIRKS_VERSION: u32 = 5;
+
+/// The first version in which resetting `VF` with the logic instructions can be disabled.
+pub(crate) const LOGIC_VERSION: u32 = 6;
+
+/// The first version with the index overflow quirk.
+pub(crate) const INDEX_OVERFLOW_VERSION: u32 = 7;
+
+/// The first version in which sprites can wrap around the right edge of the display.
+pub(crate) const HORIZONTAL_WRAP_VERSION: u32 = 8;
+
+/// The first version in which a key press request waits for the key to be released.
+pub(crate) const KEY_RELEASE_VERSION: u32 = 9;
+
+/// The first version with the SUPER-CHIP RPL user flags.
+pub(crate) const RPL_FLAGS_VERSION: u32 = 10;
+
+/// The first version that stores the sound timer.
+pub(crate) const SOUND_TIMER_VERSION: u32 = 11;
+
+thread_local! {
+    static FORMAT_VERSION: Cell<u32> = const { Cell::new(VERSION) };
+}
+
+/// The format version of the save state being decoded or encoded on this thread.
+/// This is the current [`VERSION`] outside of [`load`].
+pub(crate) fn format_version() -> u32 {
+    FORMAT_VERSION.with(Cell::get)
+}
+
+/// Call `f` with the [`format_version`] set to `version`.
+fn with_format_version<T>(version: u32, f: impl FnOnce() -> T) -> T {
+    let previous = FORMAT_VERSION.with(|format_version| format_version.replace(version));
+    let result = f();
+    FORMAT_VERSION.with(|format_version| format_version.set(previous));
+    result
+}
+
+/// Serialize a field added in `version`, which is left out of older save states.
+pub(crate) fn serialize_since<T: Serialize, S: Serializer>(
+    version: u32,
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    if format_version() < version {
+        serializer.serialize_unit()
+    } else {
+        value.serialize(serializer)
+    }
+}
+
+/// Deserialize a field added in `version`. Older save states don't store it,
+/// so it is set to the value returned by `default` instead.
+pub(crate) fn deserialize_since<'de, T: Deserialize<'de>, D: Deserializer<'de>>(
+    version: u32,
+    deserializer: D,
+    default: impl FnOnce() -> T,
+) -> Result<T, D::Error> {
+    if format_version() < version {
+        Ok(default())
+    } else {
+        T::deserialize(deserializer)
+    }
+}
+
+/// Define a module for `#[serde(with = "...")]` with the layout of fields added in the
+/// given format version. Older save states don't store them, so they are loaded with
+/// their default value.
+macro_rules! since_version {
+    ($name:ident, $version:ident) => {
+        pub(crate) mod $name {
+            use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+            pub fn serialize<T: Serialize, S: Serializer>(
+                value: &T,
+                serializer: S,
+            ) -> Result<S::Ok, S::Error> {
+                super::serialize_since(super::$version, value, serializer)
+            }
+
+            pub fn deserialize<'de, T: Deserialize<'de> + Default, D: Deserializer<'de>>(
+                deserializer: D,
+            ) -> Result<T, D::Error> {
+                super::deserialize_since(super::$version, deserializer, T::default)
+            }
+        }
+    };
+}
+
+since_version!(since_chip_48_version, CHIP_48_VERSION);
+since_version!(since_index_overflow_version, INDEX_OVERFLOW_VERSION);
+since_version!(since_horizontal_wrap_version, HORIZONTAL_WRAP_VERSION);
+since_version!(since_key_release_version, KEY_RELEASE_VERSION);
+since_version!(since_rpl_flags_version, RPL_FLAGS_VERSION);
+since_version!(since_sound_timer_version, SOUND_TIMER_VERSION);
+
+/// An error caused by an invalid or unsupported save state.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum StateError {
+    /// The save state was written by a newer version of the format.
+    UnsupportedVersion(u32),
+
+    /// The save state couldn't be encoded or decoded.
+    Encoding(bincode::Error),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::UnsupportedVersion(version) => write!(
+                f,
+                "Save state version {version} is newer than the supported version {VERSION}"
+            ),
+            StateError::Encoding(e) => write!(f, "Invalid save state: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StateError::UnsupportedVersion(_) => None,
+            StateError::Encoding(e) => Some(e),
+        }
+    }
+}
+
+impl From<bincode::Error> for StateError {
+    fn from(e: bincode::Error) -> Self {
+        StateError::Encoding(e)
+    }
+}
+
+/// Encode the `Chip8` as a save state of the current version.
+pub fn save(chip8: &Chip8) -> Result<Vec<u8>, StateError> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bincode::serialize_into(&mut bytes, chip8)?;
+    Ok(bytes)
+}
+
+/// Get the format version of the given save state, [`LEGACY_VERSION`] if it has no header.
+pub fn version(bytes: &[u8]) -> u32 {
+    match bytes.strip_prefix(MAGIC.as_slice()) {
+        Some(&[a, b, c, d, ..]) => u32::from_le_bytes([a, b, c, d]),
+        _ => LEGACY_VERSION,
+    }
+}
+
+/// Decode a save state of any supported version, including legacy save states.
+pub fn load(bytes: &[u8]) -> Result<Chip8, StateError> {
+    let version = version(bytes);
+    let data = match version {
+        LEGACY_VERSION => bytes,
+        1..=VERSION => &bytes[MAGIC.len() + 4..],
+        _ => return Err(StateError::UnsupportedVersion(version)),
+    };
+    let mut chip8: Chip8 = with_format_version(version, || bincode::deserialize(data))?;
+    chip8.latch_display();
+    Ok(chip8)
+}
+
+/// Upgrade a save state of any supported version to the current version.
+pub fn migrate(bytes: &[u8]) -> Result<Vec<u8>, StateError> {
+    save(&load(bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encode the `Chip8` like a save state of the given format version.
+    fn encode_as(version: u32, chip8: &Chip8) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&version.to_le_bytes());
+        with_format_version(version, || bincode::serialize_into(&mut bytes, chip8)).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_migrate_legacy_state() {
+        let mut chip8 = Chip8::new();
+        chip8.processor.v[3] = 42;
+        chip8.processor.i = 0x234;
+        let legacy = with_format_version(LEGACY_VERSION, || bincode::serialize(&chip8)).unwrap();
+        assert_eq!(version(&legacy), LEGACY_VERSION);
+
+        let migrated = migrate(&legacy).unwrap();
+        assert_eq!(version(&migrated), VERSION);
+        let loaded = load(&migrated).unwrap();
+        assert_eq!(loaded.processor.v[3], 42);
+        assert_eq!(loaded.processor.i, 0x234);
+        assert_eq!(migrate(&migrated).unwrap(), migrated);
+    }
+
+    #[test]
+    fn test_load_version_1_display_and_memory() {
+        let mut chip8 = Chip8::new();
+        chip8.bus.graphics.draw_byte(0, 0, 0x80);
+        chip8.bus.memory[0x300] = 7;
+        let bytes = encode_as(1, &chip8);
+
+        let loaded = load(&bytes).unwrap();
+        assert!(loaded.display().is_pixel_on(0, 0));
+        assert!(!loaded.display().is_pixel_on(1, 0));
+        assert_eq!(loaded.memory().len(), crate::memory::MEMORY_SIZE);
+        assert_eq!(loaded.memory()[0x300], 7);
+        // the format version is restored after loading
+        assert_eq!(format_version(), VERSION);
+    }
+
+    #[test]
+    fn test_xo_chip_state_round_trip() {
+        let mut chip8 = Chip8::new();
+        chip8.set_variant(crate::Variant::XoChip);
+        chip8.bus.graphics.select_planes(crate::graphics::PLANE_2);
+        chip8.bus.graphics.draw_byte(1, 0, 0x80);
+        chip8.bus.memory[0xFFFF] = 9;
+
+        let loaded = load(&save(&chip8).unwrap()).unwrap();
+        assert_eq!(loaded.memory().len(), crate::memory::XO_CHIP_MEMORY_SIZE);
+        assert_eq!(loaded.memory()[0xFFFF], 9);
+        assert_eq!(loaded.display().planes(), crate::graphics::PLANE_2);
+        assert_eq!(loaded.display().as_rgb8(), chip8.display().as_rgb8());
+    }
+
+    #[test]
+    fn test_load_version_2_quirks() {
+        let mut chip8 = Chip8::new();
+        chip8.set_quirks(crate::Quirks::CHIP_48);
+        chip8.processor.v[0xA] = 5;
+        let bytes = encode_as(2, &chip8);
+
+        // version 2 didn't store the CHIP-48 quirks, so they are back to the defaults
+        let loaded = load(&bytes).unwrap();
+        assert_eq!(loaded.quirks(), crate::Quirks::default());
+        assert_eq!(loaded.registers()[0xA], 5);
+
+        let loaded = load(&save(&chip8).unwrap()).unwrap();
+        assert_eq!(loaded.quirks(), crate::Quirks::CHIP_48);
+    }
+
+    #[test]
+    fn test_load_version_3_start_address() {
+        let mut chip8 = Chip8::new();
+        chip8.set_start_address(crate::rom::ETI_660_START);
+        let bytes = encode_as(3, &chip8);
+
+        // programs always started at 0x200 before version 4
+        assert_eq!(load(&bytes).unwrap().start_address(), crate::rom::START);
+        let loaded = load(&save(&chip8).unwrap()).unwrap();
+        assert_eq!(loaded.start_address(), crate::rom::ETI_660_START);
+    }
+
+    #[test]
+    fn test_load_version_4_quirks() {
+        // version 4 can't store the logic quirk, which was always enabled
+        let quirks = crate::Quirks {
+            logic: true,
+            ..crate::Quirks::SUPER_CHIP
+        };
+        let mut chip8 = Chip8::new();
+        chip8.set_quirks(quirks);
+        chip8.set_start_address(crate::rom::ETI_660_START);
+        let bytes = encode_as(4, &chip8);
+
+        let loaded = load(&bytes).unwrap();
+        assert_eq!(loaded.quirks(), quirks);
+        assert_eq!(loaded.start_address(), crate::rom::ETI_660_START);
+        assert_eq!(migrate(&bytes).unwrap(), save(&chip8).unwrap());
+    }
+
+    #[test]
+    fn test_load_version_5_logic_quirk() {
+        let mut chip8 = Chip8::new();
+        chip8.set_quirks(crate::Quirks::SUPER_CHIP);
+        let bytes = encode_as(5, &chip8);
+
+        // VF was always reset before version 6
+        let loaded = load(&bytes).unwrap();
+        assert!(loaded.quirks().logic);
+        assert_eq!(loaded.quirks().jump, crate::Quirks::SUPER_CHIP.jump);
+        let loaded = load(&save(&chip8).unwrap()).unwrap();
+        assert_eq!(loaded.quirks(), crate::Quirks::SUPER_CHIP);
+    }
+
+    #[test]
+    fn test_load_version_6_index_overflow_quirk() {
+        let mut chip8 = Chip8::new();
+        chip8.set_quirks(crate::Quirks {
+            index_overflow: true,
+            ..crate::Quirks::default()
+        });
+        let bytes = encode_as(6, &chip8);
+
+        assert_eq!(load(&bytes).unwrap().quirks(), crate::Quirks::default());
+        assert_eq!(
+            load(&save(&chip8).unwrap()).unwrap().quirks(),
+            chip8.quirks()
+        );
+    }
+
+    #[test]
+    fn test_load_version_7_horizontal_wrap() {
+        let mut chip8 = Chip8::new();
+        chip8.set_quirks(crate::Quirks::XO_CHIP);
+        let bytes = encode_as(7, &chip8);
+
+        // sprites were always clipped at the right edge before version 8
+        let loaded = load(&bytes).unwrap();
+        assert!(!loaded.quirks().horizontal_wrap);
+        assert!(loaded.quirks().vertical_wrap);
+        let loaded = load(&save(&chip8).unwrap()).unwrap();
+        assert_eq!(loaded.quirks(), crate::Quirks::XO_CHIP);
+    }
+
+    #[test]
+    fn test_load_version_8_key_request() {
+        let mut chip8 = Chip8::new();
+        chip8.bus.input.request_key_press(0);
+        chip8.update_key_state(0x5, true);
+        let bytes = encode_as(8, &chip8);
+
+        // the key pressed before saving has to be pressed again
+        let loaded = load(&bytes).unwrap();
+        assert!(loaded.waiting_for_key());
+        assert_eq!(loaded.pending_key(), None);
+        assert_eq!(
+            load(&save(&chip8).unwrap()).unwrap().pending_key(),
+            Some(0x5)
+        );
+    }
+
+    #[test]
+    fn test_load_version_9_rpl_flags() {
+        let mut chip8 = Chip8::new();
+        chip8.set_rpl_flags([7; 16]);
+        let bytes = encode_as(9, &chip8);
+
+        // there were no flags before version 10
+        assert_eq!(load(&bytes).unwrap().rpl_flags(), [0; 16]);
+        assert_eq!(load(&save(&chip8).unwrap()).unwrap().rpl_flags(), [7; 16]);
+    }
+
+    #[test]
+    fn test_load_version_10_sound_timer() {
+        let mut chip8 = Chip8::new();
+        chip8.bus.clock.sound_timer = 30;
+        let bytes = encode_as(10, &chip8);
+
+        // the sound timer wasn't stored before version 11
+        assert_eq!(load(&bytes).unwrap().sound_timer(), 0);
+        assert_eq!(load(&save(&chip8).unwrap()).unwrap().sound_timer(), 30);
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            load(&bytes),
+            Err(StateError::UnsupportedVersion(version)) if version == VERSION + 1
+        ));
+    }
+}
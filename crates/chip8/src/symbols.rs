@@ -0,0 +1,142 @@
+//! Symbol maps, which name the addresses of a program with the labels of its source,
+//! so traces, disassemblies and breakpoints can show `draw_player` instead of `0x0346`.
+
+use std::{collections::BTreeMap, fmt};
+
+/// An error in a symbol file, with the line it occurred on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolError {
+    /// The line of the error, starting at 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for SymbolError {}
+
+/// The names of the addresses of a program.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Symbols {
+    names: BTreeMap<usize, String>,
+}
+
+impl Symbols {
+    /// Create an empty symbol map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a symbol file. Every line names one address, either as `<address> <name>`
+    /// like the `.sym` files of assemblers, or as `<name> = <address>` like the labels
+    /// exported by Octo. Addresses are hexadecimal, with or without a `0x` prefix,
+    /// and everything after a `#` or `;` is a comment.
+    pub fn parse(text: &str) -> Result<Self, SymbolError> {
+        let mut symbols = Self::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split(['#', ';']).next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: String| SymbolError {
+                line: index + 1,
+                message,
+            };
+            let (address, name) = match line.split_once('=') {
+                Some((name, address)) => (address.trim(), name.trim()),
+                None => match line.split_once(char::is_whitespace) {
+                    Some((address, name)) => (address, name.trim()),
+                    None => {
+                        return Err(error(format!("Expected an address and a name in `{line}`")))
+                    }
+                },
+            };
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(error(format!("Invalid name `{name}`")));
+            }
+            let digits = address
+                .strip_prefix("0x")
+                .or_else(|| address.strip_prefix("0X"))
+                .unwrap_or(address);
+            let address = usize::from_str_radix(digits, 16)
+                .map_err(|_| error(format!("Invalid address `{address}`")))?;
+            symbols.insert(address, name);
+        }
+        Ok(symbols)
+    }
+
+    /// Name the given address, replacing its previous name.
+    pub fn insert(&mut self, address: usize, name: impl Into<String>) {
+        self.names.insert(address, name.into());
+    }
+
+    /// The name of the given address, if it has one.
+    pub fn name(&self, address: usize) -> Option<&str> {
+        self.names.get(&address).map(String::as_str)
+    }
+
+    /// The address with the given name, if there is one.
+    pub fn address_of(&self, name: &str) -> Option<usize> {
+        self.names
+            .iter()
+            .find(|(_, symbol)| *symbol == name)
+            .map(|(&address, _)| address)
+    }
+
+    /// The named addresses, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.names
+            .iter()
+            .map(|(&address, name)| (address, name.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Format the address with its name if it has one, e.g. `draw_player (0x0346)`,
+    /// otherwise as hex like `0x0346`.
+    pub fn format_address(&self, address: usize) -> String {
+        match self.name(address) {
+            Some(name) => format!("{name} ({address:#06X})"),
+            None => format!("{address:#06X}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let symbols = Symbols::parse(
+            "# exported labels\n\
+             0x0200 main\n\
+             2a0 draw_player ; the player sprite routine\n\
+             \n\
+             score = 0x300\n",
+        )
+        .unwrap();
+        assert_eq!(symbols.len(), 3);
+        assert_eq!(symbols.name(0x200), Some("main"));
+        assert_eq!(symbols.name(0x2A0), Some("draw_player"));
+        assert_eq!(symbols.address_of("score"), Some(0x300));
+        assert_eq!(symbols.address_of("missing"), None);
+        assert_eq!(symbols.format_address(0x2A0), "draw_player (0x02A0)");
+        assert_eq!(symbols.format_address(0x202), "0x0202");
+
+        let error = Symbols::parse("0x200 main\n0x2G0 loop").unwrap_err();
+        assert_eq!(error.line, 2);
+        assert_eq!(error.message, "Invalid address `0x2G0`");
+        assert_eq!(Symbols::parse("main").unwrap_err().line, 1);
+    }
+}
@@ -0,0 +1,98 @@
+use std::fmt;
+
+use crate::Instr;
+
+/// The machine cycles the COSMAC VIP interpreter gets in a frame, the rest of the frame being
+/// taken by the display interrupt and the DMA transfer of the display.
+pub const VIP_CYCLES_PER_FRAME: u64 = 1760;
+
+/// The machine cycles the COSMAC VIP interpreter takes to fetch and decode an instruction.
+const FETCH_CYCLES: u32 = 40;
+
+/// The machine cycles of a step waiting for a key press, in which the keypad is polled once.
+pub(crate) const KEY_WAIT_CYCLES: u32 = FETCH_CYCLES;
+
+/// How the amount of steps executed in a frame is decided, see [`Chip8::run_frame`](crate::Chip8::run_frame).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Timing {
+    /// A flat amount of steps per frame, given in the [`FrameInput`](crate::FrameInput).
+    #[default]
+    Steps,
+
+    /// Every instruction takes the machine cycles it took on the COSMAC VIP, and a frame
+    /// ends once the [`VIP_CYCLES_PER_FRAME`] are consumed, so programs run at their
+    /// original speed.
+    CosmacVip,
+}
+
+impl Timing {
+    /// All timings, in the order they should be listed.
+    pub const ALL: [Timing; 2] = [Timing::Steps, Timing::CosmacVip];
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Timing::Steps => write!(f, "Steps per frame"),
+            Timing::CosmacVip => write!(f, "COSMAC VIP cycles"),
+        }
+    }
+}
+
+/// The machine cycles the COSMAC VIP interpreter takes to execute `instr`, including the fetch,
+/// where `skipped` tells whether a conditional skip was taken. The costs are approximations of
+/// the paths through the original interpreter; instructions it doesn't have only cost the fetch.
+pub(crate) fn vip_cycles(instr: Instr, skipped: bool) -> u32 {
+    let skip = if skipped { 4 } else { 0 };
+    let execute = match instr {
+        // every byte of the display is cleared
+        Instr::Clear => 3078,
+        Instr::Return => 10,
+        Instr::Jump(_) => 12,
+        Instr::Call(_) => 26,
+        Instr::SkipIfEqual { .. } | Instr::SkipIfNotEqual { .. } => 10 + skip,
+        Instr::SkipIfEqualReg { .. } | Instr::SkipIfNotEqualReg { .. } => 14 + skip,
+        Instr::Set { .. } => 6,
+        Instr::Add { .. } => 10,
+        Instr::SetReg { .. }
+        | Instr::Or { .. }
+        | Instr::And { .. }
+        | Instr::Xor { .. }
+        | Instr::AddReg { .. }
+        | Instr::SubReg { .. }
+        | Instr::ShiftRight { .. }
+        | Instr::SubRegReversed { .. }
+        | Instr::ShiftLeft { .. } => 44,
+        Instr::SetIndex(_) => 12,
+        Instr::JumpOffset { .. } => 22,
+        Instr::Random { .. } => 36,
+        // the rows are shifted into place and drawn one after another
+        Instr::Draw { n, .. } => 68 + 46 * n as u32,
+        Instr::SkipIfKey(_) | Instr::SkipIfNotKey(_) => 14 + skip,
+        Instr::GetDelay(_) | Instr::SetDelay(_) | Instr::SetSound(_) => 10,
+        Instr::WaitKey(_) => 18,
+        Instr::AddIndex(_) => 16,
+        Instr::Digit(_) => 16,
+        Instr::Bcd(_) => 84,
+        Instr::Store(x) | Instr::Load(x) => 14 + 14 * (x as u32 + 1),
+        Instr::ClearHires
+        | Instr::ScrollDown(_)
+        | Instr::ScrollUp(_)
+        | Instr::ScrollRight
+        | Instr::ScrollLeft
+        | Instr::Exit
+        | Instr::LowRes
+        | Instr::HighRes
+        | Instr::MachineCode(_)
+        | Instr::StoreRange { .. }
+        | Instr::LoadRange { .. }
+        | Instr::SetLongIndex
+        | Instr::SelectPlanes(_)
+        | Instr::BigDigit(_)
+        | Instr::SaveFlags(_)
+        | Instr::LoadFlags(_)
+        | Instr::Invalid => 0,
+    };
+    FETCH_CYCLES + execute
+}
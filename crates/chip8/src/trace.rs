@@ -0,0 +1,57 @@
+use std::io::{self, BufWriter, Write};
+
+use crate::processor::Processor;
+
+/// The registers of the `Processor` before an instruction, to find the ones it changed.
+pub(crate) struct Registers {
+    v: [u8; 16],
+    i: usize,
+}
+
+impl Registers {
+    pub fn of(processor: &Processor) -> Self {
+        Self {
+            v: processor.v,
+            i: processor.i,
+        }
+    }
+}
+
+/// Writes every executed instruction to a sink, see
+/// [`Chip8::set_trace_writer`](crate::Chip8::set_trace_writer).
+pub(crate) struct TraceWriter {
+    writer: BufWriter<Box<dyn Write + Send>>,
+}
+
+impl TraceWriter {
+    pub fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            writer: BufWriter::new(writer),
+        }
+    }
+
+    /// Write the line of the instruction at `address` with the given `opcode`: the address,
+    /// the opcode, then the registers it changed with their new values, e.g. `0204 7001 V0=02`.
+    pub fn write(
+        &mut self,
+        address: usize,
+        opcode: usize,
+        before: &Registers,
+        processor: &Processor,
+    ) -> io::Result<()> {
+        write!(self.writer, "{address:04X} {opcode:04X}")?;
+        for (x, (old, new)) in before.v.iter().zip(processor.v).enumerate() {
+            if *old != new {
+                write!(self.writer, " V{x:X}={new:02X}")?;
+            }
+        }
+        if before.i != processor.i {
+            write!(self.writer, " I={:04X}", processor.i)?;
+        }
+        writeln!(self.writer)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
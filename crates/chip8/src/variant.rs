@@ -0,0 +1,83 @@
+use std::fmt;
+
+use crate::{graphics, memory::MemorySize};
+
+/// A variant of the CHIP-8 platform. Variants differ in the instructions
+/// they support, and in the behavior of some shared instructions.
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Variant {
+    /// The original CHIP-8 interpreter for the COSMAC VIP.
+    #[default]
+    Chip8,
+
+    /// SUPER-CHIP 1.1 for the HP 48 calculators, which adds a 128x64 hi-res mode.
+    SuperChip,
+
+    /// XO-CHIP, the extension of SUPER-CHIP by Octo, which adds 64KB of memory
+    /// and a second display plane for four colors.
+    XoChip,
+
+    /// The two-page hi-res CHIP-8 interpreter for the COSMAC VIP, which has a 64x64 display.
+    /// Its programs start with a jump to 0x260, which enters them at 0x2C0 instead.
+    HiresChip8,
+}
+
+impl Variant {
+    /// All variants, in the order they should be listed.
+    pub const ALL: [Variant; 4] = [
+        Variant::Chip8,
+        Variant::HiresChip8,
+        Variant::SuperChip,
+        Variant::XoChip,
+    ];
+
+    /// Returns whether the variant has the SUPER-CHIP hi-res mode and large font.
+    pub fn has_hires(self) -> bool {
+        match self {
+            Variant::Chip8 | Variant::HiresChip8 => false,
+            Variant::SuperChip | Variant::XoChip => true,
+        }
+    }
+
+    /// The width and height of the display the variant starts with.
+    pub fn display_size(self) -> (usize, usize) {
+        match self {
+            Variant::HiresChip8 => (graphics::TWO_PAGE_WIDTH, graphics::TWO_PAGE_HEIGHT),
+            Variant::Chip8 | Variant::SuperChip | Variant::XoChip => {
+                (graphics::WIDTH, graphics::HEIGHT)
+            }
+        }
+    }
+
+    /// The size of the memory the variant starts with.
+    pub fn memory_size(self) -> MemorySize {
+        match self {
+            Variant::Chip8 | Variant::HiresChip8 | Variant::SuperChip => MemorySize::Standard,
+            Variant::XoChip => MemorySize::Extended,
+        }
+    }
+
+    /// Returns whether the display is cleared when switching between lo-res and hi-res mode.
+    ///
+    /// SUPER-CHIP 1.1 draws lo-res pixels at double size into a single hi-res buffer,
+    /// so the contents of the display are kept when switching. XO-CHIP clears all planes.
+    pub fn clears_on_resolution_switch(self) -> bool {
+        match self {
+            Variant::Chip8 | Variant::HiresChip8 | Variant::SuperChip => false,
+            Variant::XoChip => true,
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Variant::Chip8 => write!(f, "CHIP-8"),
+            Variant::SuperChip => write!(f, "SUPER-CHIP"),
+            Variant::XoChip => write!(f, "XO-CHIP"),
+            Variant::HiresChip8 => write!(f, "HI-RES CHIP-8"),
+        }
+    }
+}
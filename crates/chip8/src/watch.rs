@@ -0,0 +1,407 @@
+//! Watch expressions, small expressions over the state of a [`Chip8`] like `V3 + V4`,
+//! `mem[I+2]` or `stack[sp-1]` that are evaluated while a program runs.
+
+use std::fmt;
+
+use crate::{breakpoint::Operand, Chip8};
+
+/// An error in the text of an expression, with the position it occurred at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// The position of the error in characters, starting at 0.
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {}: {}", self.position + 1, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// An error evaluating an expression with the current state of a [`Chip8`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvalError {
+    /// A read of `mem[address]` past the end of memory.
+    MemoryOutOfBounds(usize),
+
+    /// A read of `stack[index]` past the return addresses on the stack.
+    StackOutOfBounds(usize),
+
+    /// A division or remainder by zero.
+    DivisionByZero,
+
+    /// A result that is negative or doesn't fit.
+    Overflow,
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::MemoryOutOfBounds(address) => {
+                write!(f, "Address {address:#06X} is out of bounds")
+            }
+            EvalError::StackOutOfBounds(index) => {
+                write!(f, "Stack index {index} is out of bounds")
+            }
+            EvalError::DivisionByZero => write!(f, "Division by zero"),
+            EvalError::Overflow => write!(f, "Overflow"),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// A binary operator of an [`Expr`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr,
+}
+
+impl BinaryOp {
+    /// The operators, from the lowest to the highest precedence.
+    const PRECEDENCE: [&'static [BinaryOp]; 5] = [
+        &[BinaryOp::Or, BinaryOp::Xor],
+        &[BinaryOp::And],
+        &[BinaryOp::Shl, BinaryOp::Shr],
+        &[BinaryOp::Add, BinaryOp::Sub],
+        &[BinaryOp::Mul, BinaryOp::Div, BinaryOp::Rem],
+    ];
+
+    fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+            BinaryOp::And => "&",
+            BinaryOp::Or => "|",
+            BinaryOp::Xor => "^",
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
+        }
+    }
+
+    fn apply(self, left: usize, right: usize) -> Result<usize, EvalError> {
+        let result = match self {
+            BinaryOp::Add => left.checked_add(right),
+            BinaryOp::Sub => left.checked_sub(right),
+            BinaryOp::Mul => left.checked_mul(right),
+            BinaryOp::Div => Some(left.checked_div(right).ok_or(EvalError::DivisionByZero)?),
+            BinaryOp::Rem => Some(left.checked_rem(right).ok_or(EvalError::DivisionByZero)?),
+            BinaryOp::And => Some(left & right),
+            BinaryOp::Or => Some(left | right),
+            BinaryOp::Xor => Some(left ^ right),
+            BinaryOp::Shl => u32::try_from(right)
+                .ok()
+                .and_then(|right| left.checked_shl(right)),
+            BinaryOp::Shr => u32::try_from(right)
+                .ok()
+                .and_then(|right| left.checked_shr(right)),
+        };
+        result.ok_or(EvalError::Overflow)
+    }
+}
+
+/// An expression over the state of a [`Chip8`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    /// A number, written in decimal or in hex with a `0x` prefix.
+    Number(usize),
+
+    /// A register or timer, e.g. `V3` or `DT`.
+    Operand(Operand),
+
+    /// The program counter, `PC`.
+    Pc,
+
+    /// The amount of return addresses on the stack, `SP`.
+    Sp,
+
+    /// The byte of memory at an address, `mem[...]`.
+    Memory(Box<Expr>),
+
+    /// A return address on the stack, `stack[...]`, the first call at index 0.
+    Stack(Box<Expr>),
+
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Parse an expression. Names are case-insensitive, and the operators are those of
+    /// Rust, with their precedence: `* / %`, `+ -`, `<< >>`, `&`, then `^ |`.
+    pub fn parse(text: &str) -> Result<Expr, ParseError> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            position: 0,
+        };
+        let expr = parser.expression(0)?;
+        parser.skip_whitespace();
+        if parser.position < parser.chars.len() {
+            return parser.error("Unexpected text after the expression");
+        }
+        Ok(expr)
+    }
+
+    /// Evaluate the expression with the current state of the `chip8`.
+    pub fn evaluate(&self, chip8: &Chip8) -> Result<usize, EvalError> {
+        match self {
+            Expr::Number(value) => Ok(*value),
+            Expr::Operand(operand) => Ok(operand.value(chip8)),
+            Expr::Pc => Ok(chip8.pc()),
+            Expr::Sp => Ok(chip8.stack().len()),
+            Expr::Memory(address) => {
+                let address = address.evaluate(chip8)?;
+                match chip8.memory().get(address) {
+                    Some(&value) => Ok(usize::from(value)),
+                    None => Err(EvalError::MemoryOutOfBounds(address)),
+                }
+            }
+            Expr::Stack(index) => {
+                let index = index.evaluate(chip8)?;
+                match chip8.stack().get(index) {
+                    Some(&address) => Ok(address),
+                    None => Err(EvalError::StackOutOfBounds(index)),
+                }
+            }
+            Expr::Binary(op, left, right) => {
+                op.apply(left.evaluate(chip8)?, right.evaluate(chip8)?)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Number(value) => write!(f, "{value:#X}"),
+            Expr::Operand(operand) => write!(f, "{operand}"),
+            Expr::Pc => write!(f, "PC"),
+            Expr::Sp => write!(f, "SP"),
+            Expr::Memory(address) => write!(f, "mem[{address}]"),
+            Expr::Stack(index) => write!(f, "stack[{index}]"),
+            Expr::Binary(op, left, right) => write!(f, "({left} {} {right})", op.symbol()),
+        }
+    }
+}
+
+/// A recursive descent parser of [`Expr`]s.
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, ParseError> {
+        Err(ParseError {
+            position: self.position,
+            message: message.into(),
+        })
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.chars.get(self.position), Some(c) if c.is_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    /// Consume the given text after any whitespace, returning whether it was there.
+    fn eat(&mut self, text: &str) -> bool {
+        self.skip_whitespace();
+        let end = self.position + text.chars().count();
+        let matches = matches!(
+            self.chars.get(self.position..end),
+            Some(chars) if chars.iter().copied().eq(text.chars())
+        );
+        if matches {
+            self.position = end;
+        }
+        matches
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), ParseError> {
+        if self.eat(text) {
+            Ok(())
+        } else {
+            self.error(format!("Expected `{text}`"))
+        }
+    }
+
+    /// Parse the operators of the given level of [`BinaryOp::PRECEDENCE`] and higher.
+    fn expression(&mut self, level: usize) -> Result<Expr, ParseError> {
+        let operators = match BinaryOp::PRECEDENCE.get(level) {
+            Some(operators) => operators,
+            None => return self.operand(),
+        };
+        let mut left = self.expression(level + 1)?;
+        'outer: loop {
+            for &op in *operators {
+                if self.eat(op.symbol()) {
+                    let right = self.expression(level + 1)?;
+                    left = Expr::Binary(op, Box::new(left), Box::new(right));
+                    continue 'outer;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    /// Parse a number, a name, an indexing like `mem[...]`, or an expression in parentheses.
+    fn operand(&mut self) -> Result<Expr, ParseError> {
+        if self.eat("(") {
+            let expr = self.expression(0)?;
+            self.expect(")")?;
+            return Ok(expr);
+        }
+        self.skip_whitespace();
+        let start = self.position;
+        while matches!(
+            self.chars.get(self.position),
+            Some(c) if c.is_ascii_alphanumeric() || *c == '_'
+        ) {
+            self.position += 1;
+        }
+        let word: String = self.chars[start..self.position].iter().collect();
+        let word = word.to_ascii_uppercase();
+        let indexed = |parser: &mut Parser| -> Result<Box<Expr>, ParseError> {
+            parser.expect("[")?;
+            let index = parser.expression(0)?;
+            parser.expect("]")?;
+            Ok(Box::new(index))
+        };
+        let expr = match word.as_str() {
+            "" => return self.error("Expected a number, a name or `(`"),
+            "I" => Expr::Operand(Operand::I),
+            "DT" => Expr::Operand(Operand::DelayTimer),
+            "ST" => Expr::Operand(Operand::SoundTimer),
+            "PC" => Expr::Pc,
+            "SP" => Expr::Sp,
+            "MEM" => Expr::Memory(indexed(self)?),
+            "STACK" => Expr::Stack(indexed(self)?),
+            _ => {
+                let register = word
+                    .strip_prefix('V')
+                    .filter(|digit| digit.len() == 1)
+                    .and_then(|digit| usize::from_str_radix(digit, 16).ok());
+                let number = match word.strip_prefix("0X") {
+                    Some(digits) => usize::from_str_radix(digits, 16).ok(),
+                    None => word.parse().ok(),
+                };
+                match (register, number) {
+                    (Some(x), _) => Expr::Operand(Operand::V(x)),
+                    (None, Some(value)) => Expr::Number(value),
+                    (None, None) => {
+                        self.position = start;
+                        return self.error(format!("Unknown name `{word}`"));
+                    }
+                }
+            }
+        };
+        Ok(expr)
+    }
+}
+
+/// A named expression whose value is shown while a program runs, see [`Expr`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watch {
+    pub name: String,
+    pub expr: Expr,
+}
+
+impl Watch {
+    /// Create a watch of the given expression text, named after the text itself
+    /// unless it is given like `name = expression`.
+    pub fn parse(text: &str) -> Result<Watch, ParseError> {
+        match text.split_once('=') {
+            Some((name, expression)) => {
+                let offset = name.chars().count() + 1;
+                let expr = Expr::parse(expression).map_err(|e| ParseError {
+                    position: e.position + offset,
+                    ..e
+                })?;
+                Ok(Watch {
+                    name: name.trim().to_owned(),
+                    expr,
+                })
+            }
+            None => Ok(Watch {
+                name: text.trim().to_owned(),
+                expr: Expr::parse(text)?,
+            }),
+        }
+    }
+
+    /// Evaluate the expression with the current state of the `chip8`.
+    pub fn evaluate(&self, chip8: &Chip8) -> Result<usize, EvalError> {
+        self.expr.evaluate(chip8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let expr = Expr::parse("v3 + V4 * 2 - mem[I+0x2] << 1 & stack[sp - 1]").unwrap();
+        assert_eq!(
+            expr.to_string(),
+            "((((V3 + (V4 * 0x2)) - mem[(I + 0x2)]) << 0x1) & stack[(SP - 0x1)])"
+        );
+        assert_eq!(
+            Expr::parse("(1 + 2) * 3").unwrap().to_string(),
+            "((0x1 + 0x2) * 0x3)"
+        );
+
+        let error = Expr::parse("V3 + VG").unwrap_err();
+        assert_eq!(
+            (error.position, error.message.as_str()),
+            (5, "Unknown name `VG`")
+        );
+        assert_eq!(Expr::parse("mem[I").unwrap_err().message, "Expected `]`");
+        assert_eq!(Expr::parse("1 2").unwrap_err().position, 2);
+
+        let watch = Watch::parse("lives = mem[0x300]").unwrap();
+        assert_eq!(watch.name, "lives");
+        assert_eq!(watch.expr, Expr::Memory(Box::new(Expr::Number(0x300))));
+        assert_eq!(Watch::parse("V3 + 1").unwrap().name, "V3 + 1");
+        assert_eq!(Watch::parse("x = V").unwrap_err().position, 4);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let mut chip8 = Chip8::new();
+        // V3 = 5, V4 = 7, I = 0x204, call the subroutine
+        chip8
+            .load_rom_data(vec![
+                0x63, 0x05, 0x64, 0x07, 0xA2, 0x04, 0x22, 0x0A, 0x00, 0x00, 0x00, 0xEE,
+            ])
+            .unwrap();
+        chip8.run_until(4, |_| false);
+        let evaluate = |text: &str| Expr::parse(text).unwrap().evaluate(&chip8);
+
+        assert_eq!(evaluate("V3 + V4"), Ok(12));
+        assert_eq!(evaluate("mem[I+1]"), Ok(0x04));
+        assert_eq!(evaluate("stack[sp-1]"), Ok(0x208));
+        assert_eq!(evaluate("PC"), Ok(0x20A));
+        assert_eq!(evaluate("V4 / (V3 - 5)"), Err(EvalError::DivisionByZero));
+        assert_eq!(evaluate("V3 - V4"), Err(EvalError::Overflow));
+        assert_eq!(evaluate("stack[1]"), Err(EvalError::StackOutOfBounds(1)));
+        assert_eq!(
+            evaluate("mem[0x10000]"),
+            Err(EvalError::MemoryOutOfBounds(0x10000))
+        );
+    }
+}
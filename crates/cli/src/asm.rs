@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Context};
+
+use crate::disasm::ROM_START;
+
+/// An operand of an instruction.
+enum Operand<'a> {
+    /// A `Vx` register.
+    V(u16),
+    /// The index register `I`.
+    I,
+    /// The memory `I` points to, `[I]`.
+    IndirectI,
+    /// The delay timer.
+    Dt,
+    /// The sound timer.
+    St,
+    /// A key press.
+    K,
+    /// The location of a font digit.
+    F,
+    /// The location of a big font digit.
+    Hf,
+    /// The BCD representation of a register.
+    B,
+    /// The RPL user flags.
+    R,
+    /// A number or a label.
+    Value(&'a str),
+}
+
+impl<'a> Operand<'a> {
+    fn parse(text: &'a str) -> Self {
+        match text.to_ascii_uppercase().as_str() {
+            "I" => Operand::I,
+            "[I]" => Operand::IndirectI,
+            "DT" => Operand::Dt,
+            "ST" => Operand::St,
+            "K" => Operand::K,
+            "F" => Operand::F,
+            "HF" => Operand::Hf,
+            "B" => Operand::B,
+            "R" => Operand::R,
+            register => match register
+                .strip_prefix('V')
+                .map(|x| u16::from_str_radix(x, 16))
+            {
+                Some(Ok(x)) if x < 16 => Operand::V(x),
+                _ => Operand::Value(text),
+            },
+        }
+    }
+}
+
+/// A line of source containing an instruction or data.
+struct Line<'a> {
+    number: usize,
+    mnemonic: String,
+    operands: Vec<&'a str>,
+}
+
+/// Assemble the given source into a ROM.
+///
+/// The source uses the mnemonics printed by the disassembler, one instruction per line.
+/// Lines may start with a `label:`, which can be used in place of an address, and
+/// anything after a `;` is a comment. Raw bytes are written with `DB 0x12, 0x34`.
+pub fn assemble(source: &str) -> anyhow::Result<Vec<u8>> {
+    let mut labels = HashMap::new();
+    let mut lines = Vec::new();
+    let mut address = ROM_START;
+    for (index, line) in source.lines().enumerate() {
+        let number = index + 1;
+        let mut line = line.split(';').next().unwrap_or_default().trim();
+        if let Some((label, rest)) = line.split_once(':') {
+            let label = label.trim();
+            if !is_identifier(label) {
+                bail!("line {number}: invalid label `{label}`");
+            }
+            if labels.insert(label, address).is_some() {
+                bail!("line {number}: duplicate label `{label}`");
+            }
+            line = rest.trim();
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, operands) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        let operands: Vec<&str> = operands
+            .split(',')
+            .map(str::trim)
+            .filter(|operand| !operand.is_empty())
+            .collect();
+        address += if mnemonic == "DB" { operands.len() } else { 2 };
+        lines.push(Line {
+            number,
+            mnemonic,
+            operands,
+        });
+    }
+
+    let mut rom = Vec::new();
+    for line in lines {
+        let context = || format!("line {}", line.number);
+        if line.mnemonic == "DB" {
+            for operand in &line.operands {
+                let byte = value(operand, 0xFF, &labels).with_context(context)?;
+                rom.push(byte as u8);
+            }
+        } else {
+            let opcode = encode(&line.mnemonic, &line.operands, &labels).with_context(context)?;
+            rom.extend(opcode.to_be_bytes());
+        }
+    }
+    Ok(rom)
+}
+
+/// Encode a single instruction into its opcode.
+fn encode(mnemonic: &str, operands: &[&str], labels: &HashMap<&str, usize>) -> anyhow::Result<u16> {
+    let parsed: Vec<Operand> = operands
+        .iter()
+        .map(|operand| Operand::parse(operand))
+        .collect();
+    let address = |text| value(text, 0xFFF, labels);
+    let byte = |text| value(text, 0xFF, labels);
+
+    use Operand::*;
+    let opcode = match (mnemonic, parsed.as_slice()) {
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("SCD", [Value(n)]) => 0x00C0 | value(n, 0xF, labels)?,
+        ("SCR", []) => 0x00FB,
+        ("SCL", []) => 0x00FC,
+        ("EXIT", []) => 0x00FD,
+        ("LOW", []) => 0x00FE,
+        ("HIGH", []) => 0x00FF,
+        ("SYS", [Value(a)]) => address(a)?,
+        ("JP", [Value(a)]) => 0x1000 | address(a)?,
+        ("JP", [V(0), Value(a)]) => 0xB000 | address(a)?,
+        ("CALL", [Value(a)]) => 0x2000 | address(a)?,
+        ("SE", [V(x), Value(b)]) => 0x3000 | x << 8 | byte(b)?,
+        ("SNE", [V(x), Value(b)]) => 0x4000 | x << 8 | byte(b)?,
+        ("SE", [V(x), V(y)]) => 0x5000 | x << 8 | y << 4,
+        ("LD", [V(x), Value(b)]) => 0x6000 | x << 8 | byte(b)?,
+        ("ADD", [V(x), Value(b)]) => 0x7000 | x << 8 | byte(b)?,
+        (operation, [V(x), V(y)]) if operation != "SE" && operation != "SNE" => {
+            let n = match operation {
+                "LD" => 0x0,
+                "OR" => 0x1,
+                "AND" => 0x2,
+                "XOR" => 0x3,
+                "ADD" => 0x4,
+                "SUB" => 0x5,
+                "SHR" => 0x6,
+                "SUBN" => 0x7,
+                "SHL" => 0xE,
+                _ => bail!("unknown instruction `{mnemonic}`"),
+            };
+            0x8000 | x << 8 | y << 4 | n
+        }
+        ("SNE", [V(x), V(y)]) => 0x9000 | x << 8 | y << 4,
+        ("LD", [I, Value(a)]) => 0xA000 | address(a)?,
+        ("RND", [V(x), Value(b)]) => 0xC000 | x << 8 | byte(b)?,
+        ("DRW", [V(x), V(y), Value(n)]) => 0xD000 | x << 8 | y << 4 | value(n, 0xF, labels)?,
+        ("SKP", [V(x)]) => 0xE09E | x << 8,
+        ("SKNP", [V(x)]) => 0xE0A1 | x << 8,
+        ("LD", [V(x), Dt]) => 0xF007 | x << 8,
+        ("LD", [V(x), K]) => 0xF00A | x << 8,
+        ("LD", [Dt, V(x)]) => 0xF015 | x << 8,
+        ("LD", [St, V(x)]) => 0xF018 | x << 8,
+        ("ADD", [I, V(x)]) => 0xF01E | x << 8,
+        ("LD", [F, V(x)]) => 0xF029 | x << 8,
+        ("LD", [Hf, V(x)]) => 0xF030 | x << 8,
+        ("LD", [B, V(x)]) => 0xF033 | x << 8,
+        ("LD", [IndirectI, V(x)]) => 0xF055 | x << 8,
+        ("LD", [V(x), IndirectI]) => 0xF065 | x << 8,
+        ("LD", [R, V(x)]) => 0xF075 | x << 8,
+        ("LD", [V(x), R]) => 0xF085 | x << 8,
+        _ => bail!("invalid instruction `{mnemonic} {}`", operands.join(", ")),
+    };
+    Ok(opcode)
+}
+
+/// Parse a number (`18`, `0x12`, `#12` or `0b10010`) or a label, which must be at most `max`.
+fn value(text: &str, max: u16, labels: &HashMap<&str, usize>) -> anyhow::Result<u16> {
+    let number = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix('#')) {
+        usize::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = text.strip_prefix("0b") {
+        usize::from_str_radix(binary, 2).ok()
+    } else {
+        text.parse().ok()
+    };
+    let value = match number {
+        Some(number) => number,
+        None if is_identifier(text) => *labels
+            .get(text)
+            .with_context(|| format!("unknown label `{text}`"))?,
+        None => bail!("invalid number `{text}`"),
+    };
+    match u16::try_from(value) {
+        Ok(value) if value <= max => Ok(value),
+        _ => bail!("`{text}` is larger than {max:#X}"),
+    }
+}
+
+/// Returns whether `text` can be used as a label.
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8::{disasm::disassemble, Symbols};
+
+    #[test]
+    fn test_disassembly_round_trip() {
+        for opcode in 0..=u16::MAX {
+            if let Some(mnemonic) = disassemble(opcode, &Symbols::new()) {
+                let rom = assemble(&mnemonic).unwrap();
+                assert_eq!(rom, opcode.to_be_bytes(), "{mnemonic}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_disassembly_with_symbols() {
+        let mut symbols = Symbols::new();
+        symbols.insert(0x204, "draw");
+        let mnemonic = disassemble(0x2204, &symbols).unwrap();
+        assert_eq!(mnemonic, "CALL draw");
+        let rom = assemble(&format!("{mnemonic}\nJP 0x200\ndraw: RET")).unwrap();
+        assert_eq!(rom, [0x22, 0x04, 0x12, 0x00, 0x00, 0xEE]);
+    }
+
+    #[test]
+    fn test_labels_and_data() {
+        let source = "\
+            start: LD I, sprite ; point at the sprite
+                   DRW V0, V1, 2
+            loop:  JP loop
+            sprite:
+                   DB 0x3C, #FF";
+        let rom = assemble(source).unwrap();
+        assert_eq!(rom, [0xA2, 0x06, 0xD0, 0x12, 0x12, 0x04, 0x3C, 0xFF]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(assemble("JP nowhere").is_err());
+        assert!(assemble("LD V0, 0x100").is_err());
+        assert!(assemble("FOO V0").is_err());
+        assert!(assemble("a:\na: CLS").is_err());
+    }
+}
@@ -0,0 +1,37 @@
+use chip8::{disasm, Symbols};
+
+pub use chip8::rom::START as ROM_START;
+
+/// Disassemble a ROM linearly, two bytes at a time, into lines of the form
+/// `0x0200  6012  LD V0, 0x12`. Words that aren't instructions are shown as data (`DB`).
+///
+/// With `explain`, instructions are followed by a comment with the summary from the
+/// [opcode reference](chip8::reference). The addresses named in the `symbols` are
+/// preceded by a `name:` line, and their name is used in place of the address.
+///
+/// With `follow`, only the instructions reached by following the control flow from the
+/// start are disassembled, see [`disasm::find_code`], and the other bytes are shown as data.
+pub fn disassemble_rom(rom: &[u8], explain: bool, follow: bool, symbols: &Symbols) -> Vec<String> {
+    let disassembly = if follow {
+        disasm::disassemble_program(rom, ROM_START, symbols)
+    } else {
+        disasm::disassemble_rom(rom, ROM_START, symbols)
+    };
+    let mut lines = Vec::new();
+    for line in disassembly {
+        if let Some(name) = symbols.name(line.address) {
+            lines.push(format!("{name}:"));
+        }
+        match (line.opcode(), &line.mnemonic) {
+            (Some(opcode), Some(mnemonic)) if explain => {
+                let summary = chip8::reference::lookup(opcode).map_or("", |entry| entry.summary);
+                lines.push(format!(
+                    "{:#06X}  {opcode:04X}  {mnemonic:<20}; {summary}",
+                    line.address
+                ));
+            }
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
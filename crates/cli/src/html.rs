@@ -0,0 +1,172 @@
+//! Exports a ROM as a single self-contained HTML page, which runs it in
+//! the web build of the interpreter (the `chip8-web` crate).
+
+/// The page, with `{{...}}` placeholders for the title, configuration, and the
+/// base64 encoded web build and ROM.
+const TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{{TITLE}}</title>
+<style>
+  html, body { margin: 0; height: 100%; background: #{{BACKGROUND}}; }
+  body { display: flex; align-items: center; justify-content: center; }
+  canvas { width: 100vw; max-height: 100vh; object-fit: contain; image-rendering: pixelated; }
+</style>
+</head>
+<body>
+<canvas id="screen" width="64" height="32"></canvas>
+<script type="module">
+const config = {{CONFIG}};
+const decode = (base64) => Uint8Array.from(atob(base64), (c) => c.charCodeAt(0));
+
+// keys of the modern `1234`/`QWER` layout, indexed by CHIP-8 key code
+const layout = ["KeyX", "Digit1", "Digit2", "Digit3", "KeyQ", "KeyW", "KeyE", "KeyA",
+                "KeyS", "KeyD", "KeyZ", "KeyC", "Digit4", "KeyR", "KeyF", "KeyV"];
+let keys = 0;
+const onKey = (pressed) => (event) => {
+  const key = layout.indexOf(event.code);
+  if (key < 0) return;
+  keys = pressed ? keys | (1 << key) : keys & ~(1 << key);
+  event.preventDefault();
+};
+addEventListener("keydown", onKey(true));
+addEventListener("keyup", onKey(false));
+
+const { instance } = await WebAssembly.instantiate(decode("{{WASM}}"), {
+  env: { chip8_now: () => performance.now() },
+});
+const wasm = instance.exports;
+const rom = decode("{{ROM}}");
+new Uint8Array(wasm.memory.buffer, wasm.rom_buffer(rom.length), rom.length).set(rom);
+wasm.seed(crypto.getRandomValues(new Uint32Array(1))[0]);
+wasm.start(config.variant, config.quirks);
+
+const canvas = document.getElementById("screen");
+const context = canvas.getContext("2d");
+const colors = [config.background, config.foreground];
+
+// a square wave tone, audible while the sound timer is active
+let audio = null;
+const beep = (on) => {
+  if (!audio && on) {
+    const context = new AudioContext();
+    const oscillator = context.createOscillator();
+    const gain = context.createGain();
+    oscillator.type = "square";
+    oscillator.frequency.value = 440;
+    gain.gain.value = 0;
+    oscillator.connect(gain).connect(context.destination);
+    oscillator.start();
+    audio = { context, gain };
+  }
+  if (audio) audio.gain.gain.value = on ? 0.1 : 0;
+};
+
+const frame = () => {
+  const sound = wasm.run_frame(config.steps, keys);
+  const width = wasm.display_width();
+  const height = wasm.display_height();
+  if (canvas.width !== width || canvas.height !== height) {
+    canvas.width = width;
+    canvas.height = height;
+  }
+  const pixels = new Uint8Array(wasm.memory.buffer, wasm.display(), width * height);
+  const image = context.createImageData(width, height);
+  pixels.forEach((pixel, i) => {
+    image.data.set(colors[pixel], i * 4);
+    image.data[i * 4 + 3] = 255;
+  });
+  context.putImageData(image, 0, 0);
+  beep(sound !== 0);
+  requestAnimationFrame(frame);
+};
+requestAnimationFrame(frame);
+</script>
+</body>
+</html>
+"#;
+
+/// How the exported page runs the ROM.
+pub struct Config {
+    /// The title of the page.
+    pub title: String,
+
+    /// The variant, as understood by `chip8-web`: `0` for CHIP-8, `1` for SUPER-CHIP,
+    /// `2` for XO-CHIP, `3` for two-page hi-res CHIP-8.
+    pub variant: u32,
+
+    /// The quirk flags, as understood by `chip8-web`.
+    pub quirks: u32,
+
+    /// The amount of steps executed per frame.
+    pub steps_per_frame: u32,
+
+    pub foreground: [u8; 3],
+    pub background: [u8; 3],
+}
+
+/// Build the page running the `rom` with the given `wasm` build of `chip8-web`.
+pub fn export(wasm: &[u8], rom: &[u8], config: &Config) -> String {
+    let rgb = |[r, g, b]: [u8; 3]| format!("[{r}, {g}, {b}]");
+    let settings = format!(
+        "{{ variant: {}, quirks: {}, steps: {}, foreground: {}, background: {} }}",
+        config.variant,
+        config.quirks,
+        config.steps_per_frame,
+        rgb(config.foreground),
+        rgb(config.background),
+    );
+    let [r, g, b] = config.background;
+    TEMPLATE
+        .replace("{{TITLE}}", &escape(&config.title))
+        .replace("{{BACKGROUND}}", &format!("{r:02x}{g:02x}{b:02x}"))
+        .replace("{{CONFIG}}", &settings)
+        .replace("{{WASM}}", &base64(wasm))
+        .replace("{{ROM}}", &base64(rom))
+}
+
+/// Escape the characters of `text` that have a special meaning in HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Encode `data` with the standard base64 alphabet, with padding.
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(char::from(ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(&[0xFF, 0xEE, 0x00]), "/+4A");
+    }
+}
@@ -0,0 +1,828 @@
+//! A command line companion of the interpreter, making the functionality
+//! that doesn't need a display available without the GUI.
+
+mod asm;
+mod disasm;
+mod html;
+mod png;
+
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use chip8::{
+    batch::{self, BatchRom},
+    graphics::{HIRES_HEIGHT, HIRES_WIDTH},
+    sprites::{self, Sprite},
+    Chip8, Deterministic, Quirks, Symbols, Variant,
+};
+use clap::{Args, Parser, Subcommand};
+
+#[derive(Parser)]
+#[command(name = "chip8-cli", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a ROM without a display, then print the final screen.
+    Run(RunArgs),
+
+    /// Disassemble a ROM into addressed mnemonics.
+    Disasm {
+        rom: PathBuf,
+
+        /// Explain each instruction in a comment, using the opcode reference.
+        #[arg(long)]
+        explain: bool,
+
+        /// Follow the control flow from the start to tell code apart from data like sprites.
+        #[arg(long)]
+        follow: bool,
+
+        /// Name the addresses with the labels of a symbol file, e.g. one exported by Octo.
+        #[arg(long)]
+        symbols: Option<PathBuf>,
+    },
+
+    /// Export the control flow graph of a ROM, found by following its jumps, calls and skips,
+    /// in the DOT format of Graphviz.
+    Cfg {
+        rom: PathBuf,
+
+        /// Write the graph to a file instead of printing it.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Name the addresses with the labels of a symbol file, e.g. one exported by Octo.
+        #[arg(long)]
+        symbols: Option<PathBuf>,
+    },
+
+    /// Assemble a source file into a ROM.
+    Asm {
+        source: PathBuf,
+
+        /// Where to write the ROM.
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+
+    /// Print information about a ROM.
+    Info { rom: PathBuf },
+
+    /// Check a ROM for suspicious patterns, like jumps to odd addresses or sprites drawn
+    /// before `I` is set, without running it.
+    Lint { rom: PathBuf },
+
+    /// Print the hashes identifying ROMs, as used by the ROM profiles and crash dumps.
+    Hash { roms: Vec<PathBuf> },
+
+    /// Find the sprites of a ROM, and print them as Octo sprite declarations.
+    Sprites {
+        rom: PathBuf,
+
+        /// Write the declarations to a file instead of printing them.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Also write the sprites side by side to a PNG sprite sheet.
+        #[arg(long)]
+        png: Option<PathBuf>,
+
+        /// The size of a sprite pixel in the PNG sprite sheet.
+        #[arg(long, default_value_t = 4)]
+        scale: usize,
+    },
+
+    /// Strip the trailing zero padding from a ROM dump.
+    Trim {
+        rom: PathBuf,
+
+        /// Where to write the trimmed ROM.
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+
+    /// Concatenate ROMs or data blocks into a single ROM.
+    Concat {
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Where to write the combined ROM.
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// The variant the ROM is for, which decides the size of its memory.
+        #[arg(long, default_value = "chip8", value_parser = parse_variant)]
+        variant: Variant,
+    },
+
+    /// Move the segment from `start` up to `end` to `destination`, given as memory addresses.
+    Relocate {
+        rom: PathBuf,
+        #[arg(value_parser = parse_address)]
+        start: usize,
+        #[arg(value_parser = parse_address)]
+        end: usize,
+        #[arg(value_parser = parse_address)]
+        destination: usize,
+
+        /// Where to write the edited ROM.
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// The variant the ROM is for, which decides the size of its memory.
+        #[arg(long, default_value = "chip8", value_parser = parse_variant)]
+        variant: Variant,
+    },
+
+    /// Run every ROM in a folder without a display, and write a PNG thumbnail of its screen.
+    ///
+    /// The thumbnails are named after the ROM files, and all have the size of the hi-res display.
+    Thumbnails {
+        /// The folder with the ROMs, recognized by their `.ch8`, `.c8` or `.sc8` extension.
+        folder: PathBuf,
+
+        /// The folder to write the thumbnails to.
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// The amount of frames to run each ROM for.
+        #[arg(short, long, default_value_t = 180)]
+        frames: u32,
+
+        /// The amount of steps executed per frame.
+        #[arg(short, long, default_value_t = 10)]
+        steps_per_frame: u32,
+
+        /// The variant to run the ROMs as, `chip8`, `hires`, `schip` or `xochip`.
+        #[arg(long, default_value = "chip8", value_parser = parse_variant)]
+        variant: Variant,
+
+        /// The size of a hi-res pixel in the thumbnails.
+        #[arg(long, default_value_t = 1)]
+        scale: usize,
+
+        /// Run the ROMs deterministically with the given random seed, so the thumbnails
+        /// don't depend on the speed of the machine.
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+
+    /// Export a ROM as a single HTML page that plays it in the browser.
+    ///
+    /// The page embeds the web build of the interpreter, built with
+    /// `cargo build -p chip8-web --release --target wasm32-unknown-unknown`.
+    Html {
+        rom: PathBuf,
+
+        /// Where to write the page.
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// The web build of the interpreter to embed.
+        #[arg(
+            long,
+            default_value = "target/wasm32-unknown-unknown/release/chip8_web.wasm"
+        )]
+        wasm: PathBuf,
+
+        /// The title of the page, the name of the ROM file by default.
+        #[arg(long)]
+        title: Option<String>,
+
+        /// The variant to run the ROM as, `chip8`, `hires`, `schip` or `xochip`.
+        #[arg(long, default_value = "chip8", value_parser = parse_variant)]
+        variant: Variant,
+
+        /// The amount of steps executed per frame.
+        #[arg(short, long, default_value_t = 10)]
+        steps_per_frame: u32,
+
+        /// Enable the shift quirk.
+        #[arg(long)]
+        shift_quirk: bool,
+
+        /// Wait for the vertical blank before drawing a sprite.
+        #[arg(long)]
+        vblank_wait: bool,
+
+        /// Wrap sprites around the bottom edge of the display.
+        #[arg(long)]
+        vertical_wrap: bool,
+
+        /// Wrap sprites around the right edge of the display.
+        #[arg(long)]
+        horizontal_wrap: bool,
+
+        /// Leave the index register unchanged when storing and loading registers (`Fx55`/`Fx65`).
+        #[arg(long)]
+        load_store_quirk: bool,
+
+        /// Jump relative to `Vx` instead of `V0` with `Bxnn`.
+        #[arg(long)]
+        jump_quirk: bool,
+
+        /// The color of pixels that are on, as a `RRGGBB` hex code.
+        #[arg(long, default_value = "ffffff", value_parser = parse_color)]
+        foreground: [u8; 3],
+
+        /// The color of pixels that are off, as a `RRGGBB` hex code.
+        #[arg(long, default_value = "000000", value_parser = parse_color)]
+        background: [u8; 3],
+    },
+
+    /// Upgrade a save state written by an older version of the interpreter to the current format.
+    MigrateState {
+        /// The save state to upgrade.
+        input: PathBuf,
+
+        /// Where to write the upgraded save state, which may be the input file.
+        output: PathBuf,
+    },
+
+    /// Print the differences between two save states, like the registers and memory
+    /// that changed.
+    DiffStates {
+        /// The earlier save state.
+        before: PathBuf,
+
+        /// The later save state.
+        after: PathBuf,
+    },
+
+    /// Run a ROM without a display, then print its disassembly with the executed
+    /// instructions marked, to find the code that was never reached.
+    Coverage {
+        #[command(flatten)]
+        run: RunArgs,
+    },
+
+    /// Run a ROM without a display, then print a report of the full interpreter state.
+    Report {
+        #[command(flatten)]
+        run: RunArgs,
+
+        /// Write the report to a file instead of printing it.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Replay a movie recorded in the GUI, then print a report of the full interpreter state.
+    Replay {
+        rom: PathBuf,
+
+        /// The movie to replay.
+        movie: PathBuf,
+
+        /// Write the report to a file instead of printing it.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+/// The arguments for running a ROM.
+#[derive(Args)]
+struct RunArgs {
+    rom: PathBuf,
+
+    /// The amount of frames to run.
+    #[arg(short, long, default_value_t = 60)]
+    frames: u32,
+
+    /// The amount of steps executed per frame.
+    #[arg(short, long, default_value_t = 10)]
+    steps_per_frame: u32,
+
+    /// The variant to run the ROM as, `chip8`, `hires`, `schip` or `xochip`.
+    #[arg(long, default_value = "chip8", value_parser = parse_variant)]
+    variant: Variant,
+
+    /// Run deterministically with the given random seed, so every run gives the same result.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Write every executed instruction, with the registers it changed, to this file.
+    #[arg(long)]
+    trace: Option<PathBuf>,
+}
+
+fn main() -> anyhow::Result<()> {
+    match Cli::parse().command {
+        Command::Run(args) => {
+            let (chip8, _) = run(&args)?;
+            print!("{}", screen(&chip8));
+            if let Some(fault) = chip8.fault() {
+                println!("Halted: {fault}");
+            }
+        }
+        Command::Disasm {
+            rom,
+            explain,
+            follow,
+            symbols,
+        } => {
+            let symbols = match symbols {
+                Some(path) => read_symbols(&path)?,
+                None => Symbols::new(),
+            };
+            for line in disasm::disassemble_rom(&read(&rom)?, explain, follow, &symbols) {
+                println!("{line}");
+            }
+        }
+        Command::Cfg {
+            rom,
+            output,
+            symbols,
+        } => {
+            let symbols = match symbols {
+                Some(path) => read_symbols(&path)?,
+                None => Symbols::new(),
+            };
+            let dot = chip8::disasm::control_flow_dot(&read(&rom)?, disasm::ROM_START, &symbols);
+            match output {
+                Some(path) => write(&path, dot.as_bytes())?,
+                None => print!("{dot}"),
+            }
+        }
+        Command::Asm { source, output } => {
+            let source = std::fs::read_to_string(&source)
+                .with_context(|| format!("Failed to read {}", source.display()))?;
+            let rom = asm::assemble(&source)?;
+            write(&output, &rom)?;
+            println!("Assembled {} bytes", rom.len());
+        }
+        Command::Info { rom } => print!("{}", info(&read(&rom)?)),
+        Command::Lint { rom } => {
+            let diagnostics = chip8::lint::lint(&read(&rom)?, disasm::ROM_START);
+            for diagnostic in &diagnostics {
+                println!("{diagnostic}");
+            }
+            eprintln!("Found {} problems", diagnostics.len());
+        }
+        Command::Hash { roms } => {
+            for path in roms {
+                println!(
+                    "{:016x}  {}",
+                    chip8::rom_hash(&read(&path)?),
+                    path.display()
+                );
+            }
+        }
+        Command::Sprites {
+            rom,
+            output,
+            png,
+            scale,
+        } => {
+            let rom = read(&rom)?;
+            let sprites: Vec<Sprite> = sprites::find_sprites(&rom)
+                .into_iter()
+                .filter(|sprite| sprite.data(&rom).is_some())
+                .collect();
+            let octo = sprites::to_octo(&rom, &sprites);
+            match output {
+                Some(path) => write(&path, octo.as_bytes())?,
+                None => print!("{octo}"),
+            }
+            if let Some(path) = png {
+                write_sprite_sheet(&path, &rom, &sprites, scale)?;
+            }
+            eprintln!("Found {} sprites", sprites.len());
+        }
+        Command::Trim { rom, output } => {
+            let data = read(&rom)?;
+            let trimmed = chip8::rom::trim(&data);
+            write(&output, trimmed)?;
+            println!("Removed {} bytes of padding", data.len() - trimmed.len());
+        }
+        Command::Concat {
+            inputs,
+            output,
+            variant,
+        } => {
+            let blocks = inputs
+                .iter()
+                .map(read)
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let rom = chip8::rom::concat(blocks.iter().map(Vec::as_slice));
+            let capacity = chip8::rom::max_size(variant.memory_size());
+            if rom.len() > capacity {
+                eprintln!(
+                    "Warning: the ROM is {} bytes, which is larger than the {capacity} bytes of \
+                     program memory of {variant}, so it will be rejected when loaded",
+                    rom.len()
+                );
+            }
+            write(&output, &rom)?;
+        }
+        Command::Relocate {
+            rom,
+            start,
+            end,
+            destination,
+            output,
+            variant,
+        } => {
+            let relocated =
+                chip8::rom::relocate(&read(&rom)?, start, end, destination, variant.memory_size())?;
+            write(&output, &relocated)?;
+        }
+        Command::Thumbnails {
+            folder,
+            output,
+            frames,
+            steps_per_frame,
+            variant,
+            scale,
+            seed,
+        } => {
+            std::fs::create_dir_all(&output)
+                .with_context(|| format!("Failed to create {}", output.display()))?;
+            let mut roms: Vec<PathBuf> = std::fs::read_dir(&folder)
+                .with_context(|| format!("Failed to read {}", folder.display()))?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<_, _>>()?;
+            roms.retain(|path| is_rom_file(path));
+            roms.sort();
+            let batch = roms
+                .iter()
+                .map(|path| Ok(batch_rom(read(path)?, variant, steps_per_frame, seed)))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let results = batch::run_batch(&batch, frames);
+            let mut written = 0;
+            for (path, result) in roms.iter().zip(&results) {
+                let chip8 = match result {
+                    Ok(chip8) => chip8,
+                    Err(e) => {
+                        eprintln!("Skipped {}: {e}", path.display());
+                        continue;
+                    }
+                };
+                let mut thumbnail = output.join(path.file_stem().unwrap_or_default());
+                thumbnail.set_extension("png");
+                write_thumbnail(&thumbnail, chip8, scale)?;
+                println!("{} -> {}", path.display(), thumbnail.display());
+                written += 1;
+            }
+            eprintln!("Wrote {written} thumbnails");
+        }
+        Command::Html {
+            rom,
+            output,
+            wasm,
+            title,
+            variant,
+            steps_per_frame,
+            shift_quirk,
+            vblank_wait,
+            vertical_wrap,
+            horizontal_wrap,
+            load_store_quirk,
+            jump_quirk,
+            foreground,
+            background,
+        } => {
+            let wasm_build = std::fs::read(&wasm).with_context(|| {
+                format!(
+                    "Failed to read the web build at {}, build it with \
+                     `cargo build -p chip8-web --release --target wasm32-unknown-unknown`",
+                    wasm.display()
+                )
+            })?;
+            let quirks = [
+                (shift_quirk, chip8_web::QUIRK_SHIFT),
+                (vblank_wait, chip8_web::QUIRK_VBLANK_WAIT),
+                (vertical_wrap, chip8_web::QUIRK_VERTICAL_WRAP),
+                (horizontal_wrap, chip8_web::QUIRK_HORIZONTAL_WRAP),
+                (load_store_quirk, chip8_web::QUIRK_LOAD_STORE),
+                (jump_quirk, chip8_web::QUIRK_JUMP),
+            ]
+            .into_iter()
+            .filter(|&(enabled, _)| enabled)
+            .fold(0, |quirks, (_, flag)| quirks | flag);
+            let config = html::Config {
+                title: title.unwrap_or_else(|| {
+                    rom.file_stem()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned()
+                }),
+                variant: match variant {
+                    Variant::Chip8 => 0,
+                    Variant::SuperChip => 1,
+                    Variant::XoChip => 2,
+                    Variant::HiresChip8 => 3,
+                    // like the web build, which runs unknown variants as CHIP-8
+                    _ => 0,
+                },
+                quirks,
+                steps_per_frame,
+                foreground,
+                background,
+            };
+            let rom_data = read(&rom)?;
+            // the page can't report a ROM that doesn't fit, so it isn't exported
+            let capacity = chip8::rom::max_size(variant.memory_size());
+            if rom_data.len() > capacity {
+                return Err(chip8::rom::RomError::TooLarge {
+                    size: rom_data.len(),
+                    capacity,
+                }
+                .into());
+            }
+            let page = html::export(&wasm_build, &rom_data, &config);
+            write(&output, page.as_bytes())?;
+        }
+        Command::MigrateState { input, output } => {
+            let bytes = read(&input)?;
+            let version = chip8::state::version(&bytes);
+            let migrated = chip8::state::migrate(&bytes)
+                .with_context(|| format!("Failed to migrate {}", input.display()))?;
+            write(&output, &migrated)?;
+            if version == chip8::state::VERSION {
+                println!("The save state is already at version {version}");
+            } else {
+                println!(
+                    "Migrated the save state from version {version} to {}",
+                    chip8::state::VERSION
+                );
+            }
+        }
+        Command::DiffStates { before, after } => {
+            let load = |path: &PathBuf| {
+                chip8::state::load(&read(path)?)
+                    .with_context(|| format!("Failed to load {}", path.display()))
+            };
+            print!("{}", chip8::diff::diff(&load(&before)?, &load(&after)?));
+        }
+        Command::Coverage { run: args } => {
+            let (chip8, rom) = run(&args)?;
+            print!("{}", coverage(&chip8, &rom));
+        }
+        Command::Report { run: args, output } => {
+            let (chip8, rom) = run(&args)?;
+            let report = report(&chip8, &rom, u64::from(args.frames))?;
+            write_report(&report, output)?;
+        }
+        Command::Replay { rom, movie, output } => {
+            let rom = read(&rom)?;
+            let movie = chip8::movie::load(&read(&movie)?)
+                .with_context(|| format!("Invalid movie {}", movie.display()))?;
+            let chip8 = movie.replay(&rom)?;
+            let report = report(&chip8, &rom, movie.frames)?;
+            write_report(&report, output)?;
+        }
+    }
+    Ok(())
+}
+
+/// A report of the full state of the `Chip8`, after running the `rom` for the given
+/// amount of `frames`.
+fn report(chip8: &Chip8, rom: &[u8], frames: u64) -> anyhow::Result<String> {
+    let mut report = String::new();
+    writeln!(report, "CHIP-8 report")?;
+    writeln!(report, "ROM hash: {:016x}", chip8::rom_hash(rom))?;
+    writeln!(report, "Frames: {frames}")?;
+    writeln!(report, "State hash: {:016x}", chip8.state_hash())?;
+    if let Some(fault) = chip8.fault() {
+        writeln!(report, "Fault: {fault}")?;
+    }
+    writeln!(report)?;
+    chip8::report::write_state(&mut report, chip8)?;
+    Ok(report)
+}
+
+/// Write the `report` to the `output` file, or print it if there is none.
+fn write_report(report: &str, output: Option<PathBuf>) -> anyhow::Result<()> {
+    match output {
+        Some(path) => std::fs::write(&path, report)
+            .with_context(|| format!("Failed to write {}", path.display()))?,
+        None => print!("{report}"),
+    }
+    Ok(())
+}
+
+/// Read the ROM at the given `path`.
+fn read(path: &PathBuf) -> anyhow::Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// Read the symbol file at the given `path`.
+fn read_symbols(path: &PathBuf) -> anyhow::Result<Symbols> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Symbols::parse(&text).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Write the `data` to the given `path`.
+fn write(path: &PathBuf, data: &[u8]) -> anyhow::Result<()> {
+    std::fs::write(path, data).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Run the ROM given by `args`, returning the `Chip8` and the ROM data.
+fn run(args: &RunArgs) -> anyhow::Result<(Chip8, Vec<u8>)> {
+    let rom = read(&args.rom)?;
+    let batch = batch_rom(rom.clone(), args.variant, args.steps_per_frame, args.seed);
+    let mut chip8 = batch.prepare()?;
+    if let Some(path) = &args.trace {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        chip8.set_trace_writer(Some(Box::new(file)));
+    }
+    batch.run_frames(&mut chip8, args.frames);
+    // flush the trace
+    chip8.set_trace_writer(None);
+    Ok((chip8, rom))
+}
+
+/// Prepare the `rom` to run as the given `variant`, deterministically if a `seed` is given.
+fn batch_rom(rom: Vec<u8>, variant: Variant, steps_per_frame: u32, seed: Option<u64>) -> BatchRom {
+    BatchRom {
+        data: rom,
+        variant,
+        quirks: Quirks::default(),
+        steps_per_frame,
+        deterministic: seed.map(|seed| Deterministic {
+            seed,
+            steps_per_tick: steps_per_frame,
+        }),
+    }
+}
+
+/// Returns whether the file at `path` is a ROM, judging by its extension.
+fn is_rom_file(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    path.is_file() && matches!(extension.as_deref(), Some("ch8" | "c8" | "sc8"))
+}
+
+/// Write the display of the `Chip8` to a PNG thumbnail at `path`. The display is scaled
+/// to the hi-res resolution, so thumbnails of all ROMs have the same size.
+fn write_thumbnail(path: &Path, chip8: &Chip8, scale: usize) -> anyhow::Result<()> {
+    let graphics = chip8.display();
+    let scale = scale.max(1);
+    let (width, height) = (HIRES_WIDTH * scale, HIRES_HEIGHT * scale);
+    png::write_png(path, width, height, |x, y| {
+        graphics.is_pixel_on(x * graphics.width() / width, y * graphics.height() / height)
+    })
+}
+
+/// Write the given sprites of `rom` side by side to a PNG sprite sheet at `path`,
+/// separated by a column of empty pixels. Every sprite pixel is `scale` pixels wide.
+fn write_sprite_sheet(
+    path: &Path,
+    rom: &[u8],
+    sprites: &[Sprite],
+    scale: usize,
+) -> anyhow::Result<()> {
+    // the column each sprite starts at, in sprite pixels
+    let columns: Vec<usize> = sprites
+        .iter()
+        .scan(0, |column, sprite| {
+            let start = *column;
+            *column += sprite.width() + 1;
+            Some(start)
+        })
+        .collect();
+    let width = sprites
+        .iter()
+        .map(|sprite| sprite.width() + 1)
+        .sum::<usize>();
+    let height = sprites
+        .iter()
+        .map(|sprite| sprite.height)
+        .max()
+        .unwrap_or(0);
+    let scale = scale.max(1);
+
+    png::write_png(path, width.max(1) * scale, height.max(1) * scale, |x, y| {
+        let (x, y) = (x / scale, y / scale);
+        // the last sprite starting at or before the column
+        let index = columns
+            .partition_point(|&column| column <= x)
+            .saturating_sub(1);
+        match (sprites.get(index), columns.get(index)) {
+            (Some(sprite), Some(&column)) if x - column < sprite.width() && y < sprite.height => {
+                matches!(sprite.data(rom), Some(data) if sprite.pixel(data, x - column, y))
+            }
+            _ => false,
+        }
+    })
+}
+
+/// Draw the display of the `Chip8` as text, two pixel rows per line.
+fn screen(chip8: &Chip8) -> String {
+    let graphics = chip8.display();
+    let mut screen = String::new();
+    for y in (0..graphics.height()).step_by(2) {
+        for x in 0..graphics.width() {
+            let top = graphics.is_pixel_on(x, y);
+            let bottom = y + 1 < graphics.height() && graphics.is_pixel_on(x, y + 1);
+            screen.push(match (top, bottom) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            });
+        }
+        screen.push('\n');
+    }
+    screen
+}
+
+/// Disassemble the `rom` with the instructions the `chip8` executed marked with `>`,
+/// followed by the share of the bytes of the ROM that were executed.
+fn coverage(chip8: &Chip8, rom: &[u8]) -> String {
+    let executed = &chip8.coverage()[disasm::ROM_START..][..rom.len()];
+    let mut coverage = String::new();
+    for (line, bytes) in disasm::disassemble_rom(rom, false, false, &Symbols::new())
+        .into_iter()
+        .zip(executed.chunks(2))
+    {
+        // code that isn't aligned to a word is executed from the middle of a line
+        let marker = if bytes.contains(&true) { '>' } else { ' ' };
+        let _ = writeln!(coverage, "{marker} {line}");
+    }
+    let count = executed.iter().filter(|&&executed| executed).count();
+    let _ = writeln!(
+        coverage,
+        "Executed {count} of {} bytes ({:.1}%)",
+        rom.len(),
+        100.0 * count as f64 / rom.len().max(1) as f64
+    );
+    coverage
+}
+
+/// Describe the given ROM: its size, hash, and the instructions it appears to use.
+fn info(rom: &[u8]) -> String {
+    let opcodes: Vec<u16> = rom
+        .chunks_exact(2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .collect();
+    let uses = |matches: fn(u16) -> bool| opcodes.iter().any(|&opcode| matches(opcode));
+    let padding = rom.iter().rev().take_while(|&&byte| byte == 0).count();
+
+    let mut info = String::new();
+    let yes_no = |value| if value { "yes" } else { "no" };
+    // writing to a string can't fail
+    let _ = writeln!(
+        info,
+        "Size: {} bytes ({padding} bytes of trailing zeroes)",
+        rom.len()
+    );
+    let _ = writeln!(info, "Hash: {:016x}", chip8::rom_hash(rom));
+    let _ = writeln!(
+        info,
+        "SUPER-CHIP instructions: {}",
+        yes_no(uses(|op| matches!(op, 0x00C0..=0x00CF | 0x00FB..=0x00FF)
+            || matches!(op & 0xF0FF, 0xF030 | 0xF075 | 0xF085)))
+    );
+    let _ = writeln!(
+        info,
+        "Machine code calls (0NNN): {}",
+        yes_no(uses(|op| op & 0xF000 == 0
+            && !matches!(op, 0x0000 | 0x00E0 | 0x00EE | 0x00C0..=0x00CF | 0x00FB..=0x00FF)))
+    );
+    info
+}
+
+/// Parse a memory address, given in hexadecimal with an optional `0x` prefix.
+fn parse_address(text: &str) -> Result<usize, String> {
+    let hex = text.strip_prefix("0x").unwrap_or(text);
+    usize::from_str_radix(hex, 16).map_err(|e| format!("invalid address `{text}`: {e}"))
+}
+
+/// Parse a color given as a `RRGGBB` hex code, with an optional `#` prefix.
+fn parse_color(text: &str) -> Result<[u8; 3], String> {
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    match u32::from_str_radix(hex, 16) {
+        Ok(rgb) if hex.len() == 6 => {
+            let [_, r, g, b] = rgb.to_be_bytes();
+            Ok([r, g, b])
+        }
+        _ => Err(format!(
+            "invalid color `{text}`, expected a `RRGGBB` hex code"
+        )),
+    }
+}
+
+/// Parse the name of a [`Variant`].
+fn parse_variant(name: &str) -> Result<Variant, String> {
+    match name.to_ascii_lowercase().as_str() {
+        "chip8" | "chip-8" => Ok(Variant::Chip8),
+        "hires" | "hires-chip8" | "hires-chip-8" => Ok(Variant::HiresChip8),
+        "schip" | "superchip" | "super-chip" => Ok(Variant::SuperChip),
+        "xochip" | "xo-chip" => Ok(Variant::XoChip),
+        _ => Err(format!(
+            "unknown variant `{name}`, expected `chip8`, `hires`, `schip` or `xochip`"
+        )),
+    }
+}
@@ -0,0 +1,25 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use anyhow::Context;
+
+/// Write a grayscale image to a PNG file at `path`, where `pixel`
+/// returns whether the pixel at the given coordinates is set.
+pub fn write_png(
+    path: &Path,
+    width: usize,
+    height: usize,
+    pixel: impl Fn(usize, usize) -> bool,
+) -> anyhow::Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let data: Vec<u8> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| if pixel(x, y) { 0xFF } else { 0x00 })
+        .collect();
+    encoder.write_header()?.write_image_data(&data)?;
+    Ok(())
+}
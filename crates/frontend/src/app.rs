@@ -1,165 +1,579 @@
-use std::path::Path;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
 
-use crate::audio::AudioSystem;
-use crate::gui::{Chip8Message, Gui};
-use anyhow::Context;
-use chip8::Chip8;
+use chip8::{Quirks, Timing};
+
+use crate::bundle::SessionBundle;
+use crate::crash;
+use crate::gui::{Chip8Message, Gui, GuiState};
+use crate::latency::LatencyTest;
+use crate::logging::{Logging, TRACE_TARGET};
+use crate::net::{NetSession, NetStatus};
+use crate::renderer::DisplayFrame;
+use crate::rumble::Rumble;
+use crate::session::Session;
 
 pub const DEFAULT_STEPS_PER_FRAME: u32 = 10;
 
+/// The amount of steps that can be stepped back through while the debug view is shown.
+const REWIND_DEPTH: usize = 1000;
+
 /// The main application state.
 ///
-/// Handles interactions between the frontend [`Gui`] and the backend [`Chip8`].
+/// Handles interactions between the frontend [`Gui`] and the backend [`Chip8`](chip8::Chip8)
+/// instances, each of which is owned by a [`Session`] shown as a tab.
 pub struct App {
-    pub chip8: Chip8,
     gui: Gui,
-    // keep the audio system alive for as long as the app,
-    // so the stream is not dropped.
-    audio: AudioSystem,
+    /// All open sessions, there is always at least one.
+    sessions: Vec<Session>,
+    /// The index of the session shown in the `Gui`. Only the active session is executed.
+    active: usize,
     steps_per_frame: u32,
-    paused: bool,
-    last_rom: Vec<u8>,
+    net: Option<NetSession>,
+    /// The key state sent by a spectator that has been granted the keypad.
+    remote_keys: [bool; 16],
+    /// ROMs opened by other instances of the app, e.g. by double-clicking a `.ch8` file.
+    opened_roms: Option<Receiver<PathBuf>>,
+    /// Rumbles the connected gamepads while the active session is beeping.
+    rumble: Option<Rumble>,
+    latency: LatencyTest,
 }
 
 impl App {
-    /// Create a new `App` instance.
-    pub fn new(cc: &eframe::CreationContext) -> Self {
-        let mut chip8 = Chip8::new();
-        let mut last_rom = Vec::new();
-
-        if let Some(data) = Self::get_arg_rom() {
-            chip8.load_rom_data(data.clone());
-            last_rom = data;
+    /// Create a new `App` instance, showing the log records captured by `logging`.
+    /// Each ROM path received from `opened_roms` is opened in a new session.
+    pub fn new(
+        cc: &eframe::CreationContext,
+        logging: Logging,
+        opened_roms: Option<Receiver<PathBuf>>,
+    ) -> Self {
+        let mut session = Session::new();
+        if let Some((name, data)) = Self::get_arg_rom() {
+            session.load_rom(name, data);
         }
 
-        let gui = Gui::new(cc);
-
-        let audio = Self::create_audio_system(&chip8).expect("Failed to create AudioSystem");
+        let gui = Gui::new(cc, logging);
+        let rumble = Rumble::new()
+            .map_err(|e| log::error!("Failed to initialize gamepads: {e}"))
+            .ok();
 
         Self {
             gui,
-            chip8,
-            audio,
+            sessions: vec![session],
+            active: 0,
             steps_per_frame: DEFAULT_STEPS_PER_FRAME,
-            paused: false,
-            last_rom,
+            net: None,
+            remote_keys: [false; 16],
+            opened_roms,
+            rumble,
+            latency: LatencyTest::default(),
         }
     }
 
-    /// Create a new `AudioSystem` using the sound timer from the given
-    /// `Chip8` instance.
-    ///
-    /// This will also start the audio stream. This function will only return
-    /// the `AudioSystem` if it can be both created and played without errors,
-    /// otherwise it returns `Err`.
-    fn create_audio_system(chip8: &Chip8) -> Result<AudioSystem, anyhow::Error> {
-        let audio = AudioSystem::new(chip8.bus.clock.sound_timer.clone())?;
-        audio.play().map(|_| audio).map_err(|e| {
-            log::error!("Failed to play audio stream: {e}");
-            e
-        })
-    }
-
-    /// Get the ROM data from the path provided as the first argument when
-    /// run from the command line.
-    fn get_arg_rom() -> Option<Vec<u8>> {
-        std::env::args().nth(1).and_then(|rom_path| {
-            std::fs::read(&rom_path)
-                .map_err(|e| log::error!("Failed to read ROM from {rom_path}: {e}"))
-                .ok()
-        })
-    }
-
-    /// Save `Chip8` state to a file specified by `path`.
-    fn save_chip8(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
-        let bytes = bincode::serialize(&self.chip8)?;
-        std::fs::write(path, bytes)?;
+    /// Get the name and data of the ROM from the path provided as the
+    /// first argument when run from the command line.
+    fn get_arg_rom() -> Option<(String, Vec<u8>)> {
+        std::env::args().nth(1).and_then(Self::read_rom)
+    }
+
+    /// Read the ROM at the given path, returning its file name and data.
+    fn read_rom(rom_path: impl AsRef<Path>) -> Option<(String, Vec<u8>)> {
+        let rom_path = rom_path.as_ref();
+        let name = rom_path.file_name().map_or_else(
+            || rom_path.to_string_lossy().into(),
+            |name| name.to_string_lossy().into(),
+        );
+        std::fs::read(rom_path)
+            .map(|data| (name, data))
+            .map_err(|e| log::error!("Failed to read ROM from {}: {e}", rom_path.display()))
+            .ok()
+    }
+
+    /// Open each ROM received from other instances in a new session.
+    fn update_opened_roms(&mut self) {
+        let paths: Vec<PathBuf> = match &self.opened_roms {
+            Some(receiver) => receiver.try_iter().collect(),
+            None => return,
+        };
+        for (name, data) in paths.iter().filter_map(Self::read_rom) {
+            self.open_session();
+            self.session_mut().load_rom(name, data);
+        }
+    }
+
+    /// Get the active session.
+    fn session(&self) -> &Session {
+        &self.sessions[self.active]
+    }
+
+    /// Get the active session mutably.
+    fn session_mut(&mut self) -> &mut Session {
+        &mut self.sessions[self.active]
+    }
+
+    /// Switch the active session, routing the audio to it.
+    fn select_session(&mut self, index: usize) {
+        self.session().set_audio_playing(false);
+        self.active = index;
+        self.session().set_audio_playing(true);
+    }
+
+    /// Change the quirk settings of every session.
+    fn update_quirks(&mut self, update: impl Fn(&mut Quirks)) {
+        for session in &mut self.sessions {
+            let mut quirks = session.chip8.quirks();
+            update(&mut quirks);
+            session.chip8.set_quirks(quirks);
+        }
+    }
+
+    /// Import the session bundle at `path` into a new session, and select it.
+    fn import_session(&mut self, path: &Path) -> anyhow::Result<()> {
+        let session = SessionBundle::load(path).and_then(Session::from_bundle)?;
+        session.set_audio_playing(false);
+        self.sessions.push(session);
+        self.select_session(self.sessions.len() - 1);
         Ok(())
     }
 
-    /// Load `Chip8` state from the given `path`.
-    fn load_chip8(&mut self, path: impl AsRef<Path>) -> anyhow::Result<Chip8> {
-        let bytes = std::fs::read(path)?;
-        let chip8 = bincode::deserialize(&bytes)
-            .context("Failed to deserialize Chip8 instance from file.")?;
-        Ok(chip8)
+    /// Delete the session saved after the last crash, so it isn't offered again.
+    fn discard_emergency_session() {
+        let path = crash::emergency_session_path();
+        if let Err(e) = std::fs::remove_file(&path) {
+            log::error!("Failed to delete {}: {e}", path.display());
+        }
+    }
+
+    /// The maximum amount of steps run in a frame. With the COSMAC VIP timing,
+    /// the frame ends once its cycles are consumed instead.
+    fn steps_per_frame(&self) -> u32 {
+        match self.session().chip8.timing() {
+            Timing::Steps => self.steps_per_frame,
+            _ => u32::MAX,
+        }
+    }
+
+    /// Open a new empty session with the same settings as the active session, and select it.
+    fn open_session(&mut self) {
+        let mut session = Session::new();
+        let current = &self.session().chip8;
+        let chip8 = &mut session.chip8;
+        chip8.set_variant(current.variant());
+        chip8.set_start_address(current.start_address());
+        chip8.set_memory_size(current.memory_size());
+        chip8.set_machine_code_policy(current.machine_code_policy());
+        chip8.set_timing(current.timing());
+        chip8.set_quirks(current.quirks());
+        chip8.set_palette(current.palette());
+        session.set_audio_playing(false);
+        self.sessions.push(session);
+        self.select_session(self.sessions.len() - 1);
+    }
+
+    /// Close the session at the given index. The last remaining session can't be closed.
+    fn close_session(&mut self, index: usize) {
+        if self.sessions.len() == 1 {
+            return;
+        }
+        self.sessions.remove(index);
+        if index < self.active || self.active == self.sessions.len() {
+            self.active -= 1;
+        }
+        self.session().set_audio_playing(true);
+    }
+
+    /// Receive any pending network messages, and send the current frame to the spectator
+    /// if this instance is hosting a session.
+    fn update_net(&mut self) {
+        let session = &mut self.sessions[self.active];
+        match &mut self.net {
+            Some(NetSession::Host(host)) => {
+                if let Some(keys) = host.poll() {
+                    self.remote_keys = keys;
+                    for (key_code, pressed) in (0..).zip(keys) {
+                        session.chip8.update_key_state(key_code, pressed);
+                    }
+                }
+                host.send_frame(DisplayFrame::of(session.display()));
+            }
+            Some(NetSession::Spectator(spectator)) => {
+                let connected = spectator.poll();
+                if !connected {
+                    self.net = None;
+                }
+            }
+            None => {}
+        }
     }
 
-    /// Reset the audio system. This should be called anytime the `Chip8` is reset,
-    /// as the new sound timer needs to be linked to a new `AudioSystem`.
-    fn reset_audio(&mut self) {
-        match Self::create_audio_system(&self.chip8) {
-            Ok(audio) => self.audio = audio,
-            Err(e) => log::error!("Failed to create new AudioSystem: {e}"),
+    /// Get the frame that should be shown to the user. This is the
+    /// host's display when spectating, otherwise the display of the active session.
+    fn display_frame(&self) -> DisplayFrame {
+        match &self.net {
+            Some(NetSession::Spectator(spectator)) => spectator
+                .frame()
+                .cloned()
+                .unwrap_or_else(DisplayFrame::blank),
+            _ => DisplayFrame::of(self.session().display()),
         }
     }
 
     /// Update the `Gui` and handle all state-changing messages.
     fn update_gui(&mut self, ctx: &eframe::egui::Context) {
-        for message in self.gui.update(ctx, &self.chip8) {
+        let net_status = self
+            .net
+            .as_ref()
+            .map_or(NetStatus::Offline, NetSession::status);
+        let display = self.display_frame();
+        let tabs: Vec<&str> = self
+            .sessions
+            .iter()
+            .map(|session| session.name.as_str())
+            .collect();
+        let session = &self.sessions[self.active];
+        let state = GuiState {
+            chip8: &session.chip8,
+            display: &display,
+            net_status: &net_status,
+            profile: &session.profile,
+            rom: session.rom(),
+            paused: session.paused,
+            recording: session.is_recording(),
+            break_reason: session.break_reason,
+            crash_dump: session.crash_dump.as_ref(),
+            profiler: &session.profiler,
+            timeline: &session.timeline,
+            annotations: &session.annotations,
+            tabs: &tabs,
+            active_tab: self.active,
+            latency: &self.latency,
+        };
+        let messages = self.gui.update(ctx, &state);
+        for message in messages {
             match message {
-                Chip8Message::LoadRom(data) => {
-                    self.chip8.reset_and_load(data.clone());
-                    self.last_rom = data;
-                    self.reset_audio();
+                Chip8Message::LoadRom { name, data } => self.session_mut().load_rom(name, data),
+                Chip8Message::EditRom(edit) => {
+                    if let Err(e) = self.session_mut().edit_rom(edit) {
+                        log::error!("Failed to edit ROM: {e}");
+                    }
                 }
-                Chip8Message::ResetROM => {
-                    // load the last loaded ROM
-                    self.chip8.reset_and_load(self.last_rom.clone());
-                    self.reset_audio();
+                Chip8Message::ExportRom(path) => {
+                    if let Err(e) = std::fs::write(&path, self.session().rom()) {
+                        log::error!("Failed to write ROM to {}: {e}", path.display());
+                    }
                 }
-                Chip8Message::SetForegroundColor(color) => {
-                    self.chip8.bus.graphics.set_foreground_color(color)
+                Chip8Message::WriteMemory { address, data } => {
+                    self.session_mut().chip8.write_memory(address, &data);
                 }
-                Chip8Message::SetBackgroundColor(color) => {
-                    self.chip8.bus.graphics.set_background_color(color)
+                Chip8Message::ResetROM => self.session_mut().reset(),
+                Chip8Message::SetPalette(palette) => {
+                    for session in &mut self.sessions {
+                        session.chip8.set_palette(palette);
+                    }
                 }
                 Chip8Message::SetStepRate(steps) => self.steps_per_frame = steps,
+                Chip8Message::SetTiming(timing) => {
+                    for session in &mut self.sessions {
+                        session.chip8.set_timing(timing);
+                    }
+                }
+                Chip8Message::SetRumbleIntensity(intensity) => {
+                    if let Some(rumble) = &mut self.rumble {
+                        rumble.set_intensity(intensity);
+                    }
+                }
+                Chip8Message::SetVariant(variant) => {
+                    for session in &mut self.sessions {
+                        session.chip8.set_variant(variant);
+                    }
+                }
+                Chip8Message::SetStartAddress(start) => {
+                    // the programs have to be loaded again at the new address
+                    for session in &mut self.sessions {
+                        session.chip8.set_start_address(start);
+                        session.reset();
+                    }
+                }
+                Chip8Message::SetMemorySize(size) => {
+                    // the programs have to be loaded again into the resized memory
+                    for session in &mut self.sessions {
+                        session.chip8.set_memory_size(size);
+                        session.reset();
+                    }
+                }
+                Chip8Message::SetMachineCodePolicy(policy) => {
+                    for session in &mut self.sessions {
+                        session.chip8.set_machine_code_policy(policy);
+                    }
+                }
                 Chip8Message::SetShiftQuirk(enabled) => {
-                    self.chip8.processor.shift_quirk_enabled = enabled
+                    self.update_quirks(|quirks| quirks.shift = enabled);
                 }
                 Chip8Message::SetVblankWait(enabled) => {
-                    self.chip8.processor.vblank_wait = enabled;
+                    self.update_quirks(|quirks| quirks.vblank_wait = enabled);
+                }
+                Chip8Message::SetVerticalWrap(enabled) => {
+                    self.update_quirks(|quirks| quirks.vertical_wrap = enabled);
+                }
+                Chip8Message::SetHorizontalWrap(enabled) => {
+                    self.update_quirks(|quirks| quirks.horizontal_wrap = enabled);
+                }
+                Chip8Message::SetPauseTimersOnKeyWait(enabled) => {
+                    self.update_quirks(|quirks| quirks.pause_timers_on_key_wait = enabled);
+                }
+                Chip8Message::SetLogicQuirk(enabled) => {
+                    self.update_quirks(|quirks| quirks.logic = enabled);
+                }
+                Chip8Message::SetIndexOverflowQuirk(enabled) => {
+                    self.update_quirks(|quirks| quirks.index_overflow = enabled);
+                }
+                Chip8Message::SetJumpQuirk(enabled) => {
+                    self.update_quirks(|quirks| quirks.jump = enabled);
+                }
+                Chip8Message::SetIndexIncrement(increment) => {
+                    self.update_quirks(|quirks| quirks.index_increment = increment);
+                }
+                Chip8Message::SetQuirkPreset(preset) => {
+                    self.update_quirks(|quirks| *quirks = preset.quirks());
                 }
                 Chip8Message::UpdateKeys(key_updates) => {
+                    if let Some(NetSession::Spectator(spectator)) = &mut self.net {
+                        let mut keys = [false; 16];
+                        for (key_code, pressed) in key_updates {
+                            keys[usize::from(key_code)] = pressed;
+                        }
+                        spectator.send_keys(keys);
+                        continue;
+                    }
                     for (key_code, pressed) in key_updates {
-                        self.chip8.update_key_state(key_code, pressed);
+                        let remote_pressed = self.remote_keys[usize::from(key_code)];
+                        let chip8 = &mut self.sessions[self.active].chip8;
+                        if pressed && !chip8.is_key_pressed(key_code) {
+                            self.latency.press(key_code, chip8);
+                        }
+                        chip8.update_key_state(key_code, pressed || remote_pressed);
+                    }
+                }
+                Chip8Message::SetLatencyTest(running) => {
+                    if running {
+                        self.latency.start();
+                    } else {
+                        self.latency.stop();
                     }
                 }
-                Chip8Message::TogglePause => self.paused = !self.paused,
+                Chip8Message::TogglePause => self.session_mut().toggle_pause(),
                 Chip8Message::SaveState(path) => {
-                    if let Err(e) = self.save_chip8(&path) {
+                    if let Err(e) = self.session().save_state(&path) {
                         log::error!("Failed to save Chip8 state to {}: {e}.", path.display());
                     }
                 }
-                Chip8Message::LoadState(path) => match self.load_chip8(&path) {
-                    Ok(chip8) => {
-                        self.chip8 = chip8;
-                        self.reset_audio();
-                    }
-                    Err(e) => {
+                Chip8Message::LoadState(path) => {
+                    if let Err(e) = self.session_mut().load_state(&path) {
                         log::error!("Failed to load Chip8 state from {}: {e}.", path.display())
                     }
+                }
+                Chip8Message::LoadSymbols(path) => {
+                    if let Err(e) = self.session_mut().load_symbols(&path) {
+                        log::error!("Failed to load symbols from {}: {e:#}.", path.display())
+                    }
+                }
+                Chip8Message::StartRecording => {
+                    let steps_per_frame = self.steps_per_frame;
+                    self.session_mut().start_recording(steps_per_frame);
+                }
+                Chip8Message::StopRecording(path) => {
+                    if let Err(e) = self.session_mut().stop_recording(&path) {
+                        log::error!("Failed to save the movie to {}: {e:#}", path.display());
+                    }
+                }
+                Chip8Message::Step(granularity) => {
+                    let steps_per_frame = self.steps_per_frame();
+                    self.session_mut().step(granularity, steps_per_frame);
+                }
+                Chip8Message::StepBack => self.session_mut().step_back(),
+                Chip8Message::SetTriggers(triggers) => {
+                    self.session_mut().debugger.triggers = triggers
+                }
+                Chip8Message::SetBreakpoints(breakpoints) => {
+                    self.session_mut().debugger.breakpoints = breakpoints
+                }
+                Chip8Message::SetProfiling(enabled) => {
+                    let session = self.session_mut();
+                    if enabled && !session.profiler.enabled {
+                        session.profiler.reset(&session.chip8);
+                    }
+                    session.profiler.enabled = enabled;
+                }
+                Chip8Message::ResetProfile => {
+                    let session = self.session_mut();
+                    session.profiler.reset(&session.chip8);
+                }
+                Chip8Message::ExportCallGraph(path) => {
+                    if let Err(e) = self.session().profiler.export_call_graph(&path) {
+                        log::error!("{e}");
+                    }
+                }
+                Chip8Message::SetTimelineRecording(enabled) => {
+                    let session = self.session_mut();
+                    if enabled && !session.timeline.enabled {
+                        session.timeline.reset(&session.chip8);
+                    }
+                    session.timeline.enabled = enabled;
+                }
+                Chip8Message::ResetTimeline => {
+                    let session = self.session_mut();
+                    session.timeline.reset(&session.chip8);
+                }
+                Chip8Message::ExportCrashDump(path) => {
+                    if let Some(dump) = &self.session().crash_dump {
+                        if let Err(e) = dump.export(&path) {
+                            log::error!("{e}");
+                        }
+                    }
+                }
+                Chip8Message::HostSession { port, allow_input } => {
+                    match NetSession::host(port, allow_input) {
+                        Ok(session) => self.net = Some(session),
+                        Err(e) => log::error!("Failed to host network session on port {port}: {e}"),
+                    }
+                }
+                Chip8Message::JoinSession(address) => match NetSession::join(&address) {
+                    Ok(session) => self.net = Some(session),
+                    Err(e) => log::error!("Failed to join network session at {address}: {e}"),
                 },
-                Chip8Message::Step => self.chip8.step(),
+                Chip8Message::AddCheat(cheat) => {
+                    let session = self.session_mut();
+                    session.profile.cheats.push(cheat);
+                    session.save_profile();
+                }
+                Chip8Message::RemoveCheat(index) => {
+                    let session = self.session_mut();
+                    session.profile.cheats.remove(index);
+                    session.save_profile();
+                }
+                Chip8Message::SetCheatEnabled(index, enabled) => {
+                    let session = self.session_mut();
+                    session.profile.cheats[index].enabled = enabled;
+                    session.save_profile();
+                }
+                Chip8Message::SetKeypadLayout(layout) => {
+                    let session = self.session_mut();
+                    session.profile.keypad_layout = layout;
+                    session.save_profile();
+                }
+                Chip8Message::SetKeyLabel(key_code, label) => {
+                    let session = self.session_mut();
+                    if label.is_empty() {
+                        session.profile.key_labels.remove(&key_code);
+                    } else {
+                        session.profile.key_labels.insert(key_code, label);
+                    }
+                    session.save_profile();
+                }
+                Chip8Message::SetTouchLayout(layout) => {
+                    let session = self.session_mut();
+                    session.profile.touch_layout = layout;
+                    session.save_profile();
+                }
+                Chip8Message::LeaveSession => {
+                    self.net = None;
+                    self.remote_keys = [false; 16];
+                }
+                Chip8Message::SetRemoteInput(allow_input) => {
+                    if let Some(NetSession::Host(host)) = &mut self.net {
+                        host.set_allow_input(allow_input);
+                    }
+                    if !allow_input {
+                        self.remote_keys = [false; 16];
+                    }
+                }
+                Chip8Message::SetAnnotations(annotations) => {
+                    self.session_mut().annotations = annotations
+                }
+                Chip8Message::ExportSession(path) => {
+                    let result = self
+                        .session()
+                        .bundle()
+                        .and_then(|bundle| bundle.save(&path));
+                    if let Err(e) = result {
+                        log::error!("Failed to export session to {}: {e}.", path.display());
+                    }
+                }
+                Chip8Message::ImportSession(path) => {
+                    if let Err(e) = self.import_session(&path) {
+                        log::error!("Failed to import session from {}: {e}.", path.display())
+                    }
+                }
+                Chip8Message::RestoreEmergencySession => {
+                    let path = crash::emergency_session_path();
+                    match self.import_session(&path) {
+                        Ok(()) => Self::discard_emergency_session(),
+                        Err(e) => log::error!("Failed to restore the crashed session: {e}."),
+                    }
+                }
+                Chip8Message::DiscardEmergencySession => Self::discard_emergency_session(),
+                Chip8Message::NewTab => self.open_session(),
+                Chip8Message::SelectTab(index) => self.select_session(index),
+                Chip8Message::CloseTab(index) => self.close_session(index),
             }
         }
     }
+
+    /// Run the active session for a frame, and update the network session and the GUI.
+    fn update_frame(&mut self, ctx: &eframe::egui::Context) {
+        // a spectator only mirrors the host's display, so the local chip8 is not advanced
+        let spectating = matches!(self.net, Some(NetSession::Spectator(_)));
+
+        self.update_opened_roms();
+
+        // update chip8 state of the active session, the other sessions are suspended
+        let steps_per_frame = self.steps_per_frame();
+        // describing every instruction is only worth it while someone looks at the trace
+        let debugging = self.gui.showing_debug_view();
+        let tracing = debugging || log::log_enabled!(target: TRACE_TARGET, log::Level::Trace);
+        let session = self.session_mut();
+        session.chip8.set_tracing(tracing || session.paused);
+        // every step takes a snapshot to step back to, which is only needed while debugging
+        session
+            .chip8
+            .set_rewind_depth(if debugging { REWIND_DEPTH } else { 0 });
+        let mut keys_read = [false; 16];
+        if !session.paused && !spectating {
+            keys_read = session.run_frame(steps_per_frame);
+        }
+        session.update();
+
+        if let Some(rumble) = &mut self.rumble {
+            rumble.update(self.sessions[self.active].chip8.sound_timer() > 0);
+        }
+
+        self.update_net();
+
+        // update gui
+        self.update_gui(ctx);
+
+        // the frame has been drawn, so a response to a key press is now on its way to the screen
+        self.latency
+            .end_frame(keys_read, &self.sessions[self.active].chip8);
+    }
 }
 
 impl eframe::App for App {
     /// Updates the app and gui state and renders the GUI.
+    ///
+    /// If anything panics, the active session is saved before the panic continues,
+    /// so it can be restored on the next launch.
     fn update(&mut self, ctx: &eframe::egui::Context, _frame: &mut eframe::Frame) {
-        // update chip8 state
-        if !self.paused {
-            for _ in 0..self.steps_per_frame {
-                self.chip8.step();
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| self.update_frame(ctx)));
+        if let Err(panic) = result {
+            match crash::save_emergency_session(self.session()) {
+                Ok(path) => log::error!("Saved the active session to {}", path.display()),
+                Err(e) => log::error!("Failed to save the active session: {e:#}"),
             }
+            std::panic::resume_unwind(panic);
         }
 
-        // update gui
-        self.update_gui(ctx);
-
         // request another call to `update` right after this call
         ctx.request_repaint();
     }
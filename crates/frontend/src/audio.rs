@@ -1,41 +1,102 @@
 use std::{
     f32::consts::{PI, TAU},
-    sync::{atomic::AtomicU8, Arc},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
 use anyhow::Context;
 use cpal::{
     traits::{DeviceTrait, HostTrait, StreamTrait},
-    Device, Stream, StreamConfig,
+    Device, Stream, StreamConfig, StreamInstant,
 };
 
+use chip8::SoundSink;
+
+use crate::metrics::METRICS;
+
 /// Manages the audio on the current system, and plays a single
 /// frequency whenever the `Chip8` sound timer is above `0`.
 pub struct AudioSystem {
     stream: Stream,
+
+    /// Whether the tone is playing, set by the [`AudioSink`]s of the `AudioSystem`.
+    beeping: Arc<AtomicBool>,
+
+    /// Whether the stream was played since its last callback, so the pause before
+    /// isn't mistaken for an underrun.
+    resumed: Arc<AtomicBool>,
+}
+
+/// The [`SoundSink`] of an [`AudioSystem`], which plays the tone while the
+/// sound timer of the `Chip8` it is set on is active.
+pub struct AudioSink {
+    beeping: Arc<AtomicBool>,
+}
+
+impl SoundSink for AudioSink {
+    fn beep_started(&mut self) {
+        self.beeping.store(true, Ordering::Relaxed);
+    }
+
+    fn beep_stopped(&mut self) {
+        self.beeping.store(false, Ordering::Relaxed);
+    }
+}
+
+impl Drop for AudioSink {
+    // a `Chip8` that is replaced or reset while beeping doesn't get to stop the tone
+    fn drop(&mut self) {
+        self.beeping.store(false, Ordering::Relaxed);
+    }
 }
 
 impl AudioSystem {
-    /// Create a new `AudioSystem` associated with the given sound timer.
+    /// Create a new `AudioSystem`.
     ///
-    /// Whenver the sound timer is above `0`, a frequency will play (assuming
-    /// `AudioSystem::play` has been called beforehand).
-    pub fn new(timer: Arc<AtomicU8>) -> anyhow::Result<Self> {
+    /// A frequency will play while the sound timer of the `Chip8` that the [`AudioSystem::sink`]
+    /// is set on is above `0` (assuming `AudioSystem::play` has been called beforehand).
+    pub fn new() -> anyhow::Result<Self> {
         let host = cpal::default_host();
         let device = host
             .default_output_device()
             .expect("failed to get output device");
 
-        Self::get_stream(device, timer).map(|stream| Self { stream })
+        let beeping = Arc::new(AtomicBool::new(false));
+        let resumed = Arc::new(AtomicBool::new(false));
+        Self::get_stream(device, beeping.clone(), resumed.clone()).map(|stream| Self {
+            stream,
+            beeping,
+            resumed,
+        })
+    }
+
+    /// Create a [`SoundSink`] to set on a `Chip8`, which plays the tone of this `AudioSystem`.
+    pub fn sink(&self) -> AudioSink {
+        AudioSink {
+            beeping: self.beeping.clone(),
+        }
     }
 
     /// Create and retrieve a [`Stream`] depending on the sample format of the given [`Device`].
-    fn get_stream(device: Device, timer: Arc<AtomicU8>) -> anyhow::Result<Stream> {
+    fn get_stream(
+        device: Device,
+        beeping: Arc<AtomicBool>,
+        resumed: Arc<AtomicBool>,
+    ) -> anyhow::Result<Stream> {
         let config = device.default_output_config()?;
         match config.sample_format() {
-            cpal::SampleFormat::I16 => Self::create_stream::<i16>(device, config.into(), timer),
-            cpal::SampleFormat::U16 => Self::create_stream::<u16>(device, config.into(), timer),
-            cpal::SampleFormat::F32 => Self::create_stream::<f32>(device, config.into(), timer),
+            cpal::SampleFormat::I16 => {
+                Self::create_stream::<i16>(device, config.into(), beeping, resumed)
+            }
+            cpal::SampleFormat::U16 => {
+                Self::create_stream::<u16>(device, config.into(), beeping, resumed)
+            }
+            cpal::SampleFormat::F32 => {
+                Self::create_stream::<f32>(device, config.into(), beeping, resumed)
+            }
         }
     }
 
@@ -43,7 +104,8 @@ impl AudioSystem {
     fn create_stream<T: cpal::Sample>(
         device: Device,
         config: StreamConfig,
-        timer: Arc<AtomicU8>,
+        beeping: Arc<AtomicBool>,
+        resumed: Arc<AtomicBool>,
     ) -> anyhow::Result<Stream> {
         let sample_rate = config.sample_rate.0 as f32;
         let channels = usize::from(config.channels);
@@ -51,16 +113,39 @@ impl AudioSystem {
         let mut sample_clock = 0f32;
         let mut next_sample = move || {
             sample_clock = (sample_clock + 1.0) % sample_rate;
-            if timer.load(std::sync::atomic::Ordering::SeqCst) > 0 {
+            if beeping.load(Ordering::Relaxed) {
                 (440.0 * TAU * sample_clock / sample_rate).sin().asin() * 2.0 / PI
             } else {
                 0.0
             }
         };
 
+        // the time of the last callback, and the duration of the samples it provided
+        let mut last_callback: Option<(StreamInstant, Duration)> = None;
+
         let stream = device.build_output_stream(
             &config,
-            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            move |data: &mut [T], info: &cpal::OutputCallbackInfo| {
+                let callback = info.timestamp().callback;
+                if resumed.swap(false, Ordering::Relaxed) {
+                    last_callback = None;
+                }
+                if let Some((previous, buffered)) = last_callback {
+                    // the device ran out of samples if it had to wait well past the end of
+                    // the previous buffer for this one
+                    if matches!(
+                        callback.duration_since(&previous),
+                        Some(gap) if gap > buffered + buffered / 2
+                    ) {
+                        METRICS.record_audio_underrun();
+                    }
+                }
+                let frames = data.len() / channels;
+                last_callback = Some((
+                    callback,
+                    Duration::from_secs_f32(frames as f32 / sample_rate),
+                ));
+
                 for frame in data.chunks_mut(channels) {
                     let value: T = cpal::Sample::from::<f32>(&next_sample());
                     for sample in frame.iter_mut() {
@@ -75,6 +160,12 @@ impl AudioSystem {
 
     /// Play the audio stream.
     pub fn play(&self) -> anyhow::Result<()> {
+        self.resumed.store(true, Ordering::Relaxed);
         self.stream.play().context("Failed to play audio stream.")
     }
+
+    /// Pause the audio stream.
+    pub fn pause(&self) -> anyhow::Result<()> {
+        self.stream.pause().context("Failed to pause audio stream.")
+    }
 }
@@ -0,0 +1,127 @@
+use std::path::Path;
+
+use anyhow::Context;
+use chip8::{Chip8, IndexIncrement, MachineCodePolicy, Variant};
+use serde::{Deserialize, Serialize};
+
+/// The version of the bundle format, increased whenever the format changes.
+const BUNDLE_VERSION: u32 = 8;
+
+/// The variant and quirk settings a session was run with.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct QuirkConfig {
+    pub variant: Variant,
+    pub start_address: usize,
+    pub machine_code_policy: MachineCodePolicy,
+    pub shift_quirk_enabled: bool,
+    pub vblank_wait: bool,
+    pub vertical_wrap: bool,
+    pub horizontal_wrap: bool,
+    pub jump_quirk_enabled: bool,
+    pub index_increment: IndexIncrement,
+    pub logic_quirk_enabled: bool,
+    pub index_overflow_quirk_enabled: bool,
+}
+
+impl QuirkConfig {
+    /// Get the quirk config of the given `Chip8`.
+    pub fn of(chip8: &Chip8) -> Self {
+        Self {
+            variant: chip8.variant(),
+            start_address: chip8.start_address(),
+            machine_code_policy: chip8.machine_code_policy(),
+            shift_quirk_enabled: chip8.quirks().shift,
+            vblank_wait: chip8.quirks().vblank_wait,
+            vertical_wrap: chip8.quirks().vertical_wrap,
+            horizontal_wrap: chip8.quirks().horizontal_wrap,
+            jump_quirk_enabled: chip8.quirks().jump,
+            index_increment: chip8.quirks().index_increment,
+            logic_quirk_enabled: chip8.quirks().logic,
+            index_overflow_quirk_enabled: chip8.quirks().index_overflow,
+        }
+    }
+
+    /// Apply this quirk config to the given `Chip8`.
+    pub fn apply(self, chip8: &mut Chip8) {
+        chip8.set_variant(self.variant);
+        chip8.set_start_address(self.start_address);
+        chip8.set_machine_code_policy(self.machine_code_policy);
+        let mut quirks = chip8.quirks();
+        quirks.shift = self.shift_quirk_enabled;
+        quirks.vblank_wait = self.vblank_wait;
+        quirks.vertical_wrap = self.vertical_wrap;
+        quirks.horizontal_wrap = self.horizontal_wrap;
+        quirks.jump = self.jump_quirk_enabled;
+        quirks.index_increment = self.index_increment;
+        quirks.logic = self.logic_quirk_enabled;
+        quirks.index_overflow = self.index_overflow_quirk_enabled;
+        chip8.set_quirks(quirks);
+    }
+}
+
+/// Everything needed to reproduce a session on another machine, exported
+/// to a single file so it can be attached to a bug report.
+#[derive(Serialize, Deserialize)]
+pub struct SessionBundle {
+    version: u32,
+
+    /// The name of the session's tab.
+    pub name: String,
+
+    /// The hash of `rom`, so the receiver can check they are running the same ROM.
+    pub rom_hash: u64,
+    pub rom: Vec<u8>,
+    pub quirks: QuirkConfig,
+
+    /// A save state of the `Chip8` at the time of the export.
+    pub state: Vec<u8>,
+
+    /// Notes written by the user, e.g. steps to reproduce a bug.
+    pub annotations: String,
+}
+
+impl SessionBundle {
+    /// Create a new `SessionBundle`.
+    pub fn new(
+        name: String,
+        rom: Vec<u8>,
+        chip8: &Chip8,
+        annotations: String,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            version: BUNDLE_VERSION,
+            name,
+            rom_hash: chip8::rom_hash(&rom),
+            rom,
+            quirks: QuirkConfig::of(chip8),
+            state: chip8::state::save(chip8)?,
+            annotations,
+        })
+    }
+
+    /// Write this bundle to the given `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let bytes = bincode::serialize(self)?;
+        std::fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    /// Read a bundle from the given `path`.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        let version: u32 =
+            bincode::deserialize(&bytes).context("Failed to read session bundle version.")?;
+        if version != BUNDLE_VERSION {
+            anyhow::bail!("Unsupported session bundle version {version}.");
+        }
+        let bundle: Self =
+            bincode::deserialize(&bytes).context("Failed to deserialize session bundle.")?;
+        if chip8::rom_hash(&bundle.rom) != bundle.rom_hash {
+            log::warn!(
+                "The ROM in session bundle {} has been modified.",
+                bundle.name
+            );
+        }
+        Ok(bundle)
+    }
+}
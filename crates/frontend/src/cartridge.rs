@@ -0,0 +1,282 @@
+use anyhow::Context;
+use chip8::{Chip8, IndexIncrement, Symbols};
+use serde_json::Value;
+
+/// The largest code of the LZW compression used by GIF images.
+const MAX_LZW_CODES: usize = 4096;
+
+/// An Octo "cartridge": a GIF image with the source code and options of an
+/// Octo program hidden in the lowest two bits of the color index of each pixel.
+pub struct Cartridge {
+    /// The Octo source code of the program.
+    pub program: String,
+    pub options: OctoOptions,
+}
+
+impl Cartridge {
+    /// Returns whether the given file data is a GIF image, and could therefore be a cartridge.
+    pub fn is_cartridge(data: &[u8]) -> bool {
+        data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a")
+    }
+
+    /// Extract the program and options from the data of a cartridge image.
+    pub fn decode(gif: &[u8]) -> anyhow::Result<Self> {
+        let pixels = decode_first_frame(gif).context("Failed to decode the cartridge image")?;
+        // every byte of the payload is spread over 4 pixels, most significant bits first
+        let bytes: Vec<u8> = pixels
+            .chunks_exact(4)
+            .map(|pixels| pixels.iter().fold(0, |byte, pixel| byte << 2 | pixel & 3))
+            .collect();
+        let size = match bytes.get(..4) {
+            Some(&[a, b, c, d]) => u32::from_be_bytes([a, b, c, d]) as usize,
+            _ => anyhow::bail!("The cartridge image is too small to contain a program"),
+        };
+        let json: String = bytes
+            .get(4..4 + size)
+            .context("The program of the cartridge is cut off")?
+            .iter()
+            .map(|&byte| char::from(byte))
+            .collect();
+        let payload: Value =
+            serde_json::from_str(&json).context("The cartridge contains no valid program")?;
+        let program = payload["program"]
+            .as_str()
+            .context("The cartridge contains no program")?
+            .to_string();
+        Ok(Self {
+            program,
+            options: OctoOptions::from_json(&payload["options"]),
+        })
+    }
+
+    /// Compile the program of this cartridge into a ROM, along with the symbols of its labels.
+    pub fn compile(&self) -> anyhow::Result<(Vec<u8>, Symbols)> {
+        chip8::octo::compile_with_symbols(&self.program)
+            .context("Failed to compile the Octo program")
+    }
+}
+
+/// The options of an Octo program that are supported by the interpreter.
+/// Options that a cartridge doesn't set are left unchanged.
+#[derive(Default)]
+pub struct OctoOptions {
+    pub shift_quirk: Option<bool>,
+    pub vblank_wait: Option<bool>,
+
+    /// Whether sprites wrap around the right and bottom edges, the opposite of Octo's `clipQuirks`.
+    pub wrap: Option<bool>,
+    pub jump_quirk: Option<bool>,
+
+    /// Whether `I` is left unchanged by `Fx55` and `Fx65`, Octo's `loadStoreQuirks`.
+    pub index_unchanged: Option<bool>,
+    pub logic_quirk: Option<bool>,
+}
+
+impl OctoOptions {
+    /// Read the options from the `options` object of a cartridge.
+    fn from_json(options: &Value) -> Self {
+        // the VF order quirk isn't emulated
+        if options["vfOrderQuirks"].as_bool() == Some(true) {
+            log::warn!("The cartridge enables `vfOrderQuirks`, which is not supported");
+        }
+        Self {
+            shift_quirk: options["shiftQuirks"].as_bool(),
+            vblank_wait: options["vBlankQuirks"].as_bool(),
+            wrap: options["clipQuirks"].as_bool().map(|clip| !clip),
+            jump_quirk: options["jumpQuirks"].as_bool(),
+            index_unchanged: options["loadStoreQuirks"].as_bool(),
+            logic_quirk: options["logicQuirks"].as_bool(),
+        }
+    }
+
+    /// Apply these options to the quirk settings of the given `Chip8`.
+    pub fn apply(&self, chip8: &mut Chip8) {
+        let mut quirks = chip8.quirks();
+        if let Some(enabled) = self.shift_quirk {
+            quirks.shift = enabled;
+        }
+        if let Some(enabled) = self.vblank_wait {
+            quirks.vblank_wait = enabled;
+        }
+        if let Some(enabled) = self.wrap {
+            quirks.horizontal_wrap = enabled;
+            quirks.vertical_wrap = enabled;
+        }
+        if let Some(enabled) = self.jump_quirk {
+            quirks.jump = enabled;
+        }
+        if let Some(enabled) = self.logic_quirk {
+            quirks.logic = enabled;
+        }
+        if let Some(unchanged) = self.index_unchanged {
+            quirks.index_increment = if unchanged {
+                IndexIncrement::Unchanged
+            } else {
+                IndexIncrement::PastLast
+            };
+        }
+        chip8.set_quirks(quirks);
+    }
+}
+
+/// Reads the blocks of a GIF image.
+struct GifReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl GifReader<'_> {
+    fn bytes(&mut self, count: usize) -> anyhow::Result<&[u8]> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + count)
+            .context("Unexpected end of the image")?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> anyhow::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> anyhow::Result<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Skip a color table if the `flags` of the preceding block indicate one.
+    fn skip_color_table(&mut self, flags: u8) -> anyhow::Result<()> {
+        if flags & 0x80 != 0 {
+            self.bytes(3 << ((flags & 0x07) + 1))?;
+        }
+        Ok(())
+    }
+
+    /// Read a sequence of data sub-blocks into a single buffer.
+    fn sub_blocks(&mut self) -> anyhow::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        loop {
+            let length = usize::from(self.byte()?);
+            if length == 0 {
+                return Ok(data);
+            }
+            data.extend_from_slice(self.bytes(length)?);
+        }
+    }
+}
+
+/// Decode the color indices of the pixels of the first frame of a GIF image, row by row.
+fn decode_first_frame(gif: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut reader = GifReader {
+        data: gif,
+        position: 6,
+    };
+    // logical screen descriptor
+    reader.bytes(4)?;
+    let flags = reader.byte()?;
+    reader.bytes(2)?;
+    reader.skip_color_table(flags)?;
+
+    loop {
+        match reader.byte()? {
+            // extension
+            0x21 => {
+                reader.byte()?;
+                reader.sub_blocks()?;
+            }
+            // image descriptor
+            0x2C => {
+                reader.bytes(4)?;
+                let width = usize::from(reader.u16()?);
+                let height = usize::from(reader.u16()?);
+                let flags = reader.byte()?;
+                reader.skip_color_table(flags)?;
+                let min_code_size = reader.byte()?;
+                let data = reader.sub_blocks()?;
+                let pixels = decode_lzw(min_code_size, &data, width * height)?;
+                let interlaced = flags & 0x40 != 0;
+                return Ok(if interlaced {
+                    deinterlace(&pixels, width, height)
+                } else {
+                    pixels
+                });
+            }
+            0x3B => anyhow::bail!("The image contains no frames"),
+            block => anyhow::bail!("Unknown block {block:#04X}"),
+        }
+    }
+}
+
+/// Decompress the variable length LZW codes of GIF image data into `pixel_count` color indices.
+fn decode_lzw(min_code_size: u8, data: &[u8], pixel_count: usize) -> anyhow::Result<Vec<u8>> {
+    anyhow::ensure!(
+        (1..=11).contains(&min_code_size),
+        "Invalid LZW code size {min_code_size}"
+    );
+    let clear_code = 1usize << min_code_size;
+    let end_code = clear_code + 1;
+    let initial_table =
+        || -> Vec<Vec<u8>> { (0..clear_code + 2).map(|code| vec![code as u8]).collect() };
+
+    let mut table = initial_table();
+    let mut code_size = min_code_size + 1;
+    let mut previous: Option<Vec<u8>> = None;
+    let mut pixels = Vec::with_capacity(pixel_count);
+    let mut bit = 0;
+    while pixels.len() < pixel_count && bit + usize::from(code_size) <= data.len() * 8 {
+        // codes are packed starting at the least significant bit
+        let code = (0..usize::from(code_size)).fold(0, |code, i| {
+            let position = bit + i;
+            code | usize::from(data[position / 8] >> (position % 8) & 1) << i
+        });
+        bit += usize::from(code_size);
+
+        if code == clear_code {
+            table = initial_table();
+            code_size = min_code_size + 1;
+            previous = None;
+            continue;
+        }
+        if code == end_code {
+            break;
+        }
+        let entry = match (table.get(code), &previous) {
+            (Some(entry), _) => entry.clone(),
+            // the code that is about to be added to the table
+            (None, Some(previous)) if code == table.len() => {
+                let mut entry = previous.clone();
+                entry.push(previous[0]);
+                entry
+            }
+            _ => anyhow::bail!("Invalid LZW code {code}"),
+        };
+        pixels.extend_from_slice(&entry);
+        if let Some(mut previous) = previous.take() {
+            if table.len() < MAX_LZW_CODES {
+                previous.push(entry[0]);
+                table.push(previous);
+            }
+        }
+        if table.len() == 1 << code_size && code_size < 12 {
+            code_size += 1;
+        }
+        previous = Some(entry);
+    }
+    pixels.resize(pixel_count, 0);
+    Ok(pixels)
+}
+
+/// Reorder the rows of an interlaced image, which are stored in four passes.
+fn deinterlace(pixels: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let rows = (0..height)
+        .step_by(8)
+        .chain((4..height).step_by(8))
+        .chain((2..height).step_by(4))
+        .chain((1..height).step_by(2));
+    let mut deinterlaced = vec![0; pixels.len()];
+    for (source, row) in rows.enumerate() {
+        deinterlaced[row * width..(row + 1) * width]
+            .copy_from_slice(&pixels[source * width..(source + 1) * width]);
+    }
+    deinterlaced
+}
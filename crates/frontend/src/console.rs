@@ -0,0 +1,61 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+/// The maximum amount of entries kept in a [`LogBuffer`], older entries are discarded.
+const MAX_ENTRIES: usize = 1000;
+
+/// A single record captured from the logger.
+pub struct LogEntry {
+    pub level: log::Level,
+    pub target: String,
+    pub message: String,
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}][{}] {}", self.target, self.level, self.message)
+    }
+}
+
+/// A shared buffer of the most recent log records, so they
+/// can be shown in the `Gui` when there is no console.
+#[derive(Clone, Default)]
+pub struct LogBuffer {
+    entries: Arc<Mutex<VecDeque<LogEntry>>>,
+}
+
+impl LogBuffer {
+    /// Get a [`fern::Output`] that captures all records into this buffer.
+    pub fn output(&self) -> fern::Output {
+        let buffer = self.clone();
+        fern::Output::call(move |record| {
+            buffer.push(LogEntry {
+                level: record.level(),
+                target: record.target().to_string(),
+                message: record.args().to_string(),
+            })
+        })
+    }
+
+    /// Add an entry to the buffer, discarding the oldest entry if the buffer is full.
+    fn push(&self, entry: LogEntry) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.push_back(entry);
+        if entries.len() > MAX_ENTRIES {
+            entries.pop_front();
+        }
+    }
+
+    /// Call `f` with all entries currently in the buffer, oldest first.
+    pub fn with_entries<R>(&self, f: impl FnOnce(&VecDeque<LogEntry>) -> R) -> R {
+        f(&self.entries.lock().unwrap())
+    }
+
+    /// Remove all entries from the buffer.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
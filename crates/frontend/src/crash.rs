@@ -0,0 +1,134 @@
+use std::{
+    fmt::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use chip8::{Chip8, Fault};
+
+use crate::{profile, session::Session};
+
+/// The amount of instructions listed in the notes of an emergency session.
+const EMERGENCY_INSTRUCTIONS: usize = 20;
+
+/// The message of the last panic, recorded by the hook installed with [`install_panic_hook`].
+static LAST_PANIC: Mutex<Option<String>> = Mutex::new(None);
+
+/// A post-mortem report of the `Chip8` state, taken when the processor halts on a fault.
+pub struct CrashDump {
+    pub fault: Fault,
+
+    /// The human readable report, containing the full `Chip8` state,
+    /// the last executed instructions, and the ROM hash.
+    pub report: String,
+
+    /// Where the dump was automatically written to, if writing it succeeded.
+    pub path: Option<PathBuf>,
+}
+
+impl CrashDump {
+    /// Create a new `CrashDump` of the given `Chip8` running the given ROM.
+    pub fn new(chip8: &Chip8, rom: &[u8], fault: Fault) -> Self {
+        let mut report = String::new();
+        write_report(&mut report, chip8, rom, fault).expect("Failed to format crash dump");
+        Self {
+            fault,
+            report,
+            path: None,
+        }
+    }
+
+    /// Write this dump to the crash dump directory, remembering the path it was written to.
+    pub fn save(&mut self) -> anyhow::Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let path = profile::data_dir()
+            .join("crashes")
+            .join(format!("crash-{timestamp}.txt"));
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        self.export(&path)?;
+        log::info!("Crash dump saved to {}", path.display());
+        self.path = Some(path);
+        Ok(())
+    }
+
+    /// Write this dump to the given `path`.
+    pub fn export(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, &self.report)
+            .with_context(|| format!("Failed to write crash dump to {}", path.display()))
+    }
+}
+
+/// Write the crash report for the given `Chip8` state to `w`.
+fn write_report(w: &mut impl Write, chip8: &Chip8, rom: &[u8], fault: Fault) -> fmt::Result {
+    writeln!(w, "CHIP-8 crash dump")?;
+    writeln!(w, "Fault: {fault}")?;
+    writeln!(w, "ROM hash: {:016x}", chip8::rom_hash(rom))?;
+    writeln!(w)?;
+    chip8::report::write_state(w, chip8)
+}
+
+/// Install a panic hook that records the panic message for [`save_emergency_session`],
+/// before running the default hook.
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if let Ok(mut last_panic) = LAST_PANIC.lock() {
+            *last_panic = Some(info.to_string());
+        }
+        default_hook(info);
+    }));
+}
+
+/// The path of the session saved after a panic, which is offered to be restored on the next launch.
+pub fn emergency_session_path() -> PathBuf {
+    profile::data_dir().join("emergency.session")
+}
+
+/// Save the given session as a bundle to the [`emergency_session_path`] after a panic,
+/// noting the panic message and the last executed instructions in its annotations.
+pub fn save_emergency_session(session: &Session) -> anyhow::Result<PathBuf> {
+    let panic = LAST_PANIC
+        .lock()
+        .ok()
+        .and_then(|last_panic| last_panic.clone())
+        .unwrap_or_else(|| "The cause of the panic is unknown.".to_string());
+    let mut notes = String::new();
+    writeln!(notes, "Saved automatically after the app crashed:")?;
+    writeln!(notes, "{panic}")?;
+    writeln!(notes)?;
+    writeln!(notes, "Last instructions:")?;
+    for instruction in session
+        .chip8
+        .instructions()
+        .iter()
+        .take(EMERGENCY_INSTRUCTIONS)
+    {
+        writeln!(
+            notes,
+            "{:#06X}: {:04X} {}",
+            instruction.address, instruction.opcode, instruction.display
+        )?;
+    }
+
+    let mut bundle = session.bundle()?;
+    if !bundle.annotations.is_empty() {
+        writeln!(notes)?;
+        notes.push_str(&bundle.annotations);
+    }
+    bundle.annotations = notes;
+
+    let path = emergency_session_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    bundle.save(&path)?;
+    Ok(path)
+}
@@ -0,0 +1,227 @@
+use std::fmt;
+
+use chip8::{Breakpoint, Chip8, InvalidOpcodePolicy};
+
+/// Events that pause the execution right before they happen.
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub struct Triggers {
+    /// Break before a sprite is drawn (`Dxyn`).
+    pub on_draw: bool,
+
+    /// Break before the sound timer is set (`Fx18`).
+    pub on_sound: bool,
+
+    /// Break before the program waits for a key press (`Fx0A`).
+    pub on_key_wait: bool,
+
+    /// Break after an opcode that isn't an instruction of the variant is skipped.
+    pub on_invalid_opcode: bool,
+
+    /// Break after an instruction writes to memory that was executed before.
+    pub on_code_write: bool,
+
+    /// Break after an instruction changes one of the watched `Vx` registers.
+    pub watch_v: [bool; 16],
+
+    /// Break after an instruction changes the `I` register.
+    pub watch_i: bool,
+}
+
+impl Triggers {
+    /// Returns whether the given register is watched for changes.
+    fn is_watched(&self, register: Register) -> bool {
+        match register {
+            Register::V(x) => self.watch_v[x],
+            Register::I => self.watch_i,
+        }
+    }
+}
+
+/// A register of the `Chip8` processor that can be watched for changes.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    V(usize),
+    I,
+}
+
+impl Register {
+    /// All registers that can be watched, in the order `V0`-`VF`, `I`.
+    const ALL: [Register; 17] = [
+        Register::V(0x0),
+        Register::V(0x1),
+        Register::V(0x2),
+        Register::V(0x3),
+        Register::V(0x4),
+        Register::V(0x5),
+        Register::V(0x6),
+        Register::V(0x7),
+        Register::V(0x8),
+        Register::V(0x9),
+        Register::V(0xA),
+        Register::V(0xB),
+        Register::V(0xC),
+        Register::V(0xD),
+        Register::V(0xE),
+        Register::V(0xF),
+        Register::I,
+    ];
+
+    /// Get the current value of this register in the given `Chip8`.
+    fn value(self, chip8: &Chip8) -> usize {
+        match self {
+            Register::V(x) => usize::from(chip8.registers()[x]),
+            Register::I => chip8.index(),
+        }
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Register::V(x) => write!(f, "V{x:X}"),
+            Register::I => write!(f, "I"),
+        }
+    }
+}
+
+/// The reason the `Debugger` paused the execution.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BreakReason {
+    /// A sprite is about to be drawn at the given address.
+    Draw(usize),
+
+    /// The sound timer is about to be set at the given address.
+    SoundTimer(usize),
+
+    /// The program is about to wait for a key press at the given address.
+    KeyWait(usize),
+
+    /// A breakpoint was hit before the instruction at the given address.
+    Breakpoint(usize),
+
+    /// The opcode at the given address isn't an instruction of the variant, and was skipped.
+    InvalidOpcode(usize),
+
+    /// The instruction at `address` wrote to the executed code at `target`.
+    CodeWrite { address: usize, target: usize },
+
+    /// The instruction at `address` changed a watched register from `old` to `new`.
+    RegisterChanged {
+        register: Register,
+        address: usize,
+        old: usize,
+        new: usize,
+    },
+}
+
+impl fmt::Display for BreakReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BreakReason::Draw(address) => write!(f, "Sprite draw at {address:#06X}"),
+            BreakReason::SoundTimer(address) => write!(f, "Sound timer set at {address:#06X}"),
+            BreakReason::KeyWait(address) => write!(f, "Key wait at {address:#06X}"),
+            BreakReason::Breakpoint(address) => write!(f, "Breakpoint at {address:#06X}"),
+            BreakReason::InvalidOpcode(address) => {
+                write!(f, "Invalid opcode skipped at {address:#06X}")
+            }
+            BreakReason::CodeWrite { address, target } => write!(
+                f,
+                "Executed code at {target:#06X} modified at {address:#06X}"
+            ),
+            BreakReason::RegisterChanged {
+                register,
+                address,
+                old,
+                new,
+            } => write!(
+                f,
+                "{register} changed from {old:#X} to {new:#X} at {address:#06X}"
+            ),
+        }
+    }
+}
+
+/// Decides when the execution of the `Chip8` should be paused.
+#[derive(Default)]
+pub struct Debugger {
+    pub triggers: Triggers,
+
+    /// The breakpoints, with or without a condition, checked after every instruction.
+    pub breakpoints: Vec<Breakpoint>,
+
+    /// When `true`, the next instruction is executed without being checked,
+    /// so resuming from a break doesn't immediately break again.
+    skip_next: bool,
+
+    /// The values of `V0`-`VF` and `I` before the last checked instruction was executed.
+    registers: [usize; 17],
+}
+
+impl Debugger {
+    /// Check whether the execution should break before the next instruction is executed.
+    pub fn check(&mut self, chip8: &Chip8) -> Option<BreakReason> {
+        for (value, register) in self.registers.iter_mut().zip(Register::ALL) {
+            *value = register.value(chip8);
+        }
+
+        if std::mem::take(&mut self.skip_next) {
+            return None;
+        }
+
+        let opcode = chip8.next_opcode()?;
+        let address = chip8.pc();
+        match opcode & 0xF0FF {
+            _ if self.triggers.on_draw && opcode & 0xF000 == 0xD000 => {
+                Some(BreakReason::Draw(address))
+            }
+            0xF018 if self.triggers.on_sound => Some(BreakReason::SoundTimer(address)),
+            0xF00A if self.triggers.on_key_wait => Some(BreakReason::KeyWait(address)),
+            _ => None,
+        }
+    }
+
+    /// Check whether the instruction executed since the last call to [`Debugger::check`]
+    /// changed any of the watched registers.
+    pub fn check_registers(&self, chip8: &Chip8) -> Option<BreakReason> {
+        let address = chip8.instructions().front()?.address;
+        self.registers
+            .iter()
+            .zip(Register::ALL)
+            .filter(|&(_, register)| self.triggers.is_watched(register))
+            .find_map(|(&old, register)| {
+                let new = register.value(chip8);
+                (old != new).then_some(BreakReason::RegisterChanged {
+                    register,
+                    address,
+                    old,
+                    new,
+                })
+            })
+    }
+
+    /// Check whether the instruction executed since the last call to [`Debugger::check`]
+    /// wrote to memory that was executed before.
+    pub fn check_code_write(&self, chip8: &Chip8) -> Option<BreakReason> {
+        if !self.triggers.on_code_write {
+            return None;
+        }
+        chip8.code_write().map(|write| BreakReason::CodeWrite {
+            address: write.address,
+            target: write.target,
+        })
+    }
+
+    /// The policy the `Chip8` should handle invalid opcodes with, so they can be broken on.
+    pub fn invalid_opcode_policy(&self) -> InvalidOpcodePolicy {
+        if self.triggers.on_invalid_opcode {
+            InvalidOpcodePolicy::Break
+        } else {
+            InvalidOpcodePolicy::Ignore
+        }
+    }
+
+    /// Resume the execution after a break, without breaking on the current instruction again.
+    pub fn resume(&mut self) {
+        self.skip_next = true;
+    }
+}
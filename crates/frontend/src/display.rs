@@ -0,0 +1,147 @@
+use std::{collections::BTreeMap, fmt, path::PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+/// The maximum amount of colors in the gradient of a [`DisplayStyle`].
+pub const MAX_GRADIENT_COLORS: usize = 4;
+
+/// The built-in color gradients, by name. Colors are listed darkest first.
+pub const GRADIENTS: [(&str, &[[u8; 3]]); 4] = [
+    ("Amber", &[[20, 8, 0], [255, 176, 0]]),
+    ("Green Phosphor", &[[0, 16, 4], [51, 255, 102]]),
+    (
+        "Game Boy",
+        &[[15, 56, 15], [48, 98, 48], [139, 172, 15], [155, 188, 15]],
+    ),
+    ("Sepia", &[[40, 26, 13], [112, 66, 20], [240, 220, 180]]),
+];
+
+/// The shape pixels are drawn with by the fragment shader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PixelShape {
+    #[default]
+    Square,
+
+    /// Round dots, like an LED matrix.
+    Round,
+
+    /// Red, green and blue sub-pixels, like an LCD screen.
+    Lcd,
+}
+
+impl PixelShape {
+    /// All pixel shapes, in the order they should be listed.
+    pub const ALL: [PixelShape; 3] = [PixelShape::Square, PixelShape::Round, PixelShape::Lcd];
+
+    /// The value of the `pixel_shape` uniform of the fragment shader.
+    pub fn uniform(self) -> i32 {
+        match self {
+            PixelShape::Square => 0,
+            PixelShape::Round => 1,
+            PixelShape::Lcd => 2,
+        }
+    }
+}
+
+impl fmt::Display for PixelShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PixelShape::Square => "Square",
+            PixelShape::Round => "Round",
+            PixelShape::Lcd => "LCD",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// How the display is presented. This only changes what is shown,
+/// not the display of the `Chip8`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplayStyle {
+    /// The amount of recent frames that are blended, `1` for no blending.
+    pub frame_blending: usize,
+
+    /// The colors the brightness of the display is mapped to, darkest first.
+    /// Fewer than two colors keep the colors of the display.
+    pub gradient: Vec<[u8; 3]>,
+
+    pub pixel_shape: PixelShape,
+
+    /// The fraction of a pixel left dark around round and LCD pixels, from `0.0` to `0.5`.
+    pub pixel_gap: f32,
+}
+
+impl Default for DisplayStyle {
+    fn default() -> Self {
+        Self {
+            frame_blending: 1,
+            gradient: Vec::new(),
+            pixel_shape: PixelShape::default(),
+            pixel_gap: 0.15,
+        }
+    }
+}
+
+impl DisplayStyle {
+    /// The name of the built-in gradient this style uses, `None` if it uses no
+    /// gradient, or `Some("Custom")` if the gradient was edited.
+    pub fn gradient_name(&self) -> Option<&'static str> {
+        if self.gradient.len() < 2 {
+            return None;
+        }
+        let name = GRADIENTS
+            .iter()
+            .find(|(_, colors)| *colors == self.gradient.as_slice())
+            .map_or("Custom", |(name, _)| name);
+        Some(name)
+    }
+
+    /// The colors of the gradient as the `vec3` array uniform of the fragment shader,
+    /// padded to [`MAX_GRADIENT_COLORS`].
+    pub fn gradient_uniform(&self) -> [f32; MAX_GRADIENT_COLORS * 3] {
+        let mut uniform = [0.0; MAX_GRADIENT_COLORS * 3];
+        for (values, color) in uniform.chunks_exact_mut(3).zip(&self.gradient) {
+            for (value, channel) in values.iter_mut().zip(color) {
+                *value = f32::from(*channel) / 255.0;
+            }
+        }
+        uniform
+    }
+}
+
+/// Display styles saved by the user under a name.
+#[derive(Default, Serialize, Deserialize)]
+pub struct DisplayPresets {
+    pub presets: BTreeMap<String, DisplayStyle>,
+}
+
+impl DisplayPresets {
+    /// Load the saved presets from disk, or no presets if none have been saved yet.
+    pub fn load() -> Self {
+        let path = Self::path();
+        match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                log::error!("Failed to parse display presets {}: {e}", path.display());
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Save the presets to disk.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_vec_pretty(self)?;
+        std::fs::write(&path, json)
+            .with_context(|| format!("Failed to write display presets to {}", path.display()))
+    }
+
+    fn path() -> PathBuf {
+        crate::profile::data_dir().join("display_presets.json")
+    }
+}
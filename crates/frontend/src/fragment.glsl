@@ -7,6 +7,46 @@ out vec4 out_color;
 
 uniform sampler2D tex;
 
+// the resolution of the display in pixels
+uniform vec2 resolution;
+
+// the colors the brightness is mapped to, darkest first
+uniform vec3 gradient[4];
+// the amount of colors in the gradient, grading is disabled with fewer than 2
+uniform int gradient_len;
+
+// 0: square, 1: round, 2: LCD sub-pixels
+uniform int pixel_shape;
+// the fraction of a pixel left dark around round and LCD pixels
+uniform float pixel_gap;
+
+vec3 grade(vec3 color) {
+    if (gradient_len < 2) {
+        return color;
+    }
+    float luminance = dot(color, vec3(0.299, 0.587, 0.114));
+    float position = luminance * float(gradient_len - 1);
+    int index = min(int(position), gradient_len - 2);
+    return mix(gradient[index], gradient[index + 1], position - float(index));
+}
+
 void main() {
-    out_color = texture(tex, tex_coord);
-}
\ No newline at end of file
+    vec3 color = grade(texture(tex, tex_coord).rgb);
+    // the position inside the current display pixel
+    vec2 cell = fract(tex_coord * resolution);
+    // the gaps between pixels show the darkest color
+    vec3 gap_color = grade(vec3(0.0));
+
+    if (pixel_shape == 1) {
+        float radius = 0.5 - pixel_gap * 0.5;
+        float edge = 1.0 - smoothstep(radius - 0.05, radius, distance(cell, vec2(0.5)));
+        color = mix(gap_color, color, edge);
+    } else if (pixel_shape == 2) {
+        int channel = min(int(cell.x * 3.0), 2);
+        vec3 mask = vec3(channel == 0, channel == 1, channel == 2);
+        float inside = step(pixel_gap * 0.5, cell.y) * step(cell.y, 1.0 - pixel_gap * 0.5);
+        color = mix(gap_color, color * mix(vec3(0.3), vec3(1.0), mask), inside);
+    }
+
+    out_color = vec4(color, 1.0);
+}
@@ -1,57 +1,108 @@
 use std::{
+    collections::{BTreeMap, HashMap},
     path::PathBuf,
-    sync::{atomic::Ordering, Arc, Mutex},
+    sync::{Arc, Mutex},
 };
 
-use chip8::{graphics::RGB8, Chip8};
+use chip8::{
+    disasm,
+    graphics::{OCTO_PALETTES, RGB8},
+    lint, reference,
+    search::{RamSearch, SearchFilter},
+    watch::Watch,
+    Breakpoint, Chip8, Comparison, Condition, Fault, IndexIncrement, MachineCodePolicy, MemorySize,
+    Operand, ProcessorState, QuirkPreset, Quirks, Symbols, Timing, Variant,
+};
 use eframe::egui::{self, Context, Key, Ui};
 
-use crate::renderer::Renderer;
-
-/// Key mapping from a standard english keyboard to Chip8 key codes.
-static KEY_MAP: [(Key, u8); 16] = [
-    (Key::Num1, 0x1),
-    (Key::Num2, 0x2),
-    (Key::Num3, 0x3),
-    (Key::Num4, 0xC),
-    (Key::Q, 0x4),
-    (Key::W, 0x5),
-    (Key::E, 0x6),
-    (Key::R, 0xD),
-    (Key::A, 0x7),
-    (Key::S, 0x8),
-    (Key::D, 0x9),
-    (Key::F, 0xE),
-    (Key::Z, 0xA),
-    (Key::X, 0x0),
-    (Key::C, 0xB),
-    (Key::V, 0xF),
-];
+use crate::console::LogBuffer;
+use crate::crash::CrashDump;
+use crate::debugger::{BreakReason, Triggers};
+use crate::display::{DisplayPresets, DisplayStyle, PixelShape, GRADIENTS, MAX_GRADIENT_COLORS};
+use crate::keypad::{KeyMap, KeypadLayout};
+use crate::latency::{LatencySample, LatencyTest};
+use crate::logging::{LogSettings, Logging, TRACE_TARGET};
+use crate::net::{self, NetStatus};
+use crate::profile::{Cheat, RomProfile, TouchButton};
+use crate::profiler::Profiler;
+use crate::renderer::{DisplayFrame, Renderer};
+use crate::timeline::{EventKind, Lane, Timeline};
 
 /// A message sent from the GUI to the backend.
 pub enum Chip8Message {
-    /// Load the given ROM into the `Chip8`.
-    LoadRom(Vec<u8>),
+    /// Load the given ROM into the `Chip8` of the current tab,
+    /// naming the tab after the ROM file.
+    LoadRom { name: String, data: Vec<u8> },
+
+    /// Edit the ROM of the current tab, and reload it.
+    EditRom(RomEdit),
+
+    /// Write the ROM of the current tab to the given file.
+    ExportRom(PathBuf),
+
+    /// Write the given bytes into the memory of the current tab, starting at `address`.
+    WriteMemory { address: usize, data: Vec<u8> },
 
     /// Reset the currently loaded `Chip8` ROM.
     ResetROM,
 
-    /// Set the foreground color of the `Chip8` graphics.
-    SetForegroundColor(RGB8),
-
-    /// Set the background color of the `Chip8` graphics.
-    SetBackgroundColor(RGB8),
+    /// Set the colors of the `Chip8` graphics for every combination of planes,
+    /// see [`Chip8::palette`](chip8::Chip8::palette).
+    SetPalette([RGB8; 4]),
 
     /// Set the amount of steps the `Chip8` interpreter should
     /// advance on each frame.
     SetStepRate(u32),
 
+    /// Set how the amount of steps run in a frame is decided in the Chip8 instance.
+    SetTiming(Timing),
+
+    /// Set the intensity of the gamepad rumble played while the sound timer is active,
+    /// from `0.0` (off) to `1.0` (full strength).
+    SetRumbleIntensity(f32),
+
+    /// Set the variant of the platform emulated by the Chip8 instance.
+    SetVariant(Variant),
+
+    /// Set the address programs are loaded at in the Chip8 instance, and reload them.
+    SetStartAddress(usize),
+
+    /// Set the size of the memory of the Chip8 instance, and reload the programs.
+    SetMemorySize(MemorySize),
+
+    /// Set how the Chip8 instance handles calls to machine code routines (`0nnn`).
+    SetMachineCodePolicy(MachineCodePolicy),
+
     /// Enable/disable the shift quirk in the Chip8 instance
     SetShiftQuirk(bool),
 
     /// Enable/disable the vblank wait option in the Chip8 instance.
     SetVblankWait(bool),
 
+    /// Enable/disable wrapping sprites drawn past the bottom edge of the display in the Chip8 instance.
+    SetVerticalWrap(bool),
+
+    /// Enable/disable wrapping sprites drawn past the right edge of the display in the Chip8 instance.
+    SetHorizontalWrap(bool),
+
+    /// Enable/disable pausing the timers while the Chip8 instance is waiting for a key press.
+    SetPauseTimersOnKeyWait(bool),
+
+    /// Enable/disable resetting VF with the logic instructions in the Chip8 instance.
+    SetLogicQuirk(bool),
+
+    /// Enable/disable setting VF when `I` overflows with `Fx1E` in the Chip8 instance.
+    SetIndexOverflowQuirk(bool),
+
+    /// Enable/disable jumping relative to `Vx` with `Bxnn` in the Chip8 instance.
+    SetJumpQuirk(bool),
+
+    /// Set how `Fx55` and `Fx65` change `I` in the Chip8 instance.
+    SetIndexIncrement(IndexIncrement),
+
+    /// Replace all quirk settings of the Chip8 instance with the ones of a platform.
+    SetQuirkPreset(QuirkPreset),
+
     /// Update the key state of the `Chip8`. This contains
     /// a `Vec` of tuples, where each tuple contains a `u8` `Chip8` key
     /// code, as well as a `bool` representing if it is pressed down or not.
@@ -66,10 +117,163 @@ pub enum Chip8Message {
     /// Load the `Chip8` state and any `App` state.
     LoadState(PathBuf),
 
+    /// Load a symbol file naming the addresses of the loaded program.
+    LoadSymbols(PathBuf),
+
+    /// Restart the ROM and record the key presses into a movie.
+    StartRecording,
+
+    /// Stop recording the movie, and write it to the given path.
+    StopRecording(PathBuf),
+
     /// This indicates that the "step" button was clicked,
-    /// meaning the user would like to execute one step of the interpreter.
+    /// meaning the user would like to advance the interpreter by the given granularity.
     /// This should still step the interpreter even if the execution is paused.
-    Step,
+    Step(Granularity),
+
+    /// Undo the last executed instruction, even if the execution is paused.
+    StepBack,
+
+    /// Host a network session on the given port, optionally letting
+    /// the spectator use the keypad.
+    HostSession { port: u16, allow_input: bool },
+
+    /// Join the network session hosted at the given address as a spectator.
+    JoinSession(String),
+
+    /// Stop hosting or leave the current network session.
+    LeaveSession,
+
+    /// Grant or revoke the spectator's access to the keypad.
+    SetRemoteInput(bool),
+
+    /// Set the events that pause the execution.
+    SetTriggers(Triggers),
+
+    /// Set the breakpoints of the debugger.
+    SetBreakpoints(Vec<Breakpoint>),
+
+    /// Add a cheat to the profile of the current ROM.
+    AddCheat(Cheat),
+
+    /// Remove the cheat at the given index from the profile of the current ROM.
+    RemoveCheat(usize),
+
+    /// Enable/disable the cheat at the given index.
+    SetCheatEnabled(usize, bool),
+
+    /// Set the layout of the touch keypad for the current ROM.
+    /// `None` restores the default layout.
+    SetTouchLayout(Option<Vec<TouchButton>>),
+
+    /// Set how the keyboard is mapped to the keypad for the current ROM.
+    SetKeypadLayout(KeypadLayout),
+
+    /// Set the label describing what the given key does in the current ROM.
+    /// An empty label removes it.
+    SetKeyLabel(u8, String),
+
+    /// Write the report of the current crash dump to the given path.
+    ExportCrashDump(PathBuf),
+
+    /// Enable/disable the subroutine profiler.
+    SetProfiling(bool),
+
+    /// Clear the statistics gathered by the subroutine profiler.
+    ResetProfile,
+
+    /// Write the call graph recorded by the subroutine profiler to the given path.
+    ExportCallGraph(PathBuf),
+
+    /// Enable/disable recording the execution timeline.
+    SetTimelineRecording(bool),
+
+    /// Clear the events recorded on the execution timeline.
+    ResetTimeline,
+
+    /// Set the annotations of the current session.
+    SetAnnotations(String),
+
+    /// Export the current session to a bundle at the given path.
+    ExportSession(PathBuf),
+
+    /// Import the session bundle at the given path into a new tab.
+    ImportSession(PathBuf),
+
+    /// Start/stop the input latency test.
+    SetLatencyTest(bool),
+
+    /// Import the session saved when the app last crashed into a new tab, and delete it.
+    RestoreEmergencySession,
+
+    /// Delete the session saved when the app last crashed.
+    DiscardEmergencySession,
+
+    /// Open a new tab with an empty `Chip8` instance, and switch to it.
+    NewTab,
+
+    /// Switch to the tab at the given index.
+    SelectTab(usize),
+
+    /// Close the tab at the given index.
+    CloseTab(usize),
+}
+
+/// An edit of the loaded ROM.
+pub enum RomEdit {
+    /// Strip the trailing zero padding.
+    Trim,
+
+    /// Append the given data block.
+    Append(Vec<u8>),
+
+    /// Move the segment from `start` up to `end` to `destination`.
+    Relocate {
+        start: usize,
+        end: usize,
+        destination: usize,
+    },
+}
+
+/// How far the interpreter advances when the "step" button is clicked.
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum Granularity {
+    /// Execute a single instruction.
+    #[default]
+    Instruction,
+
+    /// Execute the steps of one 60Hz frame.
+    Frame,
+
+    /// Execute until the next sprite has been drawn.
+    UntilDraw,
+
+    /// Execute a single instruction, running through a subroutine it calls.
+    Over,
+
+    /// Execute until the current subroutine returns.
+    Out,
+}
+
+impl Granularity {
+    const ALL: [Granularity; 5] = [
+        Granularity::Instruction,
+        Granularity::Frame,
+        Granularity::UntilDraw,
+        Granularity::Over,
+        Granularity::Out,
+    ];
+
+    /// Get a display friendly name for this granularity.
+    fn name(self) -> &'static str {
+        match self {
+            Granularity::Instruction => "Instruction",
+            Granularity::Frame => "Frame",
+            Granularity::UntilDraw => "Until draw",
+            Granularity::Over => "Over call",
+            Granularity::Out => "Out of subroutine",
+        }
+    }
 }
 
 /// The current view in the `Gui`.
@@ -83,41 +287,178 @@ enum CurrentView {
     Debug,
 }
 
+/// State owned by the `App` that is displayed by the `Gui`.
+pub struct GuiState<'a> {
+    pub chip8: &'a Chip8,
+
+    /// The RGB buffer shown on the screen.
+    pub display: &'a DisplayFrame,
+
+    pub net_status: &'a NetStatus,
+    pub profile: &'a RomProfile,
+
+    /// The data of the loaded ROM.
+    pub rom: &'a [u8],
+
+    pub paused: bool,
+
+    /// Whether a movie of the key presses is being recorded.
+    pub recording: bool,
+
+    /// The reason the execution was last paused by the debugger, if any.
+    pub break_reason: Option<BreakReason>,
+
+    /// The dump taken when the processor halted on a fault, if it has.
+    pub crash_dump: Option<&'a CrashDump>,
+
+    pub profiler: &'a Profiler,
+    pub timeline: &'a Timeline,
+
+    /// The user's notes on the current session.
+    pub annotations: &'a str,
+
+    /// The names of all open tabs.
+    pub tabs: &'a [&'a str],
+
+    /// The index of the tab whose state is shown.
+    pub active_tab: usize,
+
+    pub latency: &'a LatencyTest,
+}
+
 /// A user interface constructed with `egui`,
 /// with a `glow` renderer used to display the `Chip8` state.
 pub struct Gui {
     menu_panel: MenuPanel,
     config_window: ConfigWindow,
+    network_window: NetworkWindow,
+    cheat_window: CheatWindow,
+    key_label_window: KeyLabelWindow,
+    touch_keypad: TouchKeypad,
+    crash_window: CrashWindow,
+    recovery_window: RecoveryWindow,
+    session_window: SessionWindow,
+    rom_tool_window: RomToolWindow,
+    sprite_editor_window: SpriteEditorWindow,
+    log_window: LogWindow,
+    latency_window: LatencyWindow,
     screen_view: ScreenView,
-    debug_view: DebugView,
+    /// The debug view of each tab, so every tab keeps its own watches and searches.
+    debug_views: Vec<DebugView>,
     current_view: CurrentView,
+
+    /// When `true`, only the screen is shown, see [`StreamerSettings`].
+    streamer_mode: bool,
 }
 
 impl Gui {
-    /// Create a new `Gui` from an [`eframe::CreationContext`].
-    pub fn new(cc: &eframe::CreationContext) -> Self {
+    /// Create a new `Gui` from an [`eframe::CreationContext`],
+    /// showing the records captured by `logging` in its log window.
+    pub fn new(cc: &eframe::CreationContext, logging: Logging) -> Self {
         let gl = cc.gl.as_ref().unwrap();
 
         Self {
             menu_panel: Default::default(),
-            config_window: Default::default(),
+            config_window: ConfigWindow {
+                display_presets: DisplayPresets::load(),
+                log_settings: logging.settings(),
+                logging: logging.clone(),
+                ..Default::default()
+            },
+            network_window: Default::default(),
+            cheat_window: Default::default(),
+            key_label_window: Default::default(),
+            touch_keypad: Default::default(),
+            crash_window: Default::default(),
+            recovery_window: RecoveryWindow {
+                visible: crate::crash::emergency_session_path().exists(),
+            },
+            session_window: Default::default(),
+            rom_tool_window: Default::default(),
+            sprite_editor_window: Default::default(),
+            log_window: LogWindow::new(logging.buffer().clone()),
+            latency_window: Default::default(),
             screen_view: ScreenView::new(gl),
-            debug_view: Default::default(),
+            debug_views: Vec::new(),
             current_view: Default::default(),
+            streamer_mode: false,
         }
     }
 
+    /// Whether the debug view, which shows the trace of the executed instructions, is shown.
+    pub fn showing_debug_view(&self) -> bool {
+        !self.streamer_mode && matches!(self.current_view, CurrentView::Debug)
+    }
+
     /// Renders the next frame, which includes any UI updates as well
-    /// as the `Chip8` graphics state.
-    pub fn update(&mut self, ctx: &Context, chip8: &Chip8) -> Vec<Chip8Message> {
+    /// as the display buffer of the given `state`.
+    pub fn update(&mut self, ctx: &Context, state: &GuiState) -> Vec<Chip8Message> {
         let mut messages = Vec::new();
 
+        self.screen_view
+            .set_display_style(&self.config_window.display_style);
+        if self.streamer_mode {
+            if ctx.input().key_pressed(Key::Escape) {
+                self.streamer_mode = false;
+            }
+            let touch_keys =
+                self.touch_keypad
+                    .update(ctx, ctx.available_rect(), state, &mut messages);
+            self.screen_view.update_streamer(
+                ctx,
+                state,
+                &self.config_window.streamer,
+                self.key_label_window.show_overlay,
+            );
+            self.update_key_state(
+                ctx,
+                state.profile.keypad_layout.key_map(),
+                touch_keys,
+                &mut messages,
+            );
+            return messages;
+        }
+
+        self.debug_views
+            .resize_with(state.tabs.len(), Default::default);
+
         let menu_response = self
             .menu_panel
-            .update(ctx, &self.current_view, &mut messages);
+            .update(ctx, &self.current_view, state, &mut messages);
+        let closed_tab = Self::draw_tab_bar(ctx, state, &mut messages);
         if menu_response.toggle_config {
             self.config_window.toggle_visibility();
         }
+        if menu_response.toggle_network {
+            self.network_window.toggle_visibility();
+        }
+        if menu_response.toggle_cheats {
+            self.cheat_window.toggle_visibility();
+        }
+        if menu_response.toggle_key_labels {
+            self.key_label_window.toggle_visibility();
+        }
+        if menu_response.toggle_touch_keypad {
+            self.touch_keypad.toggle_visibility();
+        }
+        if menu_response.toggle_session {
+            self.session_window.toggle_visibility();
+        }
+        if menu_response.toggle_rom_tools {
+            self.rom_tool_window.toggle_visibility();
+        }
+        if menu_response.toggle_sprite_editor {
+            self.sprite_editor_window.toggle_visibility();
+        }
+        if menu_response.toggle_log {
+            self.log_window.toggle_visibility();
+        }
+        if menu_response.toggle_latency {
+            self.latency_window.toggle_visibility();
+        }
+        if menu_response.enter_streamer_mode {
+            self.streamer_mode = true;
+        }
         if menu_response.reset {
             // send the color message to the chip8 backend so that
             // it restores the color settings for this session
@@ -129,31 +470,113 @@ impl Gui {
                 CurrentView::Debug => CurrentView::Screen,
             }
         }
-        if menu_response.toggle_pause {
-            self.menu_panel.toggle_pause();
-            self.debug_view.toggle_pause();
-        }
 
+        // the keypad covers the area left over by the panels
+        let touch_keys = self
+            .touch_keypad
+            .update(ctx, ctx.available_rect(), state, &mut messages);
+
+        let sprite_preview = self.sprite_editor_window.preview();
         match self.current_view {
-            CurrentView::Screen => self.screen_view.update(ctx, chip8),
-            CurrentView::Debug => self.debug_view.update(ctx, &self.screen_view, chip8),
+            CurrentView::Screen => self.screen_view.update(
+                ctx,
+                state,
+                self.key_label_window.show_overlay,
+                sprite_preview,
+            ),
+            CurrentView::Debug => self.debug_views[state.active_tab].update(
+                ctx,
+                &self.screen_view,
+                state,
+                self.key_label_window.show_overlay,
+                sprite_preview,
+                &mut messages,
+            ),
         }
 
         self.config_window.update(ctx, &mut messages);
+        self.network_window
+            .update(ctx, state.net_status, &mut messages);
+        self.cheat_window
+            .update(ctx, &state.profile.cheats, &mut messages);
+        self.key_label_window.update(ctx, state, &mut messages);
+        self.touch_keypad.update_window(ctx, state, &mut messages);
+        self.crash_window
+            .update(ctx, state.crash_dump, &mut messages);
+        self.recovery_window.update(ctx, &mut messages);
+        self.session_window
+            .update(ctx, state.annotations, &mut messages);
+        self.rom_tool_window
+            .update(ctx, state.rom, state.chip8, &mut messages);
+        self.sprite_editor_window
+            .update(ctx, state.chip8, &mut messages);
+        self.log_window.update(ctx);
+        self.latency_window
+            .update(ctx, state.latency, &mut messages);
+
+        self.update_key_state(
+            ctx,
+            state.profile.keypad_layout.key_map(),
+            touch_keys,
+            &mut messages,
+        );
 
-        self.update_key_state(ctx, &mut messages);
+        if let Some(index) = closed_tab {
+            self.debug_views.remove(index);
+        }
 
         messages
     }
 
+    /// Draw a bar with a tab for every open session.
+    ///
+    /// Returns the index of the tab that was closed, if any.
+    fn draw_tab_bar(
+        ctx: &Context,
+        state: &GuiState,
+        messages: &mut Vec<Chip8Message>,
+    ) -> Option<usize> {
+        let mut closed_tab = None;
+        egui::TopBottomPanel::top("tab_panel").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let closable = state.tabs.len() > 1;
+                for (i, name) in state.tabs.iter().enumerate() {
+                    if ui.selectable_label(i == state.active_tab, *name).clicked() {
+                        messages.push(Chip8Message::SelectTab(i));
+                    }
+                    if closable && ui.small_button("\u{2715}").clicked() {
+                        closed_tab = Some(i);
+                        messages.push(Chip8Message::CloseTab(i));
+                    }
+                    ui.separator();
+                }
+                if ui.button("\u{2795}").on_hover_text("New tab").clicked() {
+                    messages.push(Chip8Message::NewTab);
+                }
+            });
+        });
+        closed_tab
+    }
+
     /// Handles key events by updating the key
     /// state in the `Chip8` instance if necessary.
-    fn update_key_state(&mut self, ctx: &Context, messages: &mut Vec<Chip8Message>) {
+    /// The keyboard is mapped to the keypad with `key_map`, and the keys
+    /// pressed on the touch keypad are given by `touch_keys`, indexed by key code.
+    fn update_key_state(
+        &mut self,
+        ctx: &Context,
+        key_map: &KeyMap,
+        touch_keys: [bool; 16],
+        messages: &mut Vec<Chip8Message>,
+    ) {
         let mut update = Vec::new();
-        if !ctx.wants_keyboard_input() {
+        let keyboard_enabled = !ctx.wants_keyboard_input();
+        if keyboard_enabled || self.touch_keypad.enabled {
             let keys_down = &ctx.input().keys_down;
-            for (key, key_code) in KEY_MAP {
-                update.push((key_code, keys_down.contains(&key)));
+            for &(key, key_code) in key_map {
+                let pressed = (keyboard_enabled && keys_down.contains(&key))
+                    || touch_keys[usize::from(key_code)];
+                update.push((key_code, pressed));
             }
         }
         if !update.is_empty() {
@@ -179,15 +602,43 @@ struct MenuPanelResponse {
     /// Indicates to the `Gui` to toggle the current view.
     toggle_view: bool,
 
-    /// Indicates to the `Gui` to toggle its pause state.
-    toggle_pause: bool,
+    /// Indicates whether the network window should be toggled.
+    toggle_network: bool,
+
+    /// Indicates whether the cheat window should be toggled.
+    toggle_cheats: bool,
+
+    /// Indicates whether the session window should be toggled.
+    toggle_session: bool,
+
+    /// Indicates whether the key label window should be toggled.
+    toggle_key_labels: bool,
+
+    /// Indicates whether the touch keypad window should be toggled.
+    toggle_touch_keypad: bool,
+
+    /// Indicates whether the ROM tool window should be toggled.
+    toggle_rom_tools: bool,
+
+    /// Indicates whether the sprite editor window should be toggled.
+    toggle_sprite_editor: bool,
+
+    /// Indicates whether the log window should be toggled.
+    toggle_log: bool,
+
+    /// Indicates whether the latency test window should be toggled.
+    toggle_latency: bool,
+
+    /// Indicates whether the `Gui` should switch to streamer mode.
+    enter_streamer_mode: bool,
 }
 
 /// A menu panel intended to be placed near the top of the window,
 /// shows Ui widgets for selecting roms, saving state, etc.
 #[derive(Default)]
 struct MenuPanel {
-    paused: bool,
+    /// The granularity used by the step button.
+    step_granularity: Granularity,
 }
 
 impl MenuPanel {
@@ -197,14 +648,15 @@ impl MenuPanel {
         &mut self,
         ctx: &Context,
         view: &CurrentView,
+        state: &GuiState,
         messages: &mut Vec<Chip8Message>,
     ) -> MenuPanelResponse {
         let mut response = MenuPanelResponse::default();
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
                 if ui.button("\u{1F4C1} Open ROM").clicked() {
-                    if let Some(data) = Self::load_file_from_dialog() {
-                        messages.push(Chip8Message::LoadRom(data));
+                    if let Some((name, data)) = Self::load_file_from_dialog() {
+                        messages.push(Chip8Message::LoadRom { name, data });
                         response.reset = true;
                     }
                 };
@@ -213,6 +665,42 @@ impl MenuPanel {
                     response.toggle_config = true;
                 }
 
+                if ui.button("\u{1F310} Network").clicked() {
+                    response.toggle_network = true;
+                }
+
+                if ui.button("\u{1F3B2} Cheats").clicked() {
+                    response.toggle_cheats = true;
+                }
+
+                if ui.button("\u{2328} Key Labels").clicked() {
+                    response.toggle_key_labels = true;
+                }
+
+                if ui.button("\u{1F446} Touch").clicked() {
+                    response.toggle_touch_keypad = true;
+                }
+
+                if ui.button("\u{1F4E6} Session").clicked() {
+                    response.toggle_session = true;
+                }
+
+                if ui.button("\u{1F527} ROM Tools").clicked() {
+                    response.toggle_rom_tools = true;
+                }
+
+                if ui.button("\u{1F3A8} Sprites").clicked() {
+                    response.toggle_sprite_editor = true;
+                }
+
+                if ui.button("\u{1F4DC} Log").clicked() {
+                    response.toggle_log = true;
+                }
+
+                if ui.button("\u{23F1} Latency").clicked() {
+                    response.toggle_latency = true;
+                }
+
                 ui.separator();
 
                 if ui.button("\u{2B06} Save State").clicked() {
@@ -228,11 +716,51 @@ impl MenuPanel {
                     }
                 }
 
+                if ui
+                    .button("\u{1F3F7} Load Symbols")
+                    .on_hover_text("Name the addresses of the program with the labels of a symbol file, e.g. one exported by Octo.")
+                    .clicked()
+                {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .add_filter("Symbol files", &["sym", "txt"])
+                        .pick_file()
+                    {
+                        messages.push(Chip8Message::LoadSymbols(path));
+                    }
+                }
+
+                if state.recording {
+                    if ui.button("\u{23F9} Stop Recording").clicked() {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("CHIP-8 movies", &["c8m"])
+                            .save_file()
+                        {
+                            messages.push(Chip8Message::StopRecording(path));
+                        }
+                    }
+                } else if ui
+                    .button("\u{23FA} Record Movie")
+                    .on_hover_text("Restart the ROM and record the key presses, to replay them exactly with the `replay` command of chip8-cli.")
+                    .clicked()
+                {
+                    messages.push(Chip8Message::StartRecording);
+                }
+
                 ui.separator();
 
                 Self::draw_view_toggle(view, ui, &mut response);
 
-                self.draw_execution_controls(ui, messages, &mut response);
+                if ui
+                    .button("\u{1F3A5} Streamer Mode")
+                    .on_hover_text(
+                        "Only show the screen, for capturing the window. Press Escape to leave.",
+                    )
+                    .clicked()
+                {
+                    response.enter_streamer_mode = true;
+                }
+
+                self.draw_execution_controls(ui, state, messages, &mut response);
             });
         });
         response
@@ -253,42 +781,68 @@ impl MenuPanel {
     fn draw_execution_controls(
         &mut self,
         ui: &mut Ui,
+        state: &GuiState,
         messages: &mut Vec<Chip8Message>,
         response: &mut MenuPanelResponse,
     ) {
         ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-            let play_pause_label = if self.paused {
+            let play_pause_label = if state.paused {
                 "\u{23F5} Play"
             } else {
                 "\u{23F8} Pause"
             };
             if ui.button(play_pause_label).clicked() {
                 messages.push(Chip8Message::TogglePause);
-                response.toggle_pause = true;
             }
 
+            egui::ComboBox::from_id_source("step_granularity")
+                .selected_text(self.step_granularity.name())
+                .show_ui(ui, |ui| {
+                    for granularity in Granularity::ALL {
+                        ui.selectable_value(
+                            &mut self.step_granularity,
+                            granularity,
+                            granularity.name(),
+                        );
+                    }
+                });
+
             if ui.button("\u{27A1} Step").clicked() {
-                messages.push(Chip8Message::Step);
+                messages.push(Chip8Message::Step(self.step_granularity));
+            }
+
+            if ui
+                .add_enabled(
+                    state.chip8.rewindable_steps() > 0,
+                    egui::Button::new("\u{2B05} Back"),
+                )
+                .on_hover_text("Undo the last instruction. The recent instructions are remembered while the debug view is shown.")
+                .clicked()
+            {
+                messages.push(Chip8Message::StepBack);
             }
 
             if ui.button("\u{21BB} Reset").clicked() {
                 messages.push(Chip8Message::ResetROM);
                 response.reset = true;
             }
-        });
-    }
 
-    /// Toggle the `MenuPanel` paused state.
-    fn toggle_pause(&mut self) {
-        self.paused = !self.paused;
+            if let Some(reason) = state.break_reason {
+                ui.label(format!("Break: {reason}"));
+            }
+        });
     }
 
     /// Retrieves data from a file selected by a file dialog.
     /// Returns `None` if the chosen file cannot be read, or if the user
-    /// cancelled the operation. Otherwise, returns the file's data as a `Vec<u8>`.
-    fn load_file_from_dialog() -> Option<Vec<u8>> {
+    /// cancelled the operation. Otherwise, returns the file's name and data.
+    fn load_file_from_dialog() -> Option<(String, Vec<u8>)> {
         rfd::FileDialog::new().pick_file().and_then(|file| {
-            std::fs::read(file)
+            let name = file
+                .file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().into());
+            std::fs::read(&file)
+                .map(|data| (name, data))
                 .map_err(|e| log::error!("Failed to load ROM file: {}", e))
                 .ok()
         })
@@ -311,50 +865,247 @@ impl ScreenView {
 
     /// Update and draw this `ScreenView`. This creates a central panel, therefore it
     /// should be called after all other panels are drawn.
-    fn update(&self, ctx: &Context, chip8: &Chip8) {
+    ///
+    /// The key labels of the current ROM are shown on top of the screen if `show_key_labels` is set,
+    /// and the sprite of the sprite editor if `sprite_preview` is given.
+    fn update(
+        &self,
+        ctx: &Context,
+        state: &GuiState,
+        show_key_labels: bool,
+        sprite_preview: Option<&SpriteEditorWindow>,
+    ) {
         egui::CentralPanel::default()
             .frame(egui::Frame::default().inner_margin(egui::vec2(0.0, 0.0)))
             .show(ctx, |ui| {
-                self.draw_chip8_renderer(ui, chip8);
+                let screen_rect = self.draw_screen(ui, state, show_key_labels);
+                if let Some(editor) = sprite_preview {
+                    editor.draw_preview(ui, screen_rect, state.display);
+                }
+            });
+    }
+
+    /// Draw the display of the given `state` along with its overlays.
+    fn draw_screen(&self, ui: &mut Ui, state: &GuiState, show_key_labels: bool) -> egui::Rect {
+        let screen_rect = self.draw_chip8_renderer(ui, state.display);
+        if show_key_labels {
+            Self::draw_key_labels_overlay(
+                ui,
+                screen_rect,
+                state.profile.keypad_layout.key_map(),
+                &state.profile.key_labels,
+            );
+        }
+        Self::draw_status_overlay(ui, screen_rect, state.chip8);
+        if state.latency.flashing() {
+            ui.painter()
+                .rect_filled(screen_rect, 0.0, egui::Color32::WHITE);
+        }
+        screen_rect
+    }
+
+    /// Update and draw this `ScreenView` in streamer mode. The screen is centered
+    /// on a plain background, with an optional overlay of the pressed keys.
+    fn update_streamer(
+        &self,
+        ctx: &Context,
+        state: &GuiState,
+        settings: &StreamerSettings,
+        show_key_labels: bool,
+    ) {
+        let [r, g, b] = settings.background_rgb;
+        egui::CentralPanel::default()
+            .frame(
+                egui::Frame::none()
+                    .fill(egui::Color32::from_rgb(r, g, b))
+                    .inner_margin(STREAMER_MARGIN),
+            )
+            .show(ctx, |ui| {
+                // fit the screen to the window, keeping the aspect ratio of the display
+                let aspect_ratio = state.display.width as f32 / state.display.height as f32;
+                let available = ui.available_size();
+                let width = available.x.min(available.y * aspect_ratio);
+                let size = egui::vec2(width, width / aspect_ratio);
+                ui.vertical_centered(|ui| {
+                    ui.add_space((available.y - size.y) / 2.0);
+                    let screen_rect = ui
+                        .allocate_ui(size, |ui| self.draw_screen(ui, state, show_key_labels))
+                        .inner;
+                    if settings.show_keypad {
+                        Self::draw_keypad_overlay(ui, screen_rect, state.chip8);
+                    }
+                });
             });
     }
 
+    /// Draw the keypad in the bottom right corner of `screen_rect`, highlighting the pressed keys.
+    fn draw_keypad_overlay(ui: &Ui, screen_rect: egui::Rect, chip8: &Chip8) {
+        let painter = ui.painter();
+        let key_size = screen_rect.height() / 12.0;
+        let origin = screen_rect.right_bottom() - egui::vec2(key_size * 4.5, key_size * 4.5);
+        // every key map is ordered like the keypad, row by row
+        for (i, (_, key_code)) in KeypadLayout::default().key_map().iter().enumerate() {
+            let (row, column) = (i / 4, i % 4);
+            let rect = egui::Rect::from_min_size(
+                origin + egui::vec2(column as f32, row as f32) * key_size,
+                egui::vec2(key_size, key_size),
+            )
+            .shrink(key_size * 0.05);
+            let (fill, text_color) = if chip8.is_key_pressed(*key_code) {
+                (
+                    egui::Color32::from_rgba_unmultiplied(255, 255, 255, 220),
+                    egui::Color32::BLACK,
+                )
+            } else {
+                (
+                    egui::Color32::from_rgba_unmultiplied(0, 0, 0, 140),
+                    egui::Color32::WHITE,
+                )
+            };
+            painter.rect_filled(rect, key_size * 0.1, fill);
+            painter.text(
+                rect.center(),
+                egui::Align2::CENTER_CENTER,
+                format!("{key_code:X}"),
+                egui::FontId::monospace(key_size * 0.6),
+                text_color,
+            );
+        }
+    }
+
+    /// Draw the given key labels in the top left corner of `screen_rect`,
+    /// along with the keyboard keys that `key_map` maps to the labeled keys.
+    fn draw_key_labels_overlay(
+        ui: &Ui,
+        screen_rect: egui::Rect,
+        key_map: &KeyMap,
+        labels: &BTreeMap<u8, String>,
+    ) {
+        if labels.is_empty() {
+            return;
+        }
+        let text = key_map
+            .iter()
+            .filter_map(|(key, key_code)| {
+                let label = labels.get(key_code)?;
+                Some(format!("{} ({key_code:X}): {label}", key_name(*key)))
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let painter = ui.painter();
+        let galley = painter.layout_no_wrap(
+            text,
+            egui::FontId::monospace(screen_rect.height() / 24.0),
+            egui::Color32::WHITE,
+        );
+        let padding = egui::vec2(6.0, 4.0);
+        let rect = egui::Rect::from_min_size(
+            screen_rect.left_top() + padding,
+            galley.size() + padding * 2.0,
+        );
+        painter.rect_filled(rect, 4.0, egui::Color32::from_black_alpha(160));
+        painter.galley(rect.min + padding, galley);
+    }
+
+    /// Draw a banner at the bottom of `screen_rect` if the program is waiting for
+    /// a key press or has exited, so it's clear why the program appears frozen.
+    fn draw_status_overlay(ui: &Ui, screen_rect: egui::Rect, chip8: &Chip8) {
+        let text = match (chip8.state(), chip8.pending_key()) {
+            (
+                ProcessorState::Halted {
+                    reason: Fault::Exited,
+                },
+                _,
+            ) => "Program exited".to_string(),
+            (ProcessorState::Finished { .. }, _) => "Program finished".to_string(),
+            (ProcessorState::WaitingForKey { .. }, Some(key)) => {
+                format!("Waiting for key {key:X} to be released…")
+            }
+            (ProcessorState::WaitingForKey { .. }, None) => "Waiting for key…".to_string(),
+            // faults are shown in the crash report window
+            _ => return,
+        };
+        let painter = ui.painter();
+        let height = screen_rect.height() / 10.0;
+        let rect = egui::Rect::from_min_max(
+            screen_rect.left_bottom() - egui::vec2(0.0, height),
+            screen_rect.right_bottom(),
+        );
+        painter.rect_filled(rect, 0.0, egui::Color32::from_black_alpha(180));
+        painter.text(
+            rect.center(),
+            egui::Align2::CENTER_CENTER,
+            text,
+            egui::FontId::proportional(height * 0.6),
+            egui::Color32::WHITE,
+        );
+    }
+
+    /// Set the style the renderer presents the display with.
+    fn set_display_style(&self, style: &DisplayStyle) {
+        self.renderer.lock().unwrap().set_style(style);
+    }
+
     /// Clean up the renderer's GL context.
     fn clean_up(&self, gl: &eframe::glow::Context) {
         self.renderer.lock().unwrap().clean_up(gl);
     }
 
-    /// Draw the `Chip8` graphics state onto a `Ui` object.
+    /// Draw the given display frame onto a `Ui` object.
     ///
     /// This uses the rest of the available size in the `Ui`.
-    fn draw_chip8_renderer(&self, ui: &mut Ui, chip8: &Chip8) {
+    /// Returns the rect the display was drawn in.
+    fn draw_chip8_renderer(&self, ui: &mut Ui, display: &DisplayFrame) -> egui::Rect {
         let renderer = self.renderer.clone();
         ui.with_layout(
             egui::Layout::top_down_justified(egui::Align::Center),
             |ui| {
-                egui::Frame::canvas(ui.style()).show(ui, |ui| {
-                    let (rect, _) = ui.allocate_exact_size(
-                        ui.available_size(),
-                        egui::Sense::focusable_noninteractive(),
-                    );
-                    let graphics_buffer = chip8.bus.graphics.as_rgb8();
-                    let callback = egui::PaintCallback {
-                        rect,
-                        callback: Arc::new(eframe::egui_glow::CallbackFn::new(
-                            move |_, painter| {
-                                // at this point, egui has set the rect viewport,
-                                // so all we do is render like normal
-                                renderer
-                                    .lock()
-                                    .unwrap()
-                                    .render(painter.gl(), graphics_buffer.as_slice());
-                            },
-                        )),
-                    };
-                    ui.painter().add(callback);
-                });
+                egui::Frame::canvas(ui.style())
+                    .show(ui, |ui| {
+                        let (rect, _) = ui.allocate_exact_size(
+                            ui.available_size(),
+                            egui::Sense::focusable_noninteractive(),
+                        );
+                        let frame = display.clone();
+                        let callback = egui::PaintCallback {
+                            rect,
+                            callback: Arc::new(eframe::egui_glow::CallbackFn::new(
+                                move |_, painter| {
+                                    // at this point, egui has set the rect viewport,
+                                    // so all we do is render like normal
+                                    renderer.lock().unwrap().render(painter.gl(), &frame);
+                                },
+                            )),
+                        };
+                        ui.painter().add(callback);
+                        rect
+                    })
+                    .inner
             },
-        );
+        )
+        .inner
+    }
+}
+
+/// The margin around the screen in streamer mode.
+const STREAMER_MARGIN: f32 = 16.0;
+
+/// Settings for streamer mode, a compact presentation mode designed for capturing
+/// the window, e.g. in OBS. All other Ui elements are hidden in streamer mode.
+struct StreamerSettings {
+    /// The color shown around the screen.
+    background_rgb: [u8; 3],
+
+    /// Whether to show an overlay of the pressed keys.
+    show_keypad: bool,
+}
+
+impl Default for StreamerSettings {
+    fn default() -> Self {
+        Self {
+            background_rgb: [0, 0, 0],
+            show_keypad: true,
+        }
     }
 }
 
@@ -364,9 +1115,37 @@ struct ConfigWindow {
     visible: bool,
     foreground_rgb: [u8; 3],
     background_rgb: [u8; 3],
+    plane_2_rgb: [u8; 3],
+    both_planes_rgb: [u8; 3],
     steps_per_frame: u32,
+    timing: Timing,
+    rumble_intensity: f32,
+    variant: Variant,
+    start_address: usize,
+    memory_size: MemorySize,
+    machine_code_policy: MachineCodePolicy,
     shift_quirk_enabled: bool,
     vblank_wait_enabled: bool,
+    vertical_wrap_enabled: bool,
+    horizontal_wrap_enabled: bool,
+    pause_timers_on_key_wait: bool,
+    jump_quirk_enabled: bool,
+    index_increment: IndexIncrement,
+    logic_quirk_enabled: bool,
+    index_overflow_quirk_enabled: bool,
+
+    display_style: DisplayStyle,
+    display_presets: DisplayPresets,
+
+    /// The name the display style is saved as.
+    preset_name: String,
+    streamer: StreamerSettings,
+
+    logging: Logging,
+    log_settings: LogSettings,
+
+    /// The module to add a log level for.
+    log_module: String,
 }
 
 impl Default for ConfigWindow {
@@ -375,9 +1154,31 @@ impl Default for ConfigWindow {
             visible: false,
             foreground_rgb: chip8::graphics::DEFAULT_FOREGROUND.0,
             background_rgb: chip8::graphics::DEFAULT_BACKGROUND.0,
+            plane_2_rgb: chip8::graphics::DEFAULT_PLANE_2.0,
+            both_planes_rgb: chip8::graphics::DEFAULT_BOTH_PLANES.0,
             steps_per_frame: crate::app::DEFAULT_STEPS_PER_FRAME,
+            timing: Timing::default(),
+            rumble_intensity: crate::rumble::DEFAULT_RUMBLE_INTENSITY,
+            variant: Variant::default(),
+            start_address: chip8::rom::START,
+            memory_size: MemorySize::default(),
+            machine_code_policy: MachineCodePolicy::default(),
             shift_quirk_enabled: false,
             vblank_wait_enabled: false,
+            vertical_wrap_enabled: false,
+            horizontal_wrap_enabled: false,
+            pause_timers_on_key_wait: false,
+            jump_quirk_enabled: false,
+            index_increment: IndexIncrement::default(),
+            logic_quirk_enabled: true,
+            index_overflow_quirk_enabled: false,
+            display_style: DisplayStyle::default(),
+            display_presets: DisplayPresets::default(),
+            preset_name: String::new(),
+            streamer: StreamerSettings::default(),
+            logging: Logging::default(),
+            log_settings: LogSettings::default(),
+            log_module: String::new(),
         }
     }
 }
@@ -386,27 +1187,63 @@ impl ConfigWindow {
     /// Update and render the `ConfigWindow` to the given `Context`.
     /// This will append any GUI messages to `messages` if the `Chip8` state should be updated.
     fn update(&mut self, ctx: &Context, messages: &mut Vec<Chip8Message>) {
+        let current_preset = QuirkPreset::of(self.quirks());
+        let previous_palette = self.palette();
+        // the quirk preset chosen this frame
+        let mut chosen_preset = None;
         egui::Window::new("Config")
             .open(&mut self.visible)
             .show(ctx, |ui| {
                 egui::Grid::new("config_grid").show(ui, |ui| {
                     // foreground color selector
                     ui.label("Foreground Color");
-                    if ui
-                        .color_edit_button_srgb(&mut self.foreground_rgb)
-                        .changed()
-                    {
-                        messages.push(Chip8Message::SetForegroundColor(RGB8(self.foreground_rgb)));
-                    }
+                    ui.color_edit_button_srgb(&mut self.foreground_rgb);
                     ui.end_row();
 
                     // background color selector
                     ui.label("Background Color");
-                    if ui
-                        .color_edit_button_srgb(&mut self.background_rgb)
-                        .changed()
-                    {
-                        messages.push(Chip8Message::SetBackgroundColor(RGB8(self.background_rgb)));
+                    ui.color_edit_button_srgb(&mut self.background_rgb);
+                    ui.end_row();
+
+                    ui.label("Plane 2 Color");
+                    ui.color_edit_button_srgb(&mut self.plane_2_rgb)
+                        .on_hover_text("The color of XO-CHIP pixels only on the second plane.");
+                    ui.end_row();
+
+                    ui.label("Both Planes Color");
+                    ui.color_edit_button_srgb(&mut self.both_planes_rgb)
+                        .on_hover_text("The color of XO-CHIP pixels on both planes.");
+                    ui.end_row();
+
+                    ui.label("Palette");
+                    let mut palette = [
+                        self.background_rgb,
+                        self.foreground_rgb,
+                        self.plane_2_rgb,
+                        self.both_planes_rgb,
+                    ]
+                    .map(RGB8);
+                    let name = OCTO_PALETTES
+                        .iter()
+                        .find(|(_, octo_palette)| *octo_palette == palette)
+                        .map_or("Custom", |(name, _)| name);
+                    egui::ComboBox::from_id_source("palette")
+                        .selected_text(name)
+                        .show_ui(ui, |ui| {
+                            for (name, octo_palette) in OCTO_PALETTES {
+                                ui.selectable_value(&mut palette, octo_palette, name);
+                            }
+                        })
+                        .response
+                        .on_hover_text("Set all four colors to one of the palettes of Octo.");
+                    [
+                        self.background_rgb,
+                        self.foreground_rgb,
+                        self.plane_2_rgb,
+                        self.both_planes_rgb,
+                    ] = palette.map(|color| color.0);
+                    if palette != previous_palette {
+                        messages.push(Chip8Message::SetPalette(palette));
                     }
                     ui.end_row();
 
@@ -418,6 +1255,141 @@ impl ConfigWindow {
                     }
                     ui.end_row();
 
+                    ui.label("Timing");
+                    let previous_timing = self.timing;
+                    egui::ComboBox::from_id_source("timing")
+                        .selected_text(self.timing.to_string())
+                        .show_ui(ui, |ui| {
+                            for timing in Timing::ALL {
+                                ui.selectable_value(&mut self.timing, timing, timing.to_string());
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Run a flat amount of steps per frame, or as many instructions \
+                            as the COSMAC VIP ran in a frame, for the original speed.",
+                        );
+                    if self.timing != previous_timing {
+                        messages.push(Chip8Message::SetTiming(self.timing));
+                    }
+                    ui.end_row();
+
+                    ui.label("Gamepad Rumble");
+                    let rumble_slider = egui::Slider::new(&mut self.rumble_intensity, 0.0..=1.0)
+                        .custom_formatter(|value, _| format!("{:.0}%", value * 100.0));
+                    let rumble_slider = ui.add(rumble_slider);
+                    if rumble_slider.changed() {
+                        messages.push(Chip8Message::SetRumbleIntensity(self.rumble_intensity));
+                    }
+                    rumble_slider.on_hover_text(
+                        "Rumble connected gamepads while the sound timer is active. Set to 0% to disable.",
+                    );
+                    ui.end_row();
+
+                    ui.label("Variant");
+                    let previous_variant = self.variant;
+                    egui::ComboBox::from_id_source("variant")
+                        .selected_text(self.variant.to_string())
+                        .show_ui(ui, |ui| {
+                            for variant in Variant::ALL {
+                                ui.selectable_value(&mut self.variant, variant, variant.to_string());
+                            }
+                        });
+                    if self.variant != previous_variant {
+                        messages.push(Chip8Message::SetVariant(self.variant));
+                        // setting the variant changes the memory to its size
+                        self.memory_size = self.variant.memory_size();
+                    }
+                    ui.end_row();
+
+                    ui.label("Program Start");
+                    let previous_start = self.start_address;
+                    egui::ComboBox::from_id_source("start_address")
+                        .selected_text(format!("{:#05X}", self.start_address))
+                        .show_ui(ui, |ui| {
+                            for (start, name) in [
+                                (chip8::rom::START, "CHIP-8"),
+                                (chip8::rom::ETI_660_START, "ETI-660"),
+                            ] {
+                                ui.selectable_value(
+                                    &mut self.start_address,
+                                    start,
+                                    format!("{start:#05X} ({name})"),
+                                );
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "The address programs are loaded and started at. \
+                            Changing it restarts the loaded programs.",
+                        );
+                    if self.start_address != previous_start {
+                        messages.push(Chip8Message::SetStartAddress(self.start_address));
+                    }
+                    ui.end_row();
+
+                    ui.label("Memory");
+                    let previous_size = self.memory_size;
+                    egui::ComboBox::from_id_source("memory_size")
+                        .selected_text(self.memory_size.to_string())
+                        .show_ui(ui, |ui| {
+                            for size in MemorySize::ALL {
+                                ui.selectable_value(&mut self.memory_size, size, size.to_string());
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "The size of the memory, which is set to the size of the variant \
+                            when it is changed. Changing it restarts the loaded programs.",
+                        );
+                    if self.memory_size != previous_size {
+                        messages.push(Chip8Message::SetMemorySize(self.memory_size));
+                    }
+                    ui.end_row();
+
+                    ui.label("Machine Code Calls");
+                    let previous_policy = self.machine_code_policy;
+                    egui::ComboBox::from_id_source("machine_code_policy")
+                        .selected_text(self.machine_code_policy.to_string())
+                        .show_ui(ui, |ui| {
+                            for policy in MachineCodePolicy::ALL {
+                                ui.selectable_value(
+                                    &mut self.machine_code_policy,
+                                    policy,
+                                    policy.to_string(),
+                                );
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "How 0NNN instructions, which call machine code routines \
+                            of the original hardware, are handled.",
+                        );
+                    if self.machine_code_policy != previous_policy {
+                        messages.push(Chip8Message::SetMachineCodePolicy(self.machine_code_policy));
+                    }
+                    ui.end_row();
+
+                    ui.label("Quirk Preset");
+                    egui::ComboBox::from_id_source("quirk_preset")
+                        .selected_text(current_preset.map_or("Custom".into(), |p| p.to_string()))
+                        .show_ui(ui, |ui| {
+                            for preset in QuirkPreset::ALL {
+                                if ui
+                                    .selectable_label(current_preset == Some(preset), preset.to_string())
+                                    .clicked()
+                                {
+                                    chosen_preset = Some(preset);
+                                }
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Set all quirks like the platform a program was written for, \
+                            instead of guessing them one by one.",
+                        );
+                    ui.end_row();
+
                     ui.label("Enable Shift Quirk");
                     let shift_quirk_checkbox = ui.checkbox(&mut self.shift_quirk_enabled, "");
                     if shift_quirk_checkbox.changed() {
@@ -439,45 +1411,2309 @@ impl ConfigWindow {
                         This will limit the amount of sprite draw calls to 60 calls per second."
                     );
                     ui.end_row();
-                });
-            });
+
+                    ui.label("Enable Vertical Wrap");
+                    let vertical_wrap_checkbox = ui.checkbox(&mut self.vertical_wrap_enabled, "");
+                    if vertical_wrap_checkbox.changed() {
+                        messages.push(Chip8Message::SetVerticalWrap(self.vertical_wrap_enabled));
+                    }
+                    vertical_wrap_checkbox.on_hover_text(
+                        "Wrap sprites drawn past the bottom edge of the screen around to the top, \
+                        instead of clipping them.",
+                    );
+                    ui.end_row();
+
+                    ui.label("Enable Horizontal Wrap");
+                    let horizontal_wrap_checkbox =
+                        ui.checkbox(&mut self.horizontal_wrap_enabled, "");
+                    if horizontal_wrap_checkbox.changed() {
+                        messages.push(Chip8Message::SetHorizontalWrap(
+                            self.horizontal_wrap_enabled,
+                        ));
+                    }
+                    horizontal_wrap_checkbox.on_hover_text(
+                        "Wrap sprites drawn past the right edge of the screen around to the left, \
+                        instead of clipping them.",
+                    );
+                    ui.end_row();
+
+                    ui.label("Pause Timers On Key Wait");
+                    let pause_timers_checkbox = ui.checkbox(&mut self.pause_timers_on_key_wait, "");
+                    if pause_timers_checkbox.changed() {
+                        messages.push(Chip8Message::SetPauseTimersOnKeyWait(
+                            self.pause_timers_on_key_wait,
+                        ));
+                    }
+                    pause_timers_checkbox.on_hover_text(
+                        "Pause the delay and sound timers while the program is waiting for a key press.",
+                    );
+                    ui.end_row();
+
+                    ui.label("Enable Logic Quirk");
+                    let logic_quirk_checkbox = ui.checkbox(&mut self.logic_quirk_enabled, "");
+                    if logic_quirk_checkbox.changed() {
+                        messages.push(Chip8Message::SetLogicQuirk(self.logic_quirk_enabled));
+                    }
+                    logic_quirk_checkbox.on_hover_text(
+                        "Reset VF to 0 after OR, AND and XOR (8xy1, 8xy2, 8xy3), like the COSMAC VIP. \
+                        Disable this for SUPER-CHIP programs.",
+                    );
+                    ui.end_row();
+
+                    ui.label("Enable Index Overflow");
+                    let index_overflow_checkbox = ui.checkbox(&mut self.index_overflow_quirk_enabled, "");
+                    if index_overflow_checkbox.changed() {
+                        messages.push(Chip8Message::SetIndexOverflowQuirk(
+                            self.index_overflow_quirk_enabled,
+                        ));
+                    }
+                    index_overflow_checkbox.on_hover_text(
+                        "Set VF when adding to I (Fx1E) goes past 0xFFF, like the Amiga interpreter. \
+                        Some programs, e.g. Spacefight 2091!, rely on this.",
+                    );
+                    ui.end_row();
+
+                    ui.label("Enable Jump Quirk");
+                    let jump_quirk_checkbox = ui.checkbox(&mut self.jump_quirk_enabled, "");
+                    if jump_quirk_checkbox.changed() {
+                        messages.push(Chip8Message::SetJumpQuirk(self.jump_quirk_enabled));
+                    }
+                    jump_quirk_checkbox.on_hover_text(
+                        "Jump to xnn plus Vx with Bxnn, instead of nnn plus V0, like CHIP-48.",
+                    );
+                    ui.end_row();
+
+                    ui.label("Index Increment");
+                    let previous_increment = self.index_increment;
+                    egui::ComboBox::from_id_source("index_increment")
+                        .selected_text(self.index_increment.to_string())
+                        .show_ui(ui, |ui| {
+                            for increment in IndexIncrement::ALL {
+                                ui.selectable_value(
+                                    &mut self.index_increment,
+                                    increment,
+                                    increment.to_string(),
+                                );
+                            }
+                        })
+                        .response
+                        .on_hover_text("How storing and loading registers (Fx55, Fx65) changes I.");
+                    if self.index_increment != previous_increment {
+                        messages.push(Chip8Message::SetIndexIncrement(self.index_increment));
+                    }
+                    ui.end_row();
+
+                    Self::draw_display_rows(
+                        ui,
+                        &mut self.display_style,
+                        &mut self.display_presets,
+                        &mut self.preset_name,
+                    );
+
+                    ui.label("Streamer Background");
+                    ui.color_edit_button_srgb(&mut self.streamer.background_rgb);
+                    ui.end_row();
+
+                    ui.label("Streamer Keypad Overlay");
+                    ui.checkbox(&mut self.streamer.show_keypad, "")
+                        .on_hover_text("Show the pressed keys on top of the screen in streamer mode.");
+                    ui.end_row();
+
+                    let previous_log_settings = self.log_settings.clone();
+                    Self::draw_logging_rows(ui, &mut self.log_settings, &mut self.log_module);
+                    if self.log_settings != previous_log_settings {
+                        self.logging.set_settings(self.log_settings.clone());
+                    }
+                });
+            });
+        if let Some(preset) = chosen_preset {
+            self.set_quirks(preset.quirks());
+            messages.push(Chip8Message::SetQuirkPreset(preset));
+        }
+    }
+
+    /// Draw the grid rows of the logger settings: the log levels, the log file and the instruction trace.
+    fn draw_logging_rows(ui: &mut Ui, settings: &mut LogSettings, module: &mut String) {
+        let level_combo = |ui: &mut Ui, id: &str, level: &mut log::LevelFilter| {
+            egui::ComboBox::from_id_source(id)
+                .selected_text(level.to_string())
+                .show_ui(ui, |ui| {
+                    for filter in log::LevelFilter::iter() {
+                        ui.selectable_value(level, filter, filter.to_string());
+                    }
+                })
+        };
+
+        ui.label("Log Level");
+        level_combo(ui, "log_level_default", &mut settings.level);
+        ui.end_row();
+
+        ui.label("Module Log Levels");
+        ui.vertical(|ui| {
+            let mut removed = None;
+            for (name, level) in &mut settings.modules {
+                ui.horizontal(|ui| {
+                    ui.monospace(name.as_str());
+                    level_combo(ui, name, level);
+                    if ui.small_button("\u{1F5D1}").clicked() {
+                        removed = Some(name.clone());
+                    }
+                });
+            }
+            if let Some(name) = removed {
+                settings.modules.remove(&name);
+            }
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(module)
+                        .hint_text("e.g. frontend::net")
+                        .desired_width(150.0),
+                );
+                let add = ui.add_enabled(!module.trim().is_empty(), egui::Button::new("Add"));
+                if add.clicked() {
+                    settings
+                        .modules
+                        .insert(module.trim().to_string(), settings.level);
+                    module.clear();
+                }
+            });
+        })
+        .response
+        .on_hover_text(
+            "Log levels of specific modules and their submodules, overriding the log level.",
+        );
+        ui.end_row();
+
+        ui.label("Log File");
+        ui.checkbox(&mut settings.file, "").on_hover_text(format!(
+            "Also write the log to {}. The file is rotated once it reaches {} KiB, \
+            keeping {} old files.",
+            Logging::file_path().display(),
+            settings.max_file_size / 1024,
+            settings.max_files
+        ));
+        ui.end_row();
+
+        ui.label("Trace Instructions");
+        ui.checkbox(&mut settings.trace_instructions, "")
+            .on_hover_text(format!(
+                "Log every executed instruction to the `{TRACE_TARGET}` target, \
+                which is written to stdout and the log file but not shown in the log window. \
+                This slows down the interpreter.",
+            ));
+        ui.end_row();
+    }
+
+    /// Draw the grid rows of the settings that change how the display is presented,
+    /// and of the presets they can be saved as.
+    /// The quirk settings shown in the window.
+    fn quirks(&self) -> Quirks {
+        let mut quirks = Quirks::default();
+        quirks.shift = self.shift_quirk_enabled;
+        quirks.vblank_wait = self.vblank_wait_enabled;
+        quirks.vertical_wrap = self.vertical_wrap_enabled;
+        quirks.horizontal_wrap = self.horizontal_wrap_enabled;
+        quirks.pause_timers_on_key_wait = self.pause_timers_on_key_wait;
+        quirks.jump = self.jump_quirk_enabled;
+        quirks.index_increment = self.index_increment;
+        quirks.logic = self.logic_quirk_enabled;
+        quirks.index_overflow = self.index_overflow_quirk_enabled;
+        quirks
+    }
+
+    /// Show the given quirk settings, after they were applied all at once.
+    fn set_quirks(&mut self, quirks: Quirks) {
+        self.shift_quirk_enabled = quirks.shift;
+        self.vblank_wait_enabled = quirks.vblank_wait;
+        self.vertical_wrap_enabled = quirks.vertical_wrap;
+        self.horizontal_wrap_enabled = quirks.horizontal_wrap;
+        self.pause_timers_on_key_wait = quirks.pause_timers_on_key_wait;
+        self.jump_quirk_enabled = quirks.jump;
+        self.index_increment = quirks.index_increment;
+        self.logic_quirk_enabled = quirks.logic;
+        self.index_overflow_quirk_enabled = quirks.index_overflow;
+    }
+
+    fn draw_display_rows(
+        ui: &mut Ui,
+        style: &mut DisplayStyle,
+        presets: &mut DisplayPresets,
+        preset_name: &mut String,
+    ) {
+        ui.label("Frame Blending");
+        let frame_blending_text = |count: usize| match count {
+            1 => "Off".to_string(),
+            count => format!("{count} frames"),
+        };
+        egui::ComboBox::from_id_source("frame_blending")
+            .selected_text(frame_blending_text(style.frame_blending))
+            .show_ui(ui, |ui| {
+                for count in 1..=3 {
+                    ui.selectable_value(
+                        &mut style.frame_blending,
+                        count,
+                        frame_blending_text(count),
+                    );
+                }
+            })
+            .response
+            .on_hover_text(
+                "Blend the last frames of the screen to reduce the flicker of \
+                sprites that are redrawn every frame. Only changes what is shown.",
+            );
+        ui.end_row();
+
+        ui.label("Color Grading");
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("color_grading")
+                .selected_text(style.gradient_name().unwrap_or("Off"))
+                .show_ui(ui, |ui| {
+                    if ui
+                        .selectable_label(style.gradient.is_empty(), "Off")
+                        .clicked()
+                    {
+                        style.gradient.clear();
+                    }
+                    for (name, colors) in GRADIENTS {
+                        let selected = style.gradient == colors;
+                        if ui.selectable_label(selected, name).clicked() {
+                            style.gradient = colors.to_vec();
+                        }
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "Map the brightness of the screen to a gradient of colors. \
+                    Edit the colors to make a custom gradient.",
+                );
+            for color in &mut style.gradient {
+                ui.color_edit_button_srgb(color);
+            }
+            let len = style.gradient.len();
+            if (1..MAX_GRADIENT_COLORS).contains(&len) && ui.small_button("+").clicked() {
+                style.gradient.push(style.gradient[len - 1]);
+            }
+            if style.gradient.len() > 2 && ui.small_button("-").clicked() {
+                style.gradient.pop();
+            }
+        });
+        ui.end_row();
+
+        ui.label("Pixel Shape");
+        egui::ComboBox::from_id_source("pixel_shape")
+            .selected_text(style.pixel_shape.to_string())
+            .show_ui(ui, |ui| {
+                for shape in PixelShape::ALL {
+                    ui.selectable_value(&mut style.pixel_shape, shape, shape.to_string());
+                }
+            });
+        ui.end_row();
+
+        ui.label("Pixel Gap");
+        ui.add_enabled(
+            style.pixel_shape != PixelShape::Square,
+            egui::Slider::new(&mut style.pixel_gap, 0.0..=0.5),
+        );
+        ui.end_row();
+
+        ui.label("Display Preset");
+        ui.horizontal(|ui| {
+            let mut loaded = None;
+            let mut deleted = None;
+            egui::ComboBox::from_id_source("display_preset")
+                .selected_text("Load…")
+                .show_ui(ui, |ui| {
+                    for (name, preset) in &presets.presets {
+                        ui.horizontal(|ui| {
+                            if ui.selectable_label(preset == style, name).clicked() {
+                                loaded = Some(preset.clone());
+                            }
+                            if ui.small_button("\u{1F5D1}").clicked() {
+                                deleted = Some(name.clone());
+                            }
+                        });
+                    }
+                });
+            ui.add(egui::TextEdit::singleline(preset_name).desired_width(100.0));
+            let save = ui.add_enabled(!preset_name.trim().is_empty(), egui::Button::new("Save"));
+            let mut changed = deleted.is_some();
+            if save.clicked() {
+                presets
+                    .presets
+                    .insert(preset_name.trim().to_string(), style.clone());
+                changed = true;
+            }
+            if let Some(name) = deleted {
+                presets.presets.remove(&name);
+            }
+            if let Some(preset) = loaded {
+                *style = preset;
+            }
+            if changed {
+                if let Err(e) = presets.save() {
+                    log::error!("Failed to save the display presets: {e:#}");
+                }
+            }
+        });
+        ui.end_row();
+    }
+
+    /// The colors chosen for every combination of planes, see [`Chip8::palette`](chip8::Chip8::palette).
+    fn palette(&self) -> [RGB8; 4] {
+        [
+            self.background_rgb,
+            self.foreground_rgb,
+            self.plane_2_rgb,
+            self.both_planes_rgb,
+        ]
+        .map(RGB8)
+    }
+
+    /// Push a message setting all colors to `messages`.
+    fn push_color_messages(&self, messages: &mut Vec<Chip8Message>) {
+        messages.push(Chip8Message::SetPalette(self.palette()));
+    }
+
+    /// Toggle the visibility of this `ConfigWindow`,
+    fn toggle_visibility(&mut self) {
+        self.visible = !self.visible;
+    }
+}
+
+/// A window for hosting or joining a network session, where a spectator
+/// mirrors the host's display and may be granted the keypad.
+struct NetworkWindow {
+    visible: bool,
+    port: u16,
+    address: String,
+    allow_input: bool,
+}
+
+impl Default for NetworkWindow {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            port: net::DEFAULT_PORT,
+            address: format!("127.0.0.1:{}", net::DEFAULT_PORT),
+            allow_input: false,
+        }
+    }
+}
+
+impl NetworkWindow {
+    /// Update and render the `NetworkWindow` to the given `Context`, showing the
+    /// current `status` of the session.
+    fn update(&mut self, ctx: &Context, status: &NetStatus, messages: &mut Vec<Chip8Message>) {
+        egui::Window::new("Network")
+            .open(&mut self.visible)
+            .show(ctx, |ui| match status {
+                NetStatus::Offline => {
+                    egui::Grid::new("network_grid").show(ui, |ui| {
+                        ui.label("Port");
+                        ui.add(egui::DragValue::new(&mut self.port));
+                        if ui.button("Host").clicked() {
+                            messages.push(Chip8Message::HostSession {
+                                port: self.port,
+                                allow_input: self.allow_input,
+                            });
+                        }
+                        ui.end_row();
+
+                        ui.label("Host Address");
+                        ui.text_edit_singleline(&mut self.address);
+                        if ui.button("Join").clicked() {
+                            messages.push(Chip8Message::JoinSession(self.address.clone()));
+                        }
+                        ui.end_row();
+                    });
+                }
+                NetStatus::Hosting { port, peer } => {
+                    ui.label(format!("Hosting on port {port}"));
+                    match peer {
+                        Some(peer) => ui.label(format!("Spectator connected from {peer}")),
+                        None => ui.label("Waiting for a spectator to connect..."),
+                    };
+                    let input_checkbox = ui.checkbox(&mut self.allow_input, "Allow Remote Keypad");
+                    if input_checkbox.changed() {
+                        messages.push(Chip8Message::SetRemoteInput(self.allow_input));
+                    }
+                    input_checkbox.on_hover_text(
+                        "Let the spectator press keys, for playing games that share the keypad.",
+                    );
+                    if ui.button("Stop Hosting").clicked() {
+                        messages.push(Chip8Message::LeaveSession);
+                    }
+                }
+                NetStatus::Spectating {
+                    host,
+                    input_granted,
+                } => {
+                    ui.label(format!("Spectating {host}"));
+                    if *input_granted {
+                        ui.label("The host has granted you the keypad.");
+                    }
+                    if ui.button("Leave").clicked() {
+                        messages.push(Chip8Message::LeaveSession);
+                    }
+                }
+            });
+    }
+
+    /// Toggle the visibility of this `NetworkWindow`.
+    fn toggle_visibility(&mut self) {
+        self.visible = !self.visible;
+    }
+}
+
+/// A window for managing the cheats of the current ROM. Each cheat pins
+/// a memory address to a fixed value, e.g. to get infinite lives, and
+/// values can be poked into memory once.
+#[derive(Default)]
+struct CheatWindow {
+    visible: bool,
+    name: String,
+    address: String,
+    value: u8,
+}
+
+impl CheatWindow {
+    /// Update and render the `CheatWindow` to the given `Context`, listing the given `cheats`.
+    fn update(&mut self, ctx: &Context, cheats: &[Cheat], messages: &mut Vec<Chip8Message>) {
+        egui::Window::new("Cheats")
+            .open(&mut self.visible)
+            .show(ctx, |ui| {
+                egui::Grid::new("cheat_grid")
+                    .striped(true)
+                    .num_columns(5)
+                    .show(ui, |ui| {
+                        ui.label("Enabled");
+                        ui.label("Name");
+                        ui.label("Address");
+                        ui.label("Value");
+                        ui.end_row();
+
+                        for (i, cheat) in cheats.iter().enumerate() {
+                            let mut enabled = cheat.enabled;
+                            if ui.checkbox(&mut enabled, "").changed() {
+                                messages.push(Chip8Message::SetCheatEnabled(i, enabled));
+                            }
+                            ui.label(&cheat.name);
+                            ui.label(format!("{:#06X}", cheat.address));
+                            ui.label(format!("{:#04X}", cheat.value));
+                            if ui.button("\u{1F5D1}").clicked() {
+                                messages.push(Chip8Message::RemoveCheat(i));
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                ui.separator();
+
+                egui::Grid::new("new_cheat_grid").show(ui, |ui| {
+                    ui.label("Name");
+                    ui.text_edit_singleline(&mut self.name);
+                    ui.end_row();
+
+                    ui.label("Address (hex)");
+                    ui.text_edit_singleline(&mut self.address);
+                    ui.end_row();
+
+                    ui.label("Value");
+                    ui.add(egui::DragValue::new(&mut self.value));
+                    ui.end_row();
+                });
+
+                let address =
+                    parse_hex(&self.address).and_then(|address| u16::try_from(address).ok());
+                let (add_button, poke_button) = ui
+                    .horizontal(|ui| {
+                        (
+                            ui.add_enabled(address.is_some(), egui::Button::new("Add Cheat")),
+                            ui.add_enabled(address.is_some(), egui::Button::new("Poke Once"))
+                                .on_hover_text("Write the value once, without pinning it"),
+                        )
+                    })
+                    .inner;
+                if let (true, Some(address)) = (poke_button.clicked(), address) {
+                    messages.push(Chip8Message::WriteMemory {
+                        address: usize::from(address),
+                        data: vec![self.value],
+                    });
+                }
+                if let (true, Some(address)) = (add_button.clicked(), address) {
+                    let name = if self.name.is_empty() {
+                        format!("Cheat {}", cheats.len() + 1)
+                    } else {
+                        std::mem::take(&mut self.name)
+                    };
+                    messages.push(Chip8Message::AddCheat(Cheat {
+                        name,
+                        address,
+                        value: self.value,
+                        enabled: true,
+                    }));
+                }
+            });
+    }
+
+    /// Toggle the visibility of this `CheatWindow`.
+    fn toggle_visibility(&mut self) {
+        self.visible = !self.visible;
+    }
+}
+
+/// A touch-friendly keypad drawn on top of the screen, for devices without a keyboard.
+/// The buttons can be moved, resized, added and removed per ROM, since many
+/// programs only use a few keys and are easier to play with large buttons.
+#[derive(Default)]
+struct TouchKeypad {
+    /// Whether the keypad is shown and handles input.
+    enabled: bool,
+
+    /// Whether the buttons can be dragged to new positions, instead of pressing keys.
+    editing: bool,
+
+    window_visible: bool,
+
+    /// The position of every finger currently on the screen, by touch id.
+    touches: HashMap<u64, egui::Pos2>,
+
+    /// The index of the button being dragged, along with the edited layout.
+    /// The layout is sent to the backend once the button is released.
+    drag: Option<(usize, Vec<TouchButton>)>,
+
+    /// The key of the button added with the "Add Button" button.
+    new_key_code: u8,
+}
+
+impl TouchKeypad {
+    /// Update and draw the keypad on top of the given `area`, using the layout of the profile
+    /// in `state`.
+    ///
+    /// Returns the pressed state of every key, indexed by key code.
+    fn update(
+        &mut self,
+        ctx: &Context,
+        area: egui::Rect,
+        state: &GuiState,
+        messages: &mut Vec<Chip8Message>,
+    ) -> [bool; 16] {
+        let mut keys = [false; 16];
+        if !self.enabled {
+            return keys;
+        }
+
+        let input = ctx.input();
+        for event in &input.events {
+            if let egui::Event::Touch { id, phase, pos, .. } = *event {
+                match phase {
+                    egui::TouchPhase::Start | egui::TouchPhase::Move => {
+                        self.touches.insert(id.0, pos);
+                    }
+                    egui::TouchPhase::End | egui::TouchPhase::Cancel => {
+                        self.touches.remove(&id.0);
+                    }
+                }
+            }
+        }
+        let pointer = input
+            .pointer
+            .primary_down()
+            .then(|| input.pointer.interact_pos())
+            .flatten();
+
+        let mut layout = match &self.drag {
+            Some((_, layout)) => layout.clone(),
+            None => state.profile.touch_layout(),
+        };
+        let button_rect = |button: &TouchButton| {
+            let center = area.min + egui::vec2(button.x, button.y) * area.size();
+            let size = button.size * area.width().min(area.height());
+            egui::Rect::from_center_size(center, egui::vec2(size, size))
+        };
+
+        if self.editing {
+            match (&mut self.drag, pointer) {
+                (Some((index, layout)), Some(pos)) => {
+                    let position = ((pos - area.min) / area.size())
+                        .clamp(egui::Vec2::ZERO, egui::vec2(1.0, 1.0));
+                    layout[*index].x = position.x;
+                    layout[*index].y = position.y;
+                }
+                (Some((_, layout)), None) => {
+                    messages.push(Chip8Message::SetTouchLayout(Some(std::mem::take(layout))));
+                    self.drag = None;
+                }
+                (None, Some(pos)) if input.pointer.any_pressed() => {
+                    self.drag = layout
+                        .iter()
+                        .position(|button| button_rect(button).contains(pos))
+                        .map(|index| (index, layout.clone()));
+                }
+                (None, _) => {}
+            }
+            if let Some((_, dragged)) = &self.drag {
+                layout = dragged.clone();
+            }
+        } else {
+            for button in &layout {
+                let rect = button_rect(button);
+                let touched = self
+                    .touches
+                    .values()
+                    .chain(pointer.as_ref())
+                    .any(|&pos| rect.center().distance(pos) <= rect.width() / 2.0);
+                keys[usize::from(button.key_code)] |= touched;
+            }
+        }
+        drop(input);
+
+        let painter = ctx.layer_painter(egui::LayerId::new(
+            egui::Order::Foreground,
+            egui::Id::new("touch_keypad"),
+        ));
+        for button in &layout {
+            let rect = button_rect(button);
+            let pressed = keys[usize::from(button.key_code)];
+            let fill = if pressed {
+                egui::Color32::from_white_alpha(160)
+            } else {
+                egui::Color32::from_black_alpha(120)
+            };
+            let stroke_color = if self.editing {
+                ctx.style().visuals.selection.stroke.color
+            } else {
+                egui::Color32::from_white_alpha(100)
+            };
+            painter.circle(
+                rect.center(),
+                rect.width() / 2.0,
+                fill,
+                egui::Stroke::new(2.0, stroke_color),
+            );
+            let label = state
+                .profile
+                .key_labels
+                .get(&button.key_code)
+                .map_or_else(|| format!("{:X}", button.key_code), Clone::clone);
+            painter.text(
+                rect.center(),
+                egui::Align2::CENTER_CENTER,
+                label,
+                egui::FontId::proportional(rect.width() / 3.0),
+                egui::Color32::WHITE,
+            );
+        }
+
+        keys
+    }
+
+    /// Update and render the layout editor window of the keypad, editing the layout
+    /// of the profile in `state`.
+    fn update_window(&mut self, ctx: &Context, state: &GuiState, messages: &mut Vec<Chip8Message>) {
+        let mut window_visible = self.window_visible;
+        egui::Window::new("Touch Keypad")
+            .open(&mut window_visible)
+            .show(ctx, |ui| {
+                ui.checkbox(&mut self.enabled, "Show touch keypad");
+                ui.add_enabled(
+                    self.enabled,
+                    egui::Checkbox::new(&mut self.editing, "Edit layout"),
+                )
+                .on_hover_text("Drag the buttons on the screen to move them.");
+                ui.separator();
+
+                let mut layout = state.profile.touch_layout();
+                let mut changed = false;
+                let mut removed = None;
+                egui::Grid::new("touch_layout_grid")
+                    .striped(true)
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        ui.label("Key");
+                        ui.label("Size");
+                        ui.end_row();
+
+                        for (i, button) in layout.iter_mut().enumerate() {
+                            ui.label(format!("{:X}", button.key_code));
+                            changed |= ui
+                                .add(egui::Slider::new(&mut button.size, 0.05..=0.4))
+                                .changed();
+                            if ui.button("\u{1F5D1}").clicked() {
+                                removed = Some(i);
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source("touch_new_key")
+                        .selected_text(format!("{:X}", self.new_key_code))
+                        .show_ui(ui, |ui| {
+                            for key_code in 0..16 {
+                                ui.selectable_value(
+                                    &mut self.new_key_code,
+                                    key_code,
+                                    format!("{key_code:X}"),
+                                );
+                            }
+                        });
+                    if ui.button("Add Button").clicked() {
+                        layout.push(TouchButton {
+                            key_code: self.new_key_code,
+                            x: 0.5,
+                            y: 0.5,
+                            size: TouchButton::DEFAULT_SIZE,
+                        });
+                        changed = true;
+                    }
+                });
+
+                if let Some(index) = removed {
+                    layout.remove(index);
+                    changed = true;
+                }
+                if changed {
+                    messages.push(Chip8Message::SetTouchLayout(Some(layout)));
+                }
+                if ui.button("Reset Layout").clicked() {
+                    messages.push(Chip8Message::SetTouchLayout(None));
+                }
+            });
+        self.window_visible = window_visible;
+    }
+
+    /// Toggle the visibility of the layout editor window.
+    fn toggle_visibility(&mut self) {
+        self.window_visible = !self.window_visible;
+    }
+}
+
+/// A window for choosing the keypad layout of the current ROM, and editing
+/// what each key does in it, e.g. "shoot". Both are saved in the ROM's profile.
+#[derive(Default)]
+struct KeyLabelWindow {
+    visible: bool,
+
+    /// Whether the labels are shown on top of the screen.
+    show_overlay: bool,
+}
+
+impl KeyLabelWindow {
+    /// Update and render the `KeyLabelWindow` to the given `Context`,
+    /// editing the key labels of the profile in `state`.
+    fn update(&mut self, ctx: &Context, state: &GuiState, messages: &mut Vec<Chip8Message>) {
+        let show_overlay = &mut self.show_overlay;
+        egui::Window::new("Key Labels")
+            .open(&mut self.visible)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Layout");
+                    let mut layout = state.profile.keypad_layout;
+                    egui::ComboBox::from_id_source("keypad_layout")
+                        .selected_text(layout.to_string())
+                        .show_ui(ui, |ui| {
+                            for option in KeypadLayout::ALL {
+                                ui.selectable_value(&mut layout, option, option.to_string());
+                            }
+                        })
+                        .response
+                        .on_hover_text("How the keyboard is mapped to the keypad for this ROM.");
+                    if layout != state.profile.keypad_layout {
+                        messages.push(Chip8Message::SetKeypadLayout(layout));
+                    }
+                });
+                ui.checkbox(show_overlay, "Show on screen");
+                ui.separator();
+
+                egui::Grid::new("key_label_grid")
+                    .striped(true)
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        ui.label("Key");
+                        ui.label("Keyboard");
+                        ui.label("Label");
+                        ui.end_row();
+
+                        for &(key, key_code) in state.profile.keypad_layout.key_map() {
+                            ui.label(format!("{key_code:X}"));
+                            ui.label(key_name(key));
+                            let mut label = state
+                                .profile
+                                .key_labels
+                                .get(&key_code)
+                                .cloned()
+                                .unwrap_or_default();
+                            if ui.text_edit_singleline(&mut label).changed() {
+                                messages.push(Chip8Message::SetKeyLabel(key_code, label));
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+
+    /// Toggle the visibility of this `KeyLabelWindow`.
+    fn toggle_visibility(&mut self) {
+        self.visible = !self.visible;
+    }
+}
+
+/// A window showing the records captured from the logger, so warnings
+/// and errors can be seen in release builds where the console is hidden.
+struct LogWindow {
+    visible: bool,
+    log: LogBuffer,
+
+    /// The least severe level shown.
+    level: log::LevelFilter,
+
+    /// Only records containing this text are shown.
+    search: String,
+}
+
+impl LogWindow {
+    /// Create a new `LogWindow` showing the records captured in `log`.
+    fn new(log: LogBuffer) -> Self {
+        Self {
+            visible: false,
+            log,
+            level: log::LevelFilter::Info,
+            search: String::new(),
+        }
+    }
+
+    /// Update and render the `LogWindow` to the given `Context`.
+    fn update(&mut self, ctx: &Context) {
+        let Self {
+            visible,
+            log,
+            level,
+            search,
+        } = self;
+        egui::Window::new("Log")
+            .open(visible)
+            .default_size(egui::vec2(600.0, 300.0))
+            .show(ctx, |ui| {
+                // collect the shown records first, so the buffer isn't
+                // locked while drawing in case anything is logged
+                let search_lower = search.to_lowercase();
+                let entries: Vec<(log::Level, String)> = log.with_entries(|entries| {
+                    entries
+                        .iter()
+                        .filter(|entry| entry.level <= *level)
+                        .map(|entry| (entry.level, entry.to_string()))
+                        .filter(|(_, line)| line.to_lowercase().contains(&search_lower))
+                        .collect()
+                });
+
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source("log_level")
+                        .selected_text(level.to_string())
+                        .show_ui(ui, |ui| {
+                            for filter in log::LevelFilter::iter().skip(1) {
+                                ui.selectable_value(level, filter, filter.to_string());
+                            }
+                        });
+                    ui.add(egui::TextEdit::singleline(search).hint_text("Search"));
+                    if ui.button("\u{1F4CB} Copy").clicked() {
+                        let lines: Vec<&str> =
+                            entries.iter().map(|(_, line)| line.as_str()).collect();
+                        ui.output().copied_text = lines.join("\n");
+                    }
+                    if ui.button("\u{1F5D1} Clear").clicked() {
+                        log.clear();
+                    }
+                });
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, false])
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for (level, line) in entries {
+                            let color = match level {
+                                log::Level::Error => ui.visuals().error_fg_color,
+                                log::Level::Warn => ui.visuals().warn_fg_color,
+                                _ => ui.visuals().text_color(),
+                            };
+                            ui.label(egui::RichText::new(line).monospace().color(color));
+                        }
+                    });
+            });
+    }
+
+    /// Toggle the visibility of this `LogWindow`.
+    fn toggle_visibility(&mut self) {
+        self.visible = !self.visible;
+    }
+}
+
+/// A window for running the input latency test, showing the delay
+/// of the recent key presses and their average.
+#[derive(Default)]
+struct LatencyWindow {
+    visible: bool,
+}
+
+impl LatencyWindow {
+    /// Update and render the `LatencyWindow` to the given `Context`, showing the samples of `latency`.
+    fn update(&mut self, ctx: &Context, latency: &LatencyTest, messages: &mut Vec<Chip8Message>) {
+        egui::Window::new("Input Latency")
+            .open(&mut self.visible)
+            .show(ctx, |ui| {
+                ui.label(
+                    "Load a program that draws when a key is pressed, e.g. a keypad test, \
+                    start the test and press keys. The screen flashes white on every press, \
+                    so the delay of the display can also be filmed with a camera.",
+                );
+                ui.horizontal(|ui| {
+                    let label = if latency.running {
+                        "\u{23F9} Stop"
+                    } else {
+                        "\u{23F5} Start"
+                    };
+                    if ui.button(label).clicked() {
+                        messages.push(Chip8Message::SetLatencyTest(!latency.running));
+                    }
+                    if latency.measuring() {
+                        ui.label("Waiting for the program to respond…");
+                    }
+                });
+                ui.label(format!(
+                    "Frame time: {:.1} ms",
+                    latency.frame_time().as_secs_f64() * 1000.0
+                ));
+                ui.separator();
+
+                let samples = latency.samples();
+                if samples.is_empty() {
+                    ui.label("No key presses measured yet.");
+                    return;
+                }
+                egui::Grid::new("latency_grid")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Key");
+                        ui.label("Key read");
+                        ui.label("Screen changed");
+                        ui.label("Input to photon");
+                        ui.end_row();
+                        for sample in samples.iter().rev() {
+                            ui.label(format!("{:X}", sample.key));
+                            ui.label(format!("frame {}", sample.read_frame));
+                            ui.label(format!("frame {}", sample.draw_frame));
+                            ui.label(format!(
+                                "{:.1} ms",
+                                sample.input_to_photon.as_secs_f64() * 1000.0
+                            ));
+                            ui.end_row();
+                        }
+                    });
+                ui.separator();
+                let count = samples.len() as f64;
+                let average = |value: fn(&LatencySample) -> f64| {
+                    samples.iter().map(value).sum::<f64>() / count
+                };
+                ui.label(format!(
+                    "Average: key read in frame {:.1}, screen changed in frame {:.1}, {:.1} ms to photon",
+                    average(|sample| f64::from(sample.read_frame)),
+                    average(|sample| f64::from(sample.draw_frame)),
+                    average(|sample| sample.input_to_photon.as_secs_f64() * 1000.0),
+                ));
+                ui.label(
+                    "The time to photon is measured from the frame the key press was handled in, \
+                    and estimates that the display shows a frame one frame time after it is drawn.",
+                );
+            });
+    }
+
+    /// Toggle the visibility of this `LatencyWindow`.
+    fn toggle_visibility(&mut self) {
+        self.visible = !self.visible;
+    }
+}
+
+/// A window for cleaning up the loaded ROM, e.g. a ROM dump, and exporting the result.
+/// Every edit reloads the ROM.
+#[derive(Default)]
+struct RomToolWindow {
+    visible: bool,
+
+    /// The start address of the segment to relocate, in hex.
+    start: String,
+
+    /// The end address (exclusive) of the segment to relocate, in hex.
+    end: String,
+
+    /// The address to relocate the segment to, in hex.
+    destination: String,
+}
+
+impl RomToolWindow {
+    /// Update and render the `RomToolWindow` to the given `Context`, editing the `rom` loaded
+    /// into the `chip8`.
+    fn update(
+        &mut self,
+        ctx: &Context,
+        rom: &[u8],
+        chip8: &Chip8,
+        messages: &mut Vec<Chip8Message>,
+    ) {
+        let (start, end, destination) = (&mut self.start, &mut self.end, &mut self.destination);
+        egui::Window::new("ROM Tools")
+            .open(&mut self.visible)
+            .show(ctx, |ui| {
+                let padding = rom.len() - chip8::rom::trim(rom).len();
+                ui.label(format!(
+                    "{} of {} bytes of program memory, {padding} bytes of trailing padding",
+                    rom.len(),
+                    chip8::rom::max_size(chip8.memory_size())
+                ));
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(padding > 0, egui::Button::new("Trim Padding"))
+                        .clicked()
+                    {
+                        messages.push(Chip8Message::EditRom(RomEdit::Trim));
+                    }
+                    if ui
+                        .button("Append Block...")
+                        .on_hover_text(
+                            "Append the data of another file to the ROM. \
+                            The result is rejected if it doesn't fit in program memory.",
+                        )
+                        .clicked()
+                    {
+                        if let Some((_, data)) = MenuPanel::load_file_from_dialog() {
+                            messages.push(Chip8Message::EditRom(RomEdit::Append(data)));
+                        }
+                    }
+                });
+                ui.separator();
+
+                ui.label("Relocate Segment");
+                egui::Grid::new("relocate_grid").show(ui, |ui| {
+                    ui.label("Start");
+                    ui.text_edit_singleline(start);
+                    ui.end_row();
+                    ui.label("End (exclusive)");
+                    ui.text_edit_singleline(end);
+                    ui.end_row();
+                    ui.label("Destination");
+                    ui.text_edit_singleline(destination);
+                    ui.end_row();
+                });
+                let parse = |text: &str| {
+                    let text = text.trim();
+                    usize::from_str_radix(text.strip_prefix("0x").unwrap_or(text), 16).ok()
+                };
+                let edit = match (parse(start), parse(end), parse(destination)) {
+                    (Some(start), Some(end), Some(destination)) => Some(RomEdit::Relocate {
+                        start,
+                        end,
+                        destination,
+                    }),
+                    _ => None,
+                };
+                let relocate = ui
+                    .add_enabled(edit.is_some(), egui::Button::new("Relocate"))
+                    .on_hover_text(
+                        "Move the segment to the destination. Instructions referring to it are not updated.",
+                    );
+                if let (true, Some(edit)) = (relocate.clicked(), edit) {
+                    messages.push(Chip8Message::EditRom(edit));
+                }
+                ui.separator();
+
+                let diagnostics = lint::lint(rom, chip8.start_address());
+                egui::CollapsingHeader::new(format!("Diagnostics ({})", diagnostics.len()))
+                    .show(ui, |ui| {
+                        if diagnostics.is_empty() {
+                            ui.label("No suspicious patterns were found in the code.");
+                        }
+                        for diagnostic in diagnostics {
+                            ui.monospace(diagnostic.to_string());
+                        }
+                    });
+                ui.separator();
+
+                if ui.button("\u{1F4BE} Save ROM As...").clicked() {
+                    if let Some(path) = rfd::FileDialog::new().save_file() {
+                        messages.push(Chip8Message::ExportRom(path));
+                    }
+                }
+            });
+    }
+
+    /// Toggle the visibility of this `RomToolWindow`.
+    fn toggle_visibility(&mut self) {
+        self.visible = !self.visible;
+    }
+}
+
+/// The size of the largest sprite, 16x16 pixels in the SUPER-CHIP hi-res mode.
+const MAX_SPRITE_SIZE: usize = 16;
+
+/// The size of a single pixel in the grid of the [`SpriteEditorWindow`].
+const SPRITE_EDITOR_PIXEL_SIZE: f32 = 16.0;
+
+/// The color the sprite of the [`SpriteEditorWindow`] is previewed in on the screen.
+const SPRITE_PREVIEW_COLOR: egui::Color32 =
+    egui::Color32::from_rgba_premultiplied(160, 0, 160, 160);
+
+/// A window for drawing 8xN or 16x16 sprites on a pixel grid. The sprite can be previewed
+/// on the screen, read from and written into memory, or exported for the assembler.
+struct SpriteEditorWindow {
+    visible: bool,
+
+    /// Whether a 16x16 sprite is edited, instead of an 8xN sprite.
+    wide: bool,
+
+    /// The height of 8xN sprites.
+    height: usize,
+
+    /// The pixels of the sprite, row by row. 8xN sprites only use the top left part.
+    pixels: [[bool; MAX_SPRITE_SIZE]; MAX_SPRITE_SIZE],
+
+    /// The memory address the sprite is read from and written to, in hex.
+    address: String,
+
+    /// Whether the sprite is drawn on top of the screen.
+    preview: bool,
+
+    /// The screen coordinates the sprite is previewed at.
+    preview_x: usize,
+    preview_y: usize,
+}
+
+impl Default for SpriteEditorWindow {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            wide: false,
+            height: 8,
+            pixels: Default::default(),
+            address: String::new(),
+            preview: false,
+            preview_x: 0,
+            preview_y: 0,
+        }
+    }
+}
+
+impl SpriteEditorWindow {
+    /// Update and render the `SpriteEditorWindow` to the given `Context`,
+    /// reading sprites from the memory of the given `Chip8`.
+    fn update(&mut self, ctx: &Context, chip8: &Chip8, messages: &mut Vec<Chip8Message>) {
+        let mut visible = self.visible;
+        egui::Window::new("Sprite Editor")
+            .open(&mut visible)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut self.wide, false, "8xN");
+                    ui.radio_value(&mut self.wide, true, "16x16");
+                    if !self.wide {
+                        ui.add(
+                            egui::DragValue::new(&mut self.height)
+                                .clamp_range(1..=MAX_SPRITE_SIZE - 1)
+                                .prefix("N: "),
+                        );
+                    }
+                });
+
+                self.draw_grid(ui);
+                ui.horizontal(|ui| {
+                    if ui.button("Clear").clicked() {
+                        self.pixels = Default::default();
+                    }
+                    if ui.button("Invert").clicked() {
+                        let (width, height) = self.size();
+                        for row in &mut self.pixels[..height] {
+                            row[..width].iter_mut().for_each(|pixel| *pixel = !*pixel);
+                        }
+                    }
+                });
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("Address");
+                    ui.text_edit_singleline(&mut self.address);
+                });
+                let address = self.parse_address(chip8.memory().len());
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(address.is_some(), egui::Button::new("Read"))
+                        .on_hover_text("Load the sprite from memory at the address.")
+                        .clicked()
+                    {
+                        if let Some(address) = address {
+                            let bytes: Vec<u8> = (address..address + self.bytes().len())
+                                .map(|address| chip8.memory()[address])
+                                .collect();
+                            self.set_bytes(&bytes);
+                        }
+                    }
+                    if ui
+                        .add_enabled(address.is_some(), egui::Button::new("Write"))
+                        .on_hover_text("Write the sprite into memory at the address.")
+                        .clicked()
+                    {
+                        if let Some(address) = address {
+                            messages.push(Chip8Message::WriteMemory {
+                                address,
+                                data: self.bytes(),
+                            });
+                        }
+                    }
+                    if ui
+                        .button("\u{1F4CB} Copy for Assembler")
+                        .on_hover_text("Copy the sprite as `DB` lines, one per row.")
+                        .clicked()
+                    {
+                        ui.output().copied_text = self.to_assembly();
+                    }
+                });
+                ui.separator();
+
+                ui.checkbox(&mut self.preview, "Preview on screen");
+                ui.add_enabled_ui(self.preview, |ui| {
+                    ui.horizontal(|ui| {
+                        let display = chip8.display();
+                        ui.add(
+                            egui::DragValue::new(&mut self.preview_x)
+                                .clamp_range(0..=display.width() - 1)
+                                .prefix("X: "),
+                        );
+                        ui.add(
+                            egui::DragValue::new(&mut self.preview_y)
+                                .clamp_range(0..=display.height() - 1)
+                                .prefix("Y: "),
+                        );
+                    });
+                });
+            });
+        self.visible = visible;
+    }
+
+    /// Draw the pixel grid of the sprite. Pixels are set with the primary
+    /// mouse button and cleared with the secondary one.
+    fn draw_grid(&mut self, ui: &mut Ui) {
+        let (width, height) = self.size();
+        let size = egui::vec2(width as f32, height as f32) * SPRITE_EDITOR_PIXEL_SIZE;
+        let (response, painter) = ui.allocate_painter(size, egui::Sense::click_and_drag());
+        let response =
+            response.on_hover_text("Left click to set pixels, right click to clear them.");
+
+        if let Some(position) = response.interact_pointer_pos() {
+            let offset = (position - response.rect.min) / SPRITE_EDITOR_PIXEL_SIZE;
+            let (x, y) = (offset.x as usize, offset.y as usize);
+            if offset.x >= 0.0 && offset.y >= 0.0 && x < width && y < height {
+                let pointer = &ui.input().pointer;
+                if pointer.primary_down() {
+                    self.pixels[y][x] = true;
+                } else if pointer.secondary_down() {
+                    self.pixels[y][x] = false;
+                }
+            }
+        }
+
+        let visuals = ui.visuals();
+        painter.rect_filled(response.rect, 0.0, visuals.extreme_bg_color);
+        for (y, row) in self.pixels[..height].iter().enumerate() {
+            for (x, _) in row[..width].iter().enumerate().filter(|(_, &on)| on) {
+                let min =
+                    response.rect.min + egui::vec2(x as f32, y as f32) * SPRITE_EDITOR_PIXEL_SIZE;
+                painter.rect_filled(
+                    egui::Rect::from_min_size(min, egui::Vec2::splat(SPRITE_EDITOR_PIXEL_SIZE)),
+                    0.0,
+                    visuals.strong_text_color(),
+                );
+            }
+        }
+        let stroke = visuals.widgets.noninteractive.bg_stroke;
+        for x in 0..=width {
+            let x = response.rect.left() + x as f32 * SPRITE_EDITOR_PIXEL_SIZE;
+            painter.vline(x, response.rect.y_range(), stroke);
+        }
+        for y in 0..=height {
+            let y = response.rect.top() + y as f32 * SPRITE_EDITOR_PIXEL_SIZE;
+            painter.hline(response.rect.x_range(), y, stroke);
+        }
+    }
+
+    /// Draw the sprite on top of the screen at the preview coordinates, clipped at the
+    /// edges of the display like the `Dxyn` instruction.
+    fn draw_preview(&self, ui: &Ui, screen_rect: egui::Rect, display: &DisplayFrame) {
+        let painter = ui.painter();
+        let pixel_size = egui::vec2(
+            screen_rect.width() / display.width as f32,
+            screen_rect.height() / display.height as f32,
+        );
+        let (width, height) = self.size();
+        let origin =
+            screen_rect.min + egui::vec2(self.preview_x as f32, self.preview_y as f32) * pixel_size;
+        for (y, row) in self.pixels[..height].iter().enumerate() {
+            for (x, _) in row[..width].iter().enumerate().filter(|(_, &on)| on) {
+                if self.preview_x + x >= display.width || self.preview_y + y >= display.height {
+                    continue;
+                }
+                let min = origin + egui::vec2(x as f32, y as f32) * pixel_size;
+                painter.rect_filled(
+                    egui::Rect::from_min_size(min, pixel_size),
+                    0.0,
+                    SPRITE_PREVIEW_COLOR,
+                );
+            }
+        }
+        let bounds =
+            egui::Rect::from_min_size(origin, egui::vec2(width as f32, height as f32) * pixel_size);
+        painter.rect_stroke(
+            bounds.intersect(screen_rect),
+            0.0,
+            egui::Stroke::new(1.0, SPRITE_PREVIEW_COLOR),
+        );
+    }
+
+    /// Returns this window if its sprite should be previewed on the screen.
+    fn preview(&self) -> Option<&Self> {
+        (self.visible && self.preview).then_some(self)
+    }
+
+    /// The width and height of the sprite in pixels.
+    fn size(&self) -> (usize, usize) {
+        if self.wide {
+            (MAX_SPRITE_SIZE, MAX_SPRITE_SIZE)
+        } else {
+            (8, self.height)
+        }
+    }
+
+    /// The sprite data as read by the `Dxyn` instruction, one byte per row,
+    /// or two bytes per row for 16x16 sprites.
+    fn bytes(&self) -> Vec<u8> {
+        let (width, height) = self.size();
+        self.pixels[..height]
+            .iter()
+            .flat_map(|row| {
+                row[..width]
+                    .chunks(8)
+                    .map(|bits| bits.iter().fold(0, |byte, &on| byte << 1 | on as u8))
+            })
+            .collect()
+    }
+
+    /// Set the pixels of the sprite from `bytes`, laid out like [`Self::bytes`].
+    fn set_bytes(&mut self, bytes: &[u8]) {
+        let (width, height) = self.size();
+        let bytes_per_row = width / 8;
+        for (y, row) in self.pixels[..height].iter_mut().enumerate() {
+            for (x, pixel) in row[..width].iter_mut().enumerate() {
+                *pixel = bytes[y * bytes_per_row + x / 8] & (0x80 >> (x % 8)) != 0;
+            }
+        }
+    }
+
+    /// Format the sprite as `DB` lines for the assembler of the companion CLI,
+    /// with the pixels of each row in a comment.
+    fn to_assembly(&self) -> String {
+        let (width, _) = self.size();
+        self.bytes()
+            .chunks(width / 8)
+            .map(|row| {
+                let bytes: Vec<String> = row.iter().map(|byte| format!("0x{byte:02X}")).collect();
+                let pixels: String = row
+                    .iter()
+                    .flat_map(|byte| (0..8).map(move |bit| byte & (0x80 >> bit) != 0))
+                    .map(|on| if on { '#' } else { '.' })
+                    .collect();
+                format!("DB {} ; {pixels}\n", bytes.join(", "))
+            })
+            .collect()
+    }
+
+    /// Parse the address of the sprite, if the whole sprite fits in the `memory_size` bytes
+    /// of memory from there.
+    fn parse_address(&self, memory_size: usize) -> Option<usize> {
+        let text = self.address.trim();
+        let address = usize::from_str_radix(text.strip_prefix("0x").unwrap_or(text), 16).ok()?;
+        (address + self.bytes().len() <= memory_size).then_some(address)
+    }
+
+    /// Toggle the visibility of this `SpriteEditorWindow`.
+    fn toggle_visibility(&mut self) {
+        self.visible = !self.visible;
+    }
+}
+
+/// A window for exporting the current session into a bundle that another
+/// user can import to reproduce it, e.g. for bug reports.
+#[derive(Default)]
+struct SessionWindow {
+    visible: bool,
+}
+
+impl SessionWindow {
+    /// Update and render the `SessionWindow` to the given `Context`, showing the
+    /// `annotations` of the current session.
+    fn update(&mut self, ctx: &Context, annotations: &str, messages: &mut Vec<Chip8Message>) {
+        egui::Window::new("Session")
+            .open(&mut self.visible)
+            .show(ctx, |ui| {
+                ui.label("Annotations");
+                let mut text = annotations.to_string();
+                let text_edit = egui::TextEdit::multiline(&mut text)
+                    .hint_text("Describe what happens, e.g. steps to reproduce a bug.");
+                if ui.add(text_edit).changed() {
+                    messages.push(Chip8Message::SetAnnotations(text));
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("\u{2B06} Export Session").clicked() {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("Session Bundle", &[SESSION_EXTENSION])
+                            .save_file()
+                        {
+                            messages.push(Chip8Message::ExportSession(path));
+                        }
+                    }
+                    if ui.button("\u{2B07} Import Session").clicked() {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("Session Bundle", &[SESSION_EXTENSION])
+                            .pick_file()
+                        {
+                            messages.push(Chip8Message::ImportSession(path));
+                        }
+                    }
+                });
+            });
+    }
+
+    /// Toggle the visibility of this `SessionWindow`.
+    fn toggle_visibility(&mut self) {
+        self.visible = !self.visible;
+    }
+}
+
+/// The file extension of exported session bundles.
+const SESSION_EXTENSION: &str = "c8session";
+
+/// A window shown on launch when the app crashed last time, offering
+/// to restore the session that was saved when it crashed.
+struct RecoveryWindow {
+    visible: bool,
+}
+
+impl RecoveryWindow {
+    /// Update and render the `RecoveryWindow` to the given `Context`.
+    fn update(&mut self, ctx: &Context, messages: &mut Vec<Chip8Message>) {
+        if !self.visible {
+            return;
+        }
+        egui::Window::new("Restore Session")
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(
+                    "The app crashed during the last run. The active session was saved, \
+                    with the cause of the crash in its notes.",
+                );
+                ui.horizontal(|ui| {
+                    if ui.button("Restore").clicked() {
+                        messages.push(Chip8Message::RestoreEmergencySession);
+                        self.visible = false;
+                    }
+                    if ui.button("Discard").clicked() {
+                        messages.push(Chip8Message::DiscardEmergencySession);
+                        self.visible = false;
+                    }
+                });
+            });
+    }
+}
+
+/// A window shown when the processor halts on a fault, offering
+/// to export the crash dump so it can be attached to a bug report.
+#[derive(Default)]
+struct CrashWindow {
+    /// Whether the user closed the window for the current crash dump.
+    dismissed: bool,
+}
+
+impl CrashWindow {
+    /// Update and render the `CrashWindow` to the given `Context`, if there is a `crash_dump`.
+    fn update(
+        &mut self,
+        ctx: &Context,
+        crash_dump: Option<&CrashDump>,
+        messages: &mut Vec<Chip8Message>,
+    ) {
+        let crash_dump = match crash_dump {
+            Some(crash_dump) => crash_dump,
+            None => {
+                // show the window again for the next crash
+                self.dismissed = false;
+                return;
+            }
+        };
+
+        let mut open = !self.dismissed;
+        egui::Window::new("Crash").open(&mut open).show(ctx, |ui| {
+            ui.label(format!("The program halted: {}", crash_dump.fault));
+            match &crash_dump.path {
+                Some(path) => ui.label(format!("A crash dump was saved to {}", path.display())),
+                None => ui.label("The crash dump could not be saved automatically."),
+            };
+
+            ui.horizontal(|ui| {
+                if ui.button("\u{1F4BE} Export Report").clicked() {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .set_file_name("crash.txt")
+                        .save_file()
+                    {
+                        messages.push(Chip8Message::ExportCrashDump(path));
+                    }
+                }
+                if ui.button("\u{1F4CB} Copy Report").clicked() {
+                    ui.output().copied_text = crash_dump.report.clone();
+                }
+            });
+        });
+        self.dismissed = !open;
     }
+}
 
-    /// Push both foreground and background color update messages to `messages`.
-    fn push_color_messages(&self, messages: &mut Vec<Chip8Message>) {
-        messages.push(Chip8Message::SetForegroundColor(RGB8(self.foreground_rgb)));
-        messages.push(Chip8Message::SetBackgroundColor(RGB8(self.background_rgb)));
+/// A window for iteratively searching the `Chip8` memory for game variables.
+/// Results can be turned into cheats or watches.
+struct RamSearchWindow {
+    search: Option<RamSearch>,
+    filter: SearchFilter,
+    value: u8,
+}
+
+impl Default for RamSearchWindow {
+    fn default() -> Self {
+        Self {
+            search: None,
+            filter: SearchFilter::Equal(0),
+            value: 0,
+        }
     }
+}
 
-    /// Toggle the visibility of this `ConfigWindow`,
-    fn toggle_visibility(&mut self) {
-        self.visible = !self.visible;
+impl RamSearchWindow {
+    /// The maximum number of results shown in the window.
+    const MAX_RESULTS: usize = 100;
+
+    /// Update and render the `RamSearchWindow`. Returns an address if the user
+    /// chose to watch one of the results.
+    fn update(
+        &mut self,
+        ctx: &Context,
+        chip8: &Chip8,
+        messages: &mut Vec<Chip8Message>,
+    ) -> Option<usize> {
+        let mut watch = None;
+        egui::Window::new("RAM Search").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("New Search").clicked() {
+                    self.search = Some(RamSearch::new(chip8));
+                }
+
+                egui::ComboBox::from_id_source("ram_search_filter")
+                    .selected_text(self.filter.name())
+                    .show_ui(ui, |ui| {
+                        for filter in [
+                            SearchFilter::Equal(0),
+                            SearchFilter::GreaterThan(0),
+                            SearchFilter::LessThan(0),
+                            SearchFilter::Changed,
+                            SearchFilter::Unchanged,
+                            SearchFilter::Increased,
+                            SearchFilter::Decreased,
+                        ] {
+                            let selected = self.filter.name() == filter.name();
+                            if ui.selectable_label(selected, filter.name()).clicked() {
+                                self.filter = filter;
+                            }
+                        }
+                    });
+                if self.filter.has_value() {
+                    ui.add(egui::DragValue::new(&mut self.value));
+                }
+
+                let filter_button =
+                    ui.add_enabled(self.search.is_some(), egui::Button::new("Filter"));
+                if filter_button.clicked() {
+                    if let Some(search) = &mut self.search {
+                        search.filter(chip8, self.filter.with_value(self.value));
+                    }
+                }
+            });
+
+            let search = match &self.search {
+                Some(search) => search,
+                None => {
+                    ui.label("Start a new search to snapshot the memory.");
+                    return;
+                }
+            };
+
+            ui.separator();
+            ui.label(format!("{} candidates", search.candidates().len()));
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                egui::Grid::new("ram_search_grid")
+                    .striped(true)
+                    .num_columns(5)
+                    .show(ui, |ui| {
+                        ui.label("Address");
+                        ui.label("Previous");
+                        ui.label("Current");
+                        ui.end_row();
+                        for &address in search.candidates().iter().take(Self::MAX_RESULTS) {
+                            // candidates past the end of memory that shrank are dropped
+                            // by the next filter
+                            let current = match chip8.memory().get(address) {
+                                Some(&current) => current,
+                                None => continue,
+                            };
+                            ui.label(format!("{address:#06X}"));
+                            ui.label(search.snapshot_value(address).to_string());
+                            ui.label(current.to_string());
+                            if ui.button("Watch").clicked() {
+                                watch = Some(address);
+                            }
+                            if ui.button("Cheat").clicked() {
+                                messages.push(Chip8Message::AddCheat(Cheat {
+                                    name: format!("{address:#06X}"),
+                                    address: u16::try_from(address).unwrap(),
+                                    value: current,
+                                    enabled: true,
+                                }));
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+        watch
     }
 }
 
+/// Parse a hexadecimal number, with or without a leading `0x`.
+fn parse_hex(text: &str) -> Option<usize> {
+    let text = text.trim();
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    usize::from_str_radix(digits, 16).ok()
+}
+
+/// Get the name of a keyboard key as printed on the key, e.g. `1` instead of `Num1`.
+fn key_name(key: Key) -> String {
+    let name = format!("{key:?}");
+    name.strip_prefix("Num").unwrap_or(&name).to_string()
+}
+
+/// The height of a lane in the timeline window.
+const TIMELINE_LANE_HEIGHT: f32 = 20.0;
+
+/// The height of the frame numbers below the lanes of the timeline window.
+const TIMELINE_RULER_HEIGHT: f32 = 14.0;
+
+/// The width of the lane names on the left of the timeline window.
+const TIMELINE_LABEL_WIDTH: f32 = 50.0;
+
+/// The range the width of a frame on the timeline can be zoomed in.
+const TIMELINE_MIN_FRAME_WIDTH: f32 = 0.05;
+const TIMELINE_MAX_FRAME_WIDTH: f32 = 100.0;
+
+/// The maximum amount of events listed when hovering a frame of the timeline.
+const TIMELINE_MAX_TOOLTIP_EVENTS: usize = 20;
+
 /// A debug screen showing the details of the underlying state of the `Chip8`,
 /// such as registers, stack memory, instructions, and timers.
 #[derive(Default)]
 struct DebugView {
-    /// Mirrors the paused state of the `App`. This is used to determine
-    /// whether the instructions window should be drawn with every instruction or not.
-    paused: bool,
+    /// Mirrors the triggers of the `App`'s debugger.
+    triggers: Triggers,
+
+    /// Mirrors the breakpoints of the `App`'s debugger.
+    breakpoints: Vec<Breakpoint>,
+
+    new_breakpoint: NewBreakpoint,
+
+    /// The memory addresses shown in the watches window.
+    watches: Vec<usize>,
+
+    /// The expressions shown in the watches window.
+    expression_watches: Vec<Watch>,
+
+    /// The text of the watch expression being entered.
+    new_watch: String,
+
+    ram_search: RamSearchWindow,
+
+    timeline_view: TimelineView,
+
+    /// The address and opcode of the instruction shown in the opcode reference window.
+    selected_instruction: Option<(usize, usize)>,
 }
 
-impl DebugView {
-    fn toggle_pause(&mut self) {
-        self.paused = !self.paused;
+/// The breakpoint being entered in the triggers window of a [`DebugView`].
+struct NewBreakpoint {
+    /// The address to break at, empty to break at any address.
+    address: String,
+
+    /// The operand of the condition, `None` to break without a condition.
+    operand: Option<Operand>,
+
+    comparison: Comparison,
+    value: String,
+}
+
+impl Default for NewBreakpoint {
+    fn default() -> Self {
+        Self {
+            address: String::new(),
+            operand: None,
+            comparison: Comparison::Equal,
+            value: String::new(),
+        }
+    }
+}
+
+impl NewBreakpoint {
+    /// The breakpoint that was entered, if it is valid. The address can also be
+    /// given by its name in the `symbols` of the program.
+    fn breakpoint(&self, symbols: &Symbols) -> Option<Breakpoint> {
+        let address = match self.address.trim() {
+            "" => None,
+            address => Some(symbols.address_of(address).or_else(|| parse_hex(address))?),
+        };
+        let condition = match self.operand {
+            Some(operand) => Some(Condition {
+                operand,
+                comparison: self.comparison,
+                value: parse_hex(&self.value)?,
+            }),
+            None => None,
+        };
+        // a breakpoint needs an address or a condition
+        (address.is_some() || condition.is_some()).then_some(Breakpoint { address, condition })
     }
+}
+
+/// The zoom and scroll position of the timeline window of a [`DebugView`].
+struct TimelineView {
+    /// The width of a frame on the timeline.
+    frame_width: f32,
+
+    /// The frame at the left edge of the timeline, or `None` to follow the latest frame.
+    start: Option<f64>,
+}
+
+impl Default for TimelineView {
+    fn default() -> Self {
+        Self {
+            frame_width: 4.0,
+            start: None,
+        }
+    }
+}
 
+impl DebugView {
     /// Update the `DebugView`. This will draw all windows on the given context,
     /// and should be called last.
-    fn update(&mut self, ctx: &Context, screen: &ScreenView, chip8: &Chip8) {
+    fn update(
+        &mut self,
+        ctx: &Context,
+        screen: &ScreenView,
+        state: &GuiState,
+        show_key_labels: bool,
+        sprite_preview: Option<&SpriteEditorWindow>,
+        messages: &mut Vec<Chip8Message>,
+    ) {
+        let chip8 = state.chip8;
         Self::draw_registers_window(ctx, chip8);
         Self::draw_stack_window(ctx, chip8);
-        Self::draw_screen_window(ctx, screen, chip8);
+        Self::draw_screen_window(ctx, screen, state, show_key_labels, sprite_preview);
         Self::draw_timers_window(ctx, chip8);
-        Self::draw_key_window(ctx, chip8);
-        self.draw_instructions_window(ctx, chip8);
+        Self::draw_key_window(ctx, chip8, &state.profile.key_labels);
+        self.draw_instructions_window(ctx, state);
+        Self::draw_disassembly_window(ctx, state);
+        self.draw_reference_window(ctx, chip8);
+        self.draw_triggers_window(ctx, chip8, messages);
+        self.draw_watches_window(ctx, chip8);
+        Self::draw_profiler_window(ctx, state.profiler, messages);
+        self.draw_timeline_window(ctx, state.timeline, messages);
+        if let Some(address) = self.ram_search.update(ctx, chip8, messages) {
+            if !self.watches.contains(&address) {
+                self.watches.push(address);
+            }
+        }
+    }
+
+    /// Draw a window with the events that pause the execution.
+    fn draw_triggers_window(
+        &mut self,
+        ctx: &Context,
+        chip8: &Chip8,
+        messages: &mut Vec<Chip8Message>,
+    ) {
+        egui::Window::new("Triggers").show(ctx, |ui| {
+            let mut changed = false;
+            changed |= ui
+                .checkbox(&mut self.triggers.on_draw, "Break on sprite draw")
+                .changed();
+            changed |= ui
+                .checkbox(&mut self.triggers.on_sound, "Break when sound timer is set")
+                .changed();
+            changed |= ui
+                .checkbox(&mut self.triggers.on_key_wait, "Break on key wait")
+                .changed();
+            changed |= ui
+                .checkbox(
+                    &mut self.triggers.on_invalid_opcode,
+                    "Break on invalid opcode",
+                )
+                .on_hover_text("Break after an opcode that isn't an instruction is skipped.")
+                .changed();
+            changed |= ui
+                .checkbox(
+                    &mut self.triggers.on_code_write,
+                    "Break on self-modifying code",
+                )
+                .on_hover_text("Break after an instruction writes to code that was executed.")
+                .changed();
+
+            ui.separator();
+            ui.label("Break when a register changes");
+            egui::Grid::new("register_watches_grid").show(ui, |ui| {
+                for (i, watched) in self.triggers.watch_v.iter_mut().enumerate() {
+                    changed |= ui.checkbox(watched, format!("V{i:X}")).changed();
+                    if i % 4 == 3 {
+                        ui.end_row();
+                    }
+                }
+                changed |= ui.checkbox(&mut self.triggers.watch_i, "I").changed();
+            });
+
+            if changed {
+                messages.push(Chip8Message::SetTriggers(self.triggers));
+            }
+
+            ui.separator();
+            ui.label("Breakpoints");
+            let mut breakpoints_changed = false;
+            let mut removed = None;
+            for (i, breakpoint) in self.breakpoints.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.monospace(breakpoint.to_string());
+                    if let Some(name) = breakpoint.address.and_then(|a| chip8.symbols().name(a)) {
+                        ui.label(name);
+                    }
+                    if ui.button("\u{1F5D1}").clicked() {
+                        removed = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = removed {
+                self.breakpoints.remove(i);
+                breakpoints_changed = true;
+            }
+
+            let new = &mut self.new_breakpoint;
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut new.address)
+                        .hint_text("Any address")
+                        .desired_width(90.0),
+                );
+                ui.label("when");
+                let operand = new.operand.map_or("Always".to_owned(), |operand| operand.to_string());
+                egui::ComboBox::from_id_source("breakpoint_operand")
+                    .selected_text(operand)
+                    .width(70.0)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut new.operand, None, "Always");
+                        for operand in Operand::ALL {
+                            ui.selectable_value(&mut new.operand, Some(operand), operand.to_string());
+                        }
+                    });
+                if new.operand.is_some() {
+                    egui::ComboBox::from_id_source("breakpoint_comparison")
+                        .selected_text(new.comparison.to_string())
+                        .width(40.0)
+                        .show_ui(ui, |ui| {
+                            for comparison in Comparison::ALL {
+                                ui.selectable_value(
+                                    &mut new.comparison,
+                                    comparison,
+                                    comparison.to_string(),
+                                );
+                            }
+                        });
+                    ui.add(
+                        egui::TextEdit::singleline(&mut new.value)
+                            .hint_text("Value")
+                            .desired_width(50.0),
+                    );
+                }
+                let breakpoint = new.breakpoint(chip8.symbols());
+                if ui
+                    .add_enabled(breakpoint.is_some(), egui::Button::new("Add"))
+                    .on_hover_text("Break before the instruction at the address, or at any instruction, once the condition holds. Addresses and values are hexadecimal, or addresses the name of a loaded symbol.")
+                    .clicked()
+                {
+                    self.breakpoints.extend(breakpoint);
+                    breakpoints_changed = true;
+                }
+            });
+
+            if breakpoints_changed {
+                messages.push(Chip8Message::SetBreakpoints(self.breakpoints.clone()));
+            }
+        });
+    }
+
+    /// Draw a window with the disassembly of the program in memory, which includes the
+    /// changes of self-modifying code, with the instruction at the program counter highlighted.
+    fn draw_disassembly_window(ctx: &Context, state: &GuiState) {
+        let chip8 = state.chip8;
+        egui::Window::new("Disassembly").show(ctx, |ui| {
+            let start = chip8.start_address();
+            let end = (start + state.rom.len()).min(chip8.memory().len());
+            let lines =
+                disasm::disassemble_rom(&chip8.memory()[start..end], start, chip8.symbols());
+            let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+            egui::ScrollArea::vertical()
+                .auto_shrink([false, false])
+                .show_rows(ui, row_height, lines.len(), |ui, rows| {
+                    for line in &lines[rows] {
+                        let label = match chip8.symbols().name(line.address) {
+                            Some(name) => format!("{name}:"),
+                            None => String::new(),
+                        };
+                        let mut text =
+                            egui::RichText::new(format!("{label:<12}{line}")).monospace();
+                        // the program counter can be in the middle of a line of unaligned code
+                        if (line.address..line.address + line.bytes.len()).contains(&chip8.pc()) {
+                            text = text.color(ui.visuals().selection.stroke.color);
+                        }
+                        ui.label(text);
+                    }
+                });
+        });
+    }
+
+    /// Draw the values of the watch expressions, and a field to add one.
+    fn draw_expression_watches(&mut self, ui: &mut Ui, chip8: &Chip8) {
+        let mut removed = None;
+        egui::Grid::new("expression_watches_grid")
+            .striped(true)
+            .num_columns(3)
+            .show(ui, |ui| {
+                for (i, watch) in self.expression_watches.iter().enumerate() {
+                    ui.monospace(&watch.name)
+                        .on_hover_text(watch.expr.to_string());
+                    match watch.evaluate(chip8) {
+                        Ok(value) => ui.monospace(format!("{value} ({value:#X})")),
+                        Err(e) => ui.colored_label(ui.visuals().warn_fg_color, e.to_string()),
+                    };
+                    if ui.button("\u{1F5D1}").clicked() {
+                        removed = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(i) = removed {
+            self.expression_watches.remove(i);
+        }
+
+        ui.horizontal(|ui| {
+            let watch = Watch::parse(&self.new_watch);
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.new_watch)
+                    .hint_text("lives = mem[I + 2]")
+                    .desired_width(160.0),
+            );
+            let submitted = response.lost_focus() && ui.input().key_pressed(Key::Enter);
+            let add = ui
+                .add_enabled(watch.is_ok(), egui::Button::new("Add"))
+                .on_hover_text(match &watch {
+                    Ok(_) => "Watch an expression over V0-VF, I, PC, SP, DT, ST, mem[...] \
+                              and stack[...], optionally named like `name = expression`."
+                        .to_owned(),
+                    Err(e) => e.to_string(),
+                });
+            if let (true, Ok(watch)) = (add.clicked() || submitted, watch) {
+                self.expression_watches.push(watch);
+                self.new_watch.clear();
+            }
+        });
+    }
+
+    /// Draw a window that shows the values of the watched memory addresses.
+    fn draw_watches_window(&mut self, ctx: &Context, chip8: &Chip8) {
+        egui::Window::new("Watches").show(ctx, |ui| {
+            self.draw_expression_watches(ui, chip8);
+            ui.separator();
+            if self.watches.is_empty() {
+                ui.label("Add memory watches from the RAM search window.");
+                return;
+            }
+            let mut removed = None;
+            egui::Grid::new("watches_grid")
+                .striped(true)
+                .num_columns(3)
+                .show(ui, |ui| {
+                    for (i, &address) in self.watches.iter().enumerate() {
+                        ui.heading(format!("{address:#06X}"));
+                        // the memory may have shrunk since the watch was added
+                        match chip8.memory().get(address) {
+                            Some(value) => ui.heading(format!("{value} ({value:#04X})")),
+                            None => ui.heading("out of memory"),
+                        };
+                        if ui.button("\u{1F5D1}").clicked() {
+                            removed = Some(i);
+                        }
+                        ui.end_row();
+                    }
+                });
+            if let Some(i) = removed {
+                self.watches.remove(i);
+            }
+        });
+    }
+
+    /// Draw a window that shows the cycles spent in each subroutine, as recorded by the `profiler`.
+    fn draw_profiler_window(ctx: &Context, profiler: &Profiler, messages: &mut Vec<Chip8Message>) {
+        egui::Window::new("Profiler").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let mut enabled = profiler.enabled;
+                if ui.checkbox(&mut enabled, "Enabled").changed() {
+                    messages.push(Chip8Message::SetProfiling(enabled));
+                }
+                if ui.button("Reset").clicked() {
+                    messages.push(Chip8Message::ResetProfile);
+                }
+                if ui
+                    .button("Export Call Graph...")
+                    .on_hover_text("Save the calls between subroutines as a Graphviz .dot file, or as .svg if Graphviz is installed.")
+                    .clicked()
+                {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .add_filter("Graphviz", &["dot"])
+                        .add_filter("SVG", &["svg"])
+                        .save_file()
+                    {
+                        messages.push(Chip8Message::ExportCallGraph(path));
+                    }
+                }
+                ui.label(format!("{} cycles", profiler.total_cycles()));
+            });
+            ui.separator();
+
+            let total = profiler.total_cycles().max(1) as f64;
+            egui::ScrollArea::vertical()
+                .auto_shrink([false, false])
+                .show(ui, |ui| {
+                    egui::Grid::new("profiler_grid")
+                        .striped(true)
+                        .num_columns(4)
+                        .show(ui, |ui| {
+                            ui.label("Routine");
+                            ui.label("Calls");
+                            ui.label("Inclusive");
+                            ui.label("Exclusive");
+                            ui.end_row();
+                            for (address, stats) in profiler.routines() {
+                                ui.label(format!("{address:#06X}"));
+                                ui.label(stats.calls.to_string());
+                                ui.label(format!(
+                                    "{} ({:.1}%)",
+                                    stats.inclusive,
+                                    stats.inclusive as f64 / total * 100.0
+                                ));
+                                ui.label(format!(
+                                    "{} ({:.1}%)",
+                                    stats.exclusive,
+                                    stats.exclusive as f64 / total * 100.0
+                                ));
+                                ui.end_row();
+                            }
+                        });
+                });
+        });
+    }
+
+    /// Draw a window that shows the events recorded by the `timeline` on a zoomable timeline,
+    /// one lane per kind of event. Scrolling zooms, dragging pans, and hovering a frame lists its events.
+    fn draw_timeline_window(
+        &mut self,
+        ctx: &Context,
+        timeline: &Timeline,
+        messages: &mut Vec<Chip8Message>,
+    ) {
+        let view = &mut self.timeline_view;
+        egui::Window::new("Timeline")
+            .default_size(egui::vec2(600.0, 150.0))
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let mut enabled = timeline.enabled;
+                    if ui.checkbox(&mut enabled, "Record").changed() {
+                        messages.push(Chip8Message::SetTimelineRecording(enabled));
+                    }
+                    if ui.button("Reset").clicked() {
+                        messages.push(Chip8Message::ResetTimeline);
+                        view.start = None;
+                    }
+                    let mut follow = view.start.is_none();
+                    if ui.checkbox(&mut follow, "Follow").changed() && follow {
+                        view.start = None;
+                    }
+                    ui.label(format!(
+                        "{} frames, {} events",
+                        timeline.frame(),
+                        timeline.event_count()
+                    ));
+                });
+                ui.separator();
+
+                let lanes_height = TIMELINE_LANE_HEIGHT * Lane::ALL.len() as f32;
+                let size = egui::vec2(ui.available_width(), lanes_height + TIMELINE_RULER_HEIGHT);
+                let (response, painter) = ui.allocate_painter(size, egui::Sense::drag());
+                let rect = response.rect;
+                let area = egui::Rect::from_min_max(
+                    rect.min + egui::vec2(TIMELINE_LABEL_WIDTH, 0.0),
+                    egui::pos2(rect.right(), rect.top() + lanes_height),
+                );
+                let visible_frames = f64::from(area.width() / view.frame_width);
+                let follow_start = (timeline.frame() as f64 + 1.0 - visible_frames).max(0.0);
+                let frame_at = |x: f32, start: f64, frame_width: f32| {
+                    start + f64::from((x - area.left()) / frame_width)
+                };
+
+                // zoom around the pointer, and pan by dragging
+                if let Some(pointer) = response.hover_pos() {
+                    let scroll = ui.input().scroll_delta.y;
+                    if scroll != 0.0 {
+                        let start = view.start.unwrap_or(follow_start);
+                        let frame = frame_at(pointer.x, start, view.frame_width);
+                        view.frame_width = (view.frame_width * (scroll / 200.0).exp())
+                            .clamp(TIMELINE_MIN_FRAME_WIDTH, TIMELINE_MAX_FRAME_WIDTH);
+                        let start = frame - f64::from((pointer.x - area.left()) / view.frame_width);
+                        view.start = Some(start.max(0.0));
+                    }
+                }
+                if response.dragged() {
+                    let start = view.start.unwrap_or(follow_start);
+                    let delta = f64::from(response.drag_delta().x / view.frame_width);
+                    view.start = Some((start - delta).max(0.0));
+                }
+                let start = view.start.unwrap_or(follow_start);
+                let visible_frames = f64::from(area.width() / view.frame_width);
+                let x_of =
+                    |frame: u64| area.left() + (frame as f64 - start) as f32 * view.frame_width;
+
+                let visuals = ui.visuals();
+                let stroke = visuals.widgets.noninteractive.bg_stroke;
+                let font = egui::FontId::proportional(12.0);
+                painter.rect_filled(area, 0.0, visuals.extreme_bg_color);
+                for (i, lane) in Lane::ALL.iter().enumerate() {
+                    let y = area.top() + i as f32 * TIMELINE_LANE_HEIGHT;
+                    painter.text(
+                        egui::pos2(rect.left(), y + TIMELINE_LANE_HEIGHT / 2.0),
+                        egui::Align2::LEFT_CENTER,
+                        lane.to_string(),
+                        font.clone(),
+                        visuals.text_color(),
+                    );
+                    painter.hline(area.x_range(), y, stroke);
+                }
+
+                // label the frames, keeping the labels apart
+                let mut label_step = 1;
+                while label_step as f32 * view.frame_width < 50.0 {
+                    label_step *= 10;
+                }
+                let first_label = (start as u64 / label_step) * label_step;
+                let last_frame = (start + visible_frames).ceil() as u64;
+                for frame in (first_label..=last_frame).step_by(label_step as usize) {
+                    let x = x_of(frame);
+                    if x < area.left() {
+                        continue;
+                    }
+                    painter.vline(x, area.y_range(), stroke);
+                    painter.text(
+                        egui::pos2(x, area.bottom()),
+                        egui::Align2::LEFT_TOP,
+                        frame.to_string(),
+                        font.clone(),
+                        visuals.weak_text_color(),
+                    );
+                }
+
+                let frames = start as u64..last_frame + 1;
+                let event_width = view.frame_width.clamp(1.0, 3.0);
+                for event in timeline.events(frames) {
+                    let x = x_of(event.frame) + view.frame_width / 2.0;
+                    if !area.x_range().contains(&x) {
+                        continue;
+                    }
+                    let lane = Lane::ALL
+                        .iter()
+                        .position(|&lane| lane == event.kind.lane())
+                        .unwrap_or_default();
+                    let y = area.top() + lane as f32 * TIMELINE_LANE_HEIGHT;
+                    painter.vline(
+                        x,
+                        y + 3.0..=y + TIMELINE_LANE_HEIGHT - 3.0,
+                        egui::Stroke::new(event_width, Self::timeline_color(event.kind)),
+                    );
+                }
+
+                let hovered_frame = response
+                    .hover_pos()
+                    .filter(|pointer| area.x_range().contains(&pointer.x))
+                    .map(|pointer| frame_at(pointer.x, start, view.frame_width) as u64);
+                if let Some(frame) = hovered_frame {
+                    let column = egui::Rect::from_x_y_ranges(
+                        x_of(frame)..=x_of(frame) + view.frame_width,
+                        area.y_range(),
+                    );
+                    painter.rect_filled(column, 0.0, egui::Color32::from_white_alpha(20));
+                    response.on_hover_ui_at_pointer(|ui| {
+                        ui.label(format!("Frame {frame}"));
+                        let events: Vec<_> = timeline.events(frame..frame + 1).collect();
+                        for event in events.iter().take(TIMELINE_MAX_TOOLTIP_EVENTS) {
+                            ui.monospace(format!("{:#06X}  {}", event.address, event.kind));
+                        }
+                        if events.len() > TIMELINE_MAX_TOOLTIP_EVENTS {
+                            ui.label(format!(
+                                "{} more events",
+                                events.len() - TIMELINE_MAX_TOOLTIP_EVENTS
+                            ));
+                        }
+                    });
+                }
+            });
+    }
+
+    /// The color an event is drawn in on the timeline.
+    fn timeline_color(kind: EventKind) -> egui::Color32 {
+        match kind {
+            EventKind::Call(_) => egui::Color32::LIGHT_BLUE,
+            EventKind::Return => egui::Color32::DARK_GRAY,
+            EventKind::Draw => egui::Color32::LIGHT_GREEN,
+            EventKind::KeyDown(_) => egui::Color32::YELLOW,
+            EventKind::KeyUp(_) => egui::Color32::KHAKI,
+            EventKind::DelayTimer(_) => egui::Color32::LIGHT_RED,
+            EventKind::SoundTimer(_) => egui::Color32::RED,
+        }
     }
 
     /// Draw a window that shows every register in the given `Chip8`.
@@ -488,9 +3724,9 @@ impl DebugView {
                 .num_columns(2)
                 .show(ui, |ui| {
                     ui.heading("I");
-                    ui.heading(format!("{:#06X}", chip8.processor.i));
+                    ui.heading(format!("{:#06X}", chip8.index()));
                     ui.end_row();
-                    for (i, register) in chip8.processor.v.iter().enumerate() {
+                    for (i, register) in chip8.registers().iter().enumerate() {
                         ui.heading(format!("V{i:X}"));
                         ui.heading(register.to_string());
                         ui.end_row();
@@ -503,33 +3739,55 @@ impl DebugView {
     /// (stack pointer, stack memory) of the given `Chip8`.
     fn draw_stack_window(ctx: &Context, chip8: &Chip8) {
         egui::Window::new("Stack").show(ctx, |ui| {
-            ui.heading(format!("Pointer: {}", chip8.processor.sp));
-            egui::Grid::new("Stack grid")
+            ui.heading(format!("Pointer: {}", chip8.processor().sp));
+            // the backtrace, most recent call first
+            egui::Grid::new("call_stack_grid")
                 .striped(true)
-                .num_columns(2)
+                .num_columns(3)
                 .show(ui, |ui| {
-                    for (i, value) in chip8.processor.stack.iter().enumerate() {
-                        ui.heading(i.to_string());
-                        ui.heading(format!("{value:#06X}"));
+                    ui.label("Subroutine");
+                    ui.label("Called at");
+                    ui.label("Returns to");
+                    ui.end_row();
+                    for frame in chip8.call_stack().iter().rev() {
+                        ui.heading(format!("{:#06X}", frame.subroutine));
+                        ui.heading(format!("{:#06X}", frame.call_site))
+                            .on_hover_text(format!("In the subroutine at {:#06X}", frame.caller));
+                        ui.heading(format!("{:#06X}", frame.return_address));
                         ui.end_row();
                     }
                 });
+            ui.collapsing("Raw stack", |ui| {
+                egui::Grid::new("Stack grid")
+                    .striped(true)
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for (i, value) in chip8.processor().stack.iter().enumerate() {
+                            ui.heading(i.to_string());
+                            ui.heading(format!("{value:#06X}"));
+                            ui.end_row();
+                        }
+                    });
+            });
         });
     }
 
     /// Draw a window that shows the instructions executed by the `Chip8`,
     /// in their opcode form as well as a more descriptive readable form.
-    fn draw_instructions_window(&mut self, ctx: &Context, chip8: &Chip8) {
+    /// Hovering an instruction shows its reference, clicking it opens the reference window.
+    fn draw_instructions_window(&mut self, ctx: &Context, state: &GuiState) {
+        let chip8 = state.chip8;
         egui::Window::new("Instructions").show(ctx, |ui| {
-            if !self.paused {
+            if !state.paused {
                 ui.heading("Pause the execution to inspect instructions.");
                 return;
             }
 
-            ui.heading(format!(
-                "Current Program Counter: {:#06X}",
-                chip8.processor.pc
-            ));
+            ui.heading(format!("Current Program Counter: {:#06X}", chip8.pc()));
+            ui.label(format!("State: {}", chip8.state()));
+            if let Some(reason) = state.break_reason {
+                ui.label(format!("Break: {reason}"));
+            }
             ui.separator();
 
             egui::ScrollArea::vertical()
@@ -545,12 +3803,42 @@ impl DebugView {
                             ui.add(egui::Separator::default().vertical());
                             ui.heading("Description");
                             ui.end_row();
-                            for instr in &chip8.processor.instructions {
-                                ui.heading(format!("{:#06X}", instr.address));
-                                ui.add(egui::Separator::default().vertical());
-                                ui.heading(format!("{:#06X}", instr.opcode));
-                                ui.add(egui::Separator::default().vertical());
-                                ui.heading(&instr.display);
+                            // the instruction that changed a watched register is the last one executed
+                            let highlighted = matches!(
+                                state.break_reason,
+                                Some(BreakReason::RegisterChanged { .. })
+                            );
+                            for (i, instr) in chip8.instructions().iter().enumerate() {
+                                let cells = [
+                                    format!("{:#06X}", instr.address),
+                                    format!("{:#06X}", instr.opcode),
+                                    instr.display.clone(),
+                                ];
+                                let key = (instr.address, instr.opcode);
+                                let selected = self.selected_instruction == Some(key);
+                                for (column, cell) in cells.into_iter().enumerate() {
+                                    if column > 0 {
+                                        ui.add(egui::Separator::default().vertical());
+                                    }
+                                    let mut text = egui::RichText::new(cell).heading();
+                                    if highlighted && i == 0 {
+                                        text = text.color(ui.visuals().warn_fg_color);
+                                    } else if selected {
+                                        text = text.color(ui.visuals().selection.stroke.color);
+                                    }
+                                    let response = ui
+                                        .add(egui::Label::new(text).sense(egui::Sense::click()))
+                                        .on_hover_ui(|ui| {
+                                            Self::draw_opcode_reference(
+                                                ui,
+                                                instr.opcode as u16,
+                                                chip8,
+                                            );
+                                        });
+                                    if response.clicked() {
+                                        self.selected_instruction = Some(key);
+                                    }
+                                }
                                 ui.end_row();
                             }
                         });
@@ -558,12 +3846,88 @@ impl DebugView {
         });
     }
 
-    /// Draw a window that displays the `Chip8` graphics state.
-    fn draw_screen_window(ctx: &Context, screen: &ScreenView, chip8: &Chip8) {
+    /// Draw a window with the reference of the instruction selected in the instructions window.
+    fn draw_reference_window(&mut self, ctx: &Context, chip8: &Chip8) {
+        let (address, opcode) = match self.selected_instruction {
+            Some(selected) => selected,
+            None => return,
+        };
+        let mut open = true;
+        egui::Window::new("Opcode Reference")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.label(format!("{opcode:04X} at {address:#06X}"));
+                ui.separator();
+                Self::draw_opcode_reference(ui, opcode as u16, chip8);
+            });
+        if !open {
+            self.selected_instruction = None;
+        }
+    }
+
+    /// Explain the given opcode: its operands with their current values, whether the
+    /// current variant supports it, and the state of the quirks that affect it.
+    fn draw_opcode_reference(ui: &mut Ui, opcode: u16, chip8: &Chip8) {
+        let entry = match reference::lookup(opcode) {
+            Some(entry) => entry,
+            None => {
+                ui.label(format!("{opcode:04X} is not a known instruction."));
+                return;
+            }
+        };
+        ui.heading(format!("{}: {}", entry.pattern, entry.summary));
+        ui.label(entry.details);
+
+        let operands = entry.operands(opcode);
+        if !operands.is_empty() {
+            ui.separator();
+            egui::Grid::new("reference_operands_grid").show(ui, |ui| {
+                for operand in operands {
+                    ui.monospace(operand.name);
+                    ui.label(&operand.meaning);
+                    if matches!(operand.name, "x" | "y") {
+                        let value = chip8.registers()[usize::from(operand.value)];
+                        ui.label(format!("currently {value} ({value:#04X})"));
+                    }
+                    ui.end_row();
+                }
+            });
+        }
+
+        ui.separator();
+        let variants: Vec<String> = entry.variants.iter().map(Variant::to_string).collect();
+        ui.label(format!("Available on: {}", variants.join(", ")));
+        if !entry.is_supported(chip8.variant()) {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                format!("Not supported by the current variant ({})", chip8.variant()),
+            );
+        }
+        for &quirk in entry.quirks {
+            let state = if quirk.is_enabled(&chip8.quirks()) {
+                "enabled"
+            } else {
+                "disabled"
+            };
+            ui.label(format!("{quirk} ({state}): {}", quirk.description()));
+        }
+    }
+
+    /// Draw a window that displays the display buffer of the given `state`.
+    fn draw_screen_window(
+        ctx: &Context,
+        screen: &ScreenView,
+        state: &GuiState,
+        show_key_labels: bool,
+        sprite_preview: Option<&SpriteEditorWindow>,
+    ) {
         egui::Window::new("Screen")
             .default_size(egui::vec2(500.0, 250.0))
             .show(ctx, |ui| {
-                screen.draw_chip8_renderer(ui, chip8);
+                let screen_rect = screen.draw_screen(ui, state, show_key_labels);
+                if let Some(editor) = sprite_preview {
+                    editor.draw_preview(ui, screen_rect, state.display);
+                }
             });
     }
 
@@ -573,34 +3937,28 @@ impl DebugView {
         egui::Window::new("Timers").show(ctx, |ui| {
             egui::Grid::new("timer_grid").show(ui, |ui| {
                 ui.heading("Delay");
-                ui.heading(chip8.bus.clock.delay_timer.to_string());
+                ui.heading(chip8.delay_timer().to_string());
                 ui.end_row();
                 ui.heading("Sound");
-                ui.heading(
-                    chip8
-                        .bus
-                        .clock
-                        .sound_timer
-                        .load(Ordering::SeqCst)
-                        .to_string(),
-                );
+                ui.heading(chip8.sound_timer().to_string());
             });
         });
     }
 
     /// Draw a window that displays the current pressed state of the keys
     /// in the given `Chip8`.
-    fn draw_key_window(ctx: &Context, chip8: &Chip8) {
+    fn draw_key_window(ctx: &Context, chip8: &Chip8, labels: &BTreeMap<u8, String>) {
         egui::Window::new("Keys").show(ctx, |ui| {
             ui.style_mut().override_text_style = Some(egui::TextStyle::Heading);
             let key = |ui: &mut Ui, code: u8| {
                 ui.set_enabled(false);
-                let label = egui::SelectableLabel::new(
-                    chip8.bus.input.is_key_pressed(code),
-                    format!("{code:X}"),
-                );
-                
-                ui.add(label);
+                let label =
+                    egui::SelectableLabel::new(chip8.is_key_pressed(code), format!("{code:X}"));
+
+                let response = ui.add(label);
+                if let Some(text) = labels.get(&code) {
+                    response.on_disabled_hover_text(text);
+                }
             };
 
             egui::Grid::new("key_grid").show(ui, |ui| {
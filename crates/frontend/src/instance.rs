@@ -0,0 +1,158 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::Duration,
+};
+
+/// The local port the first running instance listens on for ROMs opened by later instances.
+const IPC_PORT: u16 = 38008;
+
+/// How long to wait for a running instance to accept a connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// The role of this process, decided by [`start`].
+pub enum Instance {
+    /// The ROM was sent to an already running instance, so this process should exit.
+    Forwarded,
+
+    /// This is the first running instance. ROMs opened by later instances are received here.
+    Primary(Receiver<PathBuf>),
+
+    /// The single-instance channel couldn't be set up, so this instance runs on its own.
+    Standalone,
+}
+
+/// Make sure ROMs are only ever opened by a single running instance.
+///
+/// If another instance is running and a `rom` is given, the ROM is sent to it.
+/// Otherwise this instance starts listening for ROMs opened by later instances.
+pub fn start(rom: Option<&Path>) -> Instance {
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, IPC_PORT));
+    if let Some(rom) = rom {
+        match forward(&address, rom) {
+            Ok(()) => {
+                log::info!("Opened {} in the running instance", rom.display());
+                return Instance::Forwarded;
+            }
+            Err(e) => log::debug!("No running instance to forward the ROM to: {e}"),
+        }
+    }
+
+    match TcpListener::bind(address) {
+        Ok(listener) => Instance::Primary(listen(listener)),
+        Err(e) => {
+            log::warn!("Failed to listen for ROMs opened by other instances: {e}");
+            Instance::Standalone
+        }
+    }
+}
+
+/// Send the absolute path of `rom` to the instance listening at `address`.
+fn forward(address: &SocketAddr, rom: &Path) -> std::io::Result<()> {
+    let rom = rom.canonicalize()?;
+    let mut stream = TcpStream::connect_timeout(address, CONNECT_TIMEOUT)?;
+    writeln!(stream, "{}", rom.display())
+}
+
+/// Accept ROM paths sent by other instances on a background thread.
+fn listen(listener: TcpListener) -> Receiver<PathBuf> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let path = stream.and_then(|stream| {
+                let mut line = String::new();
+                BufReader::new(stream).read_line(&mut line)?;
+                Ok(PathBuf::from(line.trim_end()))
+            });
+            match path {
+                Ok(path) => {
+                    if sender.send(path).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => log::warn!("Failed to receive ROM from another instance: {e}"),
+            }
+        }
+    });
+    receiver
+}
+
+/// Register this executable as the program that opens `.ch8` files for the current user.
+#[cfg(windows)]
+pub fn register_file_type() -> anyhow::Result<()> {
+    let exe = std::env::current_exe()?;
+    let command = format!("\"{}\" \"%1\"", exe.display());
+    run(
+        "reg",
+        &[
+            "add",
+            r"HKCU\Software\Classes\.ch8",
+            "/ve",
+            "/d",
+            "chip8.rom",
+            "/f",
+        ],
+    )?;
+    run(
+        "reg",
+        &[
+            "add",
+            r"HKCU\Software\Classes\chip8.rom\shell\open\command",
+            "/ve",
+            "/d",
+            &command,
+            "/f",
+        ],
+    )
+}
+
+/// Register this executable as the program that opens `.ch8` files for the current user.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn register_file_type() -> anyhow::Result<()> {
+    use anyhow::Context;
+
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .context("Failed to find the data directory")?;
+    let exe = std::env::current_exe()?;
+
+    let mime_dir = data_home.join("mime");
+    std::fs::create_dir_all(mime_dir.join("packages"))?;
+    std::fs::write(
+        mime_dir.join("packages/chip8.xml"),
+        include_str!("../res/chip8-mime.xml"),
+    )?;
+
+    let applications = data_home.join("applications");
+    std::fs::create_dir_all(&applications)?;
+    std::fs::write(
+        applications.join("chip8.desktop"),
+        include_str!("../res/chip8.desktop").replace("@EXEC@", &exe.to_string_lossy()),
+    )?;
+
+    run("update-mime-database", &[&mime_dir.to_string_lossy()])?;
+    run(
+        "xdg-mime",
+        &["default", "chip8.desktop", "application/x-chip8-rom"],
+    )
+}
+
+/// Register this executable as the program that opens `.ch8` files for the current user.
+#[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+pub fn register_file_type() -> anyhow::Result<()> {
+    anyhow::bail!("Registering the file type is not supported on this platform")
+}
+
+/// Run `program` with the given arguments, failing if it exits unsuccessfully.
+#[cfg(any(windows, all(unix, not(target_os = "macos"))))]
+fn run(program: &str, args: &[&str]) -> anyhow::Result<()> {
+    let status = std::process::Command::new(program).args(args).status()?;
+    if !status.success() {
+        anyhow::bail!("{program} exited with {status}");
+    }
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use std::fmt;
+
+use eframe::egui::Key;
+use serde::{Deserialize, Serialize};
+
+/// A mapping from keyboard keys to `Chip8` key codes,
+/// ordered like the keypad, row by row (`1 2 3 C`, `4 5 6 D`, `7 8 9 E`, `A 0 B F`).
+pub type KeyMap = [(Key, u8); 16];
+
+/// Maps the 4x4 block on the left of a standard english keyboard to the keypad by position.
+const MODERN_KEY_MAP: KeyMap = [
+    (Key::Num1, 0x1),
+    (Key::Num2, 0x2),
+    (Key::Num3, 0x3),
+    (Key::Num4, 0xC),
+    (Key::Q, 0x4),
+    (Key::W, 0x5),
+    (Key::E, 0x6),
+    (Key::R, 0xD),
+    (Key::A, 0x7),
+    (Key::S, 0x8),
+    (Key::D, 0x9),
+    (Key::F, 0xE),
+    (Key::Z, 0xA),
+    (Key::X, 0x0),
+    (Key::C, 0xB),
+    (Key::V, 0xF),
+];
+
+/// Maps every key of the hex keypad of the COSMAC VIP to the keyboard key with the same label.
+const VIP_KEY_MAP: KeyMap = [
+    (Key::Num1, 0x1),
+    (Key::Num2, 0x2),
+    (Key::Num3, 0x3),
+    (Key::C, 0xC),
+    (Key::Num4, 0x4),
+    (Key::Num5, 0x5),
+    (Key::Num6, 0x6),
+    (Key::D, 0xD),
+    (Key::Num7, 0x7),
+    (Key::Num8, 0x8),
+    (Key::Num9, 0x9),
+    (Key::E, 0xE),
+    (Key::A, 0xA),
+    (Key::Num0, 0x0),
+    (Key::B, 0xB),
+    (Key::F, 0xF),
+];
+
+/// Maps the keys of the HP 48 that SUPER-CHIP uses as the keypad to the same keyboard keys.
+/// The number block is shared (`7 8 9`, `4 5 6`, `1 2 3`, `0 . SPC`), while the operators
+/// next to it can't be told apart by the GUI, so `÷ × - +` are mapped to `C D E F` instead.
+const HP48_KEY_MAP: KeyMap = [
+    (Key::Num7, 0x1),
+    (Key::Num8, 0x2),
+    (Key::Num9, 0x3),
+    (Key::C, 0xC),
+    (Key::Num4, 0x4),
+    (Key::Num5, 0x5),
+    (Key::Num6, 0x6),
+    (Key::D, 0xD),
+    (Key::Num1, 0x7),
+    (Key::Num2, 0x8),
+    (Key::Num3, 0x9),
+    (Key::E, 0xE),
+    (Key::Num0, 0xA),
+    // the decimal point of the numeric keypad doubles as delete
+    (Key::Delete, 0x0),
+    (Key::Space, 0xB),
+    (Key::F, 0xF),
+];
+
+/// Maps the digits to the numeric keypad, and `A`-`F` to the navigation block above the arrow keys.
+const NUMPAD_KEY_MAP: KeyMap = [
+    (Key::Num1, 0x1),
+    (Key::Num2, 0x2),
+    (Key::Num3, 0x3),
+    (Key::PageUp, 0xC),
+    (Key::Num4, 0x4),
+    (Key::Num5, 0x5),
+    (Key::Num6, 0x6),
+    (Key::Delete, 0xD),
+    (Key::Num7, 0x7),
+    (Key::Num8, 0x8),
+    (Key::Num9, 0x9),
+    (Key::End, 0xE),
+    (Key::Insert, 0xA),
+    (Key::Num0, 0x0),
+    (Key::Home, 0xB),
+    (Key::PageDown, 0xF),
+];
+
+/// A preset mapping the keyboard to the keypad. ROMs were written for keypads with
+/// different layouts, so the most comfortable preset differs from ROM to ROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum KeypadLayout {
+    /// The keypad is laid out on the `1234`/`QWER`/`ASDF`/`ZXCV` keys.
+    #[default]
+    Modern,
+
+    /// Every key is pressed by the keyboard key with the same label, like on the COSMAC VIP.
+    Vip,
+
+    /// The number keys are laid out like on the keyboard of the HP 48.
+    Hp48,
+
+    /// The digits are on the numeric keypad, and `A`-`F` on the navigation block.
+    Numpad,
+}
+
+impl KeypadLayout {
+    /// All layouts, in the order they should be listed.
+    pub const ALL: [KeypadLayout; 4] = [
+        KeypadLayout::Modern,
+        KeypadLayout::Vip,
+        KeypadLayout::Hp48,
+        KeypadLayout::Numpad,
+    ];
+
+    /// Get the mapping from keyboard keys to key codes of this layout.
+    pub fn key_map(self) -> &'static KeyMap {
+        match self {
+            KeypadLayout::Modern => &MODERN_KEY_MAP,
+            KeypadLayout::Vip => &VIP_KEY_MAP,
+            KeypadLayout::Hp48 => &HP48_KEY_MAP,
+            KeypadLayout::Numpad => &NUMPAD_KEY_MAP,
+        }
+    }
+}
+
+impl fmt::Display for KeypadLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeypadLayout::Modern => write!(f, "Modern (1234/QWER)"),
+            KeypadLayout::Vip => write!(f, "COSMAC VIP"),
+            KeypadLayout::Hp48 => write!(f, "HP 48"),
+            KeypadLayout::Numpad => write!(f, "Numeric Keypad"),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::time::{Duration, Instant};
+
+use chip8::Chip8;
+
+/// The amount of frames after which a key press without a response is given up on.
+const MAX_FRAMES: u32 = 120;
+
+/// The amount of recent samples kept by a [`LatencyTest`].
+const MAX_SAMPLES: usize = 20;
+
+/// A measurement of how long the running program took to respond to a key press.
+#[derive(Clone, Copy)]
+pub struct LatencySample {
+    /// The key that was pressed.
+    pub key: u8,
+
+    /// The frame the program first read the key in, counted from the press.
+    pub read_frame: u32,
+
+    /// The frame the screen first changed in after the key was read, counted from the press.
+    pub draw_frame: u32,
+
+    /// The time from the key press being handled until the frame that showed the change
+    /// was presented, estimated to take one more frame after it was submitted.
+    pub input_to_photon: Duration,
+}
+
+/// A key press whose response is being waited for.
+struct Press {
+    key: u8,
+    time: Instant,
+
+    /// The frames run since the press.
+    frames: u32,
+
+    /// The frame the program read the key in, if it has.
+    read_frame: Option<u32>,
+
+    /// The display when the key was pressed.
+    display: Vec<u8>,
+}
+
+/// A built-in test of the delay between pressing a key on the host and the program
+/// responding on the screen. While the test is running, the screen flashes on every key press,
+/// so the delay of the display itself can be measured with a camera.
+///
+/// The program is expected to only draw in response to key presses, e.g. a keypad test,
+/// as any change of the screen after the key was read is taken as the response.
+#[derive(Default)]
+pub struct LatencyTest {
+    pub running: bool,
+    press: Option<Press>,
+
+    /// The most recent samples, oldest first.
+    samples: Vec<LatencySample>,
+
+    last_frame: Option<Instant>,
+
+    /// The average duration of a frame of the app.
+    frame_time: Duration,
+}
+
+impl LatencyTest {
+    /// Start the test, discarding the samples of the previous run.
+    pub fn start(&mut self) {
+        *self = Self {
+            running: true,
+            ..Default::default()
+        };
+    }
+
+    /// Stop the test, keeping its samples.
+    pub fn stop(&mut self) {
+        self.running = false;
+        self.press = None;
+    }
+
+    /// Start measuring the response to the given key, which has just been pressed on the host.
+    /// Presses while waiting for the response to an earlier press are ignored.
+    ///
+    /// This must be called before the key state of the `chip8` is updated.
+    pub fn press(&mut self, key: u8, chip8: &Chip8) {
+        if !self.running || self.press.is_some() {
+            return;
+        }
+        self.press = Some(Press {
+            key,
+            time: Instant::now(),
+            frames: 0,
+            // a program waiting for a key (`Fx0A`) takes it when it is released, which is
+            // counted as the first frame, so the time the key is held is part of the response
+            read_frame: chip8.waiting_for_key().then_some(1),
+            display: chip8.display().as_rgb8(),
+        });
+    }
+
+    /// Whether the screen should flash, which it does during the first frame after a key press.
+    pub fn flashing(&self) -> bool {
+        matches!(&self.press, Some(press) if press.frames == 0)
+    }
+
+    /// Record the end of a frame of the app, in which the program tested the keys in `keys_read`.
+    /// This must be called after the frame has been drawn.
+    pub fn end_frame(&mut self, keys_read: [bool; 16], chip8: &Chip8) {
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame.replace(now) {
+            // smooth out the frame time, it is only used as an estimate
+            self.frame_time = (self.frame_time * 7 + (now - last_frame)) / 8;
+        }
+
+        let press = match &mut self.press {
+            Some(press) => press,
+            None => return,
+        };
+        press.frames += 1;
+        if press.read_frame.is_none() && keys_read[usize::from(press.key)] {
+            press.read_frame = Some(press.frames);
+        }
+        if let Some(read_frame) = press.read_frame {
+            if chip8.display().as_rgb8() != press.display {
+                let sample = LatencySample {
+                    key: press.key,
+                    read_frame,
+                    draw_frame: press.frames,
+                    input_to_photon: now - press.time + self.frame_time,
+                };
+                log::info!(
+                    "Key {:X} read after {} frames, drawn after {} frames, {:.1} ms to photon",
+                    sample.key,
+                    sample.read_frame,
+                    sample.draw_frame,
+                    sample.input_to_photon.as_secs_f64() * 1000.0
+                );
+                if self.samples.len() == MAX_SAMPLES {
+                    self.samples.remove(0);
+                }
+                self.samples.push(sample);
+                self.press = None;
+                return;
+            }
+        }
+        if press.frames >= MAX_FRAMES {
+            log::warn!(
+                "The program didn't respond to key {:X} within {MAX_FRAMES} frames",
+                press.key
+            );
+            self.press = None;
+        }
+    }
+
+    /// Whether a key press is waiting for a response.
+    pub fn measuring(&self) -> bool {
+        self.press.is_some()
+    }
+
+    /// The most recent samples, oldest first.
+    pub fn samples(&self) -> &[LatencySample] {
+        &self.samples
+    }
+
+    /// The average duration of a frame of the app.
+    pub fn frame_time(&self) -> Duration {
+        self.frame_time
+    }
+}
@@ -0,0 +1,286 @@
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use anyhow::Context;
+use chip8::Instruction;
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+
+use crate::console::LogBuffer;
+
+/// The target of the records tracing every executed instruction.
+/// These records are never shown in the log console, as there are far too many.
+pub const TRACE_TARGET: &str = "chip8::trace";
+
+/// The log level used when there are no saved settings.
+#[cfg(debug_assertions)]
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Debug;
+
+#[cfg(not(debug_assertions))]
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+
+/// The settings of the logger, which can be changed while the app is running.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogSettings {
+    /// The level of the modules without a level of their own.
+    pub level: LevelFilter,
+
+    /// The levels of specific modules by their path, e.g. `frontend::net`.
+    /// The level of the most specific module applies to its submodules too.
+    pub modules: BTreeMap<String, LevelFilter>,
+
+    /// Also write the records to a log file, see [`Logging::file_path`].
+    pub file: bool,
+
+    /// The size in bytes after which the log file is rotated.
+    pub max_file_size: u64,
+
+    /// The amount of rotated log files that are kept.
+    pub max_files: usize,
+
+    /// Log every executed instruction to the [`TRACE_TARGET`].
+    pub trace_instructions: bool,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            level: DEFAULT_LEVEL,
+            modules: BTreeMap::new(),
+            file: false,
+            max_file_size: 1 << 20,
+            max_files: 5,
+            trace_instructions: false,
+        }
+    }
+}
+
+impl LogSettings {
+    /// The level of the records with the given target.
+    fn level_of(&self, target: &str) -> LevelFilter {
+        if target == TRACE_TARGET {
+            return if self.trace_instructions {
+                LevelFilter::Trace
+            } else {
+                LevelFilter::Off
+            };
+        }
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                target == module.as_str()
+                    || (target.starts_with(module.as_str())
+                        && target[module.len()..].starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.level, |(_, &level)| level)
+    }
+
+    /// The most verbose level of any target, so the `log` macros can skip everything below it.
+    fn max_level(&self) -> LevelFilter {
+        let trace = if self.trace_instructions {
+            LevelFilter::Trace
+        } else {
+            LevelFilter::Off
+        };
+        self.modules
+            .values()
+            .copied()
+            .chain([self.level, trace])
+            .max()
+            .unwrap_or(self.level)
+    }
+}
+
+/// A handle to the logger, to change its settings and read the captured records.
+#[derive(Clone, Default)]
+pub struct Logging {
+    settings: Arc<RwLock<LogSettings>>,
+    buffer: LogBuffer,
+}
+
+impl Logging {
+    /// Set up the [`fern`] logger with the saved settings, logging to stdout,
+    /// the in-app log console and optionally a rotating log file.
+    pub fn setup() -> Self {
+        let logging = Self {
+            settings: Arc::new(RwLock::new(Self::load_settings())),
+            buffer: LogBuffer::default(),
+        };
+
+        let settings = logging.settings.clone();
+        let file_settings = logging.settings.clone();
+        let file = Mutex::new(RotatingFile::new(Self::file_path()));
+        fern::Dispatch::new()
+            .level(LevelFilter::Trace)
+            .filter(move |metadata| {
+                metadata.level() <= settings.read().unwrap().level_of(metadata.target())
+            })
+            .chain(
+                fern::Dispatch::new()
+                    .format(|out, message, record| {
+                        out.finish(format_args!(
+                            "[{}][{}] {}",
+                            record.target(),
+                            record.level(),
+                            message
+                        ))
+                    })
+                    .chain(std::io::stdout()),
+            )
+            .chain(
+                fern::Dispatch::new()
+                    .filter(|metadata| metadata.target() != TRACE_TARGET)
+                    // the console formats the records itself
+                    .chain(logging.buffer.output()),
+            )
+            .chain(fern::Output::call(move |record| {
+                let settings = file_settings.read().unwrap();
+                if settings.file {
+                    let line = format!(
+                        "[{}][{}] {}\n",
+                        record.target(),
+                        record.level(),
+                        record.args()
+                    );
+                    let mut file = file.lock().unwrap();
+                    if let Err(e) = file.write(line.as_bytes(), &settings) {
+                        // logging the error would end up here again
+                        eprintln!("Failed to write to the log file: {e}");
+                    }
+                }
+            }))
+            .apply()
+            .unwrap();
+        // `apply` sets the maximum level to the level of the dispatch
+        log::set_max_level(logging.settings().max_level());
+
+        logging
+    }
+
+    /// The records captured for the in-app log console.
+    pub fn buffer(&self) -> &LogBuffer {
+        &self.buffer
+    }
+
+    /// The current settings of the logger.
+    pub fn settings(&self) -> LogSettings {
+        self.settings.read().unwrap().clone()
+    }
+
+    /// Change the settings of the logger, and save them for the next launch.
+    pub fn set_settings(&self, settings: LogSettings) {
+        log::set_max_level(settings.max_level());
+        if let Err(e) = Self::save_settings(&settings) {
+            log::error!("Failed to save the log settings: {e:#}");
+        }
+        *self.settings.write().unwrap() = settings;
+    }
+
+    /// The path of the log file. Rotated files have a number appended, the oldest having the highest.
+    pub fn file_path() -> PathBuf {
+        crate::profile::data_dir().join("logs").join("chip8.log")
+    }
+
+    fn settings_path() -> PathBuf {
+        crate::profile::data_dir().join("logging.json")
+    }
+
+    /// Load the saved settings, or the default settings if none have been saved yet.
+    /// This happens before the logger is set up, so errors are printed instead.
+    fn load_settings() -> LogSettings {
+        let path = Self::settings_path();
+        match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                eprintln!("Failed to parse the log settings {}: {e}", path.display());
+                LogSettings::default()
+            }),
+            Err(_) => LogSettings::default(),
+        }
+    }
+
+    fn save_settings(settings: &LogSettings) -> anyhow::Result<()> {
+        let path = Self::settings_path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_vec_pretty(settings)?;
+        std::fs::write(&path, json).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// Log an executed instruction to the [`TRACE_TARGET`].
+pub fn trace_instruction(instruction: &Instruction) {
+    log::trace!(
+        target: TRACE_TARGET,
+        "{:#06X}: {:04X} {}",
+        instruction.address,
+        instruction.opcode,
+        instruction.display
+    );
+}
+
+/// A log file that is rotated once it grows past the maximum size of the [`LogSettings`].
+struct RotatingFile {
+    path: PathBuf,
+
+    /// The open file and its current size, opened when the first record is written.
+    file: Option<(File, u64)>,
+}
+
+impl RotatingFile {
+    fn new(path: PathBuf) -> Self {
+        Self { path, file: None }
+    }
+
+    /// Append the `bytes` to the file, rotating it first if it would grow too large.
+    fn write(&mut self, bytes: &[u8], settings: &LogSettings) -> std::io::Result<()> {
+        if let Some((_, size)) = &self.file {
+            if *size + bytes.len() as u64 > settings.max_file_size {
+                self.file = None;
+                self.rotate(settings.max_files)?;
+            }
+        }
+        let (file, size) = match &mut self.file {
+            Some(file) => file,
+            None => {
+                if let Some(dir) = self.path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?;
+                let size = file.metadata()?.len();
+                self.file.insert((file, size))
+            }
+        };
+        file.write_all(bytes)?;
+        *size += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Shift the rotated files by one, dropping the oldest, and move the current file to `.1`.
+    fn rotate(&self, max_files: usize) -> std::io::Result<()> {
+        let rotated = |n: usize| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{n}"));
+            PathBuf::from(name)
+        };
+        if max_files == 0 {
+            return std::fs::remove_file(&self.path);
+        }
+        // the oldest file may not exist yet
+        let _ = std::fs::remove_file(rotated(max_files));
+        for n in (1..max_files).rev() {
+            let _ = std::fs::rename(rotated(n), rotated(n + 1));
+        }
+        std::fs::rename(&self.path, rotated(1))
+    }
+}
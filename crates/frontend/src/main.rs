@@ -3,45 +3,63 @@
 
 mod app;
 mod audio;
+mod bundle;
+mod cartridge;
+mod console;
+mod crash;
+mod debugger;
+mod display;
 mod gui;
+mod instance;
+mod keypad;
+mod latency;
+mod logging;
+mod metrics;
+mod net;
+mod profile;
+mod profiler;
 mod renderer;
+mod rumble;
+mod session;
+mod timeline;
+
+/// The argument that registers the app as the program that opens `.ch8` files.
+const REGISTER_FILE_TYPE_ARG: &str = "--register-file-type";
 
 fn main() {
-    setup_logger();
-    run_native();
+    let logging = logging::Logging::setup();
+    crash::install_panic_hook();
+
+    let arg = std::env::args().nth(1);
+    if arg.as_deref() == Some(REGISTER_FILE_TYPE_ARG) {
+        match instance::register_file_type() {
+            Ok(()) => log::info!("Registered the .ch8 file type"),
+            Err(e) => log::error!("Failed to register the .ch8 file type: {e}"),
+        }
+        return;
+    }
+
+    let opened_roms = match instance::start(arg.as_deref().map(std::path::Path::new)) {
+        instance::Instance::Forwarded => return,
+        instance::Instance::Primary(receiver) => Some(receiver),
+        instance::Instance::Standalone => None,
+    };
+    metrics::start_from_env();
+    run_native(logging, opened_roms);
 }
 
-/// Initialize and run a native [`eframe`] app.
-fn run_native() {
+/// Initialize and run a native [`eframe`] app, showing the records captured by `logging`
+/// and opening the ROMs received from `opened_roms`.
+fn run_native(
+    logging: logging::Logging,
+    opened_roms: Option<std::sync::mpsc::Receiver<std::path::PathBuf>>,
+) {
     eframe::run_native(
         "chip8!",
         eframe::NativeOptions {
             initial_window_size: Some(eframe::egui::vec2(1200.0, 800.0)),
             ..Default::default()
         },
-        Box::new(|cc| Box::new(app::App::new(cc))),
+        Box::new(|cc| Box::new(app::App::new(cc, logging, opened_roms))),
     );
 }
-
-/// Setup the [`fern`] logger.
-fn setup_logger() {
-    #[cfg(debug_assertions)]
-    let level = log::LevelFilter::Debug;
-
-    #[cfg(not(debug_assertions))]
-    let level = log::LevelFilter::Info;
-
-    fern::Dispatch::new()
-        .format(|out, message, record| {
-            out.finish(format_args!(
-                "[{}][{}] {}",
-                record.target(),
-                record.level(),
-                message
-            ))
-        })
-        .level(level)
-        .chain(std::io::stdout())
-        .apply()
-        .unwrap();
-}
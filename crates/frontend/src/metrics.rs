@@ -0,0 +1,180 @@
+use std::{
+    fmt::Write as _,
+    io::{Read, Write},
+    net::TcpListener,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    thread,
+    time::Duration,
+};
+
+use serde::Serialize;
+
+/// The address to serve the metrics on in the OpenMetrics text format, e.g. `127.0.0.1:9184`.
+const ADDRESS_VAR: &str = "CHIP8_METRICS_ADDRESS";
+
+/// The file to periodically write the metrics to as JSON.
+const JSON_PATH_VAR: &str = "CHIP8_METRICS_JSON";
+
+/// The seconds between writes of the JSON file.
+const JSON_INTERVAL_VAR: &str = "CHIP8_METRICS_INTERVAL";
+
+const DEFAULT_JSON_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Counters of everything the app has done since it was started, over all sessions.
+pub struct Metrics {
+    instructions: AtomicU64,
+    frames: AtomicU64,
+    draws: AtomicU64,
+    faults: AtomicU64,
+
+    /// Times the audio device ran out of samples before the next buffer was filled.
+    audio_underruns: AtomicU64,
+}
+
+/// The metrics of this process.
+pub static METRICS: Metrics = Metrics {
+    instructions: AtomicU64::new(0),
+    frames: AtomicU64::new(0),
+    draws: AtomicU64::new(0),
+    faults: AtomicU64::new(0),
+    audio_underruns: AtomicU64::new(0),
+};
+
+impl Metrics {
+    /// Count a frame in which the given amount of instructions were executed.
+    pub fn record_frame(&self, instructions: u32) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        self.record_instructions(instructions);
+    }
+
+    /// Count instructions that were executed outside of a frame, e.g. by the debugger.
+    pub fn record_instructions(&self, instructions: u32) {
+        self.instructions
+            .fetch_add(u64::from(instructions), Ordering::Relaxed);
+    }
+
+    pub fn record_draw(&self) {
+        self.draws.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_fault(&self) {
+        self.faults.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_audio_underrun(&self) {
+        self.audio_underruns.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The current value of every counter.
+    fn snapshot(&self) -> Snapshot {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        Snapshot {
+            instructions: load(&self.instructions),
+            frames: load(&self.frames),
+            draws: load(&self.draws),
+            faults: load(&self.faults),
+            audio_underruns: load(&self.audio_underruns),
+        }
+    }
+}
+
+/// The values of the [`Metrics`] at one point in time.
+#[derive(Serialize)]
+struct Snapshot {
+    instructions: u64,
+    frames: u64,
+    draws: u64,
+    faults: u64,
+    audio_underruns: u64,
+}
+
+impl Snapshot {
+    /// Format the counters in the OpenMetrics text format, which Prometheus can scrape.
+    fn to_openmetrics(&self) -> String {
+        let counters = [
+            ("instructions", "Instructions executed.", self.instructions),
+            ("frames", "Frames run.", self.frames),
+            ("draws", "Sprites drawn.", self.draws),
+            ("faults", "Programs halted on a fault.", self.faults),
+            (
+                "audio_underruns",
+                "Times the audio device ran out of samples.",
+                self.audio_underruns,
+            ),
+        ];
+        let mut text = String::new();
+        for (name, help, value) in counters {
+            let _ = writeln!(text, "# TYPE chip8_{name} counter");
+            let _ = writeln!(text, "# HELP chip8_{name} {help}");
+            let _ = writeln!(text, "chip8_{name}_total {value}");
+        }
+        text.push_str("# EOF\n");
+        text
+    }
+}
+
+/// Start exporting the [`METRICS`] as configured by the environment:
+/// serving them over HTTP if `CHIP8_METRICS_ADDRESS` is set, and writing them to the
+/// JSON file at `CHIP8_METRICS_JSON` every `CHIP8_METRICS_INTERVAL` seconds if set.
+pub fn start_from_env() {
+    if let Some(address) = std::env::var_os(ADDRESS_VAR) {
+        let address = address.to_string_lossy().into_owned();
+        match TcpListener::bind(&address) {
+            Ok(listener) => {
+                log::info!("Serving metrics on http://{address}/metrics");
+                thread::spawn(move || serve(listener));
+            }
+            Err(e) => log::error!("Failed to serve metrics on {address}: {e}"),
+        }
+    }
+    if let Some(path) = std::env::var_os(JSON_PATH_VAR) {
+        let interval = std::env::var(JSON_INTERVAL_VAR)
+            .ok()
+            .and_then(|seconds| seconds.parse().ok())
+            .map_or(DEFAULT_JSON_INTERVAL, Duration::from_secs);
+        let path = PathBuf::from(path);
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            if let Err(e) = write_json(&path) {
+                log::error!("Failed to write metrics to {}: {e}", path.display());
+            }
+        });
+    }
+}
+
+/// Answer every HTTP request on `listener` with the metrics, whatever the requested path.
+fn serve(listener: TcpListener) {
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::warn!("Failed to accept a metrics connection: {e}");
+                continue;
+            }
+        };
+        // the request itself is irrelevant, but has to be read before responding
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+        let _ = stream.read(&mut [0; 1024]);
+        let body = METRICS.snapshot().to_openmetrics();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\n\
+             Content-Type: application/openmetrics-text; version=1.0.0; charset=utf-8\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\r\n{body}",
+            body.len()
+        );
+        if let Err(e) = stream.write_all(response.as_bytes()) {
+            log::warn!("Failed to send metrics: {e}");
+        }
+    }
+}
+
+/// Write the metrics to `path`, replacing the file at once so readers never see a partial write.
+fn write_json(path: &PathBuf) -> anyhow::Result<()> {
+    let json = serde_json::to_vec_pretty(&METRICS.snapshot())?;
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, json)?;
+    std::fs::rename(&temporary, path)?;
+    Ok(())
+}
@@ -0,0 +1,370 @@
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread,
+};
+
+use crate::renderer::DisplayFrame;
+
+/// The default port used when hosting a network session.
+pub const DEFAULT_PORT: u16 = 8088;
+
+/// The largest payload a peer is allowed to send in a single message.
+const MAX_PAYLOAD_LENGTH: usize = 1 << 20;
+
+const TAG_FRAME: u8 = 0;
+const TAG_KEYS: u8 = 1;
+const TAG_INPUT_GRANTED: u8 = 2;
+
+/// A message sent between two instances in a network session.
+pub enum NetMessage {
+    /// The host's display.
+    Frame(DisplayFrame),
+
+    /// The pressed state of all 16 keys of the spectator.
+    Keys([bool; 16]),
+
+    /// Tells the spectator whether the host is accepting its key presses.
+    InputGranted(bool),
+}
+
+impl NetMessage {
+    /// Write this message to `writer`, prefixed with its tag and payload length.
+    fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let (tag, payload) = match self {
+            NetMessage::Frame(frame) => {
+                let mut payload = Vec::with_capacity(4 + frame.rgb.len());
+                payload.extend_from_slice(&(frame.width as u16).to_be_bytes());
+                payload.extend_from_slice(&(frame.height as u16).to_be_bytes());
+                payload.extend_from_slice(&frame.rgb);
+                (TAG_FRAME, payload)
+            }
+            NetMessage::Keys(keys) => {
+                let mask = keys
+                    .iter()
+                    .enumerate()
+                    .fold(0u16, |mask, (i, &pressed)| mask | (u16::from(pressed) << i));
+                (TAG_KEYS, mask.to_be_bytes().to_vec())
+            }
+            NetMessage::InputGranted(granted) => (TAG_INPUT_GRANTED, vec![u8::from(*granted)]),
+        };
+        let length = u32::try_from(payload.len()).unwrap();
+        writer.write_all(&[tag])?;
+        writer.write_all(&length.to_be_bytes())?;
+        writer.write_all(&payload)?;
+        writer.flush()
+    }
+
+    /// Read the next message from `reader`. This blocks until a full message is available.
+    fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        let length = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+        if length > MAX_PAYLOAD_LENGTH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("message payload of {length} bytes is too large"),
+            ));
+        }
+        let mut payload = vec![0; length];
+        reader.read_exact(&mut payload)?;
+
+        match (header[0], payload.as_slice()) {
+            (TAG_FRAME, &[w0, w1, h0, h1, ref rgb @ ..]) => Ok(NetMessage::Frame(DisplayFrame {
+                width: usize::from(u16::from_be_bytes([w0, w1])),
+                height: usize::from(u16::from_be_bytes([h0, h1])),
+                rgb: rgb.to_vec(),
+                generation: None,
+            })),
+            (TAG_KEYS, &[high, low]) => {
+                let mask = u16::from_be_bytes([high, low]);
+                let mut keys = [false; 16];
+                for (i, key) in keys.iter_mut().enumerate() {
+                    *key = mask & (1 << i) != 0;
+                }
+                Ok(NetMessage::Keys(keys))
+            }
+            (TAG_INPUT_GRANTED, &[granted]) => Ok(NetMessage::InputGranted(granted != 0)),
+            (tag, _) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid message with tag {tag}"),
+            )),
+        }
+    }
+}
+
+/// A connection to another instance. Reading and writing is done on
+/// background threads, so sending and receiving never blocks the GUI.
+pub struct Connection {
+    stream: TcpStream,
+    peer: SocketAddr,
+    sender: Sender<NetMessage>,
+    receiver: Receiver<NetMessage>,
+}
+
+impl Connection {
+    /// Create a new `Connection` from a connected [`TcpStream`], spawning its reader and writer threads.
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        let peer = stream.peer_addr()?;
+
+        let (sender, outgoing) = mpsc::channel::<NetMessage>();
+        let mut writer = stream.try_clone()?;
+        thread::spawn(move || {
+            for message in outgoing {
+                if let Err(e) = message.write_to(&mut writer) {
+                    log::warn!("Failed to send message to {peer}: {e}");
+                    break;
+                }
+            }
+        });
+
+        let (incoming, receiver) = mpsc::channel();
+        let mut reader = stream.try_clone()?;
+        thread::spawn(move || loop {
+            match NetMessage::read_from(&mut reader) {
+                Ok(message) => {
+                    if incoming.send(message).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    log::info!("Connection to {peer} closed: {e}");
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            stream,
+            peer,
+            sender,
+            receiver,
+        })
+    }
+
+    /// Queue a message to be sent to the peer.
+    fn send(&self, message: NetMessage) {
+        // a failed send means the writer thread has stopped, which is
+        // picked up as a closed connection the next time we receive.
+        let _ = self.sender.send(message);
+    }
+
+    /// Receive all messages that have arrived since the last call.
+    ///
+    /// Returns `None` if the connection has been closed.
+    fn receive(&self) -> Option<Vec<NetMessage>> {
+        let mut messages = Vec::new();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(message) => messages.push(message),
+                Err(TryRecvError::Empty) => return Some(messages),
+                Err(TryRecvError::Disconnected) => return None,
+            }
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // unblock the reader thread
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// The state of a network session, as shown to the user.
+#[derive(Clone, PartialEq, Eq)]
+pub enum NetStatus {
+    /// No network session is active.
+    Offline,
+
+    /// Hosting a session, with the connected spectator's address (if any).
+    Hosting { port: u16, peer: Option<SocketAddr> },
+
+    /// Spectating another instance's session.
+    Spectating {
+        host: SocketAddr,
+        input_granted: bool,
+    },
+}
+
+/// A network session where a host instance mirrors its display to a single spectator,
+/// and may accept the spectator's key presses.
+pub enum NetSession {
+    Host(HostSession),
+    Spectator(SpectatorSession),
+}
+
+impl NetSession {
+    /// Start hosting a session on the given port.
+    pub fn host(port: u16, allow_input: bool) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        log::info!("Hosting network session on port {port}");
+        Ok(NetSession::Host(HostSession {
+            listener,
+            port,
+            peer: None,
+            allow_input,
+            last_frame: None,
+            release_keys: false,
+        }))
+    }
+
+    /// Connect to a session hosted at `address`.
+    pub fn join(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let connection = Connection::new(TcpStream::connect(address)?)?;
+        log::info!("Joined network session hosted by {}", connection.peer);
+        Ok(NetSession::Spectator(SpectatorSession {
+            connection,
+            frame: None,
+            input_granted: false,
+            last_keys: [false; 16],
+        }))
+    }
+
+    /// Get the current [`NetStatus`] of this session.
+    pub fn status(&self) -> NetStatus {
+        match self {
+            NetSession::Host(host) => NetStatus::Hosting {
+                port: host.port,
+                peer: host.peer.as_ref().map(|peer| peer.peer),
+            },
+            NetSession::Spectator(spectator) => NetStatus::Spectating {
+                host: spectator.connection.peer,
+                input_granted: spectator.input_granted,
+            },
+        }
+    }
+}
+
+/// The host side of a [`NetSession`].
+pub struct HostSession {
+    listener: TcpListener,
+    port: u16,
+    peer: Option<Connection>,
+    allow_input: bool,
+    last_frame: Option<DisplayFrame>,
+
+    /// Whether the keys pressed by a former spectator, or by one whose input was
+    /// revoked, still have to be released.
+    release_keys: bool,
+}
+
+impl HostSession {
+    /// Accept new spectators and receive any key presses sent by the current one.
+    ///
+    /// Returns the spectator's latest key state if it has changed and the spectator
+    /// is allowed to use the keypad, or all keys released once the spectator is
+    /// replaced, disconnects or loses access to the keypad.
+    pub fn poll(&mut self) -> Option<[bool; 16]> {
+        match self.listener.accept() {
+            Ok((stream, address)) => match Connection::new(stream) {
+                Ok(connection) => {
+                    log::info!("Spectator connected from {address}");
+                    connection.send(NetMessage::InputGranted(self.allow_input));
+                    if self.peer.replace(connection).is_some() {
+                        log::info!("Replaced the previous spectator");
+                        self.release_keys = true;
+                    }
+                    // make sure the new spectator receives a frame right away
+                    self.last_frame = None;
+                }
+                Err(e) => log::error!("Failed to accept spectator from {address}: {e}"),
+            },
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => log::error!("Failed to accept spectator: {e}"),
+        }
+
+        let released = std::mem::take(&mut self.release_keys).then_some([false; 16]);
+        let messages = match self.peer.as_ref().map(Connection::receive) {
+            Some(Some(messages)) => messages,
+            Some(None) => {
+                log::info!("Spectator disconnected");
+                self.peer = None;
+                return Some([false; 16]);
+            }
+            None => return released,
+        };
+
+        messages
+            .into_iter()
+            .fold(released, |keys, message| match message {
+                NetMessage::Keys(new_keys) if self.allow_input => Some(new_keys),
+                _ => keys,
+            })
+    }
+
+    /// Send the given frame to the spectator, if it changed since the last frame sent.
+    pub fn send_frame(&mut self, frame: DisplayFrame) {
+        if let Some(peer) = &self.peer {
+            if self.last_frame.as_ref() != Some(&frame) {
+                peer.send(NetMessage::Frame(frame.clone()));
+                self.last_frame = Some(frame);
+            }
+        }
+    }
+
+    /// Grant or revoke the spectator's access to the keypad.
+    pub fn set_allow_input(&mut self, allow_input: bool) {
+        // the keys held by the spectator are released on the next poll
+        self.release_keys |= self.allow_input && !allow_input;
+        self.allow_input = allow_input;
+        if let Some(peer) = &self.peer {
+            peer.send(NetMessage::InputGranted(allow_input));
+        }
+    }
+}
+
+/// The spectator side of a [`NetSession`].
+pub struct SpectatorSession {
+    connection: Connection,
+    frame: Option<DisplayFrame>,
+    input_granted: bool,
+    last_keys: [bool; 16],
+}
+
+impl SpectatorSession {
+    /// Receive the latest frame and settings from the host.
+    ///
+    /// Returns `false` if the host closed the connection.
+    pub fn poll(&mut self) -> bool {
+        let messages = match self.connection.receive() {
+            Some(messages) => messages,
+            None => {
+                log::info!("Host closed the network session");
+                return false;
+            }
+        };
+        for message in messages {
+            match message {
+                NetMessage::Frame(frame) if frame.rgb.len() == frame.width * frame.height * 3 => {
+                    self.frame = Some(frame)
+                }
+                NetMessage::Frame(frame) => log::warn!(
+                    "Ignoring {}x{} frame of invalid length {}",
+                    frame.width,
+                    frame.height,
+                    frame.rgb.len()
+                ),
+                NetMessage::InputGranted(granted) => self.input_granted = granted,
+                NetMessage::Keys(_) => log::warn!("Ignoring key message sent by host"),
+            }
+        }
+        true
+    }
+
+    /// Get the most recent frame sent by the host.
+    pub fn frame(&self) -> Option<&DisplayFrame> {
+        self.frame.as_ref()
+    }
+
+    /// Send the local key state to the host, if it changed and the host accepts input.
+    pub fn send_keys(&mut self, keys: [bool; 16]) {
+        if self.input_granted && self.last_keys != keys {
+            self.last_keys = keys;
+            self.connection.send(NetMessage::Keys(keys));
+        }
+    }
+}
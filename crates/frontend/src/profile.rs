@@ -0,0 +1,169 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::keypad::KeypadLayout;
+
+/// A cheat that pins a memory address to a fixed value.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Cheat {
+    /// A user-friendly name for the cheat, e.g. "Infinite lives".
+    pub name: String,
+
+    /// The memory address that is pinned.
+    pub address: u16,
+
+    /// The value written to `address` after every step.
+    pub value: u8,
+
+    /// Whether the cheat is currently applied.
+    pub enabled: bool,
+}
+
+/// A button of the touch keypad, positioned relative to the area it is drawn in.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct TouchButton {
+    /// The `Chip8` key pressed by this button.
+    pub key_code: u8,
+
+    /// The horizontal position of the button's center, from `0.0` (left) to `1.0` (right).
+    pub x: f32,
+
+    /// The vertical position of the button's center, from `0.0` (top) to `1.0` (bottom).
+    pub y: f32,
+
+    /// The diameter of the button, relative to the shorter side of the area.
+    pub size: f32,
+}
+
+impl TouchButton {
+    /// The default size of a button.
+    pub const DEFAULT_SIZE: f32 = 0.1;
+
+    /// Get the default layout, a full keypad in the bottom right corner.
+    pub fn default_layout() -> Vec<Self> {
+        const KEYPAD: [[u8; 4]; 4] = [
+            [0x1, 0x2, 0x3, 0xC],
+            [0x4, 0x5, 0x6, 0xD],
+            [0x7, 0x8, 0x9, 0xE],
+            [0xA, 0x0, 0xB, 0xF],
+        ];
+        (0..)
+            .zip(KEYPAD)
+            .flat_map(|(row, keys)| {
+                (0..).zip(keys).map(move |(column, key_code)| Self {
+                    key_code,
+                    x: 0.6 + column as f32 * 0.1,
+                    y: 0.5 + row as f32 * 0.12,
+                    size: Self::DEFAULT_SIZE,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Settings and data stored for a single ROM, identified by the hash of the ROM data.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RomProfile {
+    /// The hash of the ROM this profile belongs to.
+    #[serde(skip)]
+    rom_hash: u64,
+
+    pub cheats: Vec<Cheat>,
+
+    /// How the keyboard is mapped to the keypad for the ROM.
+    pub keypad_layout: KeypadLayout,
+
+    /// What each keypad key does in the ROM, e.g. "shoot", indexed by key code.
+    pub key_labels: BTreeMap<u8, String>,
+
+    /// The customized layout of the touch keypad, or `None` to use the default layout.
+    pub touch_layout: Option<Vec<TouchButton>>,
+}
+
+impl RomProfile {
+    /// Load the profile for the given ROM data from disk.
+    ///
+    /// Returns an empty profile if no profile has been saved for the ROM yet.
+    pub fn load(rom: &[u8]) -> Self {
+        let rom_hash = chip8::rom_hash(rom);
+        let path = Self::path(rom_hash);
+        let mut profile = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                log::error!("Failed to parse ROM profile {}: {e}", path.display());
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+        profile.rom_hash = rom_hash;
+        profile
+    }
+
+    /// Save this profile to disk, so it is restored the next time its ROM is loaded.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = Self::path(self.rom_hash);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_vec_pretty(self)?;
+        std::fs::write(&path, json)
+            .with_context(|| format!("Failed to write ROM profile to {}", path.display()))
+    }
+
+    /// Get the layout of the touch keypad for this ROM.
+    pub fn touch_layout(&self) -> Vec<TouchButton> {
+        self.touch_layout
+            .clone()
+            .unwrap_or_else(TouchButton::default_layout)
+    }
+
+    /// Get the path of the profile file for the ROM with the given hash.
+    fn path(rom_hash: u64) -> PathBuf {
+        data_dir()
+            .join("profiles")
+            .join(format!("{rom_hash:016x}.json"))
+    }
+}
+
+/// Load the RPL user flags the ROM with the given hash stored in an earlier session,
+/// or all zeros if it didn't store any.
+pub fn load_rpl_flags(rom_hash: u64) -> [u8; 16] {
+    let path = rpl_flags_path(rom_hash);
+    match std::fs::read(&path) {
+        Ok(bytes) => <[u8; 16]>::try_from(bytes.as_slice()).unwrap_or_else(|_| {
+            log::error!("Invalid RPL flags file {}", path.display());
+            [0; 16]
+        }),
+        Err(_) => [0; 16],
+    }
+}
+
+/// Save the RPL user flags of the ROM with the given hash, so they are restored
+/// the next time it is loaded.
+pub fn save_rpl_flags(rom_hash: u64, flags: [u8; 16]) -> anyhow::Result<()> {
+    let path = rpl_flags_path(rom_hash);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, flags)
+        .with_context(|| format!("Failed to write RPL flags to {}", path.display()))
+}
+
+/// Get the path of the RPL flags file for the ROM with the given hash.
+fn rpl_flags_path(rom_hash: u64) -> PathBuf {
+    data_dir()
+        .join("flags")
+        .join(format!("{rom_hash:016x}.bin"))
+}
+
+/// Get the directory where the frontend stores persistent data.
+pub fn data_dir() -> PathBuf {
+    std::env::var_os("APPDATA")
+        .or_else(|| std::env::var_os("XDG_DATA_HOME"))
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .unwrap_or_default()
+        .join("chip8")
+}
@@ -0,0 +1,178 @@
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    io::Write as _,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use anyhow::Context;
+use chip8::Chip8;
+
+/// The address of the top-level routine, which is where programs start executing.
+const ENTRY_POINT: usize = 0x200;
+
+/// Statistics gathered for a single subroutine.
+#[derive(Default, Clone, Copy)]
+pub struct RoutineStats {
+    /// How many times the subroutine was called.
+    pub calls: u64,
+
+    /// Cycles spent in the subroutine, including the subroutines it called.
+    pub inclusive: u64,
+
+    /// Cycles spent in the subroutine itself, excluding the subroutines it called.
+    pub exclusive: u64,
+}
+
+/// Aggregates the cycles spent in each subroutine of the running program.
+///
+/// Subroutines are identified by their call target. Calls and returns are
+/// detected from changes to the stack pointer after each step, and cycles
+/// spent outside of any subroutine are counted towards the [`ENTRY_POINT`].
+#[derive(Default)]
+pub struct Profiler {
+    pub enabled: bool,
+
+    /// The call targets of the subroutines that are currently executing, innermost last.
+    calls: Vec<usize>,
+
+    /// The stack pointer after the last recorded step.
+    last_sp: usize,
+
+    stats: HashMap<usize, RoutineStats>,
+
+    /// How many times each subroutine was called by another one,
+    /// keyed by the call targets of the caller and the callee.
+    edges: HashMap<(usize, usize), u64>,
+
+    total_cycles: u64,
+}
+
+impl Profiler {
+    /// Record a step of the given `Chip8`. This should be called after every step.
+    pub fn record(&mut self, chip8: &Chip8) {
+        if !self.enabled {
+            return;
+        }
+
+        let sp = chip8.stack().len();
+        if sp > self.last_sp {
+            // a call jumps straight to the subroutine
+            let target = chip8.pc();
+            let caller = self.calls.last().copied().unwrap_or(ENTRY_POINT);
+            *self.edges.entry((caller, target)).or_default() += 1;
+            self.calls.push(target);
+            self.stats.entry(target).or_default().calls += 1;
+        } else if sp < self.last_sp {
+            self.calls.pop();
+        }
+        self.last_sp = sp;
+
+        self.total_cycles += 1;
+        let current = self.calls.last().copied().unwrap_or(ENTRY_POINT);
+        self.stats.entry(current).or_default().exclusive += 1;
+        for (i, &target) in self.calls.iter().enumerate() {
+            // count recursive calls only once
+            if !self.calls[..i].contains(&target) {
+                self.stats.entry(target).or_default().inclusive += 1;
+            }
+        }
+        if !self.calls.contains(&ENTRY_POINT) {
+            self.stats.entry(ENTRY_POINT).or_default().inclusive += 1;
+        }
+    }
+
+    /// Clear all statistics, starting a new profile from the current state of `chip8`.
+    pub fn reset(&mut self, chip8: &Chip8) {
+        self.calls.clear();
+        self.last_sp = chip8.stack().len();
+        self.stats.clear();
+        self.edges.clear();
+        self.total_cycles = 0;
+    }
+
+    /// Get the statistics of every subroutine seen so far,
+    /// sorted by inclusive cycles in descending order.
+    pub fn routines(&self) -> Vec<(usize, RoutineStats)> {
+        let mut routines: Vec<_> = self
+            .stats
+            .iter()
+            .map(|(&address, &stats)| (address, stats))
+            .collect();
+        routines.sort_by(|(a_address, a), (b_address, b)| {
+            b.inclusive.cmp(&a.inclusive).then(a_address.cmp(b_address))
+        });
+        routines
+    }
+
+    /// Get the total amount of cycles recorded.
+    pub fn total_cycles(&self) -> u64 {
+        self.total_cycles
+    }
+
+    /// Get the call graph recorded so far in the Graphviz dot format. Every subroutine
+    /// is a node labeled with its statistics, and every edge is labeled with the amount of calls.
+    pub fn call_graph(&self) -> String {
+        let total = self.total_cycles.max(1) as f64;
+        let mut dot = String::new();
+        // writing to a string can't fail
+        let _ = writeln!(dot, "digraph calls {{");
+        let _ = writeln!(dot, "    node [shape=box, fontname=monospace];");
+        for (address, stats) in self.routines() {
+            let _ = writeln!(
+                dot,
+                "    r{address:04X} [label=\"{address:#06X}\\n{} calls\\n{:.1}% inclusive, {:.1}% exclusive\"];",
+                stats.calls,
+                stats.inclusive as f64 / total * 100.0,
+                stats.exclusive as f64 / total * 100.0,
+            );
+        }
+        let mut edges: Vec<_> = self.edges.iter().collect();
+        edges.sort();
+        for ((caller, callee), calls) in edges {
+            let _ = writeln!(
+                dot,
+                "    r{caller:04X} -> r{callee:04X} [label=\"{calls}\"];"
+            );
+        }
+        let _ = writeln!(dot, "}}");
+        dot
+    }
+
+    /// Write the [call graph](Self::call_graph) to the given `path`. If the path has an
+    /// `svg` extension, the graph is rendered with the `dot` command of Graphviz.
+    pub fn export_call_graph(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let dot = self.call_graph();
+        let is_svg =
+            matches!(path.extension(), Some(extension) if extension.eq_ignore_ascii_case("svg"));
+        let data = if is_svg {
+            render_svg(&dot)?
+        } else {
+            dot.into_bytes()
+        };
+        std::fs::write(path, data)
+            .with_context(|| format!("Failed to write call graph to {}", path.display()))
+    }
+}
+
+/// Render a graph in the dot format to SVG with Graphviz, which needs to be installed.
+fn render_svg(dot: &str) -> anyhow::Result<Vec<u8>> {
+    let mut child = Command::new("dot")
+        .arg("-Tsvg")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to run Graphviz, is it installed?")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(dot.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    anyhow::ensure!(
+        output.status.success(),
+        "Graphviz failed to render the call graph: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(output.stdout)
+}
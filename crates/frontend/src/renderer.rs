@@ -1,6 +1,87 @@
+use std::collections::VecDeque;
+
+use chip8::graphics::GraphicsBuffer;
 use eframe::glow;
+
+use crate::display::DisplayStyle;
 use glow::*;
 
+/// An RGB image of a `Chip8` display, along with its resolution.
+#[derive(Clone, PartialEq, Eq)]
+pub struct DisplayFrame {
+    pub width: usize,
+    pub height: usize,
+    /// The RGB data of the pixels, row by row.
+    pub rgb: Vec<u8>,
+    /// The [`GraphicsBuffer::generation`] of the display, if the frame was taken of a local one.
+    pub generation: Option<u64>,
+}
+
+impl DisplayFrame {
+    /// Get the current frame of the given `GraphicsBuffer`.
+    pub fn of(graphics: &GraphicsBuffer) -> Self {
+        Self {
+            width: graphics.width(),
+            height: graphics.height(),
+            rgb: graphics.as_rgb8(),
+            generation: Some(graphics.generation()),
+        }
+    }
+
+    /// Get a black frame with the default resolution.
+    pub fn blank() -> Self {
+        Self {
+            width: chip8::graphics::WIDTH,
+            height: chip8::graphics::HEIGHT,
+            rgb: vec![0; chip8::graphics::PIXEL_COUNT * 3],
+            generation: None,
+        }
+    }
+}
+
+/// Blends the last few rendered frames, hiding the flicker of sprites that are erased
+/// and redrawn every frame with XOR drawing. Only the presented image is blended,
+/// the display of the `Chip8` is left as it is.
+#[derive(Default)]
+struct FrameBlender {
+    /// The amount of frames to blend, `1` or less disables blending.
+    count: usize,
+
+    /// The most recent frames, newest last.
+    frames: VecDeque<DisplayFrame>,
+}
+
+impl FrameBlender {
+    /// Add the given frame to the history, and return the average of the recent frames.
+    fn blend(&mut self, frame: &DisplayFrame) -> DisplayFrame {
+        if self.count <= 1 {
+            self.frames.clear();
+            return frame.clone();
+        }
+        // frames of a different resolution can't be blended
+        self.frames
+            .retain(|old| old.width == frame.width && old.height == frame.height);
+        self.frames.push_back(frame.clone());
+        while self.frames.len() > self.count {
+            self.frames.pop_front();
+        }
+
+        let mut sums = vec![0u16; frame.rgb.len()];
+        for old in &self.frames {
+            for (sum, &value) in sums.iter_mut().zip(&old.rgb) {
+                *sum += u16::from(value);
+            }
+        }
+        let count = self.frames.len() as u16;
+        DisplayFrame {
+            width: frame.width,
+            height: frame.height,
+            rgb: sums.into_iter().map(|sum| (sum / count) as u8).collect(),
+            generation: None,
+        }
+    }
+}
+
 /// A renderer for displaying the graphics
 /// buffer of the `Chip8` using an OpenGL renderer.
 pub struct Renderer {
@@ -8,6 +89,11 @@ pub struct Renderer {
     vbo: Buffer,
     vao: VertexArray,
     texture: Texture,
+
+    /// The generation of the frame in the texture, if it is of a local display and not blended.
+    uploaded: Option<u64>,
+    blender: FrameBlender,
+    style: DisplayStyle,
 }
 
 impl Renderer {
@@ -24,6 +110,17 @@ impl Renderer {
             vbo,
             vao,
             texture,
+            uploaded: None,
+            blender: FrameBlender::default(),
+            style: DisplayStyle::default(),
+        }
+    }
+
+    /// Set the style the display is presented with, see [`DisplayStyle`].
+    pub fn set_style(&mut self, style: &DisplayStyle) {
+        if *style != self.style {
+            self.blender.count = style.frame_blending;
+            self.style = style.clone();
         }
     }
 
@@ -80,8 +177,8 @@ impl Renderer {
         (vbo, vao)
     }
 
-    /// Load the given RGB buffer as a  texture into the given OpenGL context.
-    unsafe fn load_texture(&mut self, gl: &glow::Context, buffer: &[u8]) {
+    /// Load the given frame as a texture into the given OpenGL context.
+    unsafe fn load_texture(&mut self, gl: &glow::Context, frame: &DisplayFrame) {
         let texture = gl.create_texture().unwrap();
         gl.active_texture(glow::TEXTURE0);
         gl.bind_texture(glow::TEXTURE_2D, Some(texture));
@@ -101,29 +198,48 @@ impl Renderer {
             glow::TEXTURE_2D,
             0,
             glow::RGB as i32,
-            chip8::graphics::WIDTH as i32,
-            chip8::graphics::HEIGHT as i32,
+            frame.width as i32,
+            frame.height as i32,
             0,
             glow::RGB,
             glow::UNSIGNED_BYTE,
-            Some(buffer),
+            Some(&frame.rgb),
         );
 
         gl.delete_texture(self.texture);
         self.texture = texture;
     }
 
-    /// Render the given buffer of RGB data onto a texture.
-    pub fn render(&mut self, gl: &glow::Context, buffer: &[u8]) {
+    /// Render the given frame onto a texture.
+    ///
+    /// The texture is only uploaded again if the display changed since the last frame,
+    /// or if the frame can't be told apart from the last one by its generation.
+    pub fn render(&mut self, gl: &glow::Context, frame: &DisplayFrame) {
+        let unchanged = frame.generation.is_some() && frame.generation == self.uploaded;
         unsafe {
-            self.load_texture(gl, buffer);
+            if !unchanged {
+                let blended = self.blender.blend(frame);
+                self.load_texture(gl, &blended);
+                self.uploaded = blended.generation;
+            }
             self.program.use_program(gl);
+            self.set_style_uniforms(gl, frame);
             gl.bind_vertex_array(Some(self.vao));
 
             gl.draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_INT, 0);
         }
     }
 
+    /// Pass the display style and the resolution of the `frame` to the fragment shader.
+    fn set_style_uniforms(&self, gl: &glow::Context, frame: &DisplayFrame) {
+        let program = &self.program;
+        program.set_uniform_vec2(gl, "resolution", frame.width as f32, frame.height as f32);
+        program.set_uniform_vec3_array(gl, "gradient", &self.style.gradient_uniform());
+        program.set_uniform_i32(gl, "gradient_len", self.style.gradient.len() as i32);
+        program.set_uniform_i32(gl, "pixel_shape", self.style.pixel_shape.uniform());
+        program.set_uniform_f32(gl, "pixel_gap", self.style.pixel_gap);
+    }
+
     /// Clean up state from the GL context.
     pub fn clean_up(&mut self, gl: &glow::Context) {
         self.program.delete(gl);
@@ -206,6 +322,30 @@ impl ShaderProgram {
         }
     }
 
+    /// Set an `f32` uniform.
+    pub fn set_uniform_f32(&self, gl: &glow::Context, name: &str, value: f32) {
+        unsafe {
+            let uniform_location = gl.get_uniform_location(self.program, name);
+            gl.uniform_1_f32(uniform_location.as_ref(), value);
+        }
+    }
+
+    /// Set a `vec2` uniform.
+    pub fn set_uniform_vec2(&self, gl: &glow::Context, name: &str, x: f32, y: f32) {
+        unsafe {
+            let uniform_location = gl.get_uniform_location(self.program, name);
+            gl.uniform_2_f32(uniform_location.as_ref(), x, y);
+        }
+    }
+
+    /// Set a `vec3` array uniform from the components of its vectors.
+    pub fn set_uniform_vec3_array(&self, gl: &glow::Context, name: &str, values: &[f32]) {
+        unsafe {
+            let uniform_location = gl.get_uniform_location(self.program, name);
+            gl.uniform_3_f32_slice(uniform_location.as_ref(), values);
+        }
+    }
+
     /// Delete this shader program. The program should not be used again after a call to this.
     pub fn delete(&self, gl: &glow::Context) {
         unsafe {
@@ -0,0 +1,114 @@
+use gilrs::{
+    ff::{BaseEffect, BaseEffectType, Effect, EffectBuilder, Repeat, Replay, Ticks},
+    EventType, GamepadId, Gilrs,
+};
+
+/// The default rumble intensity, from `0.0` (off) to `1.0` (full strength).
+pub const DEFAULT_RUMBLE_INTENSITY: f32 = 0.5;
+
+/// How long a single rumble pulse lasts. The pulse is repeated while the sound timer is active.
+const PULSE_LENGTH_MS: u32 = 100;
+
+/// Rumbles the connected gamepads while the `Chip8` sound timer is active,
+/// so the beep can be felt even when the audio is muted.
+pub struct Rumble {
+    gilrs: Gilrs,
+    /// The rumble effect played on all connected gamepads that support force feedback.
+    /// This is `None` if no such gamepad is connected, or if the intensity is `0`.
+    effect: Option<Effect>,
+    intensity: f32,
+    playing: bool,
+}
+
+impl Rumble {
+    /// Create a new `Rumble` with the [`DEFAULT_RUMBLE_INTENSITY`].
+    pub fn new() -> anyhow::Result<Self> {
+        // the gilrs error may contain a `Gilrs` instance, so it can't be converted directly
+        let gilrs = Gilrs::new().map_err(|e| anyhow::anyhow!("{e}"))?;
+        let mut rumble = Self {
+            gilrs,
+            effect: None,
+            intensity: DEFAULT_RUMBLE_INTENSITY,
+            playing: false,
+        };
+        rumble.rebuild_effect();
+        Ok(rumble)
+    }
+
+    /// Set the rumble intensity, from `0.0` (off) to `1.0` (full strength).
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity.clamp(0.0, 1.0);
+        self.rebuild_effect();
+    }
+
+    /// Handle connected/disconnected gamepads, and start or stop rumbling
+    /// depending on whether the sound timer is `beeping`. This should be called every frame.
+    pub fn update(&mut self, beeping: bool) {
+        let mut gamepads_changed = false;
+        while let Some(event) = self.gilrs.next_event() {
+            gamepads_changed |=
+                matches!(event.event, EventType::Connected | EventType::Disconnected);
+        }
+        if gamepads_changed {
+            self.rebuild_effect();
+        }
+
+        if beeping != self.playing {
+            self.set_playing(beeping);
+        }
+    }
+
+    /// Start or stop the rumble effect.
+    fn set_playing(&mut self, playing: bool) {
+        self.playing = playing;
+        if let Some(effect) = &self.effect {
+            let result = if playing {
+                effect.play()
+            } else {
+                effect.stop()
+            };
+            if let Err(e) = result {
+                log::warn!("Failed to update gamepad rumble: {e}");
+            }
+        }
+    }
+
+    /// Create the rumble effect for the currently connected gamepads with the current intensity.
+    fn rebuild_effect(&mut self) {
+        self.effect = None;
+        let gamepads: Vec<GamepadId> = self
+            .gilrs
+            .gamepads()
+            .filter(|(_, gamepad)| gamepad.is_ff_supported())
+            .map(|(id, _)| id)
+            .collect();
+        if gamepads.is_empty() || self.intensity <= 0.0 {
+            return;
+        }
+
+        let effect = EffectBuilder::new()
+            .add_effect(BaseEffect {
+                kind: BaseEffectType::Strong {
+                    magnitude: (self.intensity * f32::from(u16::MAX)) as u16,
+                },
+                scheduling: Replay {
+                    play_for: Ticks::from_ms(PULSE_LENGTH_MS),
+                    ..Default::default()
+                },
+                envelope: Default::default(),
+            })
+            .gamepads(&gamepads)
+            .repeat(Repeat::Infinitely)
+            .finish(&mut self.gilrs);
+        match effect {
+            Ok(effect) => {
+                self.effect = Some(effect);
+                // keep rumbling if the effect was rebuilt mid-beep
+                if self.playing {
+                    self.set_playing(true);
+                }
+            }
+            Err(e) => log::warn!("Failed to create gamepad rumble effect: {e}"),
+        }
+    }
+}
@@ -0,0 +1,614 @@
+use std::{path::Path, time::SystemTime};
+
+use anyhow::Context;
+use chip8::{
+    graphics::GraphicsBuffer, movie::MovieRecorder, rom::RomError, Chip8, Deterministic, Fault,
+    FrameEvent, FrameInput, Freeze, StepHook, Symbols,
+};
+
+use crate::audio::AudioSystem;
+use crate::bundle::SessionBundle;
+use crate::cartridge::Cartridge;
+use crate::crash::CrashDump;
+use crate::debugger::{BreakReason, Debugger};
+use crate::gui::{Granularity, RomEdit};
+use crate::logging;
+use crate::metrics::METRICS;
+use crate::profile::{self, RomProfile};
+use crate::profiler::Profiler;
+use crate::timeline::Timeline;
+
+/// The maximum amount of steps executed when stepping until the next sprite draw,
+/// so programs that never draw don't freeze the app.
+const MAX_STEPS_UNTIL_DRAW: u32 = 100_000;
+
+/// The maximum amount of steps executed when stepping until a subroutine returns,
+/// so subroutines that never return don't freeze the app.
+const MAX_STEPS_UNTIL_RETURN: u32 = 100_000;
+
+/// A loaded ROM with its own `Chip8` instance, audio, and debugging state.
+/// Each session is shown as a tab in the `Gui`.
+pub struct Session {
+    /// The name shown on the session's tab.
+    pub name: String,
+    pub chip8: Chip8,
+    // keep the audio system alive for as long as the session,
+    // so the stream is not dropped.
+    audio: Option<AudioSystem>,
+    pub paused: bool,
+    pub debugger: Debugger,
+    /// The reason the debugger last paused the execution, cleared when resumed.
+    pub break_reason: Option<BreakReason>,
+    last_rom: Vec<u8>,
+    /// The profile of the currently loaded ROM.
+    pub profile: RomProfile,
+    /// The RPL user flags as last saved for the loaded ROM, to save them again once they change.
+    saved_rpl_flags: [u8; 16],
+    /// The dump taken when the processor last halted on a fault, cleared when it is reset.
+    pub crash_dump: Option<CrashDump>,
+    pub profiler: Profiler,
+    pub timeline: Timeline,
+    /// Notes written by the user, exported along with the session.
+    pub annotations: String,
+    /// Records the key presses into a movie, while recording.
+    movie: Option<MovieRecorder>,
+}
+
+impl Session {
+    /// Create a new `Session` without a ROM loaded.
+    pub fn new() -> Self {
+        let audio = Self::create_audio_system()
+            .map_err(|e| log::error!("Failed to create AudioSystem: {e}"))
+            .ok();
+        let mut session = Self {
+            name: "Empty".into(),
+            chip8: Chip8::new(),
+            audio,
+            paused: false,
+            debugger: Debugger::default(),
+            break_reason: None,
+            last_rom: Vec::new(),
+            profile: RomProfile::load(&[]),
+            saved_rpl_flags: [0; 16],
+            crash_dump: None,
+            profiler: Profiler::default(),
+            timeline: Timeline::default(),
+            annotations: String::new(),
+            movie: None,
+        };
+        session.connect_audio();
+        session
+    }
+
+    /// Create a new `AudioSystem`.
+    ///
+    /// This will also start the audio stream. This function will only return
+    /// the `AudioSystem` if it can be both created and played without errors,
+    /// otherwise it returns `Err`.
+    fn create_audio_system() -> Result<AudioSystem, anyhow::Error> {
+        let audio = AudioSystem::new()?;
+        audio.play().map(|_| audio).map_err(|e| {
+            log::error!("Failed to play audio stream: {e}");
+            e
+        })
+    }
+
+    /// Set a new sink of the audio system on the `Chip8`. This should be called anytime the
+    /// `Chip8` is reset or replaced, so the tone it was playing stops.
+    fn connect_audio(&mut self) {
+        if let Some(audio) = &self.audio {
+            self.chip8.set_sound_sink(Some(Box::new(audio.sink())));
+        }
+    }
+
+    /// Play or pause the audio of this session. Only the audio
+    /// of the active session should be playing.
+    pub fn set_audio_playing(&self, playing: bool) {
+        if let Some(audio) = &self.audio {
+            let result = if playing { audio.play() } else { audio.pause() };
+            if let Err(e) = result {
+                log::error!("{e}");
+            }
+        }
+    }
+
+    /// Reset the `Chip8` and load the given ROM, along with its profile.
+    ///
+    /// If the data is an Octo cartridge, its program is compiled and its options are applied.
+    pub fn load_rom(&mut self, name: String, data: Vec<u8>) {
+        let (data, symbols) = if Cartridge::is_cartridge(&data) {
+            match Self::load_cartridge(&data, &mut self.chip8) {
+                Ok(compiled) => compiled,
+                Err(e) => {
+                    log::error!("Failed to load Octo cartridge: {e:#}");
+                    return;
+                }
+            }
+        } else {
+            (data, Symbols::new())
+        };
+        if let Err(e) = self.chip8.reset_and_load(data.clone()) {
+            log::error!("Failed to load ROM: {e}");
+            return;
+        }
+        self.chip8.set_symbols(symbols);
+        self.cancel_recording();
+        self.profile = RomProfile::load(&data);
+        self.saved_rpl_flags = profile::load_rpl_flags(chip8::rom_hash(&data));
+        self.chip8.set_rpl_flags(self.saved_rpl_flags);
+        self.name = name;
+        self.last_rom = data;
+        self.connect_audio();
+        self.profiler.reset(&self.chip8);
+        self.timeline.reset(&self.chip8);
+    }
+
+    /// Compile the program of the Octo cartridge in `gif`, applying its options to `chip8`.
+    /// Returns the ROM, along with the symbols of the labels of the program.
+    fn load_cartridge(gif: &[u8], chip8: &mut Chip8) -> anyhow::Result<(Vec<u8>, Symbols)> {
+        let cartridge = Cartridge::decode(gif)?;
+        let compiled = cartridge.compile()?;
+        cartridge.options.apply(chip8);
+        Ok(compiled)
+    }
+
+    /// Name the addresses of the loaded program with the symbol file at the given `path`.
+    pub fn load_symbols(&mut self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let text = std::fs::read_to_string(path)?;
+        let symbols = Symbols::parse(&text).context("Invalid symbol file")?;
+        log::info!("Loaded {} symbols", symbols.len());
+        self.chip8.set_symbols(symbols);
+        Ok(())
+    }
+
+    /// Reset the `Chip8` and reload the last loaded ROM.
+    pub fn reset(&mut self) {
+        if let Err(e) = self.chip8.reset_and_load(self.last_rom.clone()) {
+            log::error!("Failed to reload ROM: {e}");
+        }
+        self.cancel_recording();
+        self.connect_audio();
+        self.profiler.reset(&self.chip8);
+        self.timeline.reset(&self.chip8);
+    }
+
+    /// Get the data of the loaded ROM.
+    pub fn rom(&self) -> &[u8] {
+        &self.last_rom
+    }
+
+    /// The display to show. While running, this is the frame latched at the last vertical blank,
+    /// so sprites are never shown half drawn. While paused, every change is shown when stepping.
+    pub fn display(&self) -> &GraphicsBuffer {
+        if self.paused {
+            self.chip8.display()
+        } else {
+            self.chip8.latched_display()
+        }
+    }
+
+    /// Apply the given edit to the loaded ROM, then reload it.
+    pub fn edit_rom(&mut self, edit: RomEdit) -> Result<(), RomError> {
+        let rom = match edit {
+            RomEdit::Trim => chip8::rom::trim(&self.last_rom).to_vec(),
+            RomEdit::Append(data) => chip8::rom::concat([self.last_rom.as_slice(), &data]),
+            RomEdit::Relocate {
+                start,
+                end,
+                destination,
+            } => chip8::rom::relocate(
+                &self.last_rom,
+                start,
+                end,
+                destination,
+                self.chip8.memory_size(),
+            )?,
+        };
+        let capacity = chip8::rom::max_size(self.chip8.memory_size());
+        if rom.len() > capacity {
+            return Err(RomError::TooLarge {
+                size: rom.len(),
+                capacity,
+            });
+        }
+        self.load_rom(self.name.clone(), rom);
+        Ok(())
+    }
+
+    /// Save `Chip8` state to a file specified by `path`.
+    pub fn save_state(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let bytes = chip8::state::save(&self.chip8)?;
+        std::fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    /// Load `Chip8` state from the given `path`.
+    pub fn load_state(&mut self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let bytes = std::fs::read(path)?;
+        self.restore_state(&bytes)
+    }
+
+    /// Restore the `Chip8` from the given serialized state, which may be a legacy save state.
+    fn restore_state(&mut self, bytes: &[u8]) -> anyhow::Result<()> {
+        let symbols = self.chip8.symbols().clone();
+        self.chip8 =
+            chip8::state::load(bytes).context("Failed to deserialize Chip8 instance from file.")?;
+        // the state is of the loaded program, so its symbols still apply
+        self.chip8.set_symbols(symbols);
+        self.cancel_recording();
+        // the flags of a save state are only saved to disk once the program changes them
+        self.saved_rpl_flags = self.chip8.rpl_flags();
+        self.connect_audio();
+        self.profiler.reset(&self.chip8);
+        self.timeline.reset(&self.chip8);
+        Ok(())
+    }
+
+    /// Restart the loaded ROM in the deterministic mode, and record the key presses into a
+    /// movie that replays the same way. The timers tick every `steps_per_frame` steps.
+    pub fn start_recording(&mut self, steps_per_frame: u32) {
+        let seed = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(1, |time| time.as_nanos() as u64);
+        let deterministic = Deterministic {
+            seed,
+            steps_per_tick: steps_per_frame,
+        };
+        match MovieRecorder::start(&mut self.chip8, self.last_rom.clone(), deterministic) {
+            Ok(recorder) => {
+                log::info!("Recording a movie with the seed {seed}");
+                self.movie = Some(recorder);
+            }
+            Err(e) => log::error!("Failed to reload ROM: {e}"),
+        }
+        self.connect_audio();
+        self.profiler.reset(&self.chip8);
+        self.timeline.reset(&self.chip8);
+    }
+
+    /// Stop recording, writing the movie to the given `path`. The `Chip8` stays in the
+    /// deterministic mode until the ROM is reloaded.
+    pub fn stop_recording(&mut self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let movie = match self.movie.take() {
+            Some(recorder) => recorder.finish(),
+            None => anyhow::bail!("No movie is being recorded"),
+        };
+        std::fs::write(path, chip8::movie::save(&movie)?)?;
+        log::info!(
+            "Recorded {} key changes over {} frames",
+            movie.events.len(),
+            movie.frames
+        );
+        Ok(())
+    }
+
+    /// Whether a movie is being recorded.
+    pub fn is_recording(&self) -> bool {
+        self.movie.is_some()
+    }
+
+    /// Drop the movie being recorded, as the `Chip8` was changed by something else
+    /// than its steps and wouldn't replay the same way.
+    fn cancel_recording(&mut self) {
+        if self.movie.take().is_some() {
+            log::warn!("The movie recording was stopped, as the program was reset or replaced");
+        }
+        self.chip8.set_deterministic(None);
+    }
+
+    /// Bundle this session into a [`SessionBundle`] that can be shared with other users.
+    pub fn bundle(&self) -> anyhow::Result<SessionBundle> {
+        SessionBundle::new(
+            self.name.clone(),
+            self.last_rom.clone(),
+            &self.chip8,
+            self.annotations.clone(),
+        )
+    }
+
+    /// Create a new session from an imported [`SessionBundle`], restoring the exported state.
+    pub fn from_bundle(bundle: SessionBundle) -> anyhow::Result<Self> {
+        let mut session = Self::new();
+        session.load_rom(bundle.name, bundle.rom);
+        session.restore_state(&bundle.state)?;
+        bundle.quirks.apply(&mut session.chip8);
+        session.annotations = bundle.annotations;
+        Ok(session)
+    }
+
+    /// Toggle the paused state, resuming from any break.
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        if !self.paused {
+            self.debugger.resume();
+            self.break_reason = None;
+        }
+    }
+
+    /// Execute the given amount of steps, pausing the execution
+    /// if the debugger decides to break.
+    ///
+    /// Returns the keys the program tested (`Ex9E` and `ExA1`) during the frame, indexed by key code.
+    pub fn run_frame(&mut self, steps: u32) -> [bool; 16] {
+        self.chip8
+            .set_invalid_opcode_policy(self.debugger.invalid_opcode_policy());
+        let mut input = FrameInput::new(steps);
+        input.breakpoints = self.debugger.breakpoints.clone();
+        let mut hook = DebugHook {
+            debugger: &mut self.debugger,
+            profiler: &mut self.profiler,
+            timeline: &mut self.timeline,
+            recorder: StepRecorder::default(),
+            movie: self.movie.as_mut(),
+            break_reason: None,
+        };
+        let output = self.chip8.run_frame_with(&input, &mut hook);
+        METRICS.record_frame(output.steps);
+        let (event, break_reason, keys_read) =
+            (output.event, hook.break_reason, hook.recorder.keys_read);
+        self.timeline.end_frame(&self.chip8);
+        if let Some(movie) = &mut self.movie {
+            movie.end_frame();
+        }
+        match (event, break_reason) {
+            (Some(FrameEvent::Stopped), Some(reason)) => self.break_on(reason),
+            (Some(FrameEvent::InvalidOpcode(address)), _) => {
+                self.break_on(BreakReason::InvalidOpcode(address))
+            }
+            (Some(FrameEvent::Breakpoint(address)), _) => {
+                self.break_on(BreakReason::Breakpoint(address))
+            }
+            // stop running a program that won't do anything else
+            (Some(FrameEvent::Finished(address)), _) => {
+                log::info!("The program finished with a jump to itself at {address:#06X}");
+                self.paused = true;
+            }
+            _ => {}
+        }
+        keys_read
+    }
+
+    /// Pause the execution because of the given `reason`.
+    fn break_on(&mut self, reason: BreakReason) {
+        log::info!("Break: {reason}");
+        self.paused = true;
+        self.break_reason = Some(reason);
+    }
+
+    /// Advance the `Chip8` by the given granularity, ignoring the debugger's triggers.
+    /// A frame is `steps_per_frame` steps.
+    pub fn step(&mut self, granularity: Granularity, steps_per_frame: u32) {
+        let depth = self.chip8.stack().len();
+        let (steps, stop_after): (u32, StopCondition) = match granularity {
+            Granularity::Instruction => (1, Box::new(|_| false)),
+            Granularity::Frame => (steps_per_frame, Box::new(|_| false)),
+            Granularity::UntilDraw => (
+                MAX_STEPS_UNTIL_DRAW,
+                Box::new(|chip8| {
+                    matches!(
+                        chip8.instructions().front(),
+                        Some(instruction) if instruction.opcode & 0xF000 == 0xD000
+                    )
+                }),
+            ),
+            // like `Chip8::step_over` and `Chip8::step_out`, but through the hook,
+            // so the steps are profiled and recorded in the timeline
+            Granularity::Over => (
+                MAX_STEPS_UNTIL_RETURN,
+                Box::new(move |chip8| chip8.stack().len() <= depth || chip8.waiting_for_key()),
+            ),
+            Granularity::Out if depth == 0 => {
+                log::info!("Not in a subroutine, there is nothing to step out of");
+                return;
+            }
+            Granularity::Out => (
+                MAX_STEPS_UNTIL_RETURN,
+                Box::new(move |chip8| chip8.stack().len() < depth || chip8.waiting_for_key()),
+            ),
+        };
+        let input = FrameInput::new(steps);
+        let mut hook = StepUntilHook {
+            profiler: &mut self.profiler,
+            timeline: &mut self.timeline,
+            recorder: StepRecorder::default(),
+            movie: self.movie.as_mut(),
+            stop_after,
+        };
+        let output = self.chip8.run_frame_with(&input, &mut hook);
+        METRICS.record_instructions(output.steps);
+        if let Some(movie) = &mut self.movie {
+            movie.end_frame();
+        }
+        match granularity {
+            Granularity::UntilDraw if output.steps == MAX_STEPS_UNTIL_DRAW => {
+                log::warn!("No sprite was drawn within {MAX_STEPS_UNTIL_DRAW} steps");
+            }
+            Granularity::Over | Granularity::Out if output.steps == MAX_STEPS_UNTIL_RETURN => {
+                log::warn!("The subroutine didn't return within {MAX_STEPS_UNTIL_RETURN} steps");
+            }
+            _ => {}
+        }
+        self.break_reason = None;
+    }
+
+    /// Undo the last executed instruction, see [`Chip8::step_back`].
+    pub fn step_back(&mut self) {
+        if self.movie.is_some() {
+            log::info!("Stepping back is disabled while recording a movie");
+            return;
+        }
+        if self.chip8.step_back() {
+            self.break_reason = None;
+        } else {
+            log::info!("There is no earlier instruction to step back to");
+        }
+    }
+
+    /// Apply cheats, take a crash dump and save the RPL flags if necessary.
+    /// This should be called every frame.
+    pub fn update(&mut self) {
+        self.apply_cheats();
+        self.update_crash_dump();
+        self.save_rpl_flags();
+    }
+
+    /// Save the RPL user flags of the loaded ROM if the program changed them,
+    /// so games that keep e.g. high scores in them remember them across sessions.
+    fn save_rpl_flags(&mut self) {
+        let flags = self.chip8.rpl_flags();
+        if flags == self.saved_rpl_flags || self.last_rom.is_empty() {
+            return;
+        }
+        self.saved_rpl_flags = flags;
+        if let Err(e) = profile::save_rpl_flags(chip8::rom_hash(&self.last_rom), flags) {
+            log::error!("Failed to save RPL flags: {e:#}");
+        }
+    }
+
+    /// Take a crash dump when the processor has just halted on a fault,
+    /// writing it to disk so it survives the app being closed.
+    /// Programs that exited by themselves didn't crash, so no dump is taken for them.
+    fn update_crash_dump(&mut self) {
+        match (self.chip8.fault(), &self.crash_dump) {
+            (Some(fault), None) if fault != Fault::Exited => {
+                METRICS.record_fault();
+                let mut dump = CrashDump::new(&self.chip8, &self.last_rom, fault);
+                if let Err(e) = dump.save() {
+                    log::error!("Failed to save crash dump: {e}");
+                }
+                self.crash_dump = Some(dump);
+            }
+            // the processor has been reset, or a state without a fault has been loaded
+            (None, Some(_)) => self.crash_dump = None,
+            _ => {}
+        }
+    }
+
+    /// Pin the values of all enabled cheats in memory, so they are written back after every
+    /// step. This also picks up cheats changed in the profile, or a newly loaded ROM.
+    fn apply_cheats(&mut self) {
+        let freezes = self
+            .profile
+            .cheats
+            .iter()
+            .filter(|cheat| cheat.enabled)
+            .map(|cheat| Freeze {
+                address: usize::from(cheat.address),
+                value: cheat.value,
+            })
+            .collect();
+        self.chip8.set_freezes(freezes);
+    }
+
+    /// Save the profile of the current ROM, logging any errors.
+    pub fn save_profile(&self) {
+        if let Err(e) = self.profile.save() {
+            log::error!("Failed to save ROM profile: {e}");
+        }
+    }
+}
+
+/// Checks the debugger's triggers around every step of a frame,
+/// and records the steps in the profiler and timeline.
+struct DebugHook<'a> {
+    debugger: &'a mut Debugger,
+    profiler: &'a mut Profiler,
+    timeline: &'a mut Timeline,
+    recorder: StepRecorder,
+    movie: Option<&'a mut MovieRecorder>,
+
+    /// The reason the debugger stopped the frame.
+    break_reason: Option<BreakReason>,
+}
+
+impl StepHook for DebugHook<'_> {
+    fn before_step(&mut self, chip8: &Chip8) -> bool {
+        self.recorder.before_step(chip8);
+        if let Some(movie) = &mut self.movie {
+            movie.before_step(chip8);
+        }
+        self.break_reason = self.debugger.check(chip8);
+        self.break_reason.is_some()
+    }
+
+    fn after_step(&mut self, chip8: &Chip8) -> bool {
+        self.recorder.after_step(chip8);
+        if let Some(movie) = &mut self.movie {
+            movie.after_step();
+        }
+        self.profiler.record(chip8);
+        self.timeline.record(chip8);
+        self.break_reason = self
+            .debugger
+            .check_registers(chip8)
+            .or_else(|| self.debugger.check_code_write(chip8));
+        self.break_reason.is_some()
+    }
+}
+
+/// Decides after a step whether stepping should stop.
+type StopCondition = Box<dyn Fn(&Chip8) -> bool>;
+
+/// Records the steps of a frame in the profiler and timeline, stopping once `stop_after` returns `true`.
+struct StepUntilHook<'a> {
+    profiler: &'a mut Profiler,
+    timeline: &'a mut Timeline,
+    recorder: StepRecorder,
+    movie: Option<&'a mut MovieRecorder>,
+    stop_after: StopCondition,
+}
+
+impl StepHook for StepUntilHook<'_> {
+    fn before_step(&mut self, chip8: &Chip8) -> bool {
+        self.recorder.before_step(chip8);
+        if let Some(movie) = &mut self.movie {
+            movie.before_step(chip8);
+        }
+        false
+    }
+
+    fn after_step(&mut self, chip8: &Chip8) -> bool {
+        self.recorder.after_step(chip8);
+        if let Some(movie) = &mut self.movie {
+            movie.after_step();
+        }
+        self.profiler.record(chip8);
+        self.timeline.record(chip8);
+        (self.stop_after)(chip8)
+    }
+}
+
+/// Records the instruction executed by each step in the metrics and the instruction trace,
+/// and the keys the program tested.
+#[derive(Default)]
+struct StepRecorder {
+    waiting_for_key: bool,
+
+    /// The keys tested by `Ex9E` and `ExA1`, indexed by key code.
+    keys_read: [bool; 16],
+}
+
+impl StepRecorder {
+    fn before_step(&mut self, chip8: &Chip8) {
+        self.waiting_for_key = chip8.waiting_for_key();
+    }
+
+    fn after_step(&mut self, chip8: &Chip8) {
+        // steps that wait for a key or halt on a fault don't execute an instruction
+        if self.waiting_for_key || chip8.fault().is_some() {
+            return;
+        }
+        if let Some(instruction) = chip8.instructions().front() {
+            if instruction.opcode & 0xF000 == 0xD000 {
+                METRICS.record_draw();
+            }
+            if matches!(instruction.opcode & 0xF0FF, 0xE09E | 0xE0A1) {
+                let x = (instruction.opcode & 0x0F00) >> 8;
+                if let Some(read) = self.keys_read.get_mut(usize::from(chip8.registers()[x])) {
+                    *read = true;
+                }
+            }
+            logging::trace_instruction(instruction);
+        }
+    }
+}
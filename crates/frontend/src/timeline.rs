@@ -0,0 +1,213 @@
+use std::{collections::VecDeque, fmt};
+
+use chip8::Chip8;
+
+/// The maximum amount of events kept in a [`Timeline`], older events are dropped first.
+const MAX_EVENTS: usize = 200_000;
+
+/// Something that happened during the execution, as recorded by a [`Timeline`].
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    /// A sprite was drawn (`Dxyn`).
+    Draw,
+
+    /// The subroutine at the given address was called.
+    Call(usize),
+
+    /// A subroutine returned.
+    Return,
+
+    /// The key with the given key code was pressed.
+    KeyDown(u8),
+
+    /// The key with the given key code was released.
+    KeyUp(u8),
+
+    /// The delay timer was set to the given value (`Fx15`).
+    DelayTimer(u8),
+
+    /// The sound timer was set to the given value (`Fx18`).
+    SoundTimer(u8),
+}
+
+impl EventKind {
+    /// The lane of the timeline the event is shown in, see [`Lane::ALL`].
+    pub fn lane(self) -> Lane {
+        match self {
+            EventKind::Draw => Lane::Draws,
+            EventKind::Call(_) | EventKind::Return => Lane::Calls,
+            EventKind::KeyDown(_) | EventKind::KeyUp(_) => Lane::Keys,
+            EventKind::DelayTimer(_) | EventKind::SoundTimer(_) => Lane::Timers,
+        }
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventKind::Draw => write!(f, "Draw"),
+            EventKind::Call(target) => write!(f, "Call {target:#06X}"),
+            EventKind::Return => write!(f, "Return"),
+            EventKind::KeyDown(key) => write!(f, "Key {key:X} down"),
+            EventKind::KeyUp(key) => write!(f, "Key {key:X} up"),
+            EventKind::DelayTimer(value) => write!(f, "Delay timer = {value}"),
+            EventKind::SoundTimer(value) => write!(f, "Sound timer = {value}"),
+        }
+    }
+}
+
+/// A row of the timeline, grouping related kinds of events.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Lane {
+    Calls,
+    Draws,
+    Keys,
+    Timers,
+}
+
+impl Lane {
+    /// All lanes, in the order they are shown.
+    pub const ALL: [Lane; 4] = [Lane::Calls, Lane::Draws, Lane::Keys, Lane::Timers];
+}
+
+impl fmt::Display for Lane {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Lane::Calls => "Calls",
+            Lane::Draws => "Draws",
+            Lane::Keys => "Keys",
+            Lane::Timers => "Timers",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A single entry of a [`Timeline`].
+#[derive(Clone, Copy)]
+pub struct Event {
+    /// The frame the event happened in.
+    pub frame: u64,
+
+    /// The address of the instruction that caused the event. Key events use
+    /// the program counter at the end of the frame.
+    pub address: usize,
+
+    pub kind: EventKind,
+}
+
+/// Records frames, sprite draws, subroutine calls, key events and timer writes,
+/// so they can be shown on a timeline.
+///
+/// Like the [`Profiler`](crate::profiler::Profiler), calls and returns are detected from
+/// changes to the stack pointer after each step.
+#[derive(Default)]
+pub struct Timeline {
+    pub enabled: bool,
+
+    /// The number of the current frame, counted since the timeline was reset.
+    frame: u64,
+
+    events: VecDeque<Event>,
+
+    /// The stack pointer after the last recorded step.
+    last_sp: usize,
+
+    /// The pressed state of every key at the end of the last frame.
+    keys: [bool; 16],
+}
+
+impl Timeline {
+    /// Record a step of the given `Chip8`. This should be called after every step.
+    pub fn record(&mut self, chip8: &Chip8) {
+        if !self.enabled {
+            return;
+        }
+
+        let sp = chip8.stack().len();
+        let instruction = chip8.instructions().front();
+        let address = instruction.map_or(chip8.pc(), |instruction| instruction.address);
+        if sp > self.last_sp {
+            // a call jumps straight to the subroutine
+            self.push(address, EventKind::Call(chip8.pc()));
+        } else if sp < self.last_sp {
+            self.push(address, EventKind::Return);
+        }
+        self.last_sp = sp;
+
+        if let Some(instruction) = instruction {
+            let opcode = instruction.opcode;
+            let vx = chip8.registers()[(opcode & 0x0F00) >> 8];
+            match opcode & 0xF0FF {
+                0xF015 => self.push(address, EventKind::DelayTimer(vx)),
+                0xF018 => self.push(address, EventKind::SoundTimer(vx)),
+                _ if opcode & 0xF000 == 0xD000 => self.push(address, EventKind::Draw),
+                _ => {}
+            }
+        }
+    }
+
+    /// End the current frame, recording the keys that changed during it.
+    /// This should be called after every frame.
+    pub fn end_frame(&mut self, chip8: &Chip8) {
+        if !self.enabled {
+            return;
+        }
+
+        for key_code in 0..16 {
+            let pressed = chip8.is_key_pressed(key_code);
+            if pressed != self.keys[usize::from(key_code)] {
+                let kind = if pressed {
+                    EventKind::KeyDown(key_code)
+                } else {
+                    EventKind::KeyUp(key_code)
+                };
+                self.push(chip8.pc(), kind);
+                self.keys[usize::from(key_code)] = pressed;
+            }
+        }
+        self.frame += 1;
+    }
+
+    /// Add an event to the current frame, dropping the oldest event if the timeline is full.
+    fn push(&mut self, address: usize, kind: EventKind) {
+        if self.events.len() == MAX_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(Event {
+            frame: self.frame,
+            address,
+            kind,
+        });
+    }
+
+    /// Clear all events, starting a new timeline from the current state of `chip8`.
+    pub fn reset(&mut self, chip8: &Chip8) {
+        self.frame = 0;
+        self.events.clear();
+        self.last_sp = chip8.stack().len();
+        for (key_code, pressed) in (0..).zip(&mut self.keys) {
+            *pressed = chip8.is_key_pressed(key_code);
+        }
+    }
+
+    /// Get the events recorded in the given range of frames, oldest first.
+    pub fn events(&self, frames: std::ops::Range<u64>) -> impl Iterator<Item = &Event> {
+        // the events are sorted by frame
+        let start = self
+            .events
+            .partition_point(|event| event.frame < frames.start);
+        self.events
+            .range(start..)
+            .take_while(move |event| event.frame < frames.end)
+    }
+
+    /// The number of the current frame, which is also the amount of frames recorded.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// The amount of events recorded.
+    pub fn event_count(&self) -> usize {
+        self.events.len()
+    }
+}
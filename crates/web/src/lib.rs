@@ -0,0 +1,164 @@
+//! A minimal web build of the interpreter, used by the standalone HTML export of `chip8-cli`.
+//!
+//! Build it with `cargo build -p chip8-web --release --target wasm32-unknown-unknown`.
+//! The module exports plain functions instead of using `wasm-bindgen`, so the page
+//! only needs a few lines of JavaScript to drive it, and provides `env.chip8_now`
+//! (the current time in milliseconds) as the only import.
+
+use std::cell::{Cell, RefCell};
+
+use chip8::{Chip8, FrameInput, IndexIncrement, Quirks, Variant};
+
+/// The `quirks` flag enabling the shift quirk.
+pub const QUIRK_SHIFT: u32 = 1;
+
+/// The `quirks` flag enabling waiting for the vertical blank before drawing.
+pub const QUIRK_VBLANK_WAIT: u32 = 2;
+
+/// The `quirks` flag enabling sprites to wrap around the bottom edge of the display.
+pub const QUIRK_VERTICAL_WRAP: u32 = 4;
+
+/// The `quirks` flag leaving `I` unchanged when storing and loading registers, like SUPER-CHIP.
+pub const QUIRK_LOAD_STORE: u32 = 8;
+
+/// The `quirks` flag making `Bxnn` jump relative to `Vx` instead of `V0`, like CHIP-48.
+pub const QUIRK_JUMP: u32 = 16;
+
+/// The `quirks` flag enabling sprites to wrap around the right edge of the display.
+pub const QUIRK_HORIZONTAL_WRAP: u32 = 32;
+
+/// The interpreter and the buffers shared with the page.
+#[derive(Default)]
+struct State {
+    chip8: Chip8,
+
+    /// The ROM, written by the page before calling [`start`].
+    rom: Vec<u8>,
+
+    /// The display, one byte per pixel, `1` if the pixel is on.
+    display: Vec<u8>,
+
+    /// The width and height of the `display`, which may lag behind the live display
+    /// of the `chip8` when it switches resolution during a frame.
+    display_size: (u32, u32),
+}
+
+thread_local! {
+    static STATE: RefCell<State> = RefCell::new(State::default());
+
+    /// The state of the xorshift generator behind `random_bytes`.
+    static RANDOM: Cell<u32> = const { Cell::new(0x2545_F491) };
+}
+
+/// Allocate a buffer of `len` bytes for the ROM, and return a pointer for the page to write it to.
+#[no_mangle]
+pub extern "C" fn rom_buffer(len: usize) -> *mut u8 {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        state.rom = vec![0; len];
+        state.rom.as_mut_ptr()
+    })
+}
+
+/// Seed the random number generator used by `Cxnn`. A seed of `0` is ignored.
+#[no_mangle]
+pub extern "C" fn seed(value: u32) {
+    if value != 0 {
+        RANDOM.with(|random| random.set(value));
+    }
+}
+
+/// Start the ROM in the buffer from [`rom_buffer`] as the given variant
+/// (`0` for CHIP-8, `1` for SUPER-CHIP, `2` for XO-CHIP, `3` for two-page hi-res CHIP-8),
+/// with the given `QUIRK_*` flags.
+/// The pixels of all XO-CHIP planes are shown in the foreground color.
+#[no_mangle]
+pub extern "C" fn start(variant: u32, quirks: u32) {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let variant = match variant {
+            1 => Variant::SuperChip,
+            2 => Variant::XoChip,
+            3 => Variant::HiresChip8,
+            _ => Variant::Chip8,
+        };
+        let flags = quirks;
+        let mut quirks = Quirks::default();
+        quirks.shift = flags & QUIRK_SHIFT != 0;
+        quirks.vblank_wait = flags & QUIRK_VBLANK_WAIT != 0;
+        quirks.vertical_wrap = flags & QUIRK_VERTICAL_WRAP != 0;
+        quirks.horizontal_wrap = flags & QUIRK_HORIZONTAL_WRAP != 0;
+        quirks.jump = flags & QUIRK_JUMP != 0;
+        quirks.index_increment = if flags & QUIRK_LOAD_STORE != 0 {
+            IndexIncrement::Unchanged
+        } else {
+            IndexIncrement::PastLast
+        };
+        let mut chip8 = Chip8::builder().variant(variant).quirks(quirks).build();
+        // the HTML export only embeds ROMs that fit in memory
+        let _ = chip8.load_rom_data(state.rom.clone());
+        state.chip8 = chip8;
+    });
+}
+
+/// Run a frame of up to `steps` steps, with the pressed keys given as a bitmask
+/// indexed by key code. Returns `1` if the tone should be played, `0` otherwise.
+#[no_mangle]
+pub extern "C" fn run_frame(steps: u32, keys: u32) -> u32 {
+    STATE.with(|state| {
+        let state = &mut *state.borrow_mut();
+        let mut pressed = [false; 16];
+        for (key_code, pressed) in pressed.iter_mut().enumerate() {
+            *pressed = keys & 1 << key_code != 0;
+        }
+        let mut input = FrameInput::new(steps);
+        input.keys = Some(pressed);
+        let output = state.chip8.run_frame(&input);
+        let framebuffer = output.framebuffer;
+        state.display_size = (framebuffer.width() as u32, framebuffer.height() as u32);
+        state.display.clear();
+        for y in 0..framebuffer.height() {
+            for x in 0..framebuffer.width() {
+                state.display.push(u8::from(framebuffer.is_pixel_on(x, y)));
+            }
+        }
+        u32::from(output.sound_active)
+    })
+}
+
+/// The width of the display as of the last [`run_frame`] in pixels, which changes when
+/// switching to hi-res mode.
+#[no_mangle]
+pub extern "C" fn display_width() -> u32 {
+    STATE.with(|state| state.borrow().display_size.0)
+}
+
+/// The height of the display as of the last [`run_frame`] in pixels.
+#[no_mangle]
+pub extern "C" fn display_height() -> u32 {
+    STATE.with(|state| state.borrow().display_size.1)
+}
+
+/// A pointer to the display as of the last [`run_frame`], one byte per pixel, row by row.
+#[no_mangle]
+pub extern "C" fn display() -> *const u8 {
+    STATE.with(|state| state.borrow().display.as_ptr())
+}
+
+/// Fill `dest` with bytes from a xorshift generator, standing in for the
+/// operating system's entropy source.
+fn random_bytes(dest: &mut [u8]) -> Result<(), getrandom::Error> {
+    RANDOM.with(|random| {
+        for byte in dest {
+            let mut x = random.get();
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            random.set(x);
+            *byte = x as u8;
+        }
+    });
+    Ok(())
+}
+
+getrandom::register_custom_getrandom!(random_bytes);